@@ -0,0 +1,5 @@
+// Headless entry point: runs one prompt against an agent without the GUI.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(commander_lib::headless::run(args));
+}
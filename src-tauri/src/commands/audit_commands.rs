@@ -0,0 +1,11 @@
+use crate::models::audit::{AuditEntry, AuditQuery};
+use crate::services::audit_log_service::AuditLog;
+use std::sync::Arc;
+
+#[tauri::command]
+pub async fn query_audit_log(
+    audit_log: tauri::State<'_, Arc<AuditLog>>,
+    query: Option<AuditQuery>,
+) -> Result<Vec<AuditEntry>, String> {
+    audit_log.query(&query.unwrap_or_default())
+}
@@ -1,11 +1,25 @@
 use crate::models::auth::{AuthUser, StoredAuth};
 use crate::services::auth_service;
-use tauri_plugin_store::StoreExt;
+use crate::services::secret_store_service;
+use tauri_plugin_store::{Store, StoreExt};
 
 const STORE_FILE: &str = "auth-store.json";
 const KEY_TOKEN: &str = "auth_token";
 const KEY_USER: &str = "auth_user";
 
+/// The token behind the handle kept under `KEY_TOKEN`. A token stored
+/// before the secret store existed is moved into it.
+fn unseal_token<R: tauri::Runtime>(store: &Store<R>, stored: &str) -> Result<String, String> {
+    let secrets = secret_store_service::global()?;
+    if secret_store_service::is_handle(stored) {
+        return secrets.resolve(stored);
+    }
+    let handle = secrets.store(stored)?;
+    store.set(KEY_TOKEN, serde_json::json!(handle));
+    store.save().map_err(|e| e.to_string())?;
+    Ok(stored.to_string())
+}
+
 #[tauri::command]
 pub async fn store_auth_token(
     app: tauri::AppHandle,
@@ -13,9 +27,14 @@ pub async fn store_auth_token(
     user: AuthUser,
     device_id: String,
 ) -> Result<(), String> {
-    // Store in Tauri secure store
+    // Keep the token in the secret store and its handle in the Tauri store
+    let secrets = secret_store_service::global()?;
     let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
-    store.set(KEY_TOKEN, serde_json::json!(token));
+    let previous = store
+        .get(KEY_TOKEN)
+        .and_then(|val| val.as_str().map(String::from));
+    let handle = secrets.replace(previous.as_deref(), &token)?;
+    store.set(KEY_TOKEN, serde_json::json!(handle));
     store.set(KEY_USER, serde_json::to_value(&user).map_err(|e| e.to_string())?);
     store.save().map_err(|e| e.to_string())?;
 
@@ -27,7 +46,7 @@ pub async fn store_auth_token(
         device_id,
         created_at: chrono::Utc::now().to_rfc3339(),
     };
-    auth_service::save_auth_to_file(&sessions_dir, &stored)?;
+    auth_service::save_auth_to_file(&sessions_dir, &stored, secrets)?;
 
     Ok(())
 }
@@ -37,11 +56,15 @@ pub async fn get_auth_token(app: tauri::AppHandle) -> Result<Option<String>, Str
     let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
     let token = store.get(KEY_TOKEN);
     match token {
-        Some(val) => Ok(val.as_str().map(|s| s.to_string())),
+        Some(val) => val
+            .as_str()
+            .map(|stored| unseal_token(&store, stored))
+            .transpose(),
         None => {
             // Fallback: check ~/.autohand/sessions/
             let sessions_dir = auth_service::get_default_sessions_dir()?;
-            let stored = auth_service::load_auth_from_file(&sessions_dir)?;
+            let stored =
+                auth_service::load_auth_from_file(&sessions_dir, secret_store_service::global()?)?;
             Ok(stored.map(|s| s.token))
         }
     }
@@ -60,7 +83,8 @@ pub async fn get_auth_user(app: tauri::AppHandle) -> Result<Option<AuthUser>, St
         None => {
             // Fallback: check ~/.autohand/sessions/
             let sessions_dir = auth_service::get_default_sessions_dir()?;
-            let stored = auth_service::load_auth_from_file(&sessions_dir)?;
+            let stored =
+                auth_service::load_auth_from_file(&sessions_dir, secret_store_service::global()?)?;
             Ok(stored.map(|s| s.user))
         }
     }
@@ -68,15 +92,29 @@ pub async fn get_auth_user(app: tauri::AppHandle) -> Result<Option<AuthUser>, St
 
 #[tauri::command]
 pub async fn clear_auth_token(app: tauri::AppHandle) -> Result<(), String> {
-    // Clear from Tauri store
+    // Signing out must not hinge on the secret store opening, so removing the
+    // token from it is best effort; the handles go regardless.
+    let secrets = secret_store_service::global()
+        .map_err(|e| eprintln!("⚠️ Secret store unavailable while signing out: {}", e))
+        .ok();
+
+    // Clear from Tauri store and the secret store
     let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    if let (Some(secrets), Some(handle)) = (
+        secrets,
+        store.get(KEY_TOKEN).and_then(|val| val.as_str().map(String::from)),
+    ) {
+        if let Err(e) = secrets.remove(&handle) {
+            eprintln!("⚠️ Failed to remove the auth token from the secret store: {}", e);
+        }
+    }
     store.delete(KEY_TOKEN);
     store.delete(KEY_USER);
     store.save().map_err(|e| e.to_string())?;
 
     // Clear from ~/.autohand/sessions/
     let sessions_dir = auth_service::get_default_sessions_dir()?;
-    auth_service::clear_auth_file(&sessions_dir)?;
+    auth_service::clear_auth_file(&sessions_dir, secrets)?;
 
     Ok(())
 }
@@ -8,6 +8,7 @@ use crate::models::autohand::*;
 use crate::services::autohand::hooks_service;
 use crate::services::autohand::protocol::AutohandProtocol;
 use crate::services::autohand::{AutohandAcpClient, AutohandRpcClient};
+use crate::services::secret_store_service::{self, SecretStore};
 
 // ---------------------------------------------------------------------------
 // Session management
@@ -28,14 +29,24 @@ struct AutohandSessionHandle {
 
 /// Build a fingerprint string from the config fields that affect the running
 /// CLI process.  If any of these change, the session must be restarted.
-fn config_fingerprint(config: &AutohandConfig) -> String {
+/// A saved API key is a secret store handle and goes in as is; a key not yet
+/// moved to the store only contributes a digest, so the fingerprint never
+/// holds a key.
+pub fn config_fingerprint(config: &AutohandConfig) -> String {
     let model = config.model.as_deref().unwrap_or("");
     let provider = &config.provider;
     let api_key = config
         .provider_details
         .as_ref()
         .and_then(|d| d.api_key.as_deref())
-        .unwrap_or("");
+        .map(|key| {
+            if secret_store_service::is_handle(key) {
+                key.to_string()
+            } else {
+                secret_store_service::fingerprint(key)
+            }
+        })
+        .unwrap_or_default();
     let base_url = config
         .provider_details
         .as_ref()
@@ -159,8 +170,22 @@ pub fn load_autohand_config_with_global(
 ///   1. Built-in defaults
 ///   2. Global user config  – `~/.autohand/config.json`
 ///   3. Workspace config    – `<working_dir>/.autohand/config.json`
+///
+/// Plaintext API keys found in either file are first moved into the secret
+/// store, leaving `secret://` handles behind.
 pub fn load_autohand_config_internal(working_dir: &str) -> Result<AutohandConfig, String> {
     let global_dir = dirs::home_dir().map(|h| h.join(".autohand"));
+    let config_files = global_dir
+        .iter()
+        .map(|dir| dir.join("config.json"))
+        .chain(std::iter::once(
+            Path::new(working_dir).join(".autohand").join("config.json"),
+        ));
+    for path in config_files {
+        if let Err(e) = secret_store_service::seal_config_file(&path, secret_store_service::global) {
+            eprintln!("⚠️ Left plaintext API keys in {}: {}", path.display(), e);
+        }
+    }
     load_autohand_config_with_global(working_dir, global_dir.as_deref())
 }
 
@@ -168,6 +193,17 @@ pub fn load_autohand_config_internal(working_dir: &str) -> Result<AutohandConfig
 ///
 /// Preserves any other top-level keys already present in the file.
 pub fn save_autohand_config_internal(working_dir: &str, config: &AutohandConfig) -> Result<(), String> {
+    save_autohand_config_with_store(working_dir, config, secret_store_service::global)
+}
+
+/// Testable core of [`save_autohand_config_internal`]. New API keys go into
+/// `store`, which is only opened when there is one to keep; the file gets
+/// their handles.
+pub fn save_autohand_config_with_store<'s>(
+    working_dir: &str,
+    config: &AutohandConfig,
+    store: impl FnOnce() -> Result<&'s SecretStore, String>,
+) -> Result<(), String> {
     let workspace = Path::new(working_dir);
     let config_dir = workspace.join(".autohand");
     let config_path = config_dir.join("config.json");
@@ -238,6 +274,10 @@ pub fn save_autohand_config_internal(working_dir: &str, config: &AutohandConfig)
     // Note: hooks are managed separately via the hooks_service and stay
     // under the "hooks.definitions" key.  We do NOT overwrite them here.
 
+    if secret_store_service::has_plaintext_secrets(&root) {
+        store()?.seal_json(&mut root)?;
+    }
+
     let pretty = serde_json::to_string_pretty(&root)
         .map_err(|e| format!("Failed to serialize autohand config: {}", e))?;
 
@@ -10,7 +10,10 @@ use crate::services::chat_history_service::{
     rename_session as rename_session_impl, save_chat_session as save_session_impl,
     unarchive_session as unarchive_session_impl, update_summary as update_summary_impl,
 };
+use crate::services::checkpoint_service::delete_checkpoints;
+use crate::services::event_sink::EventRecord;
 use crate::services::indexer::db::IndexDb;
+use crate::services::transcript_service::{delete_transcript, load_transcript};
 
 /// Save a chat session with its messages
 #[tauri::command]
@@ -69,10 +72,21 @@ pub async fn get_session_messages(
     load_session_messages(&project_path, &session_id).await
 }
 
+/// Get every recorded event of a session from its transcript
+#[tauri::command]
+pub async fn get_session_transcript(
+    project_path: String,
+    session_id: String,
+) -> Result<Vec<EventRecord>, String> {
+    load_transcript(&project_path, &session_id)
+}
+
 /// Delete a chat session
 #[tauri::command]
 pub async fn delete_chat_session(project_path: String, session_id: String) -> Result<(), String> {
-    delete_session_impl(&project_path, &session_id).await
+    delete_session_impl(&project_path, &session_id).await?;
+    delete_transcript(&project_path, &session_id)?;
+    delete_checkpoints(&project_path, &session_id).await
 }
 
 /// Get chat history statistics
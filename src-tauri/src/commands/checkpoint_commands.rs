@@ -0,0 +1,38 @@
+use crate::services::checkpoint_service::{self, Checkpoint, CheckpointDiff};
+
+/// Checkpoints taken for a session, oldest first.
+#[tauri::command]
+pub async fn list_checkpoints(project_path: String, session_id: String) -> Result<Vec<Checkpoint>, String> {
+    checkpoint_service::list_checkpoints(&project_path, &session_id).await
+}
+
+/// Diff two checkpoints of a session, or one checkpoint against the current
+/// working tree when `to` is omitted. `file_path` limits the patch text.
+#[tauri::command]
+pub async fn diff_checkpoints(
+    project_path: String,
+    session_id: String,
+    from: String,
+    to: Option<String>,
+    file_path: Option<String>,
+) -> Result<CheckpointDiff, String> {
+    checkpoint_service::diff_checkpoints(
+        &project_path,
+        &session_id,
+        &from,
+        to.as_deref(),
+        file_path.as_deref(),
+    )
+    .await
+}
+
+/// Roll the working tree back to a checkpoint. Returns the checkpoint taken
+/// just before restoring, which can itself be restored to undo.
+#[tauri::command]
+pub async fn restore_checkpoint(
+    project_path: String,
+    session_id: String,
+    checkpoint_id: String,
+) -> Result<Checkpoint, String> {
+    checkpoint_service::restore_checkpoint(&project_path, &session_id, &checkpoint_id).await
+}
@@ -6,26 +6,55 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::Mutex;
 
-use crate::commands::settings_commands::load_all_agent_settings;
+use crate::commands::settings_commands::{load_all_agent_settings, load_global_permission_policy};
+use crate::models::permission::PermissionPolicy;
 use crate::models::*;
-use crate::models::protocol::{ProtocolEvent, SessionEventKind};
+use crate::models::protocol::{AgentFailure, ProtocolEvent, SessionEndReason, SessionEventKind};
 use crate::services::cli_command_builder::build_codex_command_args;
-use crate::services::cli_output_service::{sanitize_cli_output_line, CodexStreamAccumulator};
+use crate::services::cli_output_service::{
+    sanitize_cli_output_line, stream_parser_for, AgentStreamParser, CodexStreamAccumulator,
+};
 use crate::services::codex_sdk_service::{build_codex_thread_prefs, CodexThreadPreferences};
+use crate::services::codex_stream_service::CodexEventParser;
+use crate::services::event_sink::{EventSink, SharedEventSink, TauriEventSink, TeeEventSink};
+use crate::services::env_profile_service::AgentEnvironment;
 use crate::services::execution_mode_service::ExecutionMode;
-use crate::services::executors::{AgentExecutor, ExecutorFactory};
+use crate::services::executors::{
+    own_process_group, restart_delay, AgentExecutor, ExecutorFactory, MAX_RESTARTS,
+};
+use crate::services::executors::acp_executor::AcpDialect;
 use crate::services::executors::pty_executor::PtyExecutor;
 use crate::services::session_manager::{SessionManager, ActiveSession as ManagedSession, PermissionResponse};
+use crate::services::agent_process_pool::{idle_timeout, shutdown_executors, AgentProcessPool, SharedExecutor};
 use crate::services::agent_status_service::ProtocolCache;
+use crate::services::agent_diagnostics_service;
+use crate::services::audit_log_service::{self, AuditRecorder};
+use crate::services::usage_service::{self, UsageMeter};
+use crate::services::budget_service::{
+    self, describe_refusal, exhausted_budgets, load_budgets, BudgetGuard,
+};
+use crate::services::checkpoint_service::{create_checkpoint, CheckpointKind, CheckpointRecorder};
+use crate::services::permission_policy_service::{EffectivePolicy, PermissionGate};
+use crate::services::project_settings_service::load_project_settings;
+use crate::services::run_queue_service::{ConcurrencyLimits, RunQueue};
+use crate::services::sandbox_service::{SandboxMonitor, SandboxProfile};
+use crate::services::session_limits_service::{
+    register_process_group, terminate_session_processes, ActivitySink, SessionActivity,
+    SessionLimits,
+};
+use crate::services::transcript_service::{finalize_transcript, open_transcript};
 use serde::{Deserialize, Serialize};
 use std::process::Command as StdCommand;
 use tokio::sync::Mutex as TokioMutex;
 
+/// Event carrying a `RunQueueSnapshot` whenever runs are queued, started or finish.
+pub const RUN_QUEUE_EVENT: &str = "run-queue-updated";
+
 const CODEX_SDK_RUNNER_SOURCE: &str = include_str!("../../../scripts/codex-sdk-runner.mjs");
 const CODEX_SDK_CORE_SOURCE: &str = include_str!("../../../scripts/codex-sdk-core.mjs");
 
@@ -65,6 +94,8 @@ static SESSIONS: Lazy<Arc<Mutex<HashMap<String, ActiveSession>>>> =
 struct ActiveSession {
     pub session: CLISession,
     pub process: Arc<Mutex<Option<Child>>>,
+    /// Output and idleness of the run, once it has started.
+    pub activity: Option<Arc<SessionActivity>>,
 }
 
 impl Clone for ActiveSession {
@@ -72,10 +103,20 @@ impl Clone for ActiveSession {
         Self {
             session: self.session.clone(),
             process: self.process.clone(),
+            activity: self.activity.clone(),
         }
     }
 }
 
+impl ActiveSession {
+    /// Unix seconds of the session's last output.
+    fn last_activity(&self) -> i64 {
+        self.activity
+            .as_ref()
+            .map_or(self.session.last_activity, |a| a.last_activity_at())
+    }
+}
+
 impl Drop for ActiveSession {
     fn drop(&mut self) {
         // Clean up resources when ActiveSession is dropped
@@ -89,10 +130,10 @@ impl Drop for ActiveSession {
     }
 }
 
-pub(crate) async fn build_agent_command_args(
+pub(crate) fn build_agent_command_args(
     agent: &str,
     message: &str,
-    app_handle: &tauri::AppHandle,
+    current_agent_settings: &AgentSettings,
     execution_mode: Option<String>,
     dangerous_bypass: bool,
     permission_mode: Option<String>,
@@ -100,19 +141,6 @@ pub(crate) async fn build_agent_command_args(
 ) -> Vec<String> {
     let mut args = Vec::new();
 
-    // Try to get agent settings to include model preference
-    let agent_settings = load_all_agent_settings(app_handle.clone())
-        .await
-        .unwrap_or_else(|_| AllAgentSettings::default());
-
-    let current_agent_settings = match agent {
-        "autohand" => &agent_settings.autohand,
-        "claude" => &agent_settings.claude,
-        "codex" => &agent_settings.codex,
-        "gemini" => &agent_settings.gemini,
-        _ => &AgentSettings::default(),
-    };
-
     let parsed_execution_mode = execution_mode.as_deref().and_then(ExecutionMode::from_str);
 
     match agent {
@@ -383,7 +411,7 @@ async fn cleanup_inactive_sessions() -> Result<(), String> {
 
         for (id, session) in sessions.iter() {
             // Remove sessions inactive for configured timeout
-            if current_time - session.session.last_activity > SESSION_TIMEOUT_SECONDS {
+            if current_time - session.last_activity() > SESSION_TIMEOUT_SECONDS {
                 sessions_to_remove.push(id.clone());
             }
         }
@@ -405,15 +433,16 @@ pub(crate) async fn check_command_available(command: &str) -> bool {
 // Try to spawn the command inside a PTY to get unbuffered, real-time output.
 // Falls back to stdio pipes in the caller if PTY spawn fails.
 pub(crate) async fn try_spawn_with_pty(
-    app: tauri::AppHandle,
+    sink: SharedEventSink,
     session_id: String,
     agent: &str,
     program: &str,
     args: &[String],
     working_dir: Option<String>,
+    environment: Option<AgentEnvironment>,
 ) -> Result<(), String> {
     // PTY must be used in blocking context; spawn a blocking task.
-    let app_clone = app.clone();
+    let sink_clone = Arc::clone(&sink);
     let program_s = program.to_string();
     let args_v = args.to_vec();
     let session_id_clone = session_id.clone();
@@ -436,17 +465,24 @@ pub(crate) async fn try_spawn_with_pty(
         for a in &args_v {
             cmd.arg(a);
         }
+        if let Some(environment) = &environment {
+            environment.apply_pty(&mut cmd);
+        }
         if let Some(dir) = working_dir.clone() {
-            println!("🏠 PTY: Setting working directory to: {}", dir);
+            eprintln!("🏠 PTY: Setting working directory to: {}", dir);
             cmd.cwd(dir);
         } else {
-            println!("⚠️  PTY: No working directory - using system default");
+            eprintln!("⚠️  PTY: No working directory - using system default");
         }
 
         let mut child = pair
             .slave
             .spawn_command(cmd)
             .map_err(|e| format!("Failed to spawn PTY command: {}", e))?;
+        // The PTY makes the child a session leader, so its pid is its group.
+        let _group = child
+            .process_id()
+            .map(|pid| register_process_group(&session_id_clone, pid));
 
         // Reader for master end
         let mut reader = pair
@@ -456,29 +492,18 @@ pub(crate) async fn try_spawn_with_pty(
 
         // Read loop: emit chunks as they arrive
         let mut buf = [0u8; 4096];
-        let mut codex_accumulator = if agent_ref.eq_ignore_ascii_case("codex") {
-            Some(CodexStreamAccumulator::new())
-        } else {
-            None
-        };
+        // Claude and Codex write JSON records; they can span PTY reads.
+        let mut structured = stream_parser_for(&agent_ref, &session_id_clone)
+            .map(|parser| (CodexStreamAccumulator::new(), parser));
 
         loop {
             match std::io::Read::read(&mut reader, &mut buf) {
                 Ok(0) => break, // EOF
                 Ok(n) => {
                     let text = String::from_utf8_lossy(&buf[..n]).to_string();
-                    if let Some(acc) = codex_accumulator.as_mut() {
-                        for segment in acc.push_chunk(&text) {
-                            if let Some(filtered) = sanitize_cli_output_line(&agent_ref, &segment) {
-                                let _ = app_clone.emit(
-                                    "cli-stream",
-                                    StreamChunk {
-                                        session_id: session_id_clone.clone(),
-                                        content: filtered,
-                                        finished: false,
-                                    },
-                                );
-                            }
+                    if let Some((lines, parser)) = structured.as_mut() {
+                        for line in lines.push_chunk(&text) {
+                            parser.emit_line(&sink_clone, &line);
                         }
                     } else {
                         for line in text.split_inclusive(['\n', '\r']) {
@@ -487,27 +512,17 @@ pub(crate) async fn try_spawn_with_pty(
                                 continue;
                             }
                             if let Some(filtered) = sanitize_cli_output_line(&agent_ref, trimmed) {
-                                let _ = app_clone.emit(
-                                    "cli-stream",
-                                    StreamChunk {
-                                        session_id: session_id_clone.clone(),
-                                        content: format!("{}\n", filtered),
-                                        finished: false,
-                                    },
-                                );
+                                sink_clone.emit_chunk(StreamChunk {
+                                    session_id: session_id_clone.clone(),
+                                    content: format!("{}\n", filtered),
+                                    finished: false,
+                                });
                             }
                         }
                     }
                 }
                 Err(e) => {
-                    let _ = app_clone.emit(
-                        "cli-stream",
-                        StreamChunk {
-                            session_id: session_id_clone.clone(),
-                            content: format!("\n❌ PTY read error: {}\n", e),
-                            finished: false,
-                        },
-                    );
+                    sink_clone.emit_failure(&session_id_clone, format!("PTY read error: {}", e), false);
                     break;
                 }
             }
@@ -517,33 +532,20 @@ pub(crate) async fn try_spawn_with_pty(
         let status = child
             .wait()
             .map_err(|e| format!("Failed to wait on PTY child: {}", e))?;
-        if let Some(mut acc) = codex_accumulator {
-            if let Some(remaining) = acc.flush() {
-                if let Some(filtered) = sanitize_cli_output_line(&agent_ref, &remaining) {
-                    let _ = app_clone.emit(
-                        "cli-stream",
-                        StreamChunk {
-                            session_id: session_id_clone.clone(),
-                            content: filtered,
-                            finished: false,
-                        },
-                    );
-                }
+        if let Some((mut lines, mut parser)) = structured {
+            if let Some(remaining) = lines.flush() {
+                parser.emit_line(&sink_clone, &remaining);
             }
         }
-        let final_content = if status.success() {
-            String::new()
-        } else {
-            format!("\n❌ Command failed with status\n")
-        };
-        let _ = app_clone.emit(
-            "cli-stream",
-            StreamChunk {
+        if status.success() {
+            sink_clone.emit_chunk(StreamChunk {
                 session_id: session_id_clone,
-                content: final_content,
+                content: String::new(),
                 finished: true,
-            },
-        );
+            });
+        } else {
+            sink_clone.emit_failure(&session_id_clone, "Command failed with status".to_string(), true);
+        }
         Ok(())
     })
     .await
@@ -585,12 +587,13 @@ fn resolve_codex_runner_path() -> Result<PathBuf, String> {
 }
 
 async fn try_spawn_codex_sdk(
-    app: tauri::AppHandle,
+    sink: SharedEventSink,
     session_id: String,
     prompt: String,
     working_dir: Option<String>,
     prefs: CodexThreadPreferences,
     model: Option<String>,
+    environment: Option<AgentEnvironment>,
 ) -> Result<(), String> {
     let script_path = resolve_codex_runner_path()?;
 
@@ -603,6 +606,9 @@ async fn try_spawn_codex_sdk(
     .stdin(Stdio::piped())
     .stdout(Stdio::piped())
     .stderr(Stdio::piped());
+    if let Some(environment) = &environment {
+        environment.apply(&mut cmd);
+    }
 
     let node_modules_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../node_modules");
     if let Ok(canonical) = fs::canonicalize(&node_modules_dir) {
@@ -617,10 +623,14 @@ async fn try_spawn_codex_sdk(
     if let Some(dir) = &working_dir {
         cmd.current_dir(dir);
     }
+    own_process_group(&mut cmd);
 
     let mut child = cmd
         .spawn()
         .map_err(|e| format!("Failed to spawn Codex SDK runner: {}", e))?;
+    let _group = child
+        .id()
+        .map(|pid| register_process_group(&session_id, pid));
 
     let config = CodexSdkInvocation {
         session_id: session_id.clone(),
@@ -641,8 +651,9 @@ async fn try_spawn_codex_sdk(
     }
 
     if let Some(stdout) = child.stdout.take() {
-        let app_for_stdout = app.clone();
+        let sink_for_stdout = Arc::clone(&sink);
         let session_for_stdout = session_id.clone();
+        let mut parser = CodexEventParser::new(&session_id).with_model(config.model.clone());
         tokio::spawn(async move {
             let reader = BufReader::new(stdout);
             let mut lines = reader.lines();
@@ -658,19 +669,28 @@ async fn try_spawn_codex_sdk(
                         let sid = msg.session_id.unwrap_or_else(|| session_for_stdout.clone());
 
                         if let Some(error) = msg.error {
-                            let chunk = StreamChunk {
-                                session_id: sid,
-                                content: format!("❌ Codex error: {}\n", error),
-                                finished: msg.finished,
-                            };
-                            let _ = app_for_stdout.emit("cli-stream", chunk);
+                            sink_for_stdout.emit_failure(
+                                &sid,
+                                format!("Codex error: {}", error),
+                                msg.finished,
+                            );
                         } else if let Some(content) = msg.content {
-                            let chunk = StreamChunk {
-                                session_id: sid,
-                                content,
-                                finished: msg.finished,
-                            };
-                            let _ = app_for_stdout.emit("cli-stream", chunk);
+                            // Each content is one serialized thread event.
+                            match parser.parse_line(&content) {
+                                Some(events) => {
+                                    for event in events {
+                                        sink_for_stdout.emit_event(event);
+                                    }
+                                }
+                                None => {
+                                    let chunk = StreamChunk {
+                                        session_id: sid,
+                                        content,
+                                        finished: msg.finished,
+                                    };
+                                    sink_for_stdout.emit_chunk(chunk);
+                                }
+                            }
                         }
                     }
                     Err(_) => {
@@ -679,7 +699,7 @@ async fn try_spawn_codex_sdk(
                             content: line + "\n",
                             finished: false,
                         };
-                        let _ = app_for_stdout.emit("cli-stream", chunk);
+                        sink_for_stdout.emit_chunk(chunk);
                     }
                 }
             }
@@ -687,7 +707,7 @@ async fn try_spawn_codex_sdk(
     }
 
     if let Some(stderr) = child.stderr.take() {
-        let app_for_stderr = app.clone();
+        let sink_for_stderr = Arc::clone(&sink);
         let session_for_stderr = session_id.clone();
         tokio::spawn(async move {
             let reader = BufReader::new(stderr);
@@ -702,12 +722,11 @@ async fn try_spawn_codex_sdk(
                     Ok(msg) => {
                         let sid = msg.session_id.unwrap_or_else(|| session_for_stderr.clone());
                         if let Some(error) = msg.error {
-                            let chunk = StreamChunk {
-                                session_id: sid,
-                                content: format!("❌ Codex error: {}\n", error),
-                                finished: msg.finished,
-                            };
-                            let _ = app_for_stderr.emit("cli-stream", chunk);
+                            sink_for_stderr.emit_failure(
+                                &sid,
+                                format!("Codex error: {}", error),
+                                msg.finished,
+                            );
                         }
                     }
                     Err(_) => {
@@ -716,7 +735,7 @@ async fn try_spawn_codex_sdk(
                             content: format!("{}\n", line),
                             finished: false,
                         };
-                        let _ = app_for_stderr.emit("cli-stream", chunk);
+                        sink_for_stderr.emit_chunk(chunk);
                     }
                 }
             }
@@ -726,25 +745,19 @@ async fn try_spawn_codex_sdk(
     match child.wait().await {
         Ok(status) => {
             if status.success() {
-                let _ = app.emit(
-                    "cli-stream",
-                    StreamChunk {
-                        session_id,
-                        content: "\n \n".to_string(),
-                        finished: true,
-                    },
-                );
+                sink.emit_chunk(StreamChunk {
+                    session_id,
+                    content: "\n \n".to_string(),
+                    finished: true,
+                });
             } else {
-                let _ = app.emit(
-                    "cli-stream",
-                    StreamChunk {
-                        session_id,
-                        content: format!(
-                            "\n❌ Codex SDK runner exited with status {}\n",
-                            status.code().unwrap_or(-1)
-                        ),
-                        finished: true,
-                    },
+                sink.emit_failure(
+                    &session_id,
+                    format!(
+                        "Codex SDK runner exited with status {}",
+                        status.code().unwrap_or(-1)
+                    ),
+                    true,
                 );
             }
             Ok(())
@@ -753,6 +766,28 @@ async fn try_spawn_codex_sdk(
     }
 }
 
+/// A single prompt to run against an agent, independent of which frontend
+/// (GUI window or headless CLI) asked for it.
+pub(crate) struct AgentRunRequest {
+    pub session_id: String,
+    pub agent: String,
+    pub message: String,
+    pub working_dir: Option<String>,
+    pub execution_mode: Option<String>,
+    pub dangerous_bypass: bool,
+    pub resume_session_id: Option<String>,
+    /// Turns sharing a conversation id reuse the agent process of the
+    /// earlier turns when its protocol keeps it alive.
+    pub conversation_id: Option<String>,
+}
+
+impl AgentRunRequest {
+    /// The conversation the run is a turn of; a run outside one is its own.
+    pub fn conversation_key(&self) -> &str {
+        self.conversation_id.as_deref().unwrap_or(&self.session_id)
+    }
+}
+
 #[tauri::command]
 pub async fn execute_persistent_cli_command(
     app: tauri::AppHandle,
@@ -764,6 +799,7 @@ pub async fn execute_persistent_cli_command(
     #[allow(non_snake_case)] dangerousBypass: Option<bool>,
     #[allow(non_snake_case)] _permissionMode: Option<String>,
     #[allow(non_snake_case)] resumeSessionId: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -778,14 +814,63 @@ pub async fn execute_persistent_cli_command(
         agent, working_dir
     );
 
+    let all_settings = load_all_agent_settings(app.clone())
+        .await
+        .unwrap_or_else(|_| AllAgentSettings::default());
+
+    let request = AgentRunRequest {
+        session_id,
+        agent,
+        message,
+        working_dir,
+        execution_mode: executionMode,
+        dangerous_bypass: dangerousBypass.unwrap_or(false),
+        resume_session_id: resumeSessionId,
+        conversation_id: conversationId,
+    };
+    queue_agent_run(
+        &app,
+        TauriEventSink::shared(app.clone()),
+        request,
+        all_settings,
+        Arc::clone(&*session_manager),
+        Arc::clone(&*protocol_cache),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Put a run in the run queue and start it in the background once the
+/// concurrency caps allow. The returned handle resolves when the run has
+/// ended, or right away if it is cancelled while still queued.
+pub(crate) async fn queue_agent_run(
+    app: &tauri::AppHandle,
+    sink: SharedEventSink,
+    request: AgentRunRequest,
+    all_settings: AllAgentSettings,
+    sm: Arc<TokioMutex<SessionManager>>,
+    protocol_cache_arc: Arc<TokioMutex<ProtocolCache>>,
+) -> Result<tokio::task::JoinHandle<()>, String> {
+    let session_id = request.session_id.clone();
+    let run_queue = app.state::<Arc<TokioMutex<RunQueue>>>().inner().clone();
+    let (agent_name, _) = parse_command_structure(&request.agent, &request.message);
+    let (started, queue_position) = {
+        let mut queue = run_queue.lock().await;
+        queue.set_limits(ConcurrencyLimits::from_settings(&all_settings));
+        let started = queue.enqueue(&session_id, &agent_name, request.working_dir.as_deref(), 0)?;
+        (started, queue.position(&session_id))
+    };
+    emit_run_queue(app, &run_queue).await;
+
     // Register session in the global SESSIONS map so get_active_sessions can return it
     {
         let now = chrono::Utc::now().timestamp();
         let session = CLISession {
             id: session_id.clone(),
-            agent: agent.clone(),
-            command: message.clone(),
-            working_dir: working_dir.clone(),
+            agent: request.agent.clone(),
+            command: request.message.clone(),
+            working_dir: request.working_dir.clone(),
             is_active: true,
             created_at: now,
             last_activity: now,
@@ -793,326 +878,968 @@ pub async fn execute_persistent_cli_command(
         let active = ActiveSession {
             session,
             process: Arc::new(Mutex::new(None)),
+            activity: None,
         };
         let mut sessions = SESSIONS.lock().await;
         sessions.insert(session_id.clone(), active);
     }
 
-    let app_clone = app.clone();
-    let session_id_clone = session_id.clone();
-    let session_id_for_cleanup = session_id.clone();
-    let _current_time = chrono::Utc::now().timestamp();
-    let sm = Arc::clone(&*session_manager);
-    let protocol_cache_arc = Arc::clone(&*protocol_cache);
+    if let Some(position) = queue_position {
+        sink.emit_chunk(StreamChunk {
+            session_id: session_id.clone(),
+            content: format!(
+                "⏳ Waiting for a free session slot (position {} in queue)…\n",
+                position
+            ),
+            finished: false,
+        });
+    }
 
-    tokio::spawn(async move {
-        // Ensure session is removed from SESSIONS when the task ends (any exit path)
-        struct SessionCleanup(String);
-        impl Drop for SessionCleanup {
+    let app = app.clone();
+    Ok(tokio::spawn(async move {
+        if started.await.is_err() {
+            // Cancelled while still queued
+            SESSIONS.lock().await.remove(&session_id);
+            sink.emit_chunk(StreamChunk {
+                session_id,
+                content: "🚫 Cancelled before the run started.\n".to_string(),
+                finished: true,
+            });
+            emit_run_queue(&app, &run_queue).await;
+            return;
+        }
+        emit_run_queue(&app, &run_queue).await;
+
+        // Free the slot when the run ends (any exit path)
+        struct QueueSlot(tauri::AppHandle, Arc<TokioMutex<RunQueue>>, String);
+        impl Drop for QueueSlot {
             fn drop(&mut self) {
-                let id = self.0.clone();
+                let app = self.0.clone();
+                let run_queue = Arc::clone(&self.1);
+                let id = self.2.clone();
                 tokio::spawn(async move {
-                    let mut sessions = SESSIONS.lock().await;
-                    sessions.remove(&id);
+                    run_queue.lock().await.complete(&id);
+                    emit_run_queue(&app, &run_queue).await;
                 });
             }
         }
-        let _cleanup = SessionCleanup(session_id_for_cleanup);
+        let process_pool = app.state::<Arc<TokioMutex<AgentProcessPool>>>().inner().clone();
+        let _slot = QueueSlot(app, run_queue, session_id);
 
-        // Parse command structure to handle both "/agent subcommand" and direct subcommands
-        let (agent_name, actual_message) = parse_command_structure(&agent, &message);
+        run_agent_session(sink, request, all_settings, sm, protocol_cache_arc, process_pool).await;
+    }))
+}
 
-        // Emit session status info
-        let info_chunk = StreamChunk {
-            session_id: session_id_clone.clone(),
-            content: format!("🔗 Agent: {} | Command: {}\n", agent_name, actual_message),
-            finished: false,
-        };
-        let _ = app_clone.emit("cli-stream", info_chunk);
-
-        let dangerous_bypass = dangerousBypass.unwrap_or(false);
-
-        // Only try the Codex SDK runner when the transport is NOT set to a
-        // protocol mode (acp/json-rpc).  When the user selects ACP transport
-        // we skip the SDK and let the AcpExecutor handle it.
-        if agent_name.eq_ignore_ascii_case("codex") {
-            let all_agent_settings = load_all_agent_settings(app_clone.clone())
-                .await
-                .unwrap_or_else(|_| AllAgentSettings::default());
-
-            let codex_transport = all_agent_settings.codex.transport.as_deref();
-            let use_sdk = !matches!(codex_transport, Some("acp") | Some("json-rpc"));
-
-            if use_sdk {
-                let current_agent_settings = all_agent_settings.codex.clone();
-                let parsed_execution_mode = executionMode.as_deref().and_then(ExecutionMode::from_str);
-                let prefs = build_codex_thread_prefs(parsed_execution_mode, dangerous_bypass);
-                let model = current_agent_settings.model.clone();
-
-                match try_spawn_codex_sdk(
-                    app_clone.clone(),
-                    session_id_clone.clone(),
-                    actual_message.clone(),
-                    working_dir.clone(),
-                    prefs,
-                    model,
-                )
-                .await
-                {
-                    Ok(()) => {
-                        return;
-                    }
-                    Err(err) => {
-                        let fallback_chunk = StreamChunk {
-                            session_id: session_id_clone.clone(),
-                            content: format!(
-                                "ℹ️ Codex SDK runner unavailable ({}). Falling back to CLI…\n",
-                                err
-                            ),
-                            finished: false,
-                        };
-                        let _ = app_clone.emit("cli-stream", fallback_chunk);
-                    }
-                }
+/// Push the current queue state to the frontend on `run-queue-updated`.
+pub(crate) async fn emit_run_queue(app: &tauri::AppHandle, run_queue: &Arc<TokioMutex<RunQueue>>) {
+    let snapshot = run_queue.lock().await.snapshot();
+    let _ = app.emit(RUN_QUEUE_EVENT, snapshot);
+}
+
+/// Saved settings for one of the built-in agents; defaults for anything else.
+pub(crate) fn settings_for_agent(all_settings: &AllAgentSettings, agent: &str) -> AgentSettings {
+    match agent {
+        "claude" => all_settings.claude.clone(),
+        "codex" => all_settings.codex.clone(),
+        "gemini" => all_settings.gemini.clone(),
+        "cursor" => all_settings.cursor.clone(),
+        "copilot" => all_settings.copilot.clone(),
+        "pi" => all_settings.pi.clone(),
+        "opencode" => all_settings.opencode.clone(),
+        "vibe" => all_settings.vibe.clone(),
+        "amp" => all_settings.amp.clone(),
+        "autohand" => all_settings.autohand.clone(),
+        _ => AgentSettings::default(),
+    }
+}
+
+/// Run one prompt to completion, streaming everything the agent produces
+/// into `sink`.
+///
+/// When the run has a working directory, every event is also appended to the
+/// conversation's transcript under `.commander/transcripts/`, and the chat
+/// history entry for the conversation is rebuilt from it once the run ends. Permission
+/// decisions and write, delete and execute tool results go to the audit log.
+pub(crate) async fn run_agent_session(
+    sink: SharedEventSink,
+    request: AgentRunRequest,
+    all_settings: AllAgentSettings,
+    sm: Arc<TokioMutex<SessionManager>>,
+    protocol_cache_arc: Arc<TokioMutex<ProtocolCache>>,
+    process_pool: Arc<TokioMutex<AgentProcessPool>>,
+) {
+    let session_id = request.session_id.clone();
+    let project_path = request.working_dir.clone();
+    let (agent_name, _) = parse_command_structure(&request.agent, &request.message);
+    let model = settings_for_agent(&all_settings, &agent_name).model;
+    // Each GUI send is a new run; its turns still make up one conversation.
+    let conversation_key = request.conversation_key().to_string();
+
+    // A budget that stops sessions and is already spent keeps the agent
+    // from starting at all; later turns are checked by the guard below.
+    let budgets = load_budgets().unwrap_or_else(|e| {
+        eprintln!("⚠️ Ignoring budgets: {}", e);
+        Vec::new()
+    });
+    if let Some(db) = usage_service::installed().filter(|_| !budgets.is_empty()) {
+        match exhausted_budgets(
+            &db,
+            &budgets,
+            &agent_name,
+            project_path.as_deref(),
+            budget_service::today(),
+        ) {
+            Ok(exhausted) if !exhausted.is_empty() => {
+                let reasons: String = exhausted.iter().map(describe_refusal).collect();
+                sink.emit_event(ProtocolEvent::Error {
+                    session_id: session_id.clone(),
+                    message: reasons.trim_end().to_string(),
+                    failure: None,
+                });
+                sink.emit_chunk(StreamChunk {
+                    session_id,
+                    content: reasons,
+                    finished: true,
+                });
+                return;
             }
+            Ok(_) => {}
+            Err(e) => eprintln!("⚠️ Failed to check budgets for {}: {}", session_id, e),
         }
+    }
 
-        // Load agent settings
-        let wd = working_dir.as_deref().unwrap_or("");
-        let settings = crate::commands::settings_commands::load_all_agent_settings(app_clone.clone())
+    let transcript = project_path.as_deref().and_then(|dir| {
+        match open_transcript(dir, &conversation_key) {
+            Ok(transcript) => Some(Arc::new(transcript)),
+            Err(e) => {
+                eprintln!("⚠️ Session transcript disabled for {}: {}", session_id, e);
+                None
+            }
+        }
+    });
+
+    let sink = match &transcript {
+        Some(transcript) => {
+            // The prompt never flows through the executors, so record it here
+            // to mark the start of the turn.
+            transcript.emit_event(ProtocolEvent::Message {
+                session_id: session_id.clone(),
+                role: "user".to_string(),
+                content: request.message.clone(),
+            });
+            let transcript: SharedEventSink = transcript.clone();
+            TeeEventSink::shared(vec![sink, transcript])
+        }
+        None => sink,
+    };
+
+    // Checkpoints need a git working tree; other directories just run without them.
+    let checkpoints = match project_path.as_deref() {
+        Some(dir) if Path::new(dir).join(".git").exists() => {
+            if let Err(e) = create_checkpoint(
+                dir,
+                &session_id,
+                CheckpointKind::BeforePrompt,
+                &request.message,
+                None,
+            )
             .await
-            .unwrap_or_else(|_| AllAgentSettings::default());
-        let agent_settings = match agent_name.as_str() {
-            "claude" => settings.claude.clone(),
-            "codex" => settings.codex.clone(),
-            "gemini" => settings.gemini.clone(),
-            "cursor" => settings.cursor.clone(),
-            "copilot" => settings.copilot.clone(),
-            "pi" => settings.pi.clone(),
-            "opencode" => settings.opencode.clone(),
-            "vibe" => settings.vibe.clone(),
-            "amp" => settings.amp.clone(),
-            _ => AgentSettings::default(),
-        };
+            {
+                eprintln!("⚠️ Failed to checkpoint {} before the prompt: {}", session_id, e);
+            }
+            Some(CheckpointRecorder::start(dir, &session_id))
+        }
+        _ => None,
+    };
+    let sink = match &checkpoints {
+        Some(recorder) => {
+            let recorder: SharedEventSink = recorder.clone();
+            TeeEventSink::shared(vec![sink, recorder])
+        }
+        None => sink,
+    };
+
+    // The recorder sees what passes the permission gate, including the
+    // requests a rule answered.
+    let sink = match audit_log_service::installed() {
+        Some(log) => {
+            let recorder: SharedEventSink = Arc::new(AuditRecorder::new(
+                log,
+                project_path.as_deref(),
+                &agent_name,
+                &session_id,
+            ));
+            TeeEventSink::shared(vec![sink, recorder])
+        }
+        None => sink,
+    };
+
+    // Budgets are checked once the meter has recorded a turn's usage.
+    let sink = match usage_service::installed() {
+        Some(db) if !budgets.is_empty() => BudgetGuard::shared(
+            sink,
+            db,
+            budgets,
+            &agent_name,
+            &session_id,
+            project_path.as_deref(),
+            Arc::clone(&sm),
+        ),
+        _ => sink,
+    };
+
+    // Outermost, so the cost it fills in reaches the transcript and the UI.
+    let sink = match usage_service::installed() {
+        Some(db) => UsageMeter::shared(
+            sink,
+            db,
+            usage_service::load_pricing_or_default(),
+            &agent_name,
+            &session_id,
+            project_path.as_deref(),
+            model.as_deref(),
+        ),
+        None => sink,
+    };
+
+    drive_agent_session(sink, request, all_settings, sm, protocol_cache_arc, process_pool).await;
+
+    if let Some(recorder) = checkpoints {
+        recorder.finish().await;
+    }
+
+    if let (Some(dir), Some(_)) = (project_path.as_deref(), transcript) {
+        if let Err(e) = finalize_transcript(dir, &conversation_key, &agent_name, model).await {
+            eprintln!("⚠️ Failed to update chat history for {}: {}", conversation_key, e);
+        }
+    }
+}
 
-        // Create executor using factory (protocol-aware)
-        // Honour the per-agent transport override from settings when present.
-        // resolved_binary_path: the actual binary to spawn (may differ from agent_name for sidecars).
-        // agent_name is preserved for session management, UI display, and lookups.
-        let mut resolved_binary_path = agent_name.clone();
-        let mut sidecar_resolved = false;
-
-        let cache = protocol_cache_arc.lock().await;
-        let mut executor = match agent_settings.transport.as_deref() {
-            Some("json-rpc") => {
-                let flag = cache.get(&agent_name).and_then(|e| e.flag_variant.clone());
-                Box::new(crate::services::executors::rpc_executor::RpcExecutor::new(flag))
-                    as Box<dyn AgentExecutor>
+/// The global permission policy combined with the project's. A policy that
+/// cannot be read is skipped, leaving its requests to the user.
+fn effective_permission_policy(project_path: Option<&str>) -> EffectivePolicy {
+    let global = load_global_permission_policy().unwrap_or_else(|e| {
+        eprintln!("⚠️ Ignoring global permission policy: {}", e);
+        PermissionPolicy::default()
+    });
+    let project = project_path.and_then(|dir| match load_project_settings(dir) {
+        Ok(settings) => settings.permission_policy,
+        Err(e) => {
+            eprintln!("⚠️ Ignoring project permission policy: {}", e);
+            None
+        }
+    });
+    EffectivePolicy::new(&global, project.as_ref())
+}
+
+/// How long an interrupted turn gets to wind down before its agent is killed.
+const INTERRUPT_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// What a running protocol turn is steered by: its limits, the user's
+/// permission answers and the abort signal.
+struct TurnControl<'a> {
+    activity: &'a SessionActivity,
+    perm_rx: &'a mut tokio::sync::mpsc::UnboundedReceiver<PermissionResponse>,
+    abort_rx: &'a mut tokio::sync::oneshot::Receiver<()>,
+}
+
+/// Forward permission answers to a protocol turn until it ends, the run is
+/// aborted or it goes past one of its limits. Aborting interrupts the turn
+/// of a long-lived process and only kills the agent when it cannot be
+/// interrupted or does not stop in time; a run past its limits is killed.
+async fn supervise_turn(executor: &dyn AgentExecutor, control: &mut TurnControl<'_>) {
+    let TurnControl {
+        activity,
+        perm_rx,
+        abort_rx,
+    } = control;
+    let mut permissions_open = true;
+    loop {
+        tokio::select! {
+            maybe_resp = perm_rx.recv(), if permissions_open => {
+                match maybe_resp {
+                    Some(resp) => {
+                        let _ = executor.respond_permission(&resp.request_id, resp.approved).await;
+                    }
+                    None => permissions_open = false,
+                }
             }
-            Some("acp") => {
-                let flag = cache.get(&agent_name).and_then(|e| e.flag_variant.clone());
-                // For codex, resolve "codex-acp" sidecar binary (bundled or PATH).
-                if agent_name.eq_ignore_ascii_case("codex") {
-                    match crate::services::sidecar::resolve_sidecar(
-                        "codex-acp",
-                        crate::services::sidecar::exe_dir().as_deref(),
-                    ) {
-                        Ok(path) => {
-                            resolved_binary_path = path.to_string_lossy().to_string();
-                            sidecar_resolved = true;
-                        }
-                        Err(_) => {
-                            // Fall through with "codex-acp" as bare name
-                            // so the executor can try which::which or produce a clear error.
-                            resolved_binary_path = "codex-acp".to_string();
-                        }
+            _ = activity.limit_reached() => {
+                let _ = executor.abort().await;
+                break;
+            }
+            _ = &mut *abort_rx => {
+                activity.end(SessionEndReason::Cancelled);
+                if executor.supports_multi_turn() && executor.interrupt().await.is_ok() {
+                    let deadline = tokio::time::Instant::now() + INTERRUPT_GRACE;
+                    while executor.is_busy() && tokio::time::Instant::now() < deadline {
+                        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
                     }
                 }
-                Box::new(crate::services::executors::acp_executor::AcpExecutor::new(flag))
-                    as Box<dyn AgentExecutor>
+                if executor.is_busy() {
+                    let _ = executor.abort().await;
+                }
+                break;
             }
-            _ => ExecutorFactory::create(&agent_name, &cache),
-        };
-        drop(cache);
+            _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
+                if !executor.is_busy() {
+                    break;
+                }
+            }
+        }
+    }
+}
 
-        // Skip PATH check when sidecar was resolved (binary is bundled, not in PATH)
-        if !sidecar_resolved && !check_command_available(&resolved_binary_path).await {
-            let error_chunk = StreamChunk {
-                session_id: session_id_clone.clone(),
-                content: format!(
-                    "❌ Command '{}' not found. Please install it first:\n\n",
-                    agent_name
-                ),
-                finished: false,
-            };
-            let _ = app_clone.emit("cli-stream", error_chunk);
-
-            let install_instructions = match agent_name.as_str() {
-                "claude" => "Install Claude CLI: https://docs.anthropic.com/claude/docs/cli\n",
-                "codex" => "Install Codex CLI: npm install -g @openai/codex\n",
-                "gemini" => "Install Gemini CLI: npm install -g @google/gemini-cli\n",
-                "cursor" => "Install Cursor CLI: curl https://cursor.com/install -fsS | bash\n",
-                "copilot" => "Install GitHub Copilot CLI: npm install -g @github/copilot\n",
-                "pi" => "Install Pi coding agent: npm install -g @mariozechner/pi-coding-agent\n",
-                "opencode" => "Install OpenCode: npm install -g opencode-ai\n",
-                "vibe" => "Install Mistral Vibe: curl -LsSf https://mistral.ai/vibe/install.sh | bash\n",
-                "amp" => "Install Amp: npm install -g @sourcegraph/amp\n",
-                _ => "Please check the official documentation for installation instructions.\n",
-            };
+/// How to start an agent again after it crashes.
+struct Relaunch<'a> {
+    binary: &'a str,
+    working_dir: &'a str,
+    settings: &'a AgentSettings,
+    /// Resume id the run was started with, used until the agent reports its own.
+    resume_session_id: Option<&'a str>,
+}
 
-            let instruction_chunk = StreamChunk {
-                session_id: session_id_clone,
-                content: install_instructions.to_string(),
-                finished: true,
-            };
-            let _ = app_clone.emit("cli-stream", instruction_chunk);
+/// Restart an agent that died during a turn, backing off between attempts,
+/// resuming the agent's own session and replaying the in-flight prompt.
+/// Gives up after `MAX_RESTARTS` attempts or on an exit that a restart
+/// would not fix.
+async fn recover_crashed_agent(
+    executor: &mut dyn AgentExecutor,
+    sink: &SharedEventSink,
+    session_id: &str,
+    message: &str,
+    relaunch: &Relaunch<'_>,
+    control: &mut TurnControl<'_>,
+) {
+    let mut attempt = 0;
+    while let Some(died) = executor.exit_error() {
+        attempt += 1;
+        // A restart will not sign the agent in or update it.
+        let failure = agent_diagnostics_service::last_failure(session_id);
+        if !died.is_restartable() || failure.is_some_and(|kind| !kind.is_transient()) {
+            sink.emit_event(ProtocolEvent::Error {
+                session_id: session_id.to_string(),
+                message: format!("Agent exited ({})", died),
+                failure: failure.map(AgentFailure::from),
+            });
+            return;
+        }
+        if attempt > MAX_RESTARTS {
+            sink.emit_event(ProtocolEvent::Error {
+                session_id: session_id.to_string(),
+                message: format!(
+                    "Agent exited ({}) and did not recover after {} restarts",
+                    died, MAX_RESTARTS
+                ),
+                failure: failure.map(AgentFailure::from),
+            });
+            sink.emit_event(ProtocolEvent::SessionEvent {
+                session_id: session_id.to_string(),
+                event: SessionEventKind::ReconnectFailed,
+            });
             return;
         }
 
-        let is_protocol = executor.protocol().is_some();
-
-        // Register session in SessionManager with channels
-        let (perm_tx, mut perm_rx) = tokio::sync::mpsc::unbounded_channel::<PermissionResponse>();
-        let (abort_tx, mut abort_rx) = tokio::sync::oneshot::channel::<()>();
+        let delay = restart_delay(attempt);
+        sink.emit_event(ProtocolEvent::SessionEvent {
+            session_id: session_id.to_string(),
+            event: SessionEventKind::Reconnecting,
+        });
+        sink.emit_chunk(StreamChunk {
+            session_id: session_id.to_string(),
+            content: format!(
+                "🔄 Agent exited ({}); restarting in {}s (attempt {}/{})…\n",
+                died,
+                delay.as_secs(),
+                attempt,
+                MAX_RESTARTS
+            ),
+            finished: false,
+        });
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = &mut *control.abort_rx => {
+                control.activity.end(SessionEndReason::Cancelled);
+                return;
+            }
+        }
 
+        agent_diagnostics_service::clear_failures(session_id);
+        let resume = executor
+            .agent_session_id()
+            .or_else(|| relaunch.resume_session_id.map(String::from));
+        if let Err(e) = executor
+            .execute(
+                sink,
+                session_id,
+                relaunch.binary,
+                message,
+                relaunch.working_dir,
+                relaunch.settings,
+                resume.as_deref(),
+            )
+            .await
         {
-            let mut mgr = sm.lock().await;
-            mgr.insert(ManagedSession {
-                session_id: session_id_clone.clone(),
-                permission_sender: perm_tx,
-                abort_sender: Some(abort_tx),
+            sink.emit_event(ProtocolEvent::Error {
+                session_id: session_id.to_string(),
+                message: format!("Agent could not be restarted: {}", e),
+                failure: None,
+            });
+            sink.emit_event(ProtocolEvent::SessionEvent {
+                session_id: session_id.to_string(),
+                event: SessionEventKind::ReconnectFailed,
             });
+            return;
         }
+        sink.emit_event(ProtocolEvent::SessionEvent {
+            session_id: session_id.to_string(),
+            event: SessionEventKind::Reconnected,
+        });
+        supervise_turn(executor, control).await;
+    }
+}
 
-        // Also register in legacy SESSIONS map so get_active_sessions/terminate see it
-        {
-            let cli_session = CLISession {
-                id: session_id_clone.clone(),
-                agent: agent_name.clone(),
-                command: actual_message.clone(),
-                working_dir: working_dir.clone(),
-                is_active: true,
-                created_at: chrono::Utc::now().timestamp(),
-                last_activity: chrono::Utc::now().timestamp(),
-            };
-            let mut sessions = SESSIONS.lock().await;
-            sessions.insert(session_id_clone.clone(), ActiveSession {
-                session: cli_session,
-                process: Arc::new(Mutex::new(None)),
+/// How a follow-up on a kept agent process went.
+enum PooledTurn {
+    /// The turn ran; `alive` tells whether the process is still up.
+    Done { alive: bool },
+    /// The run was aborted while queued behind an earlier turn.
+    Aborted,
+    /// The process can no longer take prompts; start a fresh one.
+    Unavailable,
+}
+
+/// Run a follow-up on the process kept from a conversation's earlier turns,
+/// queueing behind a turn that is still running.
+async fn run_pooled_turn(
+    shared: &SharedExecutor,
+    sink: &SharedEventSink,
+    session_id: &str,
+    message: &str,
+    relaunch: &Relaunch<'_>,
+    control: &mut TurnControl<'_>,
+) -> PooledTurn {
+    let mut executor = match shared.try_lock() {
+        Ok(executor) => executor,
+        Err(_) => {
+            sink.emit_event(ProtocolEvent::StateChange {
+                session_id: session_id.to_string(),
+                status: "queued".to_string(),
+                context_percent: None,
+                usage: None,
+                agent_session_id: None,
             });
+            tokio::select! {
+                executor = shared.lock() => executor,
+                _ = &mut *control.abort_rx => {
+                    control.activity.end(SessionEndReason::Cancelled);
+                    return PooledTurn::Aborted;
+                }
+            }
         }
+    };
+    if !executor.is_alive() {
+        return PooledTurn::Unavailable;
+    }
+    if let Err(e) = executor.send_prompt(sink, session_id, message).await {
+        eprintln!("⚠️ Kept agent process rejected the follow-up, starting a new one: {}", e);
+        return PooledTurn::Unavailable;
+    }
+    supervise_turn(&**executor, control).await;
+    recover_crashed_agent(&mut **executor, sink, session_id, message, relaunch, control).await;
+    PooledTurn::Done {
+        alive: executor.is_alive(),
+    }
+}
 
-        // Execute the chosen executor
-        let result = executor.execute(
-            &app_clone,
-            &session_id_clone,
-            &resolved_binary_path,
-            &actual_message,
-            wd,
-            &agent_settings,
-            resumeSessionId.as_deref(),
-        ).await;
-
-        match result {
-            Err(e) => {
-                if is_protocol {
-                    // Protocol executor failed to start -- fall back to PTY
-                    let _ = app_clone.emit("protocol-event", ProtocolEvent::SessionEvent {
-                        session_id: session_id_clone.clone(),
-                        event: SessionEventKind::FallbackToPty,
-                    });
+/// Picks the executor (Codex SDK runner, ACP, JSON-RPC or PTY) from the
+/// agent's settings, falls back to PTY when a protocol executor fails to
+/// start, and for protocol sessions keeps forwarding permission responses
+/// until the turn ends or the session is aborted.
+///
+/// Follow-ups in a conversation go to the agent process kept from its
+/// earlier turns when the protocol allows it; a fresh process is kept for
+/// `session_timeout_minutes` of idleness.
+///
+/// Runs are stopped once they go past the agent's `max_run_minutes`,
+/// `max_output_bytes` or idle timeout; `SessionEnded` says how each run ended.
+///
+/// Unlike [`run_agent_session`] this records no transcript, checkpoints,
+/// audit entries or usage, for runs in throwaway trees.
+pub(crate) async fn drive_agent_session(
+    sink: SharedEventSink,
+    request: AgentRunRequest,
+    all_settings: AllAgentSettings,
+    sm: Arc<TokioMutex<SessionManager>>,
+    protocol_cache_arc: Arc<TokioMutex<ProtocolCache>>,
+    process_pool: Arc<TokioMutex<AgentProcessPool>>,
+) {
+    let AgentRunRequest {
+        session_id: session_id_clone,
+        agent,
+        message,
+        working_dir,
+        execution_mode,
+        dangerous_bypass,
+        resume_session_id,
+        conversation_id,
+    } = request;
+
+    // Ensure session is removed from SESSIONS when the run ends (any exit path)
+    struct SessionCleanup(String);
+    impl Drop for SessionCleanup {
+        fn drop(&mut self) {
+            let id = self.0.clone();
+            tokio::spawn(async move {
+                let mut sessions = SESSIONS.lock().await;
+                sessions.remove(&id);
+            });
+        }
+    }
+    let _cleanup = SessionCleanup(session_id_clone.clone());
+
+    // Parse command structure to handle both "/agent subcommand" and direct subcommands
+    let (agent_name, actual_message) = parse_command_structure(&agent, &message);
+
+    // Pick the agent's settings
+    let wd = working_dir.as_deref().unwrap_or("");
+    let agent_settings = settings_for_agent(&all_settings, &agent_name);
+
+    // Everything the run emits counts towards its output and idle limits.
+    let activity = Arc::new(SessionActivity::new(SessionLimits::from_settings(
+        &agent_settings,
+    )));
+    let mut sink = ActivitySink::shared(sink, Arc::clone(&activity));
+
+    // The executors sandbox the agent themselves; the run watches for what
+    // the sandbox blocks.
+    let sandbox = SandboxProfile::for_run(&agent_settings, &agent_name, wd);
+    if let Some(profile) = &sandbox {
+        sink = SandboxMonitor::shared(sink, &session_id_clone, profile);
+    }
+
+    // Emit session status info
+    let info_chunk = StreamChunk {
+        session_id: session_id_clone.clone(),
+        content: format!("🔗 Agent: {} | Command: {}\n", agent_name, actual_message),
+        finished: false,
+    };
+    sink.emit_chunk(info_chunk);
+    if let Some(profile) = &sandbox {
+        sink.emit_chunk(StreamChunk {
+            session_id: session_id_clone.clone(),
+            content: profile.describe(),
+            finished: false,
+        });
+    }
+
+    // The executors apply the environment themselves; a profile that cannot
+    // be resolved stops the run before anything starts.
+    let environment = match AgentEnvironment::for_run(&agent_settings, wd) {
+        Ok(environment) => environment,
+        Err(e) => {
+            sink.emit_failure(&session_id_clone, format!("Environment: {}", e), true);
+            return;
+        }
+    };
+    if let Some(environment) = &environment {
+        sink.emit_chunk(StreamChunk {
+            session_id: session_id_clone.clone(),
+            content: environment.describe(),
+            finished: false,
+        });
+    }
+
+    // Only try the Codex SDK runner when the transport is NOT set to a
+    // protocol mode (acp/json-rpc).  When the user selects ACP transport
+    // we skip the SDK and let the AcpExecutor handle it.  The SDK runner is
+    // not sandboxed, so sandboxed runs go through the CLI.
+    if agent_name.eq_ignore_ascii_case("codex") {
+        let codex_transport = all_settings.codex.transport.as_deref();
+        let use_sdk =
+            !matches!(codex_transport, Some("acp") | Some("json-rpc")) && sandbox.is_none();
+
+        if use_sdk {
+            let current_agent_settings = all_settings.codex.clone();
+            let parsed_execution_mode = execution_mode.as_deref().and_then(ExecutionMode::from_str);
+            let prefs = build_codex_thread_prefs(parsed_execution_mode, dangerous_bypass);
+            let model = current_agent_settings.model.clone();
+
+            let run = try_spawn_codex_sdk(
+                Arc::clone(&sink),
+                session_id_clone.clone(),
+                actual_message.clone(),
+                working_dir.clone(),
+                prefs,
+                model,
+                environment,
+            );
+            match run_enforcing_limits(run, &activity, &session_id_clone, None).await {
+                Ok(()) => {
+                    emit_session_ended(&sink, &session_id_clone, &activity);
+                    return;
+                }
+                Err(err) => {
                     let fallback_chunk = StreamChunk {
                         session_id: session_id_clone.clone(),
                         content: format!(
-                            "ℹ️ Protocol executor unavailable ({}). Falling back to PTY…\n",
-                            e
+                            "ℹ️ Codex SDK runner unavailable ({}). Falling back to CLI…\n",
+                            err
                         ),
                         finished: false,
                     };
-                    let _ = app_clone.emit("cli-stream", fallback_chunk);
+                    sink.emit_chunk(fallback_chunk);
+                }
+            }
+        }
+    }
 
-                    let mut pty = PtyExecutor::new();
-                    if let Err(pty_err) = pty.execute(
-                        &app_clone,
-                        &session_id_clone,
-                        &agent_name,
-                        &actual_message,
-                        wd,
-                        &agent_settings,
-                        None,
-                    ).await {
-                        let error_chunk = StreamChunk {
-                            session_id: session_id_clone.clone(),
-                            content: format!("❌ PTY fallback error: {}\n", pty_err),
-                            finished: true,
-                        };
-                        let _ = app_clone.emit("cli-stream", error_chunk);
+    // Create executor using factory (protocol-aware)
+    // Honour the per-agent transport override from settings when present.
+    // resolved_binary_path: the actual binary to spawn (may differ from agent_name for sidecars).
+    // agent_name is preserved for session management, UI display, and lookups.
+    let mut resolved_binary_path = agent_name.clone();
+    let mut sidecar_resolved = false;
+
+    let dialect = if agent_settings.acp_legacy_envelope {
+        AcpDialect::Legacy
+    } else {
+        AcpDialect::JsonRpc
+    };
+    let cache = protocol_cache_arc.lock().await;
+    let mut executor = match agent_settings.transport.as_deref() {
+        Some("json-rpc") => {
+            let flag = cache.get(&agent_name).and_then(|e| e.flag_variant.clone());
+            Box::new(crate::services::executors::rpc_executor::RpcExecutor::new(flag))
+                as Box<dyn AgentExecutor>
+        }
+        Some("acp") => {
+            let flag = cache.get(&agent_name).and_then(|e| e.flag_variant.clone());
+            // For codex, resolve "codex-acp" sidecar binary (bundled or PATH).
+            if agent_name.eq_ignore_ascii_case("codex") {
+                match crate::services::sidecar::resolve_sidecar(
+                    "codex-acp",
+                    crate::services::sidecar::exe_dir().as_deref(),
+                ) {
+                    Ok(path) => {
+                        resolved_binary_path = path.to_string_lossy().to_string();
+                        sidecar_resolved = true;
+                    }
+                    Err(_) => {
+                        // Fall through with "codex-acp" as bare name
+                        // so the executor can try which::which or produce a clear error.
+                        resolved_binary_path = "codex-acp".to_string();
                     }
-                } else {
-                    // PTY executor error
-                    let error_chunk = StreamChunk {
-                        session_id: session_id_clone.clone(),
-                        content: format!("❌ Executor error: {}\n", e),
-                        finished: true,
-                    };
-                    let _ = app_clone.emit("cli-stream", error_chunk);
                 }
             }
-            Ok(()) if is_protocol => {
-                // ACP/RPC started successfully -- its background reader task manages
-                // the stream lifecycle. We need to forward permissions and wait for abort.
-                // The loop exits when:
-                //   (a) perm_rx returns None (all senders dropped),
-                //   (b) abort signal received, or
-                //   (c) executor's background reader finished (agent process exited).
-                loop {
-                    tokio::select! {
-                        maybe_resp = perm_rx.recv() => {
-                            match maybe_resp {
-                                Some(resp) => {
-                                    let _ = executor.respond_permission(&resp.request_id, resp.approved).await;
-                                }
-                                None => break, // All senders dropped
-                            }
-                        }
-                        _ = &mut abort_rx => {
-                            let _ = executor.abort().await;
-                            break;
-                        }
-                        _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
-                            if !executor.is_alive() {
-                                break;
-                            }
-                        }
+            Box::new(
+                crate::services::executors::acp_executor::AcpExecutor::new(flag)
+                    .with_dialect(dialect),
+            ) as Box<dyn AgentExecutor>
+        }
+        _ => ExecutorFactory::create(&agent_name, &cache, dialect),
+    };
+    drop(cache);
+
+    // Skip PATH check when sidecar was resolved (binary is bundled, not in PATH)
+    if !sidecar_resolved && !check_command_available(&resolved_binary_path).await {
+        sink.emit_failure(
+            &session_id_clone,
+            format!("Command '{}' not found. Please install it first.", agent_name),
+            false,
+        );
+
+        let install_instructions = match agent_name.as_str() {
+            "claude" => "Install Claude CLI: https://docs.anthropic.com/claude/docs/cli\n",
+            "codex" => "Install Codex CLI: npm install -g @openai/codex\n",
+            "gemini" => "Install Gemini CLI: npm install -g @google/gemini-cli\n",
+            "cursor" => "Install Cursor CLI: curl https://cursor.com/install -fsS | bash\n",
+            "copilot" => "Install GitHub Copilot CLI: npm install -g @github/copilot\n",
+            "pi" => "Install Pi coding agent: npm install -g @mariozechner/pi-coding-agent\n",
+            "opencode" => "Install OpenCode: npm install -g opencode-ai\n",
+            "vibe" => "Install Mistral Vibe: curl -LsSf https://mistral.ai/vibe/install.sh | bash\n",
+            "amp" => "Install Amp: npm install -g @sourcegraph/amp\n",
+            _ => "Please check the official documentation for installation instructions.\n",
+        };
+
+        let instruction_chunk = StreamChunk {
+            session_id: session_id_clone,
+            content: install_instructions.to_string(),
+            finished: true,
+        };
+        sink.emit_chunk(instruction_chunk);
+        return;
+    }
+
+    let is_protocol = executor.protocol().is_some();
+
+    // Register session in SessionManager with channels
+    let (perm_tx, mut perm_rx) = tokio::sync::mpsc::unbounded_channel::<PermissionResponse>();
+    let (abort_tx, mut abort_rx) = tokio::sync::oneshot::channel::<()>();
+
+    // Permission requests pass the policy gate; only those no rule decides
+    // reach the user.
+    let policy = effective_permission_policy(working_dir.as_deref());
+    let sink: SharedEventSink = {
+        let mut mgr = sm.lock().await;
+        let allowances =
+            mgr.session_allowances(conversation_id.as_deref().unwrap_or(&session_id_clone));
+        let gate = Arc::new(PermissionGate::new(
+            sink,
+            &session_id_clone,
+            policy,
+            working_dir.as_deref(),
+            allowances,
+            perm_tx.clone(),
+        ));
+        mgr.insert(ManagedSession {
+            session_id: session_id_clone.clone(),
+            permission_sender: perm_tx,
+            abort_sender: Some(abort_tx),
+        });
+        mgr.set_permission_gate(&session_id_clone, Arc::clone(&gate));
+        gate
+    };
+
+    // Also register in legacy SESSIONS map so get_active_sessions/terminate see it
+    {
+        let cli_session = CLISession {
+            id: session_id_clone.clone(),
+            agent: agent_name.clone(),
+            command: actual_message.clone(),
+            working_dir: working_dir.clone(),
+            is_active: true,
+            created_at: chrono::Utc::now().timestamp(),
+            last_activity: chrono::Utc::now().timestamp(),
+        };
+        let mut sessions = SESSIONS.lock().await;
+        sessions.insert(session_id_clone.clone(), ActiveSession {
+            session: cli_session,
+            process: Arc::new(Mutex::new(None)),
+            activity: Some(Arc::clone(&activity)),
+        });
+    }
+
+    let relaunch = Relaunch {
+        binary: &resolved_binary_path,
+        working_dir: wd,
+        settings: &agent_settings,
+        resume_session_id: resume_session_id.as_deref(),
+    };
+
+    // A follow-up goes to the process kept from the conversation's earlier turns.
+    let conversation_key = conversation_id
+        .as_deref()
+        .map(|id| AgentProcessPool::key(&agent_name, id));
+    if let Some(key) = &conversation_key {
+        let pooled = process_pool.lock().await.get(key, &agent_name, wd);
+        if let Some(shared) = pooled {
+            match run_pooled_turn(
+                &shared,
+                &sink,
+                &session_id_clone,
+                &actual_message,
+                &relaunch,
+                &mut TurnControl {
+                    activity: &activity,
+                    perm_rx: &mut perm_rx,
+                    abort_rx: &mut abort_rx,
+                },
+            )
+            .await
+            {
+                PooledTurn::Done { alive: true } => {
+                    process_pool.lock().await.touch(key);
+                    end_run(&sm, &sink, &session_id_clone, &activity).await;
+                    return;
+                }
+                PooledTurn::Done { alive: false } => {
+                    process_pool.lock().await.remove_executor(key, &shared);
+                    end_run(&sm, &sink, &session_id_clone, &activity).await;
+                    return;
+                }
+                PooledTurn::Aborted => {
+                    end_run(&sm, &sink, &session_id_clone, &activity).await;
+                    return;
+                }
+                PooledTurn::Unavailable => {
+                    if process_pool.lock().await.remove_executor(key, &shared) {
+                        shutdown_executors(vec![shared]).await;
                     }
                 }
             }
-            Ok(()) => {
-                // PTY completed normally (execute blocks until done)
+        }
+    }
+
+    // Execute the chosen executor. A PTY run only returns once it is over,
+    // so its limits are enforced here; protocol turns are supervised below.
+    let run = executor.execute(
+        &sink,
+        &session_id_clone,
+        &resolved_binary_path,
+        &actual_message,
+        wd,
+        &agent_settings,
+        resume_session_id.as_deref(),
+    );
+    let result = if is_protocol {
+        run.await
+    } else {
+        run_enforcing_limits(run, &activity, &session_id_clone, Some(&mut abort_rx)).await
+    };
+
+    match result {
+        Err(e) => {
+            if is_protocol {
+                // Protocol executor failed to start -- fall back to PTY
+                sink.emit_event(ProtocolEvent::SessionEvent {
+                    session_id: session_id_clone.clone(),
+                    event: SessionEventKind::FallbackToPty,
+                });
+                let fallback_chunk = StreamChunk {
+                    session_id: session_id_clone.clone(),
+                    content: format!(
+                        "ℹ️ Protocol executor unavailable ({}). Falling back to PTY…\n",
+                        e
+                    ),
+                    finished: false,
+                };
+                sink.emit_chunk(fallback_chunk);
+
+                let mut pty = PtyExecutor::new();
+                let run = pty.execute(
+                    &sink,
+                    &session_id_clone,
+                    &agent_name,
+                    &actual_message,
+                    wd,
+                    &agent_settings,
+                    None,
+                );
+                if let Err(pty_err) =
+                    run_enforcing_limits(run, &activity, &session_id_clone, Some(&mut abort_rx))
+                        .await
+                {
+                    sink.emit_failure(
+                        &session_id_clone,
+                        format!("PTY fallback error: {}", pty_err),
+                        true,
+                    );
+                }
+            } else {
+                // PTY executor error
+                sink.emit_failure(&session_id_clone, format!("Executor error: {}", e), true);
             }
         }
+        Ok(()) if is_protocol => {
+            // ACP/RPC started successfully -- its background reader task manages
+            // the stream lifecycle while we forward permissions until the turn ends.
+            let mut control = TurnControl {
+                activity: &activity,
+                perm_rx: &mut perm_rx,
+                abort_rx: &mut abort_rx,
+            };
+            supervise_turn(executor.as_ref(), &mut control).await;
+            recover_crashed_agent(
+                executor.as_mut(),
+                &sink,
+                &session_id_clone,
+                &actual_message,
+                &relaunch,
+                &mut control,
+            )
+            .await;
+
+            // Keep a long-lived process for the conversation's next turn.
+            if executor.supports_multi_turn() && executor.is_alive() {
+                let timeout = idle_timeout(agent_settings.session_timeout_minutes);
+                match (&conversation_key, timeout) {
+                    (Some(key), Some(timeout)) if !executor.is_busy() => {
+                        let shared: SharedExecutor = Arc::new(TokioMutex::new(executor));
+                        let replaced = process_pool
+                            .lock()
+                            .await
+                            .insert(key, shared, &agent_name, wd, timeout);
+                        shutdown_executors(replaced.into_iter().collect()).await;
+                    }
+                    _ => {
+                        let _ = executor.shutdown().await;
+                    }
+                }
+            }
+        }
+        Ok(()) => {
+            // PTY completed normally (execute blocks until done)
+        }
+    }
 
-        // Clean up session from both managers
-        {
-            let mut sessions = SESSIONS.lock().await;
-            sessions.remove(&session_id_clone);
+    end_run(&sm, &sink, &session_id_clone, &activity).await;
+}
+
+/// Run an agent that only returns once it is done, stopping its processes
+/// when the run goes past a limit or is aborted.
+async fn run_enforcing_limits<F: std::future::Future>(
+    run: F,
+    activity: &SessionActivity,
+    session_id: &str,
+    abort_rx: Option<&mut tokio::sync::oneshot::Receiver<()>>,
+) -> F::Output {
+    tokio::pin!(run);
+    let aborted = async {
+        match abort_rx {
+            Some(abort_rx) => {
+                let _ = abort_rx.await;
+            }
+            None => std::future::pending::<()>().await,
         }
-        let mut mgr = sm.lock().await;
-        mgr.remove(&session_id_clone);
+    };
+    tokio::select! {
+        output = &mut run => return output,
+        _ = activity.limit_reached() => {}
+        _ = aborted => {
+            activity.end(SessionEndReason::Cancelled);
+        }
+    }
+    terminate_session_processes(session_id).await;
+    run.await
+}
+
+/// Report how the run ended.
+fn emit_session_ended(sink: &SharedEventSink, session_id: &str, activity: &SessionActivity) {
+    sink.emit_event(ProtocolEvent::SessionEnded {
+        session_id: session_id.to_string(),
+        reason: activity.end(SessionEndReason::Completed),
     });
+}
 
-    Ok(())
+/// Report how the run ended and clean it up from both session managers.
+async fn end_run(
+    sm: &Arc<TokioMutex<SessionManager>>,
+    sink: &SharedEventSink,
+    session_id: &str,
+    activity: &SessionActivity,
+) {
+    emit_session_ended(sink, session_id, activity);
+    {
+        let mut sessions = SESSIONS.lock().await;
+        sessions.remove(session_id);
+    }
+    let mut mgr = sm.lock().await;
+    mgr.remove(session_id);
 }
 
+/// Answer an agent's permission request. With `remember`, requests like it
+/// are allowed for the rest of the conversation.
 #[tauri::command]
 pub async fn respond_permission(
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     session_id: String,
     request_id: String,
     approved: bool,
+    remember: Option<bool>,
 ) -> Result<(), String> {
     let mgr = session_manager.lock().await;
-    mgr.send_permission(&session_id, request_id, approved)
+    mgr.answer_permission(&session_id, request_id, approved, remember.unwrap_or(false))
 }
 
 #[tauri::command]
@@ -1125,6 +1852,7 @@ pub async fn execute_cli_command(
     #[allow(non_snake_case)] executionMode: Option<String>,
     #[allow(non_snake_case)] dangerousBypass: Option<bool>,
     #[allow(non_snake_case)] permissionMode: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1140,6 +1868,7 @@ pub async fn execute_cli_command(
         dangerousBypass,
         permissionMode,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1154,6 +1883,7 @@ pub async fn execute_claude_command(
     #[allow(non_snake_case)] workingDir: Option<String>,
     #[allow(non_snake_case)] permissionMode: Option<String>,
     #[allow(non_snake_case)] resumeSessionId: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1167,6 +1897,7 @@ pub async fn execute_claude_command(
         None,
         permissionMode,
         resumeSessionId,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1182,6 +1913,7 @@ pub async fn execute_codex_command(
     #[allow(non_snake_case)] executionMode: Option<String>,
     #[allow(non_snake_case)] dangerousBypass: Option<bool>,
     #[allow(non_snake_case)] permissionMode: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1195,6 +1927,7 @@ pub async fn execute_codex_command(
         dangerousBypass,
         permissionMode,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1208,6 +1941,7 @@ pub async fn execute_gemini_command(
     message: String,
     #[allow(non_snake_case)] workingDir: Option<String>,
     #[allow(non_snake_case)] approvalMode: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1221,6 +1955,7 @@ pub async fn execute_gemini_command(
         None,
         approvalMode,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1237,6 +1972,7 @@ pub async fn execute_cursor_command(
     #[allow(non_snake_case)] sessionId: String,
     message: String,
     #[allow(non_snake_case)] workingDir: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1250,6 +1986,7 @@ pub async fn execute_cursor_command(
         None,
         None,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1262,6 +1999,7 @@ pub async fn execute_copilot_command(
     #[allow(non_snake_case)] sessionId: String,
     message: String,
     #[allow(non_snake_case)] workingDir: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1275,6 +2013,7 @@ pub async fn execute_copilot_command(
         None,
         None,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1287,6 +2026,7 @@ pub async fn execute_pi_command(
     #[allow(non_snake_case)] sessionId: String,
     message: String,
     #[allow(non_snake_case)] workingDir: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1300,6 +2040,7 @@ pub async fn execute_pi_command(
         None,
         None,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1312,6 +2053,7 @@ pub async fn execute_opencode_command(
     #[allow(non_snake_case)] sessionId: String,
     message: String,
     #[allow(non_snake_case)] workingDir: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1325,6 +2067,7 @@ pub async fn execute_opencode_command(
         None,
         None,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1337,6 +2080,7 @@ pub async fn execute_vibe_command(
     #[allow(non_snake_case)] sessionId: String,
     message: String,
     #[allow(non_snake_case)] workingDir: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1350,6 +2094,7 @@ pub async fn execute_vibe_command(
         None,
         None,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1362,6 +2107,7 @@ pub async fn execute_amp_command(
     #[allow(non_snake_case)] sessionId: String,
     message: String,
     #[allow(non_snake_case)] workingDir: Option<String>,
+    #[allow(non_snake_case)] conversationId: Option<String>,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
     protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
 ) -> Result<(), String> {
@@ -1375,6 +2121,7 @@ pub async fn execute_amp_command(
         None,
         None,
         None,
+        conversationId,
         session_manager,
         protocol_cache,
     )
@@ -1429,7 +2176,10 @@ pub async fn get_sessions_status() -> Result<SessionStatus, String> {
 
     let active_sessions: Vec<CLISession> = sessions
         .values()
-        .map(|session| session.session.clone())
+        .map(|session| CLISession {
+            last_activity: session.last_activity(),
+            ..session.session.clone()
+        })
         .collect();
 
     Ok(SessionStatus {
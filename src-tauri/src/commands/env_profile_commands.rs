@@ -0,0 +1,115 @@
+use crate::commands::cli_commands::{get_sessions_status, settings_for_agent};
+use crate::commands::settings_commands::load_all_agent_settings;
+use crate::models::environment::{EnvPreview, EnvProfile};
+use crate::services::env_profile_service::{
+    import_dotenv, load_env_profiles, save_env_profiles, seal_profile, AgentEnvironment,
+};
+use crate::services::project_settings_service;
+use crate::services::secret_store_service;
+
+#[tauri::command]
+pub async fn list_env_profiles() -> Result<Vec<EnvProfile>, String> {
+    load_env_profiles()
+}
+
+/// Create or replace the profile called `profile.name`. Secret values go to
+/// the secret store; the saved profile holds their handles.
+#[tauri::command]
+pub async fn save_env_profile(mut profile: EnvProfile) -> Result<EnvProfile, String> {
+    let mut profiles = load_env_profiles()?;
+    let index = profiles.iter().position(|p| p.name == profile.name);
+    seal_profile(
+        &mut profile,
+        index.map(|i| &profiles[i]),
+        secret_store_service::global()?,
+    )?;
+    match index {
+        Some(i) => profiles[i] = profile.clone(),
+        None => profiles.push(profile.clone()),
+    }
+    save_env_profiles(&profiles)?;
+    Ok(profile)
+}
+
+/// Delete a profile and the secrets it holds. Projects and agents that
+/// still name it fail to start until it is detached.
+#[tauri::command]
+pub async fn delete_env_profile(name: String) -> Result<(), String> {
+    let mut profiles = load_env_profiles()?;
+    let Some(index) = profiles.iter().position(|p| p.name == name) else {
+        return Ok(());
+    };
+    let removed = profiles.remove(index);
+    save_env_profiles(&profiles)?;
+    let secrets: Vec<_> = removed.variables.iter().filter(|v| v.secret).collect();
+    if !secrets.is_empty() {
+        let store = secret_store_service::global()?;
+        for var in secrets {
+            store.remove(&var.value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Merge the `.env` file at `path` into the profile `profile_name`,
+/// creating it when needed. Variables that look like keys, tokens or
+/// passwords are kept as secrets.
+#[tauri::command]
+pub async fn import_env_file(profile_name: String, path: String) -> Result<EnvProfile, String> {
+    let content =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut profile = load_env_profiles()?
+        .into_iter()
+        .find(|p| p.name == profile_name)
+        .unwrap_or(EnvProfile {
+            name: profile_name,
+            ..EnvProfile::default()
+        });
+    import_dotenv(&mut profile, &content)?;
+    save_env_profile(profile).await
+}
+
+/// Attach `profile` to the project's agents, or detach it with `None`.
+#[tauri::command]
+pub async fn set_project_env_profile(
+    project_path: String,
+    profile: Option<String>,
+) -> Result<(), String> {
+    if let Some(name) = &profile {
+        if !load_env_profiles()?.iter().any(|p| &p.name == name) {
+            return Err(format!("environment profile '{}' does not exist", name));
+        }
+    }
+    let mut settings = project_settings_service::load_project_settings(&project_path)?;
+    settings.env_profile = profile;
+    project_settings_service::save_project_settings(&project_path, &settings)
+}
+
+/// The environment `agent` starts with in `working_dir`, or the running
+/// session `session_id`'s agent does. Secret values are masked.
+#[tauri::command]
+pub async fn preview_agent_environment(
+    app: tauri::AppHandle,
+    session_id: Option<String>,
+    agent: Option<String>,
+    working_dir: Option<String>,
+) -> Result<EnvPreview, String> {
+    let (agent, working_dir) = match session_id {
+        Some(id) => {
+            let session = get_sessions_status()
+                .await?
+                .active_sessions
+                .into_iter()
+                .find(|s| s.id == id)
+                .ok_or_else(|| "Session not found".to_string())?;
+            (session.agent, session.working_dir)
+        }
+        None => (
+            agent.ok_or_else(|| "An agent or a session is required".to_string())?,
+            working_dir,
+        ),
+    };
+    let all_settings = load_all_agent_settings(app).await?;
+    let settings = settings_for_agent(&all_settings, &agent);
+    AgentEnvironment::preview_for_run(&settings, working_dir.as_deref().unwrap_or(""))
+}
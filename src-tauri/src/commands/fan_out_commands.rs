@@ -0,0 +1,174 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use tauri::Emitter;
+use tokio::sync::Mutex as TokioMutex;
+
+use crate::commands::cli_commands::{queue_agent_run, AgentRunRequest};
+use crate::commands::git_commands::{
+    create_workspace_worktree, diff_workspace_vs_main, is_valid_git_repository,
+};
+use crate::commands::settings_commands::load_all_agent_settings;
+use crate::models::ai_agent::AllAgentSettings;
+use crate::services::agent_status_service::ProtocolCache;
+use crate::services::event_sink::TauriEventSink;
+use crate::services::fan_out_service::{
+    commit_worktree_changes, diff_stats, fan_out_worktree_name, normalize_fan_out_agents,
+    run_stats, DiffStats, FanOutComparison, FanOutResult, FanOutRun, FanOutStatus, RunStats,
+};
+use crate::services::session_manager::SessionManager;
+use crate::services::transcript_service::load_transcript;
+
+/// Emitted once the worktrees exist: `{ "fan_out_id", "prompt", "runs": [FanOutRun] }`.
+pub const FAN_OUT_STARTED_EVENT: &str = "fan-out-started";
+
+/// Run the same prompt against several agents, each in its own
+/// `workspace/<agent>-<id>` worktree, and compare what they did.
+///
+/// The runs go through the run queue like any other prompt and stream on
+/// their own `fanout-<agent>-<id>` session ids. Once every run has ended, the
+/// changes each agent left are committed to its branch so the winner can be
+/// merged with `merge_workspace_to_main`.
+#[tauri::command]
+pub async fn fan_out_prompt(
+    app: tauri::AppHandle,
+    project_path: String,
+    agents: Vec<String>,
+    message: String,
+    execution_mode: Option<String>,
+    session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
+    protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
+) -> Result<FanOutComparison, String> {
+    let agents = normalize_fan_out_agents(&agents)?;
+    if message.trim().is_empty() {
+        return Err("Prompt cannot be empty".to_string());
+    }
+    if !is_valid_git_repository(Path::new(&project_path)) {
+        return Err("Not a valid git repository".to_string());
+    }
+
+    let fan_out_id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let all_settings = load_all_agent_settings(app.clone())
+        .await
+        .unwrap_or_else(|_| AllAgentSettings::default());
+
+    // Worktrees first, so a bad repository state fails before any agent runs.
+    let mut runs = Vec::new();
+    for agent in &agents {
+        let name = fan_out_worktree_name(agent, &fan_out_id);
+        let setup = create_workspace_worktree(project_path.clone(), name.clone()).await;
+        let run = FanOutRun {
+            agent: agent.clone(),
+            session_id: format!("fanout-{}", name),
+            branch: format!("workspace/{}", name),
+            worktree_path: setup.as_ref().ok().cloned(),
+        };
+        runs.push((run, setup.err()));
+    }
+
+    let _ = app.emit(
+        FAN_OUT_STARTED_EVENT,
+        serde_json::json!({
+            "fan_out_id": fan_out_id,
+            "prompt": message,
+            "runs": runs.iter().map(|(run, _)| run).collect::<Vec<_>>(),
+        }),
+    );
+
+    let mut handles = Vec::new();
+    for (run, setup_error) in &runs {
+        let handle = match (&run.worktree_path, setup_error) {
+            (Some(worktree), None) => {
+                let request = AgentRunRequest {
+                    session_id: run.session_id.clone(),
+                    agent: run.agent.clone(),
+                    message: message.clone(),
+                    working_dir: Some(worktree.clone()),
+                    execution_mode: execution_mode.clone(),
+                    dangerous_bypass: false,
+                    resume_session_id: None,
+                    conversation_id: None,
+                };
+                queue_agent_run(
+                    &app,
+                    TauriEventSink::shared(app.clone()),
+                    request,
+                    all_settings.clone(),
+                    Arc::clone(&*session_manager),
+                    Arc::clone(&*protocol_cache),
+                )
+                .await
+            }
+            (_, error) => Err(error.clone().unwrap_or_else(|| "Worktree was not created".to_string())),
+        };
+        handles.push(handle);
+    }
+
+    let summary = message.lines().next().unwrap_or("").chars().take(60).collect::<String>();
+    let mut results = Vec::new();
+    for ((run, _), handle) in runs.into_iter().zip(handles) {
+        let mut result = FanOutResult {
+            agent: run.agent,
+            session_id: run.session_id,
+            branch: run.branch,
+            worktree_path: run.worktree_path,
+            status: FanOutStatus::NotRun,
+            stats: RunStats::default(),
+            diff: DiffStats::default(),
+            committed: false,
+            error: None,
+        };
+
+        let handle = match handle {
+            Ok(handle) => handle,
+            Err(e) => {
+                result.error = Some(e);
+                results.push(result);
+                continue;
+            }
+        };
+        if let Err(e) = handle.await {
+            result.error = Some(format!("Run task failed: {}", e));
+        }
+        let Some(worktree) = result.worktree_path.clone() else {
+            results.push(result);
+            continue;
+        };
+
+        match load_transcript(&worktree, &result.session_id) {
+            Ok(records) => {
+                result.stats = run_stats(&records);
+                result.status = if result.stats.failed {
+                    FanOutStatus::Failed
+                } else {
+                    FanOutStatus::Completed
+                };
+            }
+            Err(e) => {
+                result.error.get_or_insert(e);
+            }
+        }
+
+        match commit_worktree_changes(&worktree, &format!("{}: {}", result.agent, summary)).await {
+            Ok(committed) => result.committed = committed,
+            Err(e) => {
+                result.status = FanOutStatus::Failed;
+                result.error.get_or_insert(format!("Failed to commit changes: {}", e));
+            }
+        }
+
+        match diff_workspace_vs_main(project_path.clone(), worktree, None).await {
+            Ok(files) => result.diff = diff_stats(files),
+            Err(e) => {
+                result.error.get_or_insert(format!("Failed to diff workspace: {}", e));
+            }
+        }
+        results.push(result);
+    }
+
+    Ok(FanOutComparison {
+        fan_out_id,
+        prompt: message,
+        results,
+    })
+}
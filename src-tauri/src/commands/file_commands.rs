@@ -3,6 +3,7 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 use crate::models::*;
+use crate::services::executors::acp_host;
 use crate::services::file_service;
 
 // File system helper functions for file mention system
@@ -270,6 +271,21 @@ pub async fn write_file_content(file_path: String, content: String) -> Result<()
     std::fs::write(&path, content).map_err(|e| format!("Failed to write file {}: {}", file_path, e))
 }
 
+/// Publish an editor's unsaved contents so ACP agents read them instead of
+/// the file on disk and do not write over them.
+#[tauri::command]
+pub async fn set_unsaved_buffer(file_path: String, content: String) -> Result<(), String> {
+    acp_host::set_unsaved_buffer(&file_path, content);
+    Ok(())
+}
+
+/// Drop a published buffer once it was saved or discarded.
+#[tauri::command]
+pub async fn clear_unsaved_buffer(file_path: String) -> Result<(), String> {
+    acp_host::clear_unsaved_buffer(&file_path);
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn create_default_agents_docs(project_path: String) -> Result<String, String> {
     let base = PathBuf::from(&project_path);
@@ -1,8 +1,46 @@
+use crate::commands::cli_commands::{drive_agent_session, AgentRunRequest};
+use crate::commands::settings_commands::load_all_agent_settings;
+use crate::models::ai_agent::AllAgentSettings;
+use crate::models::project::MergeStrategy;
+use crate::services::agent_process_pool::AgentProcessPool;
+use crate::services::agent_status_service::ProtocolCache;
+use crate::services::event_sink::TauriEventSink;
+use crate::services::git_backend::{git_backend, CommitInfo, ConfigScope, FileChange, GitBackend};
 use crate::services::git_service;
+use crate::services::project_settings_service::load_project_settings;
+use crate::services::session_manager::SessionManager;
+use crate::services::workspace_merge_service::{
+    agent_resolution_prompt, current_branch, merge_workspace, resolve_base_branch, start_merge,
+    ConflictFileVersions, ConflictResolution, MergeConflict, MergeOutcome, MergeRegistry,
+};
 use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
-use tauri::Emitter;
+use std::sync::Arc;
+use tauri::{Emitter, Manager};
+use tokio::sync::Mutex as TokioMutex;
+
+/// Run a blocking [`GitBackend`] call off the async runtime.
+async fn with_git_backend<T, F>(call: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn GitBackend) -> Result<T, String> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || call(git_backend()))
+        .await
+        .map_err(|e| format!("Git task failed: {}", e))?
+}
+
+fn config_map(entries: Vec<(String, String)>) -> HashMap<String, String> {
+    entries
+        .into_iter()
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn change_rows(changes: Vec<FileChange>) -> Vec<HashMap<String, String>> {
+    changes.iter().map(FileChange::to_row).collect()
+}
 
 #[tauri::command]
 pub async fn validate_git_repository_url(url: String) -> Result<bool, String> {
@@ -94,79 +132,33 @@ pub async fn clone_repository(
 
 #[tauri::command]
 pub async fn get_git_global_config() -> Result<HashMap<String, String>, String> {
-    let output = tokio::process::Command::new("git")
-        .args(&["config", "--global", "--list"])
-        .output()
+    let entries = with_git_backend(|git| git.config_entries(ConfigScope::Global))
         .await
-        .map_err(|e| format!("Failed to execute git config --global --list: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Git global config command failed: {}", stderr));
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut config = HashMap::new();
-
-    for line in stdout.lines() {
-        if let Some((key, value)) = line.split_once('=') {
-            config.insert(key.trim().to_string(), value.trim().to_string());
-        }
-    }
-
-    Ok(config)
+        .map_err(|e| format!("Git global config command failed: {}", e))?;
+    Ok(config_map(entries))
 }
 
 #[tauri::command]
 pub async fn get_git_local_config() -> Result<HashMap<String, String>, String> {
-    let output = tokio::process::Command::new("git")
-        .args(&["config", "--local", "--list"])
-        .output()
+    // Not in a git repository - return empty config
+    let entries = with_git_backend(|git| git.config_entries(ConfigScope::Local(".")))
         .await
-        .map_err(|e| format!("Failed to execute git config --local --list: {}", e))?;
-
-    if !output.status.success() {
-        // Not in a git repository - return empty config
-        return Ok(HashMap::new());
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut config = HashMap::new();
-
-    for line in stdout.lines() {
-        if let Some((key, value)) = line.split_once('=') {
-            config.insert(key.trim().to_string(), value.trim().to_string());
-        }
-    }
-
-    Ok(config)
+        .unwrap_or_default();
+    Ok(config_map(entries))
 }
 
 #[tauri::command]
 pub async fn get_git_aliases() -> Result<HashMap<String, String>, String> {
-    let output = tokio::process::Command::new("git")
-        .args(&["config", "--global", "--get-regexp", "alias\\..*"])
-        .output()
+    // No global config (and so no aliases) - return empty HashMap
+    let entries = with_git_backend(|git| git.config_entries(ConfigScope::Global))
         .await
-        .map_err(|e| format!("Failed to execute git config for aliases: {}", e))?;
-
-    if !output.status.success() {
-        // No aliases found - return empty HashMap
-        return Ok(HashMap::new());
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut aliases = HashMap::new();
-
-    for line in stdout.lines() {
-        if let Some((key, value)) = line.split_once(' ') {
-            if let Some(alias_name) = key.strip_prefix("alias.") {
-                aliases.insert(alias_name.to_string(), value.trim().to_string());
-            }
-        }
-    }
-
-    Ok(aliases)
+        .unwrap_or_default();
+    Ok(config_map(
+        entries
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix("alias.")?.to_string(), value)))
+            .collect(),
+    ))
 }
 
 #[tauri::command]
@@ -437,135 +429,230 @@ pub async fn get_git_log(
     project_path: String,
     limit: Option<usize>,
 ) -> Result<Vec<std::collections::HashMap<String, String>>, String> {
-    let lim = limit.unwrap_or(50).to_string();
-    let output = tokio::process::Command::new("git")
-        .arg("-C")
-        .arg(&project_path)
-        .args([
-            "log",
-            "--pretty=%H|%an|%ad|%s",
-            "--date=iso",
-            &format!("-n{}", lim),
-        ])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to run git log: {}", e))?;
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut rows = Vec::new();
-    for line in stdout.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        let parts: Vec<&str> = line.splitn(4, '|').collect();
-        if parts.len() == 4 {
+    let limit = limit.unwrap_or(50);
+    let commits = with_git_backend(move |git| git.log(&project_path, None, limit)).await?;
+    Ok(commits
+        .into_iter()
+        .map(|commit| {
             let mut m = std::collections::HashMap::new();
-            m.insert("hash".into(), parts[0].into());
-            m.insert("author".into(), parts[1].into());
-            m.insert("date".into(), parts[2].into());
-            m.insert("subject".into(), parts[3].into());
-            rows.push(m);
-        }
-    }
-    Ok(rows)
-}
-
-async fn get_branch_from_worktree(worktree_path: &str) -> Result<String, String> {
-    let output = tokio::process::Command::new("git")
-        .arg("-C")
-        .arg(worktree_path)
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to get branch: {}", e))?;
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+            m.insert("hash".into(), commit.hash);
+            m.insert("author".into(), commit.author);
+            m.insert("date".into(), commit.date);
+            m.insert("subject".into(), commit.subject);
+            m
+        })
+        .collect())
 }
 
 #[tauri::command]
 pub async fn diff_workspace_vs_main(
     project_path: String,
     worktree_path: String,
+    base_branch: Option<String>,
 ) -> Result<Vec<std::collections::HashMap<String, String>>, String> {
-    let branch = get_branch_from_worktree(&worktree_path).await?;
-    let output = tokio::process::Command::new("git")
-        .arg("-C")
-        .arg(&project_path)
-        .args(["diff", "--name-status", "main...", &branch])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to run git diff: {}", e))?;
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut rows = Vec::new();
-    for line in stdout.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        let mut parts = line.split_whitespace();
-        if let (Some(status), Some(path)) = (parts.next(), parts.next()) {
-            let mut m = std::collections::HashMap::new();
-            m.insert("status".into(), status.into());
-            m.insert("path".into(), path.into());
-            rows.push(m);
-        }
+    let base = resolve_base_branch(&project_path, base_branch.as_deref()).await?;
+    let branch = current_branch(&worktree_path).await?;
+    let changes = with_git_backend(move |git| git.diff_range(&project_path, &base, &branch)).await?;
+    Ok(change_rows(changes))
+}
+
+/// Merge a workspace branch into the base branch without checking anything
+/// out in the project's main worktree.
+///
+/// `strategy` is `merge_commit`, `squash` or `rebase_fast_forward`; both it
+/// and `base_branch` fall back to the project settings. Conflicts are
+/// returned in the outcome rather than as an error, and leave the base
+/// branch untouched.
+#[tauri::command]
+pub async fn merge_workspace_to_main(
+    project_path: String,
+    worktree_path: String,
+    message: Option<String>,
+    base_branch: Option<String>,
+    strategy: Option<String>,
+) -> Result<MergeOutcome, String> {
+    let base = resolve_base_branch(&project_path, base_branch.as_deref()).await?;
+    let strategy = resolve_merge_strategy(&project_path, strategy.as_deref())?;
+    merge_workspace(&project_path, &worktree_path, &base, strategy, message).await
+}
+
+fn resolve_merge_strategy(project_path: &str, strategy: Option<&str>) -> Result<MergeStrategy, String> {
+    match strategy {
+        Some(value) => MergeStrategy::parse(value),
+        None => Ok(load_project_settings(project_path)?
+            .merge_strategy
+            .unwrap_or_default()),
     }
-    Ok(rows)
 }
 
+/// Returned by `start_workspace_merge`; pass `merge_id` to the other
+/// conflict commands.
+#[derive(serde::Serialize)]
+pub struct MergePreview {
+    pub merge_id: String,
+    pub base_branch: String,
+    pub workspace_branch: String,
+    pub strategy: MergeStrategy,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Dry-run a workspace merge in a scratch worktree and keep it open so its
+/// conflicts can be resolved file by file. Nothing is applied to the base
+/// branch until `complete_workspace_merge`; `abort_workspace_merge` throws
+/// the attempt away.
 #[tauri::command]
-pub async fn merge_workspace_to_main(
+pub async fn start_workspace_merge(
     project_path: String,
     worktree_path: String,
     message: Option<String>,
-) -> Result<(), String> {
-    let branch = get_branch_from_worktree(&worktree_path).await?;
-    // checkout main
-    let co = tokio::process::Command::new("git")
-        .arg("-C")
-        .arg(&project_path)
-        .args(["checkout", "main"])
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-    if !co.status.success() {
-        return Err(String::from_utf8_lossy(&co.stderr).to_string());
+    base_branch: Option<String>,
+    strategy: Option<String>,
+    registry: tauri::State<'_, Arc<TokioMutex<MergeRegistry>>>,
+) -> Result<MergePreview, String> {
+    let base = resolve_base_branch(&project_path, base_branch.as_deref()).await?;
+    let strategy = resolve_merge_strategy(&project_path, strategy.as_deref())?;
+    let (pending, conflicts) =
+        start_merge(&project_path, &worktree_path, &base, strategy, message).await?;
+
+    let merge_id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let preview = MergePreview {
+        merge_id: merge_id.clone(),
+        base_branch: pending.base_branch.clone(),
+        workspace_branch: pending.workspace_branch.clone(),
+        strategy,
+        conflicts,
+    };
+    registry.lock().await.insert(&merge_id, pending);
+    Ok(preview)
+}
+
+#[tauri::command]
+pub async fn get_workspace_merge_conflicts(
+    merge_id: String,
+    registry: tauri::State<'_, Arc<TokioMutex<MergeRegistry>>>,
+) -> Result<Vec<MergeConflict>, String> {
+    let registry = registry.lock().await;
+    Ok(registry.get(&merge_id)?.conflicts().await)
+}
+
+/// Base, ours (base branch), theirs (workspace) and working-copy contents of
+/// one conflicted file.
+#[tauri::command]
+pub async fn get_merge_conflict_versions(
+    merge_id: String,
+    path: String,
+    registry: tauri::State<'_, Arc<TokioMutex<MergeRegistry>>>,
+) -> Result<ConflictFileVersions, String> {
+    let registry = registry.lock().await;
+    registry.get(&merge_id)?.conflict_versions(&path).await
+}
+
+/// Resolve one conflicted file with `ours`, `theirs`, `manual` content,
+/// `delete`, or by asking an `agent` to edit it in the scratch worktree.
+/// Returns the conflicts that remain.
+#[tauri::command]
+pub async fn resolve_merge_conflict(
+    app: tauri::AppHandle,
+    merge_id: String,
+    path: String,
+    resolution: ConflictResolution,
+    registry: tauri::State<'_, Arc<TokioMutex<MergeRegistry>>>,
+    session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
+    protocol_cache: tauri::State<'_, Arc<TokioMutex<ProtocolCache>>>,
+) -> Result<Vec<MergeConflict>, String> {
+    let ConflictResolution::Agent { agent } = &resolution else {
+        let registry = registry.lock().await;
+        let merge = registry.get(&merge_id)?;
+        merge.resolve(&path, &resolution).await?;
+        return Ok(merge.conflicts().await);
+    };
+
+    // Hold the registry for the whole run so the merge cannot be completed
+    // or aborted while the agent edits its scratch worktree.
+    let registry = registry.lock().await;
+    let merge = registry.get(&merge_id)?;
+    if !merge.conflicts().await.iter().any(|c| c.path == path) {
+        return Err(format!("{} is not in conflict", path));
     }
-    // merge
-    let msg = message.unwrap_or_else(|| format!("Merge workspace {} into main", branch));
-    let merge = tokio::process::Command::new("git")
-        .arg("-C")
-        .arg(&project_path)
-        .args(["merge", "--no-ff", "-m", &msg, &branch])
-        .output()
+
+    let all_settings = load_all_agent_settings(app.clone())
         .await
-        .map_err(|e| e.to_string())?;
-    if !merge.status.success() {
-        return Err(String::from_utf8_lossy(&merge.stderr).to_string());
+        .unwrap_or_else(|_| AllAgentSettings::default());
+    let request = AgentRunRequest {
+        session_id: format!("merge-{}-{}", merge_id, &uuid::Uuid::new_v4().simple().to_string()[..8]),
+        agent: agent.clone(),
+        message: agent_resolution_prompt(merge, &path),
+        working_dir: Some(merge.scratch_path().to_string()),
+        execution_mode: None,
+        dangerous_bypass: false,
+        resume_session_id: None,
+        conversation_id: None,
+    };
+    // A bare run: the scratch worktree is thrown away, so it gets no
+    // transcript or checkpoints.
+    let process_pool = app.state::<Arc<TokioMutex<AgentProcessPool>>>().inner().clone();
+    drive_agent_session(
+        TauriEventSink::shared(app.clone()),
+        request,
+        all_settings,
+        Arc::clone(&*session_manager),
+        Arc::clone(&*protocol_cache),
+        process_pool,
+    )
+    .await;
+
+    merge.mark_resolved(&path).await?;
+    Ok(merge.conflicts().await)
+}
+
+/// Commit the resolved merge and move the base branch to it. A rebase may
+/// stop again on a later commit; the outcome then carries the new conflicts
+/// and the merge stays open.
+#[tauri::command]
+pub async fn complete_workspace_merge(
+    merge_id: String,
+    registry: tauri::State<'_, Arc<TokioMutex<MergeRegistry>>>,
+) -> Result<MergeOutcome, String> {
+    let mut registry = registry.lock().await;
+    let outcome = registry.get(&merge_id)?.complete().await?;
+    if outcome.merged {
+        registry.remove(&merge_id)?.close().await;
     }
+    Ok(outcome)
+}
+
+#[tauri::command]
+pub async fn abort_workspace_merge(
+    merge_id: String,
+    registry: tauri::State<'_, Arc<TokioMutex<MergeRegistry>>>,
+) -> Result<(), String> {
+    let merge = registry.lock().await.remove(&merge_id)?;
+    merge.abort().await;
     Ok(())
 }
 
+/// Branch workspaces of this project are diffed against and merged into.
+#[tauri::command]
+pub async fn get_workspace_base_branch(project_path: String) -> Result<String, String> {
+    resolve_base_branch(&project_path, None).await
+}
+
 #[tauri::command]
 pub async fn diff_workspace_file(
     project_path: String,
     worktree_path: String,
     file_path: String,
+    base_branch: Option<String>,
 ) -> Result<String, String> {
-    let branch = get_branch_from_worktree(&worktree_path).await?;
+    let base = resolve_base_branch(&project_path, base_branch.as_deref()).await?;
+    let branch = current_branch(&worktree_path).await?;
     let output = tokio::process::Command::new("git")
         .arg("-C")
         .arg(&project_path)
         .args([
             "diff",
             "-U200",
-            &format!("main...{}", branch),
+            &format!("{}...{}", base, branch),
             "--",
             &file_path,
         ])
@@ -588,27 +675,23 @@ pub struct CommitDagRow {
     pub refs: Vec<String>,
 }
 
+impl From<CommitInfo> for CommitDagRow {
+    fn from(commit: CommitInfo) -> Self {
+        Self {
+            hash: commit.hash,
+            parents: commit.parents,
+            author: commit.author,
+            date: commit.date,
+            subject: commit.subject,
+            refs: commit.refs,
+        }
+    }
+}
+
 #[tauri::command]
 pub async fn get_git_branches(project_path: String) -> Result<Vec<String>, String> {
-    // List local branches (short names)
-    let output = tokio::process::Command::new("git")
-        .arg("-C")
-        .arg(&project_path)
-        .args(["for-each-ref", "--format=%(refname:short)", "refs/heads"])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to list branches: {}", e))?;
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut branches: Vec<String> = stdout
-        .lines()
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
-    // Ensure unique and stable order (main first if present)
-    branches.sort();
+    // Local branches, sorted, with main first if present
+    let mut branches = with_git_backend(move |git| git.list_branches(&project_path)).await?;
     if let Some(pos) = branches.iter().position(|b| b == "main") {
         let main = branches.remove(pos);
         branches.insert(0, main);
@@ -632,62 +715,11 @@ pub async fn get_git_commit_dag(
     limit: Option<usize>,
     branch: Option<String>,
 ) -> Result<Vec<CommitDagRow>, String> {
-    let lim = limit.unwrap_or(50).to_string();
-    let format = "%H|%P|%an|%ad|%s||%D";
-    let mut cmd = tokio::process::Command::new("git");
-    cmd.arg("-C")
-        .arg(&project_path)
-        .arg("log")
-        .arg("--date=iso")
-        .arg(&format!("-n{}", lim))
-        .arg(&format!("--pretty={}", format));
-    if let Some(b) = branch {
-        if !b.trim().is_empty() {
-            cmd.arg(b);
-        }
-    }
-    let output = cmd
-        .output()
-        .await
-        .map_err(|e| format!("Failed to run git log: {}", e))?;
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut rows = Vec::new();
-    for line in stdout.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        let parts: Vec<&str> = line.splitn(6, '|').collect();
-        if parts.len() < 6 {
-            continue;
-        }
-        let hash = parts[0].to_string();
-        let parents = if parts[1].trim().is_empty() {
-            vec![]
-        } else {
-            parts[1].split_whitespace().map(|s| s.to_string()).collect()
-        };
-        let author = parts[2].to_string();
-        let date = parts[3].to_string();
-        let subject = parts[4].to_string();
-        let refs_str = parts[5];
-        let refs: Vec<String> = refs_str
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
-        rows.push(CommitDagRow {
-            hash,
-            parents,
-            author,
-            date,
-            subject,
-            refs,
-        });
-    }
-    Ok(rows)
+    let limit = limit.unwrap_or(50);
+    let branch = branch.filter(|b| !b.trim().is_empty());
+    let commits =
+        with_git_backend(move |git| git.log(&project_path, branch.as_deref(), limit)).await?;
+    Ok(commits.into_iter().map(CommitDagRow::from).collect())
 }
 
 #[tauri::command]
@@ -695,28 +727,9 @@ pub async fn get_commit_diff_files(
     project_path: String,
     commit_hash: String,
 ) -> Result<Vec<std::collections::HashMap<String, String>>, String> {
-    let output = tokio::process::Command::new("git")
-        .arg("-C")
-        .arg(&project_path)
-        .args(["show", "--name-status", "--format=tformat:", &commit_hash])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to run git show: {}", e))?;
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut rows = Vec::new();
-    for line in stdout.lines() {
-        let mut parts = line.split_whitespace();
-        if let (Some(status), Some(path)) = (parts.next(), parts.next()) {
-            let mut m = std::collections::HashMap::new();
-            m.insert("status".into(), status.into());
-            m.insert("path".into(), path.into());
-            rows.push(m);
-        }
-    }
-    Ok(rows)
+    let changes =
+        with_git_backend(move |git| git.commit_changes(&project_path, &commit_hash)).await?;
+    Ok(change_rows(changes))
 }
 
 #[tauri::command]
@@ -744,18 +757,11 @@ pub async fn get_file_at_commit(
     commit_hash: String,
     file_path: String,
 ) -> Result<String, String> {
-    let spec = format!("{}:{}", commit_hash, file_path);
-    let output = tokio::process::Command::new("git")
-        .arg("-C")
-        .arg(&project_path)
-        .args(["show", &spec])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to git show {}: {}", spec, e))?;
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    let bytes = with_git_backend(move |git| {
+        git.file_at_commit(&project_path, &commit_hash, &file_path)
+    })
+    .await?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
 }
 
 // ---------------- Project Chat History ----------------
@@ -11,6 +11,7 @@ use crate::commands::settings_commands::{load_agent_settings, load_all_agent_set
 use crate::models::*;
 use crate::services::agent_status_service::{AgentStatusService, ProtocolCache};
 use crate::services::llm_service;
+use crate::services::secret_store_service::{redact, resolve_value};
 
 // Check if a command is available in the system
 async fn check_command_available(command: &str) -> bool {
@@ -26,22 +27,30 @@ async fn check_command_available(command: &str) -> bool {
     }
 }
 
+/// `api_key` may be the key or the `secret://` handle it is kept under.
 #[tauri::command]
 pub async fn fetch_openrouter_models(api_key: String) -> Result<Vec<LLMModel>, String> {
     if api_key.trim().is_empty() {
         return Err("OpenRouter API key is required to fetch models".to_string());
     }
 
-    llm_service::fetch_openrouter_models(&api_key).await
+    let api_key = resolve_value(&api_key)?;
+    llm_service::fetch_openrouter_models(&api_key)
+        .await
+        .map_err(|e| redact(&e))
 }
 
+/// `api_key` may be the key or the `secret://` handle it is kept under.
 #[tauri::command]
 pub async fn fetch_openai_models(api_key: String) -> Result<Vec<LLMModel>, String> {
     if api_key.trim().is_empty() {
         return Err("OpenAI API key is required to fetch models".to_string());
     }
 
-    llm_service::fetch_openai_models(&api_key).await
+    let api_key = resolve_value(&api_key)?;
+    llm_service::fetch_openai_models(&api_key)
+        .await
+        .map_err(|e| redact(&e))
 }
 
 #[tauri::command]
@@ -5,6 +5,7 @@ use tauri_plugin_store::StoreExt;
 
 use crate::models::*;
 use crate::services::project_service;
+use crate::services::project_settings_service;
 
 async fn scan_projects_folder(projects_folder: &str) -> Result<Vec<RecentProject>, String> {
     let path = Path::new(projects_folder);
@@ -480,3 +481,17 @@ pub async fn create_new_project_with_git(
     );
     Ok(project_path_str)
 }
+
+/// Settings stored with the project in `.commander/settings.json`.
+#[tauri::command]
+pub async fn get_project_settings(project_path: String) -> Result<ProjectSettings, String> {
+    project_settings_service::load_project_settings(&project_path)
+}
+
+#[tauri::command]
+pub async fn save_project_settings(
+    project_path: String,
+    settings: ProjectSettings,
+) -> Result<(), String> {
+    project_settings_service::save_project_settings(&project_path, &settings)
+}
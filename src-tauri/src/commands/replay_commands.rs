@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use tauri::Emitter;
+use tokio::sync::Mutex as TokioMutex;
+
+use crate::services::event_sink::TauriEventSink;
+use crate::services::replay_service::{
+    play_records, ReplayCommand, ReplayInfo, ReplayPace, ReplayRegistry,
+};
+use crate::services::transcript_service::load_transcript;
+
+/// Emitted once a replay ends: `{ "replay_id": ..., "outcome": "completed" | "stopped" }`.
+pub const REPLAY_FINISHED_EVENT: &str = "replay-finished";
+
+/// Re-emit a recorded session over the `cli-stream` and `protocol-event`
+/// channels.
+///
+/// `mode` is `original` (default), `speed` with a `speed` factor, or `step`,
+/// where every event waits for a `replay_step` call. Events keep their
+/// recorded session id unless `as_session_id` is given.
+#[tauri::command]
+pub async fn replay_session(
+    app: tauri::AppHandle,
+    registry: tauri::State<'_, Arc<TokioMutex<ReplayRegistry>>>,
+    project_path: String,
+    session_id: String,
+    mode: Option<String>,
+    speed: Option<f64>,
+    as_session_id: Option<String>,
+) -> Result<ReplayInfo, String> {
+    let pace = ReplayPace::parse(mode.as_deref().unwrap_or("original"), speed)?;
+    let records = load_transcript(&project_path, &session_id)?;
+
+    let replay_id = uuid::Uuid::new_v4().to_string();
+    let info = ReplayInfo {
+        replay_id: replay_id.clone(),
+        session_id: as_session_id.clone().unwrap_or_else(|| session_id.clone()),
+        total_events: records.len(),
+        duration_ms: match (records.first(), records.last()) {
+            (Some(first), Some(last)) => last.ts - first.ts,
+            _ => 0,
+        },
+    };
+
+    let mut commands = registry.lock().await.start(&replay_id);
+    let registry = registry.inner().clone();
+    let sink = TauriEventSink::shared(app.clone());
+
+    tokio::spawn(async move {
+        let outcome = play_records(
+            &sink,
+            &records,
+            pace,
+            as_session_id.as_deref(),
+            &mut commands,
+        )
+        .await;
+        registry.lock().await.finish(&replay_id);
+        let _ = app.emit(
+            REPLAY_FINISHED_EVENT,
+            serde_json::json!({ "replay_id": replay_id, "outcome": outcome }),
+        );
+    });
+
+    Ok(info)
+}
+
+/// Emit the next event of a replay. In timed modes this skips the current wait.
+#[tauri::command]
+pub async fn replay_step(
+    registry: tauri::State<'_, Arc<TokioMutex<ReplayRegistry>>>,
+    replay_id: String,
+) -> Result<(), String> {
+    registry.lock().await.send(&replay_id, ReplayCommand::Step)
+}
+
+/// Stop a replay before it reaches the end of the transcript.
+#[tauri::command]
+pub async fn stop_replay(
+    registry: tauri::State<'_, Arc<TokioMutex<ReplayRegistry>>>,
+    replay_id: String,
+) -> Result<(), String> {
+    registry.lock().await.send(&replay_id, ReplayCommand::Stop)
+}
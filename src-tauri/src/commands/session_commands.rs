@@ -1,9 +1,12 @@
 use std::sync::Arc;
 use crate::commands::cli_commands::{
-    cleanup_cli_sessions, get_sessions_status, send_quit_to_session, terminate_all_active_sessions,
-    terminate_session_by_id,
+    cleanup_cli_sessions, emit_run_queue, get_sessions_status, send_quit_to_session,
+    terminate_all_active_sessions, terminate_session_by_id,
 };
 use crate::models::*;
+use crate::services::agent_diagnostics_service::{self, SessionDiagnostics};
+use crate::services::agent_process_pool::{shutdown_executors, AgentProcessPool};
+use crate::services::run_queue_service::{RunQueue, RunQueueSnapshot};
 use crate::services::session_manager::SessionManager;
 use tokio::sync::Mutex as TokioMutex;
 
@@ -12,11 +15,24 @@ pub async fn get_active_sessions() -> Result<SessionStatus, String> {
     get_sessions_status().await
 }
 
+/// What a protocol agent wrote to stderr during a session, with the failures
+/// recognised in it. `None` when the agent wrote nothing.
+#[tauri::command]
+pub async fn get_session_diagnostics(session_id: String) -> Result<Option<SessionDiagnostics>, String> {
+    Ok(agent_diagnostics_service::session_diagnostics(&session_id))
+}
+
 #[tauri::command]
 pub async fn terminate_session(
+    app: tauri::AppHandle,
     session_id: String,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
+    run_queue: tauri::State<'_, Arc<TokioMutex<RunQueue>>>,
 ) -> Result<(), String> {
+    // A session still waiting in the run queue never reaches the executors
+    if run_queue.lock().await.cancel(&session_id).is_ok() {
+        emit_run_queue(&app, &run_queue).await;
+    }
     // Close via SessionManager (sends abort signal to protocol executor)
     {
         let mut mgr = session_manager.lock().await;
@@ -28,17 +44,42 @@ pub async fn terminate_session(
 
 #[tauri::command]
 pub async fn terminate_all_sessions(
+    app: tauri::AppHandle,
     session_manager: tauri::State<'_, Arc<TokioMutex<SessionManager>>>,
+    run_queue: tauri::State<'_, Arc<TokioMutex<RunQueue>>>,
+    process_pool: tauri::State<'_, Arc<TokioMutex<AgentProcessPool>>>,
 ) -> Result<(), String> {
+    run_queue.lock().await.cancel_all();
+    emit_run_queue(&app, &run_queue).await;
     // Close all sessions in SessionManager
     {
         let mut mgr = session_manager.lock().await;
         mgr.close_all();
     }
+    // Stop the agent processes kept for follow-ups
+    let kept = process_pool.lock().await.drain();
+    shutdown_executors(kept).await;
     // Also clean up legacy SESSIONS map
     terminate_all_active_sessions().await
 }
 
+/// Stop the agent process kept alive for a conversation's follow-ups.
+/// Returns whether there was one.
+#[tauri::command]
+pub async fn end_agent_conversation(
+    agent: String,
+    conversation_id: String,
+    process_pool: tauri::State<'_, Arc<TokioMutex<AgentProcessPool>>>,
+) -> Result<bool, String> {
+    let kept = process_pool
+        .lock()
+        .await
+        .remove(&AgentProcessPool::key(&agent, &conversation_id));
+    let found = kept.is_some();
+    shutdown_executors(kept.into_iter().collect()).await;
+    Ok(found)
+}
+
 #[tauri::command]
 pub async fn send_quit_command_to_session(session_id: String) -> Result<(), String> {
     send_quit_to_session(&session_id).await
@@ -48,3 +89,34 @@ pub async fn send_quit_command_to_session(session_id: String) -> Result<(), Stri
 pub async fn cleanup_sessions() -> Result<(), String> {
     cleanup_cli_sessions().await
 }
+
+#[tauri::command]
+pub async fn get_run_queue(
+    run_queue: tauri::State<'_, Arc<TokioMutex<RunQueue>>>,
+) -> Result<RunQueueSnapshot, String> {
+    Ok(run_queue.lock().await.snapshot())
+}
+
+#[tauri::command]
+pub async fn cancel_queued_run(
+    app: tauri::AppHandle,
+    session_id: String,
+    run_queue: tauri::State<'_, Arc<TokioMutex<RunQueue>>>,
+) -> Result<(), String> {
+    run_queue.lock().await.cancel(&session_id)?;
+    emit_run_queue(&app, &run_queue).await;
+    Ok(())
+}
+
+/// Higher priorities start first; runs with equal priority keep FIFO order.
+#[tauri::command]
+pub async fn reprioritize_queued_run(
+    app: tauri::AppHandle,
+    session_id: String,
+    priority: i32,
+    run_queue: tauri::State<'_, Arc<TokioMutex<RunQueue>>>,
+) -> Result<(), String> {
+    run_queue.lock().await.reprioritize(&session_id, priority)?;
+    emit_run_queue(&app, &run_queue).await;
+    Ok(())
+}
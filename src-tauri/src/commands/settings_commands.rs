@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::Runtime;
 use tauri_plugin_store::StoreExt;
 
+use crate::models::permission::PermissionPolicy;
 use crate::models::*;
+use crate::services::project_settings_service;
 
 fn ensure_root_object(root: &mut serde_json::Value) {
     if !root.is_object() {
@@ -246,6 +248,38 @@ pub async fn load_all_agent_settings(app: tauri::AppHandle) -> Result<AllAgentSe
     }
 }
 
+/// Path of the store file backing `load_all_agent_settings`, resolved without
+/// an `AppHandle` so the headless CLI shares the GUI's agent settings.
+pub(crate) fn all_agent_settings_store_path() -> Option<PathBuf> {
+    crate::app_data_dir().map(|dir| dir.join("all-agent-settings.json"))
+}
+
+/// Read `all_agent_settings` straight from a store file. A missing file or
+/// key yields the defaults, matching `load_all_agent_settings`.
+pub(crate) fn read_all_agent_settings_file(path: &Path) -> Result<AllAgentSettings, String> {
+    if !path.exists() {
+        return Ok(AllAgentSettings::default());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read settings store: {}", e))?;
+    let root: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse settings store: {}", e))?;
+
+    match root.get("all_agent_settings") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to deserialize settings: {}", e)),
+        None => Ok(AllAgentSettings::default()),
+    }
+}
+
+/// Load agent settings from the app data directory without a running app.
+pub(crate) fn load_all_agent_settings_from_disk() -> Result<AllAgentSettings, String> {
+    match all_agent_settings_store_path() {
+        Some(path) => read_all_agent_settings_file(&path),
+        None => Ok(AllAgentSettings::default()),
+    }
+}
+
 fn user_settings_path() -> Result<PathBuf, String> {
     let home =
         dirs::home_dir().ok_or_else(|| "Could not determine user home directory".to_string())?;
@@ -351,3 +385,48 @@ pub async fn load_agent_settings(app: tauri::AppHandle) -> Result<HashMap<String
         }
     }
 }
+
+/// The global permission policy, kept under `permission_policy` in
+/// `~/.commander/settings.json` so the headless CLI applies it too.
+pub(crate) fn load_global_permission_policy() -> Result<PermissionPolicy, String> {
+    let root = load_user_settings_json()?;
+    match root.get("permission_policy") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse permission policy: {}", e)),
+        None => Ok(PermissionPolicy::default()),
+    }
+}
+
+fn save_global_permission_policy(policy: &PermissionPolicy) -> Result<(), String> {
+    let mut root = load_user_settings_json()?;
+    ensure_root_object(&mut root);
+    root["permission_policy"] = serde_json::to_value(policy)
+        .map_err(|e| format!("Failed to serialize permission policy: {}", e))?;
+    save_user_settings_json(root)
+}
+
+/// The permission policy of a project, or the global one without a project.
+#[tauri::command]
+pub async fn get_permission_policy(project_path: Option<String>) -> Result<PermissionPolicy, String> {
+    match project_path {
+        Some(project_path) => Ok(project_settings_service::load_project_settings(&project_path)?
+            .permission_policy
+            .unwrap_or_default()),
+        None => load_global_permission_policy(),
+    }
+}
+
+#[tauri::command]
+pub async fn save_permission_policy(
+    project_path: Option<String>,
+    policy: PermissionPolicy,
+) -> Result<(), String> {
+    match project_path {
+        Some(project_path) => {
+            let mut settings = project_settings_service::load_project_settings(&project_path)?;
+            settings.permission_policy = (policy != PermissionPolicy::default()).then_some(policy);
+            project_settings_service::save_project_settings(&project_path, &settings)
+        }
+        None => save_global_permission_policy(&policy),
+    }
+}
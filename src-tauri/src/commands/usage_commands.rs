@@ -0,0 +1,75 @@
+use crate::models::budget::{Budget, BudgetStatus};
+use crate::models::usage::{CostBreakdown, CostDimension, ModelPrice, SessionUsage};
+use crate::services::budget_service;
+use crate::services::indexer::db::IndexDb;
+use crate::services::usage_service;
+use std::sync::Arc;
+
+#[tauri::command]
+pub async fn get_model_pricing() -> Result<Vec<ModelPrice>, String> {
+    usage_service::load_pricing()
+}
+
+/// Replace the pricing table and recompute the cost of all recorded usage.
+#[tauri::command]
+pub async fn save_model_pricing(
+    db: tauri::State<'_, Arc<IndexDb>>,
+    pricing: Vec<ModelPrice>,
+) -> Result<Vec<ModelPrice>, String> {
+    usage_service::save_pricing(&pricing)?;
+    db.reprice_usage(&|record| usage_service::cost_of(record, &pricing))?;
+    Ok(pricing)
+}
+
+/// Cost of the last `days` days grouped by project, agent, model or day,
+/// optionally for one project or agent only.
+#[tauri::command]
+pub async fn get_cost_breakdown(
+    db: tauri::State<'_, Arc<IndexDb>>,
+    dimension: CostDimension,
+    days: u32,
+    project_path: Option<String>,
+    agent: Option<String>,
+) -> Result<Vec<CostBreakdown>, String> {
+    let today = chrono::Utc::now().date_naive();
+    let since = today - chrono::Duration::days(days.max(1) as i64 - 1);
+    db.get_cost_breakdown(
+        dimension,
+        &since.format("%Y-%m-%d").to_string(),
+        project_path.as_deref(),
+        agent.as_deref(),
+    )
+}
+
+/// Usage of a session, by Commander's session id or the agent's.
+#[tauri::command]
+pub async fn get_session_usage(
+    db: tauri::State<'_, Arc<IndexDb>>,
+    session_id: String,
+) -> Result<Option<SessionUsage>, String> {
+    db.get_session_usage(&session_id)
+}
+
+#[tauri::command]
+pub async fn list_budgets() -> Result<Vec<Budget>, String> {
+    budget_service::load_budgets()
+}
+
+/// Replace the budgets, returning them as saved. Runs already going keep the
+/// budgets they started with.
+#[tauri::command]
+pub async fn save_budgets(budgets: Vec<Budget>) -> Result<Vec<Budget>, String> {
+    budget_service::save_budgets(&budgets)
+}
+
+/// Every budget with its spending in the current period.
+#[tauri::command]
+pub async fn get_budget_status(
+    db: tauri::State<'_, Arc<IndexDb>>,
+) -> Result<Vec<BudgetStatus>, String> {
+    let today = budget_service::today();
+    budget_service::load_budgets()?
+        .iter()
+        .map(|budget| budget_service::budget_status(&db, budget, today))
+        .collect()
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::services::secret_store_service::redact;
+
 /// Comprehensive error types for the Commander application
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "details")]
@@ -245,9 +247,9 @@ impl CommanderError {
         }
     }
 
-    /// Get user-friendly error message
+    /// Get user-friendly error message, with secrets redacted
     pub fn user_message(&self) -> String {
-        match self {
+        let message = match self {
             CommanderError::Git {
                 operation,
                 path,
@@ -347,7 +349,8 @@ impl CommanderError {
                 Some(c) => format!("Protocol error '{}' (code {}): {}", kind, c, message),
                 None => format!("Protocol error '{}': {}", kind, message),
             },
-        }
+        };
+        redact(&message)
     }
 
 }
@@ -0,0 +1,536 @@
+//! Headless entry point behind the `commander-cli` binary.
+//!
+//! Runs a single prompt against an agent with the same executors the GUI
+//! uses, prints the session's event stream to stdout and exits with a status
+//! code, so Commander can be driven from CI and scripts without a window.
+//!
+//! ```text
+//! commander-cli run --agent claude --cwd ./repo --format jsonl "fix the failing test"
+//! ```
+
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Mutex as TokioMutex;
+
+use crate::commands::cli_commands::{run_agent_session, AgentRunRequest};
+use crate::commands::settings_commands::load_all_agent_settings_from_disk;
+use crate::models::ai_agent::{AgentSettings, AllAgentSettings, StreamChunk};
+use crate::models::protocol::{
+    ProtocolEvent, RunOutcome, SandboxAccess, SessionEndReason, SessionEventKind,
+};
+use crate::services::agent_process_pool::AgentProcessPool;
+use crate::services::agent_status_service::ProtocolCache;
+use crate::services::audit_log_service::{self, default_audit_db_path, AuditLog};
+use crate::services::event_sink::{
+    EventRecord, EventSink, FileEventSink, RecordedEvent, SharedEventSink, TeeEventSink,
+};
+use crate::services::session_manager::SessionManager;
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+const USAGE: &str = "\
+Usage: commander-cli run --agent <name> [options] [prompt...]
+
+Runs one prompt against an agent and prints the session's events.
+The prompt is read from stdin when none is given on the command line.
+
+Options:
+  --agent <name>        Agent to run (claude, codex, gemini, ...)
+  --cwd <dir>           Working directory (defaults to the current directory)
+  --format <fmt>        Output format: human (default) or jsonl
+  --mode <mode>         Execution mode: chat, collab or full
+  --model <model>       Override the model from the saved agent settings
+  --transport <t>       Override the transport: acp, json-rpc or cli-flags
+  --resume <id>         Resume an existing agent session
+  --session-id <id>     Session id to report events under
+  --log <file>          Also append every event to a JSONL log file
+  --dangerous-bypass    Skip the agent's own approval and sandbox checks
+  --auto-approve        Approve every permission request (denied otherwise)
+  -h, --help            Show this help
+
+Exit codes: 0 success, 1 agent or executor failure, 2 usage error, 130 interrupted.
+";
+
+/// How session events are written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Raw agent output plus a short line per structured event.
+    Human,
+    /// One `{"ts": ..., "event": ..., "payload": ...}` JSON object per line.
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "human" | "text" => Some(Self::Human),
+            "jsonl" | "json" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed arguments of `commander-cli run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessArgs {
+    pub agent: String,
+    pub prompt: Option<String>,
+    pub working_dir: Option<String>,
+    pub format: OutputFormat,
+    pub execution_mode: Option<String>,
+    pub model: Option<String>,
+    pub transport: Option<String>,
+    pub resume_session_id: Option<String>,
+    pub session_id: Option<String>,
+    pub log_path: Option<String>,
+    pub dangerous_bypass: bool,
+    pub auto_approve: bool,
+}
+
+/// Result of parsing the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadlessCommand {
+    Run(Box<HeadlessArgs>),
+    Help,
+}
+
+/// Parse the arguments that follow the binary name.
+pub fn parse_args(args: &[String]) -> Result<HeadlessCommand, String> {
+    let mut iter = args.iter();
+    match iter.next().map(String::as_str) {
+        Some("run") => {}
+        Some("-h") | Some("--help") | Some("help") | None => return Ok(HeadlessCommand::Help),
+        Some(other) => return Err(format!("Unknown command '{}'", other)),
+    }
+
+    let mut agent = None;
+    let mut working_dir = None;
+    let mut format = OutputFormat::Human;
+    let mut execution_mode = None;
+    let mut model = None;
+    let mut transport = None;
+    let mut resume_session_id = None;
+    let mut session_id = None;
+    let mut log_path = None;
+    let mut dangerous_bypass = false;
+    let mut auto_approve = false;
+    let mut prompt_parts: Vec<String> = Vec::new();
+
+    while let Some(arg) = iter.next() {
+        let mut value_for = |flag: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", flag))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(HeadlessCommand::Help),
+            "--agent" => agent = Some(value_for("--agent")?),
+            "--cwd" => working_dir = Some(value_for("--cwd")?),
+            "--format" => {
+                let value = value_for("--format")?;
+                format = OutputFormat::parse(&value)
+                    .ok_or_else(|| format!("Unknown output format '{}'", value))?;
+            }
+            "--mode" => {
+                let value = value_for("--mode")?;
+                if !matches!(value.as_str(), "chat" | "collab" | "full") {
+                    return Err(format!("Unknown execution mode '{}'", value));
+                }
+                execution_mode = Some(value);
+            }
+            "--model" => model = Some(value_for("--model")?),
+            "--transport" => {
+                let value = value_for("--transport")?;
+                if !matches!(value.as_str(), "acp" | "json-rpc" | "cli-flags") {
+                    return Err(format!("Unknown transport '{}'", value));
+                }
+                transport = Some(value);
+            }
+            "--resume" => resume_session_id = Some(value_for("--resume")?),
+            "--session-id" => session_id = Some(value_for("--session-id")?),
+            "--log" => log_path = Some(value_for("--log")?),
+            "--dangerous-bypass" => dangerous_bypass = true,
+            "--auto-approve" => auto_approve = true,
+            "--" => {
+                prompt_parts.extend(iter.by_ref().cloned());
+            }
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown option '{}'", flag));
+            }
+            _ => prompt_parts.push(arg.clone()),
+        }
+    }
+
+    let agent = agent.ok_or_else(|| "--agent is required".to_string())?;
+    let prompt = if prompt_parts.is_empty() {
+        None
+    } else {
+        Some(prompt_parts.join(" "))
+    };
+
+    Ok(HeadlessCommand::Run(Box::new(HeadlessArgs {
+        agent,
+        prompt,
+        working_dir,
+        format,
+        execution_mode,
+        model,
+        transport,
+        resume_session_id,
+        session_id,
+        log_path,
+        dangerous_bypass,
+        auto_approve,
+    })))
+}
+
+/// Settings slot for a built-in agent, if `agent` names one.
+pub fn agent_settings_mut<'a>(
+    settings: &'a mut AllAgentSettings,
+    agent: &str,
+) -> Option<&'a mut AgentSettings> {
+    match agent {
+        "autohand" => Some(&mut settings.autohand),
+        "claude" => Some(&mut settings.claude),
+        "codex" => Some(&mut settings.codex),
+        "gemini" => Some(&mut settings.gemini),
+        "cursor" => Some(&mut settings.cursor),
+        "copilot" => Some(&mut settings.copilot),
+        "pi" => Some(&mut settings.pi),
+        "opencode" => Some(&mut settings.opencode),
+        "vibe" => Some(&mut settings.vibe),
+        "amp" => Some(&mut settings.amp),
+        _ => None,
+    }
+}
+
+/// Render one structured event as a human readable line, or `None` for
+/// events that add nothing over the raw stream.
+pub fn format_event_human(event: &ProtocolEvent) -> Option<String> {
+    match event {
+        ProtocolEvent::Message { role, content, .. } if role != "user" => {
+            Some(content.clone())
+        }
+        ProtocolEvent::Message { .. } => None,
+        ProtocolEvent::ToolStart { tool_name, .. } => Some(format!("▶ {}", tool_name)),
+        ProtocolEvent::ToolUpdate { .. } => None,
+        ProtocolEvent::ToolEnd {
+            tool_name,
+            success,
+            duration_ms,
+            ..
+        } => {
+            let mark = if *success { "✔" } else { "✖" };
+            Some(match duration_ms {
+                Some(ms) => format!("{} {} ({}ms)", mark, tool_name, ms),
+                None => format!("{} {}", mark, tool_name),
+            })
+        }
+        ProtocolEvent::PermissionRequest {
+            tool_name,
+            description,
+            ..
+        } => Some(format!("🔐 {} requested permission: {}", tool_name, description)),
+        ProtocolEvent::PermissionResolved {
+            tool_name,
+            approved,
+            reason,
+            ..
+        } => {
+            let verdict = if *approved { "allowed" } else { "denied" };
+            Some(format!("🔐 {} {} ({})", tool_name, verdict, reason))
+        }
+        ProtocolEvent::Plan { entries, .. } => {
+            let mut lines = vec!["📋 plan".to_string()];
+            for entry in entries {
+                let mark = match entry.status.as_str() {
+                    "completed" => "✔",
+                    "in_progress" => "▶",
+                    _ => "·",
+                };
+                lines.push(format!("  {} {}", mark, entry.content));
+            }
+            Some(lines.join("\n"))
+        }
+        ProtocolEvent::StateChange { usage: Some(usage), .. } => {
+            let tokens = format!("{} in / {} out tokens", usage.input_tokens, usage.output_tokens);
+            Some(match usage.cost_usd {
+                Some(cost) => format!("💰 ${:.4} · {}", cost, tokens),
+                None => format!("💰 {}", tokens),
+            })
+        }
+        ProtocolEvent::StateChange { .. } => None,
+        ProtocolEvent::Error {
+            message,
+            failure: Some(failure),
+            ..
+        } => Some(format!("❌ {}\n   hint: {}", message, failure.hint)),
+        ProtocolEvent::Error { message, .. } => Some(format!("❌ {}", message)),
+        ProtocolEvent::SessionEvent { event, .. } => match event {
+            SessionEventKind::FallbackToPty => Some("ℹ️ falling back to PTY".to_string()),
+            SessionEventKind::Reconnecting => Some("ℹ️ agent exited, reconnecting".to_string()),
+            SessionEventKind::Reconnected => Some("ℹ️ reconnected".to_string()),
+            SessionEventKind::ReconnectFailed => Some("❌ could not reconnect".to_string()),
+            SessionEventKind::Connected | SessionEventKind::Disconnected => None,
+        },
+        ProtocolEvent::SessionEnded { reason, .. } => match reason {
+            SessionEndReason::WallClockTimeout => Some("⏱️ stopped: run time limit reached".to_string()),
+            SessionEndReason::IdleTimeout => Some("⏱️ stopped: no output within the idle timeout".to_string()),
+            SessionEndReason::OutputLimit => Some("⏱️ stopped: output limit reached".to_string()),
+            SessionEndReason::Completed | SessionEndReason::Cancelled => None,
+        },
+        ProtocolEvent::SandboxBlocked { access, detail, .. } => Some(match access {
+            SandboxAccess::Write => format!("🔒 sandbox blocked a write: {}", detail),
+            SandboxAccess::Network => format!("🔒 sandbox blocked network access: {}", detail),
+        }),
+    }
+}
+
+/// Whether `event` closes the agent's turn: the run ended, or the agent
+/// left its working state.
+pub fn ends_turn(event: &ProtocolEvent) -> bool {
+    match event {
+        ProtocolEvent::StateChange { status, .. } => {
+            !matches!(status.as_str(), "running" | "queued" | "thinking")
+        }
+        ProtocolEvent::SessionEnded { .. } => true,
+        _ => false,
+    }
+}
+
+/// Prints session events to stdout and remembers whether the run failed,
+/// by the last event that settled it.
+struct HeadlessSink {
+    format: OutputFormat,
+    auto_approve: bool,
+    session_manager: Arc<TokioMutex<SessionManager>>,
+    failed: AtomicBool,
+    /// A message is being streamed and its line is still open.
+    mid_message: AtomicBool,
+}
+
+impl HeadlessSink {
+    fn write_record(&self, recorded: &RecordedEvent) {
+        let Ok(line) = serde_json::to_string(&EventRecord::now(recorded)) else {
+            return;
+        };
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{}", line);
+        let _ = stdout.flush();
+    }
+
+    /// Close the line of a streamed message, if one is open.
+    fn end_message(&self, stdout: &mut impl Write) {
+        if self.mid_message.swap(false, Ordering::SeqCst) {
+            let _ = writeln!(stdout);
+        }
+    }
+}
+
+impl EventSink for HeadlessSink {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        match self.format {
+            OutputFormat::Jsonl => self.write_record(&RecordedEvent::Chunk(chunk)),
+            OutputFormat::Human => {
+                let mut stdout = std::io::stdout().lock();
+                let _ = write!(stdout, "{}", chunk.content);
+                let _ = stdout.flush();
+            }
+        }
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        if let Some(outcome) = event.outcome() {
+            self.failed.store(outcome == RunOutcome::Failed, Ordering::SeqCst);
+        }
+
+        match self.format {
+            OutputFormat::Jsonl => self.write_record(&RecordedEvent::Protocol(event.clone())),
+            OutputFormat::Human => {
+                let mut stdout = std::io::stdout().lock();
+                match &event {
+                    // Messages arrive as deltas; the line ends with the message.
+                    ProtocolEvent::Message { role, content, .. } if role != "user" => {
+                        let _ = write!(stdout, "{}", content);
+                        self.mid_message.store(true, Ordering::SeqCst);
+                    }
+                    _ => {
+                        let line = format_event_human(&event);
+                        if line.is_some() || ends_turn(&event) {
+                            self.end_message(&mut stdout);
+                        }
+                        if let Some(line) = line {
+                            let _ = writeln!(stdout, "{}", line);
+                        }
+                    }
+                }
+                let _ = stdout.flush();
+            }
+        }
+
+        // There is nobody to ask, so answer permission requests from the flag.
+        if let ProtocolEvent::PermissionRequest {
+            session_id,
+            request_id,
+            ..
+        } = event
+        {
+            let approved = self.auto_approve;
+            let sm = Arc::clone(&self.session_manager);
+            tokio::spawn(async move {
+                let mgr = sm.lock().await;
+                let _ = mgr.send_permission(&session_id, request_id, approved);
+            });
+        }
+    }
+}
+
+/// Entry point for `commander-cli`. Takes the arguments after the binary
+/// name and returns the process exit code.
+pub fn run(args: Vec<String>) -> i32 {
+    let args = match parse_args(&args) {
+        Ok(HeadlessCommand::Run(args)) => *args,
+        Ok(HeadlessCommand::Help) => {
+            print!("{}", USAGE);
+            return EXIT_OK;
+        }
+        Err(e) => {
+            eprintln!("commander-cli: {}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("commander-cli: failed to start async runtime: {}", e);
+            return EXIT_FAILURE;
+        }
+    };
+
+    runtime.block_on(run_headless(args))
+}
+
+async fn run_headless(args: HeadlessArgs) -> i32 {
+    let prompt = match args.prompt.clone() {
+        Some(prompt) => prompt,
+        None => {
+            let mut buf = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut buf) {
+                eprintln!("commander-cli: failed to read prompt from stdin: {}", e);
+                return EXIT_USAGE;
+            }
+            buf.trim().to_string()
+        }
+    };
+    if prompt.is_empty() {
+        eprintln!("commander-cli: a prompt is required\n\n{}", USAGE);
+        return EXIT_USAGE;
+    }
+
+    let working_dir = match args.working_dir.clone() {
+        Some(dir) => match std::fs::canonicalize(&dir) {
+            Ok(path) if path.is_dir() => path.to_string_lossy().to_string(),
+            _ => {
+                eprintln!("commander-cli: working directory '{}' does not exist", dir);
+                return EXIT_USAGE;
+            }
+        },
+        None => match std::env::current_dir() {
+            Ok(path) => path.to_string_lossy().to_string(),
+            Err(e) => {
+                eprintln!("commander-cli: failed to resolve current directory: {}", e);
+                return EXIT_FAILURE;
+            }
+        },
+    };
+
+    let mut all_settings = load_all_agent_settings_from_disk().unwrap_or_else(|e| {
+        eprintln!("commander-cli: ignoring saved agent settings: {}", e);
+        AllAgentSettings::default()
+    });
+    if let Some(settings) = agent_settings_mut(&mut all_settings, &args.agent) {
+        if let Some(model) = args.model.clone() {
+            settings.model = Some(model);
+        }
+        if let Some(transport) = args.transport.clone() {
+            settings.transport = Some(transport);
+        }
+    }
+
+    // Runs from the command line are audited alongside the app's.
+    if let Some(path) = default_audit_db_path() {
+        match AuditLog::open(&path) {
+            Ok(log) => audit_log_service::install(Arc::new(log)),
+            Err(e) => eprintln!("commander-cli: audit log disabled: {}", e),
+        }
+    }
+
+    let session_id = args
+        .session_id
+        .clone()
+        .unwrap_or_else(|| format!("cli-{}", uuid::Uuid::new_v4()));
+    let session_manager = Arc::new(TokioMutex::new(SessionManager::new()));
+    let protocol_cache = Arc::new(TokioMutex::new(ProtocolCache::new()));
+
+    let sink = Arc::new(HeadlessSink {
+        format: args.format,
+        auto_approve: args.auto_approve,
+        session_manager: Arc::clone(&session_manager),
+        failed: AtomicBool::new(false),
+        mid_message: AtomicBool::new(false),
+    });
+    let shared: SharedEventSink = match args.log_path.as_deref() {
+        Some(path) => match FileEventSink::open(path) {
+            Ok(log) => TeeEventSink::shared(vec![sink.clone(), Arc::new(log)]),
+            Err(e) => {
+                eprintln!("commander-cli: {}", e);
+                return EXIT_USAGE;
+            }
+        },
+        None => sink.clone(),
+    };
+
+    let request = AgentRunRequest {
+        session_id: session_id.clone(),
+        agent: args.agent.clone(),
+        message: prompt,
+        working_dir: Some(working_dir),
+        execution_mode: args.execution_mode.clone(),
+        dangerous_bypass: args.dangerous_bypass,
+        resume_session_id: args.resume_session_id.clone(),
+        conversation_id: None,
+    };
+
+    let session = run_agent_session(
+        shared,
+        request,
+        all_settings,
+        Arc::clone(&session_manager),
+        protocol_cache,
+        Arc::new(TokioMutex::new(AgentProcessPool::new())),
+    );
+    tokio::pin!(session);
+
+    tokio::select! {
+        _ = &mut session => {}
+        _ = tokio::signal::ctrl_c() => {
+            session_manager.lock().await.close_session(&session_id);
+            // Give protocol executors a moment to stop their agent process.
+            let _ = tokio::time::timeout(std::time::Duration::from_secs(2), &mut session).await;
+            return EXIT_INTERRUPTED;
+        }
+    }
+    sink.end_message(&mut std::io::stdout().lock());
+
+    if sink.failed.load(Ordering::SeqCst) {
+        EXIT_FAILURE
+    } else {
+        EXIT_OK
+    }
+}
@@ -3,6 +3,7 @@
 // appear "unused" to the compiler during test compilation.
 #![cfg_attr(test, allow(dead_code, unused_imports))]
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::menu::{MenuBuilder, MenuItemBuilder, SubmenuBuilder};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
@@ -15,8 +16,20 @@ mod error;
 mod models;
 mod services;
 
+/// Headless runner used by the `commander-cli` binary.
+pub mod headless;
+
 use commands::*;
 
+/// Bundle identifier from `tauri.conf.json`; names the app's data directory.
+pub(crate) const APP_IDENTIFIER: &str = "ai.autohand.commander";
+
+/// The app's data directory as Tauri resolves it, for code that runs without
+/// an `AppHandle` such as `commander-cli`.
+pub(crate) fn app_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
 // Test modules (only compiled during testing)
 #[cfg(test)]
 mod tests;
@@ -230,8 +243,14 @@ pub fn run() {
             execute_amp_command,
             execute_test_command,
             get_active_sessions,
+            get_session_diagnostics,
             terminate_session,
+            get_run_queue,
+            cancel_queued_run,
+            reprioritize_queued_run,
+            fan_out_prompt,
             terminate_all_sessions,
+            end_agent_conversation,
             send_quit_command_to_session,
             cleanup_sessions,
             validate_git_repository_url,
@@ -249,6 +268,21 @@ pub fn run() {
             set_window_theme,
             get_code_auto_collapse_sidebar_setting,
             set_code_auto_collapse_sidebar_setting,
+            get_permission_policy,
+            save_permission_policy,
+            list_env_profiles,
+            save_env_profile,
+            delete_env_profile,
+            import_env_file,
+            set_project_env_profile,
+            preview_agent_environment,
+            get_model_pricing,
+            save_model_pricing,
+            get_cost_breakdown,
+            get_session_usage,
+            list_budgets,
+            save_budgets,
+            get_budget_status,
             fetch_openrouter_models,
             fetch_openai_models,
             check_ollama_installation,
@@ -308,6 +342,18 @@ pub fn run() {
             get_git_log,
             diff_workspace_vs_main,
             merge_workspace_to_main,
+            get_workspace_base_branch,
+            start_workspace_merge,
+            get_workspace_merge_conflicts,
+            get_merge_conflict_versions,
+            resolve_merge_conflict,
+            complete_workspace_merge,
+            abort_workspace_merge,
+            list_checkpoints,
+            diff_checkpoints,
+            restore_checkpoint,
+            get_project_settings,
+            save_project_settings,
             get_git_commit_dag,
             get_commit_diff_files,
             get_commit_diff_text,
@@ -318,6 +364,10 @@ pub fn run() {
             save_chat_session,
             load_chat_sessions,
             get_session_messages,
+            get_session_transcript,
+            replay_session,
+            replay_step,
+            stop_replay,
             delete_chat_session,
             archive_chat_session,
             unarchive_chat_session,
@@ -348,6 +398,8 @@ pub fn run() {
             get_file_info,
             read_file_content,
             write_file_content,
+            set_unsaved_buffer,
+            clear_unsaved_buffer,
             create_default_agents_docs,
             menu_new_project,
             menu_clone_project,
@@ -379,6 +431,7 @@ pub fn run() {
             get_autohand_state,
             get_indexer_status,
             trigger_reindex,
+            query_audit_log,
             sync_autohand_docs,
             search_autohand_docs,
             get_autohand_doc,
@@ -390,6 +443,12 @@ pub fn run() {
             // Register protocol-aware session manager and protocol cache as managed state
             app.manage(Arc::new(TokioMutex::new(crate::services::session_manager::SessionManager::new())));
             app.manage(Arc::new(TokioMutex::new(crate::services::agent_status_service::ProtocolCache::new())));
+            app.manage(Arc::new(TokioMutex::new(crate::services::replay_service::ReplayRegistry::new())));
+            app.manage(Arc::new(TokioMutex::new(crate::services::run_queue_service::RunQueue::default())));
+            app.manage(Arc::new(TokioMutex::new(crate::services::workspace_merge_service::MergeRegistry::new())));
+            let process_pool = Arc::new(TokioMutex::new(crate::services::agent_process_pool::AgentProcessPool::new()));
+            app.manage(Arc::clone(&process_pool));
+            tauri::async_runtime::spawn(crate::services::agent_process_pool::reap_idle_processes(process_pool));
 
             // Handle command line arguments for opening projects
             let args: Vec<String> = std::env::args().collect();
@@ -519,7 +578,7 @@ pub fn run() {
                 Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
                     as Box<dyn std::error::Error>
             })?;
-            let db_path = app_data_dir.join("commander_index.db");
+            let db_path = app_data_dir.join(services::indexer::db::INDEX_DB_FILE);
             let index_db = Arc::new(
                 services::indexer::db::IndexDb::open(&db_path).map_err(|e| {
                     Box::new(std::io::Error::new(std::io::ErrorKind::Other, e))
@@ -527,6 +586,16 @@ pub fn run() {
                 })?,
             );
             app.manage(index_db.clone());
+            // Live runs record their token usage into the index
+            services::usage_service::install(index_db.clone());
+
+            // Audit log of permission decisions and destructive tool calls
+            let audit_log = Arc::new(
+                services::audit_log_service::AuditLog::open(&db_path)
+                    .map_err(|e| Box::new(std::io::Error::other(e)) as Box<dyn std::error::Error>)?,
+            );
+            services::audit_log_service::install(audit_log.clone());
+            app.manage(audit_log);
 
             // Spawn background indexer loop
             let indexer_app_handle = app.handle().clone();
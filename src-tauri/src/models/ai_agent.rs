@@ -40,7 +40,15 @@ pub struct AgentSettings {
     pub model: Option<String>,
     pub sandbox_mode: bool,
     pub auto_approval: bool,
+    /// Minutes a run may go without output before it is stopped, and a kept
+    /// agent process without prompts before it is shut down; 0 for never.
     pub session_timeout_minutes: u32,
+    /// Minutes a run may take in total; 0 for no limit.
+    #[serde(default)]
+    pub max_run_minutes: u32,
+    /// Bytes of output a run may produce before it is stopped; 0 for no limit.
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: u64,
     pub output_format: String,
     pub debug_mode: bool,
     pub max_tokens: Option<u32>,
@@ -49,6 +57,50 @@ pub struct AgentSettings {
     /// When None, the built-in default is used.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub transport: Option<String>,
+    /// Speak the older `{"type", "data"}` ndJSON envelope instead of ACP
+    /// JSON-RPC when the transport is "acp".
+    #[serde(default)]
+    pub acp_legacy_envelope: bool,
+    /// Commander's own sandbox for the agent process, on top of whatever
+    /// the agent enforces itself.
+    #[serde(default)]
+    pub process_sandbox: ProcessSandbox,
+    /// Environment profile applied on top of the project's.
+    #[serde(default)]
+    pub env_profile: Option<String>,
+}
+
+fn default_max_output_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+/// Runs the agent in Linux user, mount and (optionally) network namespaces
+/// where only the project directory is writable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessSandbox {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Let the agent reach the network.
+    #[serde(default = "default_allow_network")]
+    pub allow_network: bool,
+    /// Paths the agent may write besides the project directory and its own
+    /// state directory.
+    #[serde(default)]
+    pub writable_paths: Vec<String>,
+}
+
+fn default_allow_network() -> bool {
+    true
+}
+
+impl Default for ProcessSandbox {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allow_network: default_allow_network(),
+            writable_paths: Vec::new(),
+        }
+    }
 }
 
 impl Default for AgentSettings {
@@ -59,11 +111,16 @@ impl Default for AgentSettings {
             sandbox_mode: false,
             auto_approval: false,
             session_timeout_minutes: 30,
+            max_run_minutes: 0,
+            max_output_bytes: default_max_output_bytes(),
             output_format: "markdown".to_string(),
             debug_mode: false,
             max_tokens: None,
             temperature: None,
             transport: None,
+            acp_legacy_envelope: false,
+            process_sandbox: ProcessSandbox::default(),
+            env_profile: None,
         }
     }
 }
@@ -114,6 +171,12 @@ pub struct AllAgentSettings {
     pub custom_agents: Vec<CustomAgentDefinition>,
     #[serde(default = "default_max_concurrent_sessions")]
     pub max_concurrent_sessions: u32,
+    /// Cap on sessions running at once in the same project; unlimited when unset.
+    #[serde(default)]
+    pub max_concurrent_sessions_per_project: Option<u32>,
+    /// Cap on sessions of the same agent running at once; unlimited when unset.
+    #[serde(default)]
+    pub max_concurrent_sessions_per_agent: Option<u32>,
 }
 
 fn default_max_concurrent_sessions() -> u32 {
@@ -135,6 +198,8 @@ impl Default for AllAgentSettings {
             amp: AgentSettings::default(),
             custom_agents: Vec::new(),
             max_concurrent_sessions: default_max_concurrent_sessions(),
+            max_concurrent_sessions_per_project: None,
+            max_concurrent_sessions_per_agent: None,
         }
     }
 }
@@ -211,7 +276,7 @@ pub struct ChatMessage {
     pub agent: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StreamChunk {
     pub session_id: String,
     pub content: String,
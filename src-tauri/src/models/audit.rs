@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::protocol::ToolKind;
+
+/// What an audit entry records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventKind {
+    /// A permission request was put to the user.
+    PermissionRequested,
+    /// A permission request was answered, by the user or a policy rule.
+    PermissionDecided,
+    /// A write, edit, delete or execute tool finished.
+    ToolCompleted,
+}
+
+impl AuditEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventKind::PermissionRequested => "permission_requested",
+            AuditEventKind::PermissionDecided => "permission_decided",
+            AuditEventKind::ToolCompleted => "tool_completed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "permission_requested" => Some(AuditEventKind::PermissionRequested),
+            "permission_decided" => Some(AuditEventKind::PermissionDecided),
+            "tool_completed" => Some(AuditEventKind::ToolCompleted),
+            _ => None,
+        }
+    }
+}
+
+/// One row of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Unix seconds.
+    pub timestamp: i64,
+    pub kind: AuditEventKind,
+    pub project_path: Option<String>,
+    pub agent: String,
+    pub session_id: String,
+    /// The permission request the entry belongs to. Tool entries carry the
+    /// request that approved them, when there was one.
+    pub request_id: Option<String>,
+    pub tool_id: Option<String>,
+    pub tool_name: Option<String>,
+    pub tool_kind: Option<ToolKind>,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub paths: Vec<String>,
+    pub approved: Option<bool>,
+    /// `user` or `policy`.
+    pub decided_by: Option<String>,
+    /// The rule that decided, or how the user answered.
+    pub reason: Option<String>,
+    pub success: Option<bool>,
+    pub duration_ms: Option<u64>,
+    /// Tool output, truncated.
+    pub output: Option<String>,
+}
+
+/// Filters for `query_audit_log`. Unset fields match everything; `since`
+/// and `until` are inclusive Unix seconds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditQuery {
+    pub project_path: Option<String>,
+    pub agent: Option<String>,
+    pub session_id: Option<String>,
+    pub kind: Option<AuditEventKind>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
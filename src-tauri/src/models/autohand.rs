@@ -127,6 +127,8 @@ pub struct McpConfig {
 /// Provider-specific details (API key, model override, base URL).
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProviderDetails {
+    /// The `secret://` handle the key is kept under; a plaintext key until
+    /// the config is next loaded or saved.
     #[serde(skip_serializing_if = "Option::is_none", alias = "apiKey")]
     pub api_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+/// What a budget caps the spending of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetScope {
+    Project,
+    Agent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    /// The current UTC day.
+    Daily,
+    /// The current UTC calendar month.
+    Monthly,
+}
+
+fn default_warn_at() -> Vec<f64> {
+    vec![0.5, 0.8]
+}
+
+fn default_hard_stop() -> bool {
+    true
+}
+
+/// A spending limit, kept in `~/.commander/budgets.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    pub scope: BudgetScope,
+    /// The project path or agent id the budget applies to.
+    pub target: String,
+    pub period: BudgetPeriod,
+    pub limit_usd: f64,
+    /// Fractions of the limit to warn at, e.g. `0.8` for 80%.
+    #[serde(default = "default_warn_at")]
+    pub warn_at: Vec<f64>,
+    /// Stop a live session whose usage takes spending past the limit.
+    #[serde(default = "default_hard_stop")]
+    pub hard_stop: bool,
+}
+
+impl Budget {
+    /// Stable key of the budget's persisted state.
+    pub fn id(&self) -> String {
+        let scope = match self.scope {
+            BudgetScope::Project => "project",
+            BudgetScope::Agent => "agent",
+        };
+        let period = match self.period {
+            BudgetPeriod::Daily => "daily",
+            BudgetPeriod::Monthly => "monthly",
+        };
+        format!("{}:{}:{}", scope, period, self.target)
+    }
+}
+
+/// What has been reported about a budget in one period.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BudgetState {
+    /// Highest threshold warned about.
+    pub warned_at: Option<f64>,
+    /// When spending first went past the limit, in Unix seconds.
+    pub exceeded_at: Option<i64>,
+}
+
+/// A budget and its spending in the current period.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetStatus {
+    pub budget: Budget,
+    /// First day of the period, `YYYY-MM-DD`.
+    pub period_start: String,
+    pub spent_usd: f64,
+    /// `spent_usd` as a fraction of the limit.
+    pub used: f64,
+    pub state: BudgetState,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BudgetAlertKind {
+    /// Spending passed `threshold` of the limit.
+    Warning { threshold: f64 },
+    /// Spending is past the limit.
+    Exceeded,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetAlert {
+    pub kind: BudgetAlertKind,
+    pub status: BudgetStatus,
+}
@@ -308,7 +308,11 @@ fn generate_summary(content: &str) -> String {
         return content.to_string();
     }
 
-    let truncated = &content[..100];
+    let mut end = 100;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    let truncated = &content[..end];
     if let Some(last_space) = truncated.rfind(' ') {
         format!("{}...", &truncated[..last_space])
     } else {
@@ -6,6 +6,8 @@ pub struct DashboardStats {
     pub total_messages: usize,
     pub total_sessions: usize,
     pub total_tokens: u64,
+    /// Cost of all recorded token usage, in USD.
+    pub total_cost_usd: f64,
     pub agents_used: HashMap<String, usize>,
     pub daily_activity: Vec<DailyActivity>,
     pub current_streak: u32,
@@ -34,6 +36,7 @@ impl Default for DashboardStats {
             total_messages: 0,
             total_sessions: 0,
             total_tokens: 0,
+            total_cost_usd: 0.0,
             agents_used: HashMap::new(),
             daily_activity: Vec::new(),
             current_streak: 0,
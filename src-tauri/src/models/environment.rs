@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// A variable a profile sets. Values may reference other variables as
+/// `$NAME`, `${NAME}` or `${NAME:-default}`; `$$` is a literal `$`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvVariable {
+    pub name: String,
+    /// The value, or a `secret://` handle into the secret store when
+    /// `secret` is set.
+    pub value: String,
+    /// Keep the value in the secret store; it is never expanded or shown.
+    #[serde(default)]
+    pub secret: bool,
+}
+
+/// Named environment for agent processes, kept in
+/// `~/.commander/env-profiles.json` and attached per project (in
+/// `<project>/.commander/settings.json`) and per agent.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvProfile {
+    pub name: String,
+    #[serde(default)]
+    pub variables: Vec<EnvVariable>,
+    /// Inherited variables to keep, as names or `PREFIX_*` globs. Empty
+    /// keeps everything Commander was started with.
+    #[serde(default)]
+    pub inherit_allow: Vec<String>,
+    /// Inherited variables to drop, checked after `inherit_allow`.
+    #[serde(default)]
+    pub inherit_deny: Vec<String>,
+}
+
+/// Where a variable in the effective environment comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "profile", rename_all = "lowercase")]
+pub enum EnvSource {
+    Inherited,
+    Profile(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvPreviewEntry {
+    pub name: String,
+    /// The value, masked for secrets.
+    pub value: String,
+    pub secret: bool,
+    pub source: EnvSource,
+}
+
+/// The environment a session's agent would start with.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvPreview {
+    /// Profiles applied, project first.
+    pub profiles: Vec<String>,
+    pub variables: Vec<EnvPreviewEntry>,
+    /// Inherited variables the allow and deny lists drop.
+    pub removed: Vec<String>,
+}
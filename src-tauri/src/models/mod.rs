@@ -1,11 +1,14 @@
 // Model exports
 pub mod ai_agent;
+pub mod audit;
+pub mod budget;
 pub mod autohand;
 pub mod chat_history;
 pub mod dashboard;
 pub mod file;
 pub mod indexer;
 pub mod llm;
+pub mod permission;
 pub mod project;
 pub mod prompt;
 pub mod protocol;
@@ -13,6 +16,8 @@ pub mod session;
 pub mod sub_agent;
 pub mod auth;
 pub mod docs;
+pub mod environment;
+pub mod usage;
 
 // Re-export all models for easy access
 pub use ai_agent::*;
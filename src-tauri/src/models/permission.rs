@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::protocol::ToolKind;
+
+/// What to do with an agent's permission request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionDecision {
+    Allow,
+    Deny,
+    /// Leave it to the user.
+    #[default]
+    Ask,
+}
+
+/// A policy rule. Every criterion that is set must match; a rule with none
+/// set matches every request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionRule {
+    pub decision: PermissionDecision,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_kind: Option<ToolKind>,
+    /// Tool name as the agent reports it, compared case-insensitively.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// Glob over the whole command line, where `*` matches anything,
+    /// e.g. `git status*` or `rm -rf *`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Glob over paths relative to the project root, e.g. `src/**/*.rs`.
+    /// Every path the request touches must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// Permission rules, configured globally in `~/.commander/settings.json`
+/// and per project in `<project>/.commander/settings.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionPolicy {
+    #[serde(default)]
+    pub rules: Vec<PermissionRule>,
+    /// Decision when no rule matches. A project's default overrides the
+    /// global one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_decision: Option<PermissionDecision>,
+}
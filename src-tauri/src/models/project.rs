@@ -1,6 +1,8 @@
 use serde::de::Deserializer;
 use serde::{Deserialize, Serialize};
 
+use crate::models::permission::PermissionPolicy;
+
 const ALLOWED_DEFAULT_CLI_AGENTS: &[&str] = &["autohand", "claude", "codex", "gemini"];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -203,3 +205,47 @@ impl AppSettings {
         self.default_cli_agent = sanitize_default_cli_agent(&self.default_cli_agent);
     }
 }
+
+/// How a workspace branch is brought into the base branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// `merge --no-ff`: keep the workspace history under a merge commit.
+    #[default]
+    MergeCommit,
+    /// Collapse the workspace into a single commit on the base branch.
+    Squash,
+    /// Rebase the workspace onto the base branch, then fast-forward.
+    RebaseFastForward,
+}
+
+impl MergeStrategy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "merge" | "merge_commit" | "no_ff" => Ok(MergeStrategy::MergeCommit),
+            "squash" => Ok(MergeStrategy::Squash),
+            "rebase" | "rebase_fast_forward" | "rebase_ff" => Ok(MergeStrategy::RebaseFastForward),
+            other => Err(format!(
+                "Unknown merge strategy '{}'. Expected merge_commit, squash or rebase_fast_forward",
+                other
+            )),
+        }
+    }
+}
+
+/// Per-project settings stored in `<project>/.commander/settings.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectSettings {
+    /// Branch workspaces are diffed against and merged into. Auto-detected
+    /// from `origin/HEAD` when unset.
+    #[serde(default)]
+    pub base_branch: Option<String>,
+    #[serde(default)]
+    pub merge_strategy: Option<MergeStrategy>,
+    /// Permission rules for this project, checked before the global ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permission_policy: Option<PermissionPolicy>,
+    /// Environment profile the project's agents start with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_profile: Option<String>,
+}
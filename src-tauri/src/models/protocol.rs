@@ -42,6 +42,18 @@ impl fmt::Display for ProtocolError {
     }
 }
 
+impl ProtocolError {
+    /// Whether restarting the agent could help. An agent that could not be
+    /// executed (126, 127) or rejected its arguments or configuration
+    /// (sysexits 64–78) will fail the same way again.
+    pub fn is_restartable(&self) -> bool {
+        match self {
+            ProtocolError::ProcessDied(code) => !matches!(code, 64..=78 | 126 | 127),
+            _ => false,
+        }
+    }
+}
+
 impl From<ProtocolError> for CommanderError {
     fn from(err: ProtocolError) -> Self {
         let kind = match &err {
@@ -80,13 +92,139 @@ pub enum ToolKind {
 #[serde(rename_all = "snake_case")]
 pub enum SessionEventKind {
     Connected,
+    /// The agent died during a turn and is about to be restarted.
+    Reconnecting,
     Reconnected,
+    /// The agent could not be brought back; the turn is over.
+    ReconnectFailed,
     Disconnected,
     FallbackToPty,
 }
 
+/// Why a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEndReason {
+    /// The agent finished, successfully or not.
+    Completed,
+    /// The user stopped the run.
+    Cancelled,
+    /// The run took longer than `max_run_minutes`.
+    WallClockTimeout,
+    /// The agent produced nothing for `session_timeout_minutes`.
+    IdleTimeout,
+    /// The agent produced more than `max_output_bytes` of output.
+    OutputLimit,
+}
+
+impl SessionEndReason {
+    /// Whether a session limit stopped the run.
+    pub fn is_limit(&self) -> bool {
+        matches!(
+            self,
+            SessionEndReason::WallClockTimeout
+                | SessionEndReason::IdleTimeout
+                | SessionEndReason::OutputLimit
+        )
+    }
+}
+
+/// What Commander's process sandbox kept an agent from doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxAccess {
+    /// Writing outside the writable paths.
+    Write,
+    /// Reaching the network while it is turned off.
+    Network,
+}
+
+/// One step of the plan an agent reports while working on a prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub content: String,
+    /// `high`, `medium` or `low`.
+    pub priority: String,
+    /// `pending`, `in_progress` or `completed`.
+    pub status: String,
+}
+
+/// Token counts and cost an agent reported for one turn.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TurnUsage {
+    pub model: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    /// Cost in USD, when the agent computes it.
+    pub cost_usd: Option<f64>,
+}
+
+/// What a tool asking for permission would touch, for policy rules to
+/// match on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionTarget {
+    pub tool_kind: ToolKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+/// Known ways an agent fails, recognised from what it writes to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentFailureKind {
+    MissingApiKey,
+    RateLimit,
+    Network,
+    VersionMismatch,
+}
+
+impl AgentFailureKind {
+    /// What the user can do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            AgentFailureKind::MissingApiKey => {
+                "The agent is not signed in. Set its API key in Settings or log in with its CLI, then try again."
+            }
+            AgentFailureKind::RateLimit => {
+                "The provider is rate limiting requests. Wait a moment before retrying, or check your plan's quota."
+            }
+            AgentFailureKind::Network => {
+                "The agent could not reach its provider. Check your connection, proxy and firewall settings."
+            }
+            AgentFailureKind::VersionMismatch => {
+                "The installed agent CLI does not speak the protocol Commander expects. Update it to the latest version."
+            }
+        }
+    }
+
+    /// Whether the failure can clear up on its own, so a restart may help.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, AgentFailureKind::RateLimit | AgentFailureKind::Network)
+    }
+}
+
+/// A recognised failure and how to fix it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentFailure {
+    pub kind: AgentFailureKind,
+    pub hint: String,
+}
+
+impl From<AgentFailureKind> for AgentFailure {
+    fn from(kind: AgentFailureKind) -> Self {
+        AgentFailure {
+            kind,
+            hint: kind.hint().to_string(),
+        }
+    }
+}
+
 /// Events emitted by a running agent session.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum ProtocolEvent {
     /// A text message produced by the agent.
@@ -125,21 +263,100 @@ pub enum ProtocolEvent {
         request_id: String,
         tool_name: String,
         description: String,
+        /// What the tool would touch, when the agent says.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target: Option<PermissionTarget>,
+        /// The tool call the request is for, when the agent announced it
+        /// before asking.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tool_id: Option<String>,
+    },
+    /// A permission request was answered, by a policy rule or by the user.
+    PermissionResolved {
+        session_id: String,
+        request_id: String,
+        tool_name: String,
+        approved: bool,
+        /// Why, e.g. the rule that matched.
+        reason: String,
+        /// Whether a rule or a session allowance decided rather than the user.
+        #[serde(default)]
+        automatic: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target: Option<PermissionTarget>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tool_id: Option<String>,
+    },
+    /// The agent's current plan. Each event replaces the previous one.
+    Plan {
+        session_id: String,
+        entries: Vec<PlanEntry>,
     },
     /// Arbitrary state changed in the session.
     StateChange {
         session_id: String,
         status: String,
         context_percent: Option<f64>,
+        /// Tokens and cost of the turn that just ended, when the agent reports them.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        usage: Option<TurnUsage>,
+        /// The agent's own id for the conversation, for `--resume`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        agent_session_id: Option<String>,
     },
     /// A protocol-level error occurred.
     Error {
         session_id: String,
         message: String,
+        /// Set when the error is a failure Commander recognises.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        failure: Option<AgentFailure>,
     },
     /// Session lifecycle notification.
     SessionEvent {
         session_id: String,
         event: SessionEventKind,
     },
+    /// The run is over, and why.
+    SessionEnded {
+        session_id: String,
+        reason: SessionEndReason,
+    },
+    /// The process sandbox blocked something the agent tried.
+    SandboxBlocked {
+        session_id: String,
+        access: SandboxAccess,
+        /// The output line that showed the failure.
+        detail: String,
+    },
+}
+
+/// How a run went, by the last event that settled it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Completed,
+    Failed,
+}
+
+impl ProtocolEvent {
+    /// What the event says about how the run went, if anything. An error or
+    /// a session limit fails it; a turn ending settles it by its status. The
+    /// last event to say something wins, so an error the agent recovered
+    /// from (a retried rate limit, say) is outweighed by the turn it then
+    /// finished.
+    pub fn outcome(&self) -> Option<RunOutcome> {
+        match self {
+            ProtocolEvent::Error { .. } => Some(RunOutcome::Failed),
+            ProtocolEvent::SessionEnded { reason, .. } if reason.is_limit() => {
+                Some(RunOutcome::Failed)
+            }
+            ProtocolEvent::StateChange { status, .. } => match status.as_str() {
+                "running" | "queued" | "thinking" | "budget_warning" | "budget_exceeded" => None,
+                "failed" => Some(RunOutcome::Failed),
+                status if status.starts_with("error") => Some(RunOutcome::Failed),
+                _ => Some(RunOutcome::Completed),
+            },
+            _ => None,
+        }
+    }
 }
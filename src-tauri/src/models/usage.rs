@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+/// List price of a model in USD per million tokens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// Model id or id prefix, e.g. `claude-sonnet-4` or `gpt-5-mini`. The
+    /// longest matching prefix prices a model.
+    pub model: String,
+    pub input: f64,
+    pub output: f64,
+    #[serde(default)]
+    pub cache_read: f64,
+    #[serde(default)]
+    pub cache_write: f64,
+}
+
+/// Where a usage row was read from. A session's transcript, once indexed,
+/// replaces what the live run recorded since it also has turns run outside
+/// Commander.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageSource {
+    Live,
+    Transcript,
+}
+
+impl UsageSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageSource::Live => "live",
+            UsageSource::Transcript => "transcript",
+        }
+    }
+}
+
+/// Tokens one session spent on one model on one day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub agent_id: String,
+    /// The agent's own session id where known, otherwise Commander's.
+    pub session_key: String,
+    pub source: UsageSource,
+    pub model: String,
+    /// UTC day, `YYYY-MM-DD`.
+    pub date: String,
+    pub project_path: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    /// What the agent said the tokens cost, used when the pricing table has
+    /// no entry for the model.
+    pub reported_cost_usd: Option<f64>,
+    /// Cost from the pricing table, or the reported one; `None` when
+    /// neither knows the model.
+    pub cost_usd: Option<f64>,
+}
+
+/// What cost is grouped by in a breakdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CostDimension {
+    Project,
+    Agent,
+    Model,
+    Day,
+}
+
+/// Tokens and cost of one project, agent, model or day.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostBreakdown {
+    pub key: String,
+    pub sessions: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost_usd: f64,
+    /// Tokens of models neither priced nor costed by the agent.
+    pub unpriced_tokens: u64,
+}
+
+/// Tokens and cost of one session across its models and days.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionUsage {
+    pub agent_id: String,
+    pub session_key: String,
+    pub models: Vec<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost_usd: f64,
+}
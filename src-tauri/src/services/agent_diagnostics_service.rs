@@ -0,0 +1,143 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::models::protocol::{AgentFailure, AgentFailureKind};
+use crate::services::secret_store_service::redact;
+
+/// Stderr lines kept per session; the oldest go first.
+pub const MAX_DIAGNOSTIC_LINES: usize = 500;
+
+/// Longest stderr line kept, in characters.
+const MAX_LINE_CHARS: usize = 2000;
+
+/// Sessions whose diagnostics outlive them, for post-mortems.
+const MAX_SESSIONS: usize = 50;
+
+/// What an agent wrote to stderr during a session.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SessionDiagnostics {
+    pub session_id: String,
+    pub lines: VecDeque<String>,
+    /// Lines dropped to stay within `MAX_DIAGNOSTIC_LINES`.
+    pub dropped_lines: usize,
+    /// Failures recognised in the output, in the order they first showed up.
+    pub failures: Vec<AgentFailure>,
+}
+
+#[derive(Default)]
+struct DiagnosticsStore {
+    sessions: HashMap<String, SessionDiagnostics>,
+    /// Session ids, oldest first.
+    order: VecDeque<String>,
+}
+
+static STORE: Lazy<Mutex<DiagnosticsStore>> = Lazy::new(|| Mutex::new(DiagnosticsStore::default()));
+
+static ANSI_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").expect("valid ANSI regex"));
+
+/// Checked in order; the first match wins. Patterns need fatal context:
+/// every match is reported as an error, so update notices and retried
+/// lookups must not trip them.
+static FAILURE_PATTERNS: Lazy<Vec<(AgentFailureKind, Regex)>> = Lazy::new(|| {
+    [
+        (
+            AgentFailureKind::RateLimit,
+            r"(?i)rate[ _-]?limit|too many requests|\b(status|code|http)\D{0,10}429\b|quota (exceeded|exhausted)|insufficient[ _]quota|\boverloaded\b",
+        ),
+        (
+            AgentFailureKind::MissingApiKey,
+            r"(?i)(api[ _-]?key|access token|auth token)\b.*\b(missing|not set|not found|not configured|invalid|required|expired)\b|\b(missing|no|invalid)\b.{0,20}\b(api[ _-]?key|credentials)\b|\b(error|failed|fatal)\b.{0,40}\bunauthori[sz]ed\b|^\W*unauthori[sz]ed\b|\b(status|code|http)\D{0,10}401\b|authentication (failed|required|error)|not (logged|signed) in|please (log ?in|sign ?in|run .{0,40}\blogin)",
+        ),
+        (
+            AgentFailureKind::VersionMismatch,
+            r"(?i)(unsupported|incompatible|mismatched?)\b.{0,30}\bprotocol|protocol version\b.{0,30}\b(unsupported|mismatch|incompatible)|unknown (option|argument|flag)|unexpected argument|unrecognized (option|argument)|requires? (node|a newer)|\b(is )?no longer supported\b|\b(version|cli) is too old\b|\b(must|need to) (upgrade|update)\b",
+        ),
+        (
+            AgentFailureKind::Network,
+            r"(?i)\b(ECONNREFUSED|ECONNRESET|ENOTFOUND|ETIMEDOUT|EAI_AGAIN|ENETUNREACH)\b|network (error|is unreachable)|could not resolve host|connection (refused|reset|timed out|closed)|getaddrinfo\b.{0,20}\bfailed|fetch failed",
+        ),
+    ]
+    .into_iter()
+    .map(|(kind, pattern)| (kind, Regex::new(pattern).expect("valid failure pattern")))
+    .collect()
+});
+
+/// Recognise a known failure in one line of agent stderr.
+pub fn classify_stderr_line(line: &str) -> Option<AgentFailureKind> {
+    FAILURE_PATTERNS
+        .iter()
+        .find(|(_, pattern)| pattern.is_match(line))
+        .map(|(kind, _)| *kind)
+}
+
+/// Append a line of agent stderr to the session's diagnostics. Returns the
+/// failure the line reveals, the first time that kind shows up since the
+/// agent was (re)started.
+pub fn record_stderr_line(session_id: &str, line: &str) -> Option<AgentFailure> {
+    let line = redact(&ANSI_RE.replace_all(line, ""));
+    let line: String = line.trim_end().chars().take(MAX_LINE_CHARS).collect();
+    if line.trim().is_empty() {
+        return None;
+    }
+    let kind = classify_stderr_line(&line);
+
+    let mut store = STORE.lock().ok()?;
+    if !store.sessions.contains_key(session_id) {
+        if store.order.len() >= MAX_SESSIONS {
+            if let Some(oldest) = store.order.pop_front() {
+                store.sessions.remove(&oldest);
+            }
+        }
+        store.order.push_back(session_id.to_string());
+    }
+    let diagnostics = store
+        .sessions
+        .entry(session_id.to_string())
+        .or_insert_with(|| SessionDiagnostics {
+            session_id: session_id.to_string(),
+            ..SessionDiagnostics::default()
+        });
+    if diagnostics.lines.len() >= MAX_DIAGNOSTIC_LINES {
+        diagnostics.lines.pop_front();
+        diagnostics.dropped_lines += 1;
+    }
+    diagnostics.lines.push_back(line);
+
+    let kind = kind?;
+    if diagnostics.failures.iter().any(|f| f.kind == kind) {
+        return None;
+    }
+    let failure = AgentFailure::from(kind);
+    diagnostics.failures.push(failure.clone());
+    Some(failure)
+}
+
+/// The stderr diagnostics recorded for a session, if it wrote any.
+pub fn session_diagnostics(session_id: &str) -> Option<SessionDiagnostics> {
+    STORE.lock().ok()?.sessions.get(session_id).cloned()
+}
+
+/// Forget the failures recognised so far, keeping the lines. Called before
+/// an agent is restarted, so each crash is judged by its own output.
+pub fn clear_failures(session_id: &str) {
+    if let Ok(mut store) = STORE.lock() {
+        if let Some(diagnostics) = store.sessions.get_mut(session_id) {
+            diagnostics.failures.clear();
+        }
+    }
+}
+
+/// The failure most recently recognised for a session.
+pub fn last_failure(session_id: &str) -> Option<AgentFailureKind> {
+    let store = STORE.lock().ok()?;
+    store
+        .sessions
+        .get(session_id)?
+        .failures
+        .last()
+        .map(|f| f.kind)
+}
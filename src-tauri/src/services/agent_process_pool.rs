@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex as TokioMutex;
+
+use crate::services::executors::AgentExecutor;
+
+/// How often idle agent processes are looked for.
+pub const IDLE_REAP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// An executor that can be shared between the turns of a conversation.
+/// Holding its lock is what makes a turn the running one; follow-ups wait
+/// on the lock in arrival order.
+pub type SharedExecutor = Arc<TokioMutex<Box<dyn AgentExecutor>>>;
+
+struct LiveProcess {
+    executor: SharedExecutor,
+    agent: String,
+    working_dir: String,
+    idle_timeout: Duration,
+    last_used: Instant,
+}
+
+/// Protocol agent processes kept alive between the turns of a
+/// conversation, keyed by agent and conversation id.
+#[derive(Default)]
+pub struct AgentProcessPool {
+    processes: HashMap<String, LiveProcess>,
+}
+
+impl AgentProcessPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key(agent: &str, conversation_id: &str) -> String {
+        format!("{}:{}", agent, conversation_id)
+    }
+
+    /// The live process of a conversation, if it was started for the same
+    /// agent in the same directory.
+    pub fn get(&self, key: &str, agent: &str, working_dir: &str) -> Option<SharedExecutor> {
+        self.processes
+            .get(key)
+            .filter(|p| p.agent == agent && p.working_dir == working_dir)
+            .map(|p| Arc::clone(&p.executor))
+    }
+
+    /// Keep `executor` for the next turn of the conversation. Returns the
+    /// process it replaces, which the caller should shut down.
+    pub fn insert(
+        &mut self,
+        key: &str,
+        executor: SharedExecutor,
+        agent: &str,
+        working_dir: &str,
+        idle_timeout: Duration,
+    ) -> Option<SharedExecutor> {
+        self.processes
+            .insert(
+                key.to_string(),
+                LiveProcess {
+                    executor,
+                    agent: agent.to_string(),
+                    working_dir: working_dir.to_string(),
+                    idle_timeout,
+                    last_used: Instant::now(),
+                },
+            )
+            .map(|previous| previous.executor)
+    }
+
+    /// Restart the idle clock once a turn has ended.
+    pub fn touch(&mut self, key: &str) {
+        if let Some(process) = self.processes.get_mut(key) {
+            process.last_used = Instant::now();
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<SharedExecutor> {
+        self.processes.remove(key).map(|p| p.executor)
+    }
+
+    /// Remove `executor` from the pool unless another process has already
+    /// taken its place. Returns whether it was removed.
+    pub fn remove_executor(&mut self, key: &str, executor: &SharedExecutor) -> bool {
+        let matches = self
+            .processes
+            .get(key)
+            .is_some_and(|p| Arc::ptr_eq(&p.executor, executor));
+        if matches {
+            self.processes.remove(key);
+        }
+        matches
+    }
+
+    /// Take out every process whose agent has exited or that has been idle
+    /// longer than its timeout. A process in the middle of a turn, or with
+    /// a turn waiting for it, is never idle.
+    pub fn take_expired(&mut self, now: Instant) -> Vec<SharedExecutor> {
+        let expired: Vec<String> = self
+            .processes
+            .iter()
+            .filter(|(_, p)| {
+                let Ok(executor) = p.executor.try_lock() else {
+                    return false;
+                };
+                !executor.is_alive()
+                    || (!executor.is_busy()
+                        && now.saturating_duration_since(p.last_used) >= p.idle_timeout)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        expired
+            .iter()
+            .filter_map(|key| self.remove(key))
+            .collect()
+    }
+
+    /// Take out every process, e.g. when the app quits.
+    pub fn drain(&mut self) -> Vec<SharedExecutor> {
+        self.processes.drain().map(|(_, p)| p.executor).collect()
+    }
+}
+
+/// Idle timeout for a conversation's process from the agent's
+/// `session_timeout_minutes`; `None` when processes should not be kept.
+pub fn idle_timeout(session_timeout_minutes: u32) -> Option<Duration> {
+    (session_timeout_minutes > 0).then(|| Duration::from_secs(session_timeout_minutes as u64 * 60))
+}
+
+/// Shut down processes taken out of the pool.
+pub async fn shutdown_executors(executors: Vec<SharedExecutor>) {
+    for executor in executors {
+        let executor = executor.lock().await;
+        if executor.is_alive() {
+            if let Err(e) = executor.shutdown().await {
+                eprintln!("⚠️ Failed to stop agent process: {}", e);
+            }
+        }
+    }
+}
+
+/// Stop processes that have been idle past their timeout, checking every
+/// [`IDLE_REAP_INTERVAL`]. Runs until the app exits.
+pub async fn reap_idle_processes(pool: Arc<TokioMutex<AgentProcessPool>>) {
+    let mut interval = tokio::time::interval(IDLE_REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let expired = pool.lock().await.take_expired(Instant::now());
+        shutdown_executors(expired).await;
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::OnceCell;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+use crate::models::ai_agent::StreamChunk;
+use crate::models::audit::{AuditEntry, AuditEventKind, AuditQuery};
+use crate::models::protocol::{PermissionTarget, ProtocolEvent, ToolKind};
+use crate::services::checkpoint_service::is_mutating_tool;
+use crate::services::event_sink::EventSink;
+use crate::services::indexer::db::INDEX_DB_FILE;
+use crate::services::secret_store_service::redact;
+
+/// Longest tool output kept in an entry.
+const MAX_OUTPUT_CHARS: usize = 4000;
+const DEFAULT_QUERY_LIMIT: usize = 500;
+
+static AUDIT_LOG: OnceCell<Arc<AuditLog>> = OnceCell::new();
+
+/// Make `log` the one agent runs record into.
+pub fn install(log: Arc<AuditLog>) {
+    let _ = AUDIT_LOG.set(log);
+}
+
+pub fn installed() -> Option<Arc<AuditLog>> {
+    AUDIT_LOG.get().cloned()
+}
+
+/// Where the app keeps the audit log, for runs outside the app such as
+/// `commander-cli`: the index database.
+pub fn default_audit_db_path() -> Option<PathBuf> {
+    crate::app_data_dir().map(|dir| dir.join(INDEX_DB_FILE))
+}
+
+/// Append-only store of permission decisions and destructive tool calls.
+/// Triggers reject updates and deletes, so rows can only be added.
+///
+/// The `audit_events` table lives in the index database; the log keeps a
+/// connection of its own so appends never queue behind a scan.
+pub struct AuditLog {
+    conn: Mutex<Connection>,
+}
+
+impl AuditLog {
+    pub fn open(db_path: &Path) -> Result<Self, String> {
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create audit db dir: {}", e))?;
+        }
+        let conn = Connection::open(db_path)
+            .map_err(|e| format!("Failed to open audit database: {}", e))?;
+        conn.execute_batch(
+            "PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL; PRAGMA busy_timeout=5000;",
+        )
+        .map_err(|e| format!("Failed to set pragmas: {}", e))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS audit_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                project_path TEXT,
+                agent TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                request_id TEXT,
+                tool_id TEXT,
+                tool_name TEXT,
+                tool_kind TEXT,
+                command TEXT,
+                paths TEXT,
+                approved INTEGER,
+                decided_by TEXT,
+                reason TEXT,
+                success INTEGER,
+                duration_ms INTEGER,
+                output TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_audit_timestamp ON audit_events(timestamp);
+            CREATE INDEX IF NOT EXISTS idx_audit_session ON audit_events(session_id);
+            CREATE INDEX IF NOT EXISTS idx_audit_project ON audit_events(project_path);
+
+            CREATE TRIGGER IF NOT EXISTS audit_events_no_update
+            BEFORE UPDATE ON audit_events
+            BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+
+            CREATE TRIGGER IF NOT EXISTS audit_events_no_delete
+            BEFORE DELETE ON audit_events
+            BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;",
+        )
+        .map_err(|e| format!("Failed to init audit schema: {}", e))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Add an entry; its `id` is ignored and the new row id returned.
+    pub fn append(&self, entry: &AuditEntry) -> Result<i64, String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let tool_kind = entry.tool_kind.as_ref().map(tool_kind_str);
+        let paths = if entry.paths.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&entry.paths).ok()
+        };
+        let duration_ms = entry.duration_ms.map(|ms| ms.min(i64::MAX as u64) as i64);
+        let command = entry.command.as_deref().map(redact);
+        let output = entry.output.as_deref().map(redact);
+        conn.execute(
+            "INSERT INTO audit_events (timestamp, kind, project_path, agent, session_id,
+                request_id, tool_id, tool_name, tool_kind, command, paths, approved,
+                decided_by, reason, success, duration_ms, output)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+            params![
+                entry.timestamp,
+                entry.kind.as_str(),
+                entry.project_path,
+                entry.agent,
+                entry.session_id,
+                entry.request_id,
+                entry.tool_id,
+                entry.tool_name,
+                tool_kind,
+                command,
+                paths,
+                entry.approved,
+                entry.decided_by,
+                entry.reason,
+                entry.success,
+                duration_ms,
+                output,
+            ],
+        )
+        .map_err(|e| format!("Failed to append audit entry: {}", e))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Entries matching `query`, oldest first.
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, timestamp, kind, project_path, agent, session_id, request_id,
+                        tool_id, tool_name, tool_kind, command, paths, approved, decided_by,
+                        reason, success, duration_ms, output
+                 FROM audit_events
+                 WHERE (?1 IS NULL OR project_path = ?1)
+                   AND (?2 IS NULL OR agent = ?2)
+                   AND (?3 IS NULL OR session_id = ?3)
+                   AND (?4 IS NULL OR kind = ?4)
+                   AND (?5 IS NULL OR timestamp >= ?5)
+                   AND (?6 IS NULL OR timestamp <= ?6)
+                 ORDER BY timestamp ASC, id ASC
+                 LIMIT ?7 OFFSET ?8",
+            )
+            .map_err(|e| format!("Prepare error: {}", e))?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_QUERY_LIMIT) as i64;
+        let offset = query.offset.unwrap_or(0) as i64;
+        let rows = stmt
+            .query_map(
+                params![
+                    query.project_path,
+                    query.agent,
+                    query.session_id,
+                    query.kind.map(|k| k.as_str()),
+                    query.since,
+                    query.until,
+                    limit,
+                    offset,
+                ],
+                |row| {
+                    let kind: String = row.get(2)?;
+                    let tool_kind: Option<String> = row.get(9)?;
+                    let paths: Option<String> = row.get(11)?;
+                    let duration_ms: Option<i64> = row.get(16)?;
+                    Ok(AuditEntry {
+                        id: row.get(0)?,
+                        timestamp: row.get(1)?,
+                        kind: AuditEventKind::parse(&kind)
+                            .unwrap_or(AuditEventKind::PermissionDecided),
+                        project_path: row.get(3)?,
+                        agent: row.get(4)?,
+                        session_id: row.get(5)?,
+                        request_id: row.get(6)?,
+                        tool_id: row.get(7)?,
+                        tool_name: row.get(8)?,
+                        tool_kind: tool_kind
+                            .and_then(|k| serde_json::from_value(Value::String(k)).ok()),
+                        command: row.get(10)?,
+                        paths: paths
+                            .and_then(|p| serde_json::from_str(&p).ok())
+                            .unwrap_or_default(),
+                        approved: row.get(12)?,
+                        decided_by: row.get(13)?,
+                        reason: row.get(14)?,
+                        success: row.get(15)?,
+                        duration_ms: duration_ms.map(|ms| ms.max(0) as u64),
+                        output: row.get(17)?,
+                    })
+                },
+            )
+            .map_err(|e| format!("Query error: {}", e))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {}", e))?);
+        }
+        Ok(results)
+    }
+}
+
+fn tool_kind_str(kind: &ToolKind) -> String {
+    serde_json::to_value(kind)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+/// Tools whose results are audited: anything that changes files or runs
+/// commands.
+pub fn is_audited_tool(kind: &ToolKind) -> bool {
+    is_mutating_tool(kind) || *kind == ToolKind::Execute
+}
+
+/// The command and paths in a tool's arguments.
+fn tool_args_target(args: Option<&Value>) -> (Option<String>, Vec<String>) {
+    let Some(args) = args else {
+        return (None, Vec::new());
+    };
+    let command = match args.get("command") {
+        Some(Value::String(command)) => Some(command.clone()),
+        Some(Value::Array(parts)) => Some(
+            parts
+                .iter()
+                .filter_map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        _ => None,
+    };
+    // Codex file changes pass the list of changed files as the arguments.
+    let paths = match args {
+        Value::Array(changes) => changes
+            .iter()
+            .filter_map(|c| c.get("path").and_then(|v| v.as_str()))
+            .map(String::from)
+            .collect(),
+        _ => ["path", "file_path", "filePath", "abs_path"]
+            .iter()
+            .filter_map(|key| args.get(*key).and_then(|v| v.as_str()))
+            .map(String::from)
+            .collect(),
+    };
+    (command, paths)
+}
+
+fn truncate_output(output: String) -> String {
+    match output.char_indices().nth(MAX_OUTPUT_CHARS) {
+        Some((cut, _)) => format!("{}…", &output[..cut]),
+        None => output,
+    }
+}
+
+struct RunningTool {
+    name: String,
+    kind: ToolKind,
+    args: Option<Value>,
+}
+
+/// Event sink that writes a run's permission requests, decisions and
+/// destructive tool results to the audit log.
+///
+/// It sits behind the permission gate, so requests a rule decides arrive
+/// only as `PermissionResolved`, which carries the request's details.
+pub struct AuditRecorder {
+    log: Arc<AuditLog>,
+    project_path: Option<String>,
+    agent: String,
+    session_id: String,
+    tools: Mutex<HashMap<String, RunningTool>>,
+    /// Approving request of each tool call, by tool id.
+    approvals: Mutex<HashMap<String, String>>,
+    /// An approved request that did not name its tool call; the next tool
+    /// to start is taken to be it.
+    unclaimed_approval: Mutex<Option<String>>,
+}
+
+impl AuditRecorder {
+    pub fn new(
+        log: Arc<AuditLog>,
+        project_path: Option<&str>,
+        agent: &str,
+        session_id: &str,
+    ) -> Self {
+        Self {
+            log,
+            project_path: project_path.map(String::from),
+            agent: agent.to_string(),
+            session_id: session_id.to_string(),
+            tools: Mutex::new(HashMap::new()),
+            approvals: Mutex::new(HashMap::new()),
+            unclaimed_approval: Mutex::new(None),
+        }
+    }
+
+    fn entry(&self, kind: AuditEventKind) -> AuditEntry {
+        AuditEntry {
+            id: 0,
+            timestamp: chrono::Utc::now().timestamp(),
+            kind,
+            project_path: self.project_path.clone(),
+            agent: self.agent.clone(),
+            session_id: self.session_id.clone(),
+            request_id: None,
+            tool_id: None,
+            tool_name: None,
+            tool_kind: None,
+            command: None,
+            paths: Vec::new(),
+            approved: None,
+            decided_by: None,
+            reason: None,
+            success: None,
+            duration_ms: None,
+            output: None,
+        }
+    }
+
+    fn with_target(mut entry: AuditEntry, target: Option<PermissionTarget>) -> AuditEntry {
+        if let Some(target) = target {
+            entry.tool_kind = Some(target.tool_kind);
+            entry.command = target.command;
+            entry.paths = target.paths;
+        }
+        entry
+    }
+
+    fn append(&self, entry: AuditEntry) {
+        if let Err(e) = self.log.append(&entry) {
+            eprintln!(
+                "⚠️ Failed to write audit entry for {}: {}",
+                self.session_id, e
+            );
+        }
+    }
+}
+
+impl EventSink for AuditRecorder {
+    fn emit_chunk(&self, _chunk: StreamChunk) {}
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        match event {
+            ProtocolEvent::PermissionRequest {
+                request_id,
+                tool_name,
+                description,
+                target,
+                tool_id,
+                ..
+            } => {
+                let mut entry = self.entry(AuditEventKind::PermissionRequested);
+                entry.request_id = Some(request_id);
+                entry.tool_id = tool_id;
+                entry.tool_name = Some(tool_name);
+                entry.reason = Some(description);
+                self.append(Self::with_target(entry, target));
+            }
+            ProtocolEvent::PermissionResolved {
+                request_id,
+                tool_name,
+                approved,
+                reason,
+                automatic,
+                target,
+                tool_id,
+                ..
+            } => {
+                if approved {
+                    match &tool_id {
+                        Some(tool_id) => {
+                            if let Ok(mut approvals) = self.approvals.lock() {
+                                approvals.insert(tool_id.clone(), request_id.clone());
+                            }
+                        }
+                        None => {
+                            if let Ok(mut unclaimed) = self.unclaimed_approval.lock() {
+                                *unclaimed = Some(request_id.clone());
+                            }
+                        }
+                    }
+                }
+                let mut entry = self.entry(AuditEventKind::PermissionDecided);
+                entry.request_id = Some(request_id);
+                entry.tool_id = tool_id;
+                entry.tool_name = Some(tool_name);
+                entry.approved = Some(approved);
+                entry.decided_by = Some(if automatic { "policy" } else { "user" }.to_string());
+                entry.reason = Some(reason);
+                self.append(Self::with_target(entry, target));
+            }
+            ProtocolEvent::ToolStart {
+                tool_id,
+                tool_name,
+                tool_kind,
+                args,
+                ..
+            } => {
+                let unclaimed = self
+                    .unclaimed_approval
+                    .lock()
+                    .ok()
+                    .and_then(|mut unclaimed| unclaimed.take());
+                if let (Some(request_id), Ok(mut approvals)) = (unclaimed, self.approvals.lock()) {
+                    approvals.entry(tool_id.clone()).or_insert(request_id);
+                }
+                if let Ok(mut tools) = self.tools.lock() {
+                    tools.insert(
+                        tool_id,
+                        RunningTool {
+                            name: tool_name,
+                            kind: tool_kind,
+                            args,
+                        },
+                    );
+                }
+            }
+            ProtocolEvent::ToolEnd {
+                tool_id,
+                output,
+                success,
+                duration_ms,
+                ..
+            } => {
+                let Some(tool) = self.tools.lock().ok().and_then(|mut t| t.remove(&tool_id)) else {
+                    return;
+                };
+                let request_id = self
+                    .approvals
+                    .lock()
+                    .ok()
+                    .and_then(|mut approvals| approvals.remove(&tool_id));
+                if !is_audited_tool(&tool.kind) {
+                    return;
+                }
+                let (command, paths) = tool_args_target(tool.args.as_ref());
+                let mut entry = self.entry(AuditEventKind::ToolCompleted);
+                entry.request_id = request_id;
+                entry.tool_id = Some(tool_id);
+                entry.tool_name = Some(tool.name);
+                entry.tool_kind = Some(tool.kind);
+                entry.command = command;
+                entry.paths = paths;
+                entry.success = Some(success);
+                entry.duration_ms = duration_ms;
+                entry.output = output.map(truncate_output);
+                self.append(entry);
+            }
+            _ => {}
+        }
+    }
+}
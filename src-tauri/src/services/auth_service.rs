@@ -1,15 +1,28 @@
 use crate::models::auth::StoredAuth;
+use crate::services::secret_store_service::{self, SecretStore};
 use std::fs;
 use std::path::Path;
 
 const AUTH_FILE_NAME: &str = "commander.json";
 
-pub fn save_auth_to_file(sessions_dir: &Path, auth: &StoredAuth) -> Result<(), String> {
+/// Save the auth session for CLI sharing. The token goes into `store`; the
+/// file only holds its handle.
+pub fn save_auth_to_file(
+    sessions_dir: &Path,
+    auth: &StoredAuth,
+    store: &SecretStore,
+) -> Result<(), String> {
     fs::create_dir_all(sessions_dir)
         .map_err(|e| format!("Failed to create sessions dir: {}", e))?;
 
+    let previous = read_auth_file(sessions_dir).ok().flatten().map(|a| a.token);
+    let sealed = StoredAuth {
+        token: store.replace(previous.as_deref(), &auth.token)?,
+        ..auth.clone()
+    };
+
     let file_path = sessions_dir.join(AUTH_FILE_NAME);
-    let json = serde_json::to_string_pretty(auth)
+    let json = serde_json::to_string_pretty(&sealed)
         .map_err(|e| format!("Failed to serialize auth: {}", e))?;
 
     fs::write(&file_path, json).map_err(|e| format!("Failed to write auth file: {}", e))?;
@@ -17,7 +30,26 @@ pub fn save_auth_to_file(sessions_dir: &Path, auth: &StoredAuth) -> Result<(), S
     Ok(())
 }
 
-pub fn load_auth_from_file(sessions_dir: &Path) -> Result<Option<StoredAuth>, String> {
+/// Load the auth session with its token looked up in `store`. A file still
+/// holding the token itself is rewritten with a handle.
+pub fn load_auth_from_file(
+    sessions_dir: &Path,
+    store: &SecretStore,
+) -> Result<Option<StoredAuth>, String> {
+    let Some(mut auth) = read_auth_file(sessions_dir)? else {
+        return Ok(None);
+    };
+
+    if secret_store_service::is_handle(&auth.token) {
+        auth.token = store.resolve(&auth.token)?;
+    } else {
+        save_auth_to_file(sessions_dir, &auth, store)?;
+    }
+
+    Ok(Some(auth))
+}
+
+fn read_auth_file(sessions_dir: &Path) -> Result<Option<StoredAuth>, String> {
     let file_path = sessions_dir.join(AUTH_FILE_NAME);
 
     if !file_path.exists() {
@@ -33,9 +65,17 @@ pub fn load_auth_from_file(sessions_dir: &Path) -> Result<Option<StoredAuth>, St
     Ok(Some(auth))
 }
 
-pub fn clear_auth_file(sessions_dir: &Path) -> Result<(), String> {
+/// Remove the auth file and, when `store` is available, the token it refers
+/// to. The file goes even if the token cannot be removed.
+pub fn clear_auth_file(sessions_dir: &Path, store: Option<&SecretStore>) -> Result<(), String> {
     let file_path = sessions_dir.join(AUTH_FILE_NAME);
 
+    if let (Some(store), Ok(Some(auth))) = (store, read_auth_file(sessions_dir)) {
+        if let Err(e) = store.remove(&auth.token) {
+            eprintln!("⚠️ Failed to remove the CLI auth token from the secret store: {}", e);
+        }
+    }
+
     if file_path.exists() {
         fs::remove_file(&file_path)
             .map_err(|e| format!("Failed to remove auth file: {}", e))?;
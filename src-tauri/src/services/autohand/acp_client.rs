@@ -13,7 +13,7 @@ use crate::models::autohand::{
     PermissionRequest, ToolEvent, ToolPhase,
 };
 use crate::services::autohand::protocol::AutohandProtocol;
-use crate::services::autohand::rpc_client::write_headless_config_with_mode;
+use crate::services::autohand::rpc_client::{write_headless_config_with_mode, HeadlessConfig};
 
 // ---------------------------------------------------------------------------
 // AcpMessage enum -- classified ACP ndJSON messages
@@ -318,6 +318,8 @@ pub struct AutohandAcpClient {
     alive: Arc<std::sync::atomic::AtomicBool>,
     /// Last known state from state_change messages.
     last_state: Arc<Mutex<AutohandState>>,
+    /// Config file the child process was started with, kept until shutdown.
+    headless_config: Mutex<Option<HeadlessConfig>>,
 }
 
 impl AutohandAcpClient {
@@ -330,6 +332,7 @@ impl AutohandAcpClient {
             stderr_handle: Arc::new(Mutex::new(None)),
             alive: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             last_state: Arc::new(Mutex::new(AutohandState::default())),
+            headless_config: Mutex::new(None),
         }
     }
 
@@ -625,7 +628,7 @@ impl AutohandProtocol for AutohandAcpClient {
             None
         };
         let headless_config = write_headless_config_with_mode(working_dir, mode_override)?;
-        let args = build_acp_spawn_args(working_dir, config, Some(&headless_config));
+        let args = build_acp_spawn_args(working_dir, config, Some(headless_config.path()));
 
         let mut child = Command::new("autohand")
             .args(&args)
@@ -656,6 +659,7 @@ impl AutohandProtocol for AutohandAcpClient {
         *self.stdout_handle.lock().await = Some(stdout);
         *self.stderr_handle.lock().await = Some(stderr);
         *self.child.lock().await = Some(child);
+        *self.headless_config.lock().await = Some(headless_config);
         self.alive
             .store(true, std::sync::atomic::Ordering::SeqCst);
 
@@ -709,6 +713,7 @@ impl AutohandProtocol for AutohandAcpClient {
         *self.stdin_writer.lock().await = None;
         *self.stdout_handle.lock().await = None;
         *self.stderr_handle.lock().await = None;
+        *self.headless_config.lock().await = None;
         self.alive
             .store(false, std::sync::atomic::Ordering::SeqCst);
         Ok(())
@@ -16,6 +16,7 @@ use crate::models::autohand::{
 };
 use crate::services::autohand::protocol::AutohandProtocol;
 use crate::services::autohand::types::{rpc_methods, rpc_notifications};
+use crate::services::secret_store_service;
 
 // ---------------------------------------------------------------------------
 // RpcMessage enum -- distinguishes server responses from server notifications
@@ -136,8 +137,10 @@ pub fn build_permission_response_params(request_id: &str, approved: bool) -> Val
 /// `permissions.mode` field of the headless config, allowing the frontend
 /// dropdown to control the autohand CLI's permission mode.
 ///
-/// Returns the path to the temporary config file.
-pub fn write_headless_config_with_mode(working_dir: &str, permission_mode_override: Option<&str>) -> Result<std::path::PathBuf, CommanderError> {
+/// API keys kept in the secret store are written out in full, so each spawn
+/// gets its own file, private to the user and removed when the returned
+/// [`HeadlessConfig`] is dropped.
+pub fn write_headless_config_with_mode(working_dir: &str, permission_mode_override: Option<&str>) -> Result<HeadlessConfig, CommanderError> {
     // Read the raw global config
     let global_path = dirs::home_dir()
         .map(|h| h.join(".autohand").join("config.json"));
@@ -191,21 +194,48 @@ pub fn write_headless_config_with_mode(working_dir: &str, permission_mode_overri
         }
     }
 
-    // Write to temp file
-    let tmp_dir = std::env::temp_dir().join("commander-autohand");
+    // The CLI needs the keys themselves, not their secret store handles
+    if secret_store_service::contains_handles(&root) {
+        secret_store_service::global()
+            .and_then(|store| store.resolve_json(&mut root))
+            .map_err(|e| CommanderError::autohand("write_headless_config", e))?;
+    }
+
+    // Write to a file readable only by the user since it holds the keys. It
+    // stays out of /tmp, which the process sandbox swaps for an empty one.
+    let tmp_dir = crate::app_data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("autohand-config");
     std::fs::create_dir_all(&tmp_dir).map_err(|e| {
         CommanderError::autohand("write_headless_config", format!("failed to create temp dir: {}", e))
     })?;
-    // Use a fixed filename so successive calls overwrite instead of accumulating.
-    let tmp_path = tmp_dir.join("headless-config.json");
+    let tmp_path = tmp_dir.join(format!("headless-config-{}.json", Uuid::new_v4()));
     let content = serde_json::to_string_pretty(&root).map_err(|e| {
         CommanderError::autohand("write_headless_config", format!("failed to serialize config: {}", e))
     })?;
-    std::fs::write(&tmp_path, content).map_err(|e| {
+    secret_store_service::write_private(&tmp_path, content.as_bytes()).map_err(|e| {
         CommanderError::autohand("write_headless_config", format!("failed to write temp config: {}", e))
     })?;
 
-    Ok(tmp_path)
+    Ok(HeadlessConfig { path: tmp_path })
+}
+
+/// A headless config file written for one CLI process. The file holds the
+/// resolved API keys, so it is deleted as soon as this is dropped.
+pub struct HeadlessConfig {
+    path: std::path::PathBuf,
+}
+
+impl HeadlessConfig {
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+impl Drop for HeadlessConfig {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
 }
 
 /// Build the CLI arguments needed to spawn an autohand process in RPC/ACP mode.
@@ -256,6 +286,8 @@ pub struct AutohandRpcClient {
     alive: Arc<std::sync::atomic::AtomicBool>,
     /// Last known state from state_change notifications.
     last_state: Arc<Mutex<AutohandState>>,
+    /// Config file the child process was started with, kept until shutdown.
+    headless_config: Mutex<Option<HeadlessConfig>>,
 }
 
 impl AutohandRpcClient {
@@ -268,6 +300,7 @@ impl AutohandRpcClient {
             stderr_handle: Arc::new(Mutex::new(None)),
             alive: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             last_state: Arc::new(Mutex::new(AutohandState::default())),
+            headless_config: Mutex::new(None),
         }
     }
 
@@ -765,7 +798,7 @@ impl AutohandProtocol for AutohandRpcClient {
             None
         };
         let headless_config = write_headless_config_with_mode(working_dir, mode_override)?;
-        let args = build_spawn_args(working_dir, config, Some(&headless_config));
+        let args = build_spawn_args(working_dir, config, Some(headless_config.path()));
 
         let mut child = Command::new("autohand")
             .args(&args)
@@ -793,6 +826,7 @@ impl AutohandProtocol for AutohandRpcClient {
         *self.stdout_handle.lock().await = Some(stdout);
         *self.stderr_handle.lock().await = Some(stderr);
         *self.child.lock().await = Some(child);
+        *self.headless_config.lock().await = Some(headless_config);
         self.alive
             .store(true, std::sync::atomic::Ordering::SeqCst);
 
@@ -851,6 +885,7 @@ impl AutohandProtocol for AutohandRpcClient {
         *self.stdin_writer.lock().await = None;
         *self.stdout_handle.lock().await = None;
         *self.stderr_handle.lock().await = None;
+        *self.headless_config.lock().await = None;
         self.alive
             .store(false, std::sync::atomic::Ordering::SeqCst);
         Ok(())
@@ -0,0 +1,392 @@
+//! Spending limits on the usage [`crate::services::usage_service`] records.
+//!
+//! Budgets are kept in `~/.commander/budgets.json`. What has been reported
+//! about each one in its current period lives in the index, so warnings are
+//! not repeated after a restart.
+//!
+//! Periods follow UTC days and months, like the dates usage is recorded
+//! under; see [`today`].
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chrono::{Datelike, NaiveDate};
+use tokio::sync::Mutex as TokioMutex;
+
+use crate::models::ai_agent::StreamChunk;
+use crate::models::budget::{
+    Budget, BudgetAlert, BudgetAlertKind, BudgetPeriod, BudgetScope, BudgetStatus,
+};
+use crate::models::protocol::ProtocolEvent;
+use crate::services::event_sink::{EventSink, SharedEventSink};
+use crate::services::indexer::db::IndexDb;
+use crate::services::session_manager::SessionManager;
+use crate::services::usage_service::canonical_project_path;
+
+const BUDGETS_FILE: &str = "budgets.json";
+
+pub fn budgets_path() -> Result<PathBuf, String> {
+    let home =
+        dirs::home_dir().ok_or_else(|| "Could not determine user home directory".to_string())?;
+    Ok(home.join(".commander").join(BUDGETS_FILE))
+}
+
+/// `budgets` with their project targets in the form usage is recorded under.
+fn canonical_targets(budgets: &[Budget]) -> Vec<Budget> {
+    budgets
+        .iter()
+        .cloned()
+        .map(|mut budget| {
+            if budget.scope == BudgetScope::Project && !budget.target.trim().is_empty() {
+                budget.target = canonical_project_path(budget.target.trim());
+            }
+            budget
+        })
+        .collect()
+}
+
+/// The budgets in `path`; a missing file has none.
+pub fn load_budgets_from(path: &Path) -> Result<Vec<Budget>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read budgets: {}", e))?;
+    let budgets: Vec<Budget> =
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse budgets: {}", e))?;
+    Ok(canonical_targets(&budgets))
+}
+
+/// Save `budgets`, returning them as saved: project targets are stored in
+/// canonical form, see [`canonical_project_path`].
+pub fn save_budgets_to(path: &Path, budgets: &[Budget]) -> Result<Vec<Budget>, String> {
+    let budgets = canonical_targets(budgets);
+    let mut ids = HashSet::new();
+    for budget in &budgets {
+        let id = budget.id();
+        if budget.target.trim().is_empty() {
+            return Err("Budgets need a project or agent".to_string());
+        }
+        if !budget.limit_usd.is_finite() || budget.limit_usd <= 0.0 {
+            return Err(format!("{}: the limit must be more than zero", id));
+        }
+        if budget.warn_at.iter().any(|t| !(*t > 0.0 && *t <= 1.0)) {
+            return Err(format!(
+                "{}: warning thresholds must be between 0 and 1",
+                id
+            ));
+        }
+        if !ids.insert(id.clone()) {
+            return Err(format!("{}: defined twice", id));
+        }
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&budgets)
+        .map_err(|e| format!("Failed to serialize budgets: {}", e))?;
+    fs::write(path, content).map_err(|e| format!("Failed to write budgets: {}", e))?;
+    Ok(budgets)
+}
+
+pub fn load_budgets() -> Result<Vec<Budget>, String> {
+    load_budgets_from(&budgets_path()?)
+}
+
+pub fn save_budgets(budgets: &[Budget]) -> Result<Vec<Budget>, String> {
+    save_budgets_to(&budgets_path()?, budgets)
+}
+
+/// The current UTC day. Usage records are dated in UTC, so budget periods
+/// are too.
+pub fn today() -> NaiveDate {
+    chrono::Utc::now().date_naive()
+}
+
+/// First day of the period `today` falls in.
+pub fn period_start(period: BudgetPeriod, today: NaiveDate) -> NaiveDate {
+    match period {
+        BudgetPeriod::Daily => today,
+        BudgetPeriod::Monthly => today.with_day(1).unwrap_or(today),
+    }
+}
+
+/// Whether `budget` caps what `agent` spends in `project_path`. Project
+/// targets are compared in canonical form, as [`load_budgets_from`] keeps them.
+pub fn applies_to(budget: &Budget, agent: &str, project_path: Option<&str>) -> bool {
+    match budget.scope {
+        BudgetScope::Project => {
+            project_path.map(canonical_project_path).as_deref() == Some(budget.target.as_str())
+        }
+        BudgetScope::Agent => budget.target == agent,
+    }
+}
+
+/// Spending against `budget` in the period `today` falls in.
+pub fn budget_status(
+    db: &IndexDb,
+    budget: &Budget,
+    today: NaiveDate,
+) -> Result<BudgetStatus, String> {
+    let start = period_start(budget.period, today)
+        .format("%Y-%m-%d")
+        .to_string();
+    let spent_usd = match budget.scope {
+        BudgetScope::Project => db.get_cost_since(&start, Some(&budget.target), None)?,
+        BudgetScope::Agent => db.get_cost_since(&start, None, Some(&budget.target))?,
+    };
+    let state = db.get_budget_state(&budget.id(), &start)?;
+    Ok(BudgetStatus {
+        budget: budget.clone(),
+        period_start: start,
+        spent_usd,
+        used: spent_usd / budget.limit_usd,
+        state,
+    })
+}
+
+/// Check the budgets covering `agent` in `project_path` after it used
+/// something. New threshold crossings are warned about once per period;
+/// spending past the limit is reported the first time, and every time for
+/// budgets that stop sessions.
+pub fn check_budgets(
+    db: &IndexDb,
+    budgets: &[Budget],
+    agent: &str,
+    project_path: Option<&str>,
+    today: NaiveDate,
+) -> Result<Vec<BudgetAlert>, String> {
+    let mut alerts = Vec::new();
+    for budget in budgets
+        .iter()
+        .filter(|b| applies_to(b, agent, project_path))
+    {
+        let mut status = budget_status(db, budget, today)?;
+        let before = status.state.clone();
+
+        let crossed = budget
+            .warn_at
+            .iter()
+            .copied()
+            .filter(|threshold| status.used >= *threshold)
+            .fold(None, |highest: Option<f64>, t| {
+                Some(highest.map_or(t, |h| h.max(t)))
+            });
+        let mut warning = None;
+        if let Some(threshold) = crossed {
+            if status
+                .state
+                .warned_at
+                .is_none_or(|warned| threshold > warned)
+            {
+                status.state.warned_at = Some(threshold);
+                warning = Some(threshold);
+            }
+        }
+
+        let exceeded = status.used >= 1.0;
+        let newly_exceeded = exceeded && status.state.exceeded_at.is_none();
+        if newly_exceeded {
+            status.state.exceeded_at = Some(chrono::Utc::now().timestamp());
+        }
+
+        if status.state != before {
+            db.save_budget_state(&budget.id(), &status.period_start, &status.state)?;
+        }
+        // Going straight past the limit is reported as exceeded only.
+        if let Some(threshold) = warning.filter(|_| !exceeded) {
+            alerts.push(BudgetAlert {
+                kind: BudgetAlertKind::Warning { threshold },
+                status: status.clone(),
+            });
+        }
+        if newly_exceeded || (exceeded && budget.hard_stop) {
+            alerts.push(BudgetAlert {
+                kind: BudgetAlertKind::Exceeded,
+                status,
+            });
+        }
+    }
+    Ok(alerts)
+}
+
+/// Budgets covering `agent` in `project_path` that stop sessions and are
+/// already spent for the period, checked before a run starts. Nothing is
+/// recorded, so the alerts a running session gets are unchanged.
+pub fn exhausted_budgets(
+    db: &IndexDb,
+    budgets: &[Budget],
+    agent: &str,
+    project_path: Option<&str>,
+    today: NaiveDate,
+) -> Result<Vec<BudgetStatus>, String> {
+    let mut exhausted = Vec::new();
+    for budget in budgets
+        .iter()
+        .filter(|b| b.hard_stop && applies_to(b, agent, project_path))
+    {
+        let status = budget_status(db, budget, today)?;
+        if status.used >= 1.0 {
+            exhausted.push(status);
+        }
+    }
+    Ok(exhausted)
+}
+
+/// One line explaining why a run over `status` was not started.
+pub fn describe_refusal(status: &BudgetStatus) -> String {
+    let budget = &status.budget;
+    let scope = match budget.scope {
+        BudgetScope::Project => "Project",
+        BudgetScope::Agent => "Agent",
+    };
+    let period = match budget.period {
+        BudgetPeriod::Daily => "daily",
+        BudgetPeriod::Monthly => "monthly",
+    };
+    format!(
+        "🛑 Budget: {} {} has already spent ${:.2}, over its {} ${:.2}; not starting the session\n",
+        scope, budget.target, status.spent_usd, period, budget.limit_usd
+    )
+}
+
+/// One line describing `alert` for the session's output.
+pub fn describe_alert(alert: &BudgetAlert) -> String {
+    let status = &alert.status;
+    let budget = &status.budget;
+    let scope = match budget.scope {
+        BudgetScope::Project => "Project",
+        BudgetScope::Agent => "Agent",
+    };
+    let period = match budget.period {
+        BudgetPeriod::Daily => "daily",
+        BudgetPeriod::Monthly => "monthly",
+    };
+    match alert.kind {
+        BudgetAlertKind::Warning { threshold } => format!(
+            "⚠️ Budget: {} {} has spent ${:.2} of its {} ${:.2} ({:.0}% warning)\n",
+            scope,
+            budget.target,
+            status.spent_usd,
+            period,
+            budget.limit_usd,
+            threshold * 100.0
+        ),
+        BudgetAlertKind::Exceeded if budget.hard_stop => format!(
+            "🛑 Budget: {} {} has spent ${:.2}, over its {} ${:.2}; stopping the session\n",
+            scope, budget.target, status.spent_usd, period, budget.limit_usd
+        ),
+        BudgetAlertKind::Exceeded => format!(
+            "⚠️ Budget: {} {} has spent ${:.2}, over its {} ${:.2}\n",
+            scope, budget.target, status.spent_usd, period, budget.limit_usd
+        ),
+    }
+}
+
+/// Checks the run's budgets after every turn that reports usage, and closes
+/// the session once it takes spending past a budget that stops sessions.
+/// Sits inside [`crate::services::usage_service::UsageMeter`], which records
+/// the turn first.
+pub struct BudgetGuard {
+    inner: SharedEventSink,
+    db: Arc<IndexDb>,
+    budgets: Vec<Budget>,
+    agent: String,
+    session_id: String,
+    project_path: Option<String>,
+    sessions: Arc<TokioMutex<SessionManager>>,
+    stopped: AtomicBool,
+}
+
+impl BudgetGuard {
+    pub fn shared(
+        inner: SharedEventSink,
+        db: Arc<IndexDb>,
+        budgets: Vec<Budget>,
+        agent: &str,
+        session_id: &str,
+        project_path: Option<&str>,
+        sessions: Arc<TokioMutex<SessionManager>>,
+    ) -> SharedEventSink {
+        Arc::new(Self {
+            inner,
+            db,
+            budgets,
+            agent: agent.to_string(),
+            session_id: session_id.to_string(),
+            project_path: project_path.map(str::to_string),
+            sessions,
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    fn stop_session(&self) {
+        if self.stopped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let sessions = Arc::clone(&self.sessions);
+        let session_id = self.session_id.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    sessions.lock().await.close_session(&session_id);
+                });
+            }
+            Err(_) => sessions.blocking_lock().close_session(&session_id),
+        }
+    }
+}
+
+impl EventSink for BudgetGuard {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        self.inner.emit_chunk(chunk);
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        let used = matches!(&event, ProtocolEvent::StateChange { usage: Some(_), .. });
+        self.inner.emit_event(event);
+        if !used || self.budgets.is_empty() || self.stopped.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let alerts = match check_budgets(
+            &self.db,
+            &self.budgets,
+            &self.agent,
+            self.project_path.as_deref(),
+            today(),
+        ) {
+            Ok(alerts) => alerts,
+            Err(e) => {
+                eprintln!("⚠️ Failed to check budgets for {}: {}", self.session_id, e);
+                return;
+            }
+        };
+        let mut stop = false;
+        for alert in &alerts {
+            let status = match alert.kind {
+                BudgetAlertKind::Warning { .. } => "budget_warning",
+                BudgetAlertKind::Exceeded => "budget_exceeded",
+            };
+            self.inner.emit_chunk(StreamChunk {
+                session_id: self.session_id.clone(),
+                content: describe_alert(alert),
+                finished: false,
+            });
+            self.inner.emit_event(ProtocolEvent::StateChange {
+                session_id: self.session_id.clone(),
+                status: status.to_string(),
+                context_percent: None,
+                usage: None,
+                agent_session_id: None,
+            });
+            stop |= alert.kind == BudgetAlertKind::Exceeded && alert.status.budget.hard_stop;
+        }
+        if stop {
+            self.stop_session();
+        }
+    }
+}
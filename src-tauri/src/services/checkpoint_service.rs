@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::models::ai_agent::StreamChunk;
+use crate::models::protocol::{ProtocolEvent, ToolKind};
+use crate::services::event_sink::EventSink;
+use crate::services::git_backend::{parse_name_status_z, FileChange};
+use crate::services::workspace_merge_service::{git, git_stdout};
+
+/// Each session's checkpoints are a chain of commits on
+/// `refs/commander/checkpoints/<session>`, newest at the tip.
+pub const CHECKPOINT_REF_PREFIX: &str = "refs/commander/checkpoints/";
+
+const KIND_TRAILER: &str = "Commander-Checkpoint";
+const TIME_TRAILER: &str = "Commander-Time";
+const TOOL_TRAILER: &str = "Commander-Tool";
+const TOOL_ID_TRAILER: &str = "Commander-Tool-Id";
+const NO_OID: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointKind {
+    BeforePrompt,
+    AfterTool,
+    /// Taken automatically before a restore so the restore can be undone.
+    BeforeRestore,
+}
+
+impl CheckpointKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            CheckpointKind::BeforePrompt => "before_prompt",
+            CheckpointKind::AfterTool => "after_tool",
+            CheckpointKind::BeforeRestore => "before_restore",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "before_prompt" => Some(CheckpointKind::BeforePrompt),
+            "after_tool" => Some(CheckpointKind::AfterTool),
+            "before_restore" => Some(CheckpointKind::BeforeRestore),
+            _ => None,
+        }
+    }
+}
+
+/// The tool call a checkpoint was taken after.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckpointTool {
+    pub tool_id: String,
+    pub tool_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Checkpoint {
+    /// Commit id; pass it to `diff_checkpoints` and `restore_checkpoint`.
+    pub id: String,
+    pub session_id: String,
+    pub kind: CheckpointKind,
+    pub label: String,
+    pub tool: Option<CheckpointTool>,
+    /// Milliseconds since the epoch.
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckpointDiff {
+    pub from: String,
+    /// `None` when comparing against the current working tree.
+    pub to: Option<String>,
+    pub files: Vec<FileChange>,
+    /// Unified diff, limited to `file_path` when one was given.
+    pub patch: String,
+}
+
+/// Ref holding a session's checkpoints. Characters git does not allow in
+/// ref names are replaced.
+pub fn checkpoint_ref(session_id: &str) -> String {
+    let name: String = session_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}{}", CHECKPOINT_REF_PREFIX, name)
+}
+
+/// Run git against a throwaway index so snapshots never touch the user's
+/// staging area.
+async fn git_with_index(dir: &str, index: &Path, args: &[&str]) -> Result<String, String> {
+    let output = tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .env("GIT_INDEX_FILE", index)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git {}: {}", args.first().unwrap_or(&""), e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Temporary index file, removed on drop.
+struct ScratchIndex(std::path::PathBuf);
+
+impl ScratchIndex {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("commander-index-{}", uuid::Uuid::new_v4().simple())))
+    }
+}
+
+impl Drop for ScratchIndex {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+        let _ = std::fs::remove_file(self.0.with_extension("lock"));
+    }
+}
+
+/// Write the working tree (tracked and untracked, minus ignored files and
+/// Commander's own `.commander/` data) as a tree object.
+pub async fn snapshot_tree(project_path: &str) -> Result<String, String> {
+    let index = ScratchIndex::new();
+    let has_head = git(project_path, &["rev-parse", "--verify", "--quiet", "HEAD"])
+        .await?
+        .status
+        .success();
+    if has_head {
+        git_with_index(project_path, &index.0, &["read-tree", "HEAD"]).await?;
+    } else {
+        git_with_index(project_path, &index.0, &["read-tree", "--empty"]).await?;
+    }
+    git_with_index(
+        project_path,
+        &index.0,
+        &["add", "-A", "--", ".", ":(exclude).commander"],
+    )
+    .await?;
+    git_with_index(project_path, &index.0, &["write-tree"]).await
+}
+
+async fn checkpoint_tip(project_path: &str, session_id: &str) -> Option<String> {
+    git_stdout(
+        project_path,
+        &["rev-parse", "--verify", "--quiet", &checkpoint_ref(session_id)],
+    )
+    .await
+    .ok()
+    .filter(|tip| !tip.is_empty())
+}
+
+/// Snapshot the working tree onto the session's checkpoint ref.
+///
+/// After-tool checkpoints are skipped (returning `None`) when nothing
+/// changed since the previous checkpoint; the other kinds are always taken
+/// so prompt boundaries and restores show up in the list.
+pub async fn create_checkpoint(
+    project_path: &str,
+    session_id: &str,
+    kind: CheckpointKind,
+    label: &str,
+    tool: Option<CheckpointTool>,
+) -> Result<Option<Checkpoint>, String> {
+    let tree = snapshot_tree(project_path).await?;
+    let parent = checkpoint_tip(project_path, session_id).await;
+
+    if let (CheckpointKind::AfterTool, Some(parent)) = (kind, parent.as_deref()) {
+        let parent_tree = git_stdout(project_path, &["rev-parse", &format!("{}^{{tree}}", parent)]).await?;
+        if parent_tree == tree {
+            return Ok(None);
+        }
+    }
+
+    let created_at = chrono::Utc::now().timestamp_millis();
+    let label = label.lines().next().unwrap_or("").trim();
+    let label = if label.is_empty() { kind.as_str() } else { label };
+    let mut message = format!(
+        "{}\n\n{}: {}\n{}: {}\n",
+        label,
+        KIND_TRAILER,
+        kind.as_str(),
+        TIME_TRAILER,
+        created_at
+    );
+    if let Some(tool) = &tool {
+        message.push_str(&format!("{}: {}\n{}: {}\n", TOOL_TRAILER, tool.tool_name, TOOL_ID_TRAILER, tool.tool_id));
+    }
+
+    // Checkpoints are Commander's, not the user's; a fixed identity also
+    // keeps them working in repositories without user.name configured.
+    let mut args = vec![
+        "-c",
+        "user.name=Commander",
+        "-c",
+        "user.email=commander@localhost",
+        "commit-tree",
+        tree.as_str(),
+        "-m",
+        message.as_str(),
+    ];
+    if let Some(parent) = parent.as_deref() {
+        args.extend(["-p", parent]);
+    }
+    let commit = git_stdout(project_path, &args)
+        .await
+        .map_err(|e| format!("Failed to write checkpoint: {}", e))?;
+    git_stdout(
+        project_path,
+        &[
+            "update-ref",
+            "-m",
+            "commander checkpoint",
+            &checkpoint_ref(session_id),
+            &commit,
+            parent.as_deref().unwrap_or(NO_OID),
+        ],
+    )
+    .await
+    .map_err(|e| format!("Failed to update checkpoint ref: {}", e))?;
+
+    Ok(Some(Checkpoint {
+        id: commit,
+        session_id: session_id.to_string(),
+        kind,
+        label: label.to_string(),
+        tool,
+        created_at,
+    }))
+}
+
+/// Parse one `%H%x1f%B` record written by [`create_checkpoint`].
+fn parse_checkpoint(session_id: &str, record: &str) -> Option<Checkpoint> {
+    let (id, body) = record.trim_start_matches('\n').split_once('\x1f')?;
+    let mut lines = body.lines();
+    let label = lines.next().unwrap_or("").to_string();
+
+    let mut trailers: HashMap<&str, &str> = HashMap::new();
+    for line in lines {
+        if let Some((key, value)) = line.split_once(": ") {
+            trailers.insert(key, value.trim());
+        }
+    }
+    let tool = match (trailers.get(TOOL_ID_TRAILER), trailers.get(TOOL_TRAILER)) {
+        (Some(tool_id), Some(tool_name)) => Some(CheckpointTool {
+            tool_id: tool_id.to_string(),
+            tool_name: tool_name.to_string(),
+        }),
+        _ => None,
+    };
+
+    Some(Checkpoint {
+        id: id.trim().to_string(),
+        session_id: session_id.to_string(),
+        kind: CheckpointKind::parse(trailers.get(KIND_TRAILER)?)?,
+        label,
+        tool,
+        created_at: trailers.get(TIME_TRAILER).and_then(|t| t.parse().ok()).unwrap_or(0),
+    })
+}
+
+/// A session's checkpoints, oldest first. Empty if it has none.
+pub async fn list_checkpoints(project_path: &str, session_id: &str) -> Result<Vec<Checkpoint>, String> {
+    if checkpoint_tip(project_path, session_id).await.is_none() {
+        return Ok(Vec::new());
+    }
+    let out = git_stdout(
+        project_path,
+        &[
+            "log",
+            "--reverse",
+            "--format=%H%x1f%B%x1e",
+            &checkpoint_ref(session_id),
+        ],
+    )
+    .await?;
+    Ok(out
+        .split('\x1e')
+        .filter(|record| !record.trim().is_empty())
+        .filter_map(|record| parse_checkpoint(session_id, record))
+        .collect())
+}
+
+/// Fail unless `checkpoint` is one of this session's checkpoints.
+async fn ensure_session_checkpoint(project_path: &str, session_id: &str, checkpoint: &str) -> Result<(), String> {
+    let known = list_checkpoints(project_path, session_id).await?;
+    if known.iter().any(|c| c.id == checkpoint || (checkpoint.len() >= 7 && c.id.starts_with(checkpoint))) {
+        Ok(())
+    } else {
+        Err(format!("{} is not a checkpoint of session {}", checkpoint, session_id))
+    }
+}
+
+/// Changes from checkpoint `from` to checkpoint `to`, or to the current
+/// working tree when `to` is `None`.
+pub async fn diff_checkpoints(
+    project_path: &str,
+    session_id: &str,
+    from: &str,
+    to: Option<&str>,
+    file_path: Option<&str>,
+) -> Result<CheckpointDiff, String> {
+    ensure_session_checkpoint(project_path, session_id, from).await?;
+    if let Some(to) = to {
+        ensure_session_checkpoint(project_path, session_id, to).await?;
+    }
+    let from_tree = format!("{}^{{tree}}", from);
+    let to_tree = match to {
+        Some(to) => format!("{}^{{tree}}", to),
+        None => snapshot_tree(project_path).await?,
+    };
+
+    let names = git_stdout(
+        project_path,
+        &["diff", "--name-status", "-z", "-M", &from_tree, &to_tree, "--"],
+    )
+    .await?;
+    let mut patch_args = vec!["diff", "-M", from_tree.as_str(), to_tree.as_str(), "--"];
+    if let Some(file_path) = file_path {
+        patch_args.push(file_path);
+    }
+    let patch = git(project_path, &patch_args).await?;
+    if !patch.status.success() {
+        return Err(String::from_utf8_lossy(&patch.stderr).trim().to_string());
+    }
+
+    Ok(CheckpointDiff {
+        from: from.to_string(),
+        to: to.map(str::to_string),
+        files: parse_name_status_z(&names),
+        patch: String::from_utf8_lossy(&patch.stdout).to_string(),
+    })
+}
+
+/// Put the working tree back to how it was at `checkpoint`: files it had are
+/// rewritten, files created since are deleted. HEAD, the index and ignored
+/// files are left alone. A `before_restore` checkpoint is taken first and
+/// returned, so the restore itself can be undone.
+pub async fn restore_checkpoint(
+    project_path: &str,
+    session_id: &str,
+    checkpoint: &str,
+) -> Result<Checkpoint, String> {
+    ensure_session_checkpoint(project_path, session_id, checkpoint).await?;
+    let short = &checkpoint[..checkpoint.len().min(8)];
+    let safety = create_checkpoint(
+        project_path,
+        session_id,
+        CheckpointKind::BeforeRestore,
+        &format!("Before restoring {}", short),
+        None,
+    )
+    .await?
+    .ok_or_else(|| "Failed to snapshot the working tree before restoring".to_string())?;
+
+    let target_tree = format!("{}^{{tree}}", checkpoint);
+    let current_tree = format!("{}^{{tree}}", safety.id);
+    let names = git_stdout(
+        project_path,
+        &["diff", "--name-status", "-z", "--no-renames", &current_tree, &target_tree, "--"],
+    )
+    .await?;
+    let changes = parse_name_status_z(&names);
+
+    let (removed, rewritten): (Vec<_>, Vec<_>) = changes.into_iter().partition(|c| c.status == "D");
+    for change in removed {
+        let path = Path::new(project_path).join(&change.path);
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(format!("Failed to remove {}: {}", change.path, e));
+            }
+        }
+    }
+
+    if !rewritten.is_empty() {
+        // Check the files out of the checkpoint through a scratch index so
+        // modes and symlinks come back exactly as they were.
+        let index = ScratchIndex::new();
+        git_with_index(project_path, &index.0, &["read-tree", &target_tree]).await?;
+        let mut args = vec!["checkout-index", "-f", "--"];
+        args.extend(rewritten.iter().map(|c| c.path.as_str()));
+        git_with_index(project_path, &index.0, &args).await?;
+    }
+    Ok(safety)
+}
+
+/// Delete a session's checkpoint ref.
+pub async fn delete_checkpoints(project_path: &str, session_id: &str) -> Result<(), String> {
+    if checkpoint_tip(project_path, session_id).await.is_none() {
+        return Ok(());
+    }
+    git_stdout(project_path, &["update-ref", "-d", &checkpoint_ref(session_id)])
+        .await
+        .map(|_| ())
+}
+
+/// Tool kinds that change files and get a checkpoint when they finish.
+pub fn is_mutating_tool(kind: &ToolKind) -> bool {
+    matches!(kind, ToolKind::Write | ToolKind::Edit | ToolKind::Delete)
+}
+
+/// Short description of a tool call for the checkpoint list.
+fn tool_label(tool_name: &str, args: Option<&serde_json::Value>) -> String {
+    let path = args.and_then(|args| {
+        ["path", "file_path", "filePath", "file"]
+            .iter()
+            .find_map(|key| args.get(*key).and_then(|v| v.as_str()))
+    });
+    match path {
+        Some(path) => format!("{} {}", tool_name, path),
+        None => tool_name.to_string(),
+    }
+}
+
+struct PendingTool {
+    kind: ToolKind,
+    name: String,
+    label: String,
+}
+
+/// Event sink that takes an after-tool checkpoint whenever a write, edit or
+/// delete tool finishes. Checkpoints are taken one at a time on a background
+/// task, in the order the tools ended.
+pub struct CheckpointRecorder {
+    tools: Mutex<HashMap<String, PendingTool>>,
+    requests: Mutex<Option<mpsc::UnboundedSender<(String, CheckpointTool)>>>,
+    worker: tokio::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl CheckpointRecorder {
+    pub fn start(project_path: &str, session_id: &str) -> Arc<Self> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(String, CheckpointTool)>();
+        let project_path = project_path.to_string();
+        let session_id = session_id.to_string();
+        let worker = tokio::spawn(async move {
+            while let Some((label, tool)) = rx.recv().await {
+                if let Err(e) = create_checkpoint(
+                    &project_path,
+                    &session_id,
+                    CheckpointKind::AfterTool,
+                    &label,
+                    Some(tool),
+                )
+                .await
+                {
+                    eprintln!("⚠️ Failed to checkpoint {}: {}", session_id, e);
+                }
+            }
+        });
+        Arc::new(Self {
+            tools: Mutex::new(HashMap::new()),
+            requests: Mutex::new(Some(tx)),
+            worker: tokio::sync::Mutex::new(Some(worker)),
+        })
+    }
+
+    /// Stop accepting tool events and wait for queued checkpoints.
+    pub async fn finish(&self) {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.take();
+        }
+        if let Some(worker) = self.worker.lock().await.take() {
+            let _ = worker.await;
+        }
+    }
+}
+
+impl EventSink for CheckpointRecorder {
+    fn emit_chunk(&self, _chunk: StreamChunk) {}
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        match event {
+            ProtocolEvent::ToolStart {
+                tool_id,
+                tool_name,
+                tool_kind,
+                args,
+                ..
+            } => {
+                if let Ok(mut tools) = self.tools.lock() {
+                    let label = tool_label(&tool_name, args.as_ref());
+                    tools.insert(
+                        tool_id,
+                        PendingTool {
+                            kind: tool_kind,
+                            name: tool_name,
+                            label,
+                        },
+                    );
+                }
+            }
+            ProtocolEvent::ToolEnd { tool_id, .. } => {
+                let Some(tool) = self.tools.lock().ok().and_then(|mut t| t.remove(&tool_id)) else {
+                    return;
+                };
+                if !is_mutating_tool(&tool.kind) {
+                    return;
+                }
+                if let Some(requests) = self.requests.lock().ok().and_then(|r| r.clone()) {
+                    let _ = requests.send((
+                        tool.label,
+                        CheckpointTool {
+                            tool_id,
+                            tool_name: tool.name,
+                        },
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+}
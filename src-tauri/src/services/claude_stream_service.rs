@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde_json::Value;
+
+use crate::models::protocol::{ProtocolEvent, ToolKind, TurnUsage};
+use crate::services::cli_output_service::AgentStreamParser;
+
+/// Map a Claude Code tool name to a ToolKind.
+pub fn claude_tool_kind(name: &str) -> ToolKind {
+    match name {
+        "Read" | "NotebookRead" => ToolKind::Read,
+        "Write" => ToolKind::Write,
+        "Edit" | "MultiEdit" | "NotebookEdit" => ToolKind::Edit,
+        "Bash" | "BashOutput" | "KillShell" | "KillBash" => ToolKind::Execute,
+        "Grep" | "Glob" | "LS" => ToolKind::Search,
+        "WebFetch" | "WebSearch" => ToolKind::Fetch,
+        "TodoWrite" | "ExitPlanMode" => ToolKind::Think,
+        _ => ToolKind::Other,
+    }
+}
+
+/// Turns Claude's `--output-format stream-json` records into protocol
+/// events for one session.
+///
+/// With `--include-partial-messages` text arrives twice: as `stream_event`
+/// deltas and again in the finished `assistant` record. Deltas win; the
+/// record's text is only used for messages that were not streamed.
+pub struct ClaudeStreamParser {
+    session_id: String,
+    agent_session_id: Option<String>,
+    model: Option<String>,
+    tools: HashMap<String, (String, Instant)>,
+    streamed_text: bool,
+}
+
+impl ClaudeStreamParser {
+    pub fn new(session_id: &str) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            agent_session_id: None,
+            model: None,
+            tools: HashMap::new(),
+            streamed_text: false,
+        }
+    }
+
+    fn state_change(&self, status: String, usage: Option<TurnUsage>) -> ProtocolEvent {
+        ProtocolEvent::StateChange {
+            session_id: self.session_id.clone(),
+            status,
+            context_percent: None,
+            usage,
+            agent_session_id: self.agent_session_id.clone(),
+        }
+    }
+
+    fn system(&mut self, record: &Value) -> Vec<ProtocolEvent> {
+        if record.get("subtype").and_then(|s| s.as_str()) != Some("init") {
+            return Vec::new();
+        }
+        if let Some(id) = str_field(record, "session_id") {
+            self.agent_session_id = Some(id);
+        }
+        if let Some(model) = str_field(record, "model") {
+            self.model = Some(model);
+        }
+        vec![self.state_change("running".to_string(), None)]
+    }
+
+    fn stream_event(&mut self, event: &Value) -> Vec<ProtocolEvent> {
+        match event.get("type").and_then(|t| t.as_str()) {
+            Some("message_start") => {
+                self.streamed_text = false;
+                if let Some(model) = event.get("message").and_then(|m| str_field(m, "model")) {
+                    self.model = Some(model);
+                }
+                Vec::new()
+            }
+            Some("content_block_delta") => {
+                let delta = event.get("delta").unwrap_or(&Value::Null);
+                if delta.get("type").and_then(|t| t.as_str()) != Some("text_delta") {
+                    return Vec::new();
+                }
+                match str_field(delta, "text") {
+                    Some(text) if !text.is_empty() => {
+                        self.streamed_text = true;
+                        vec![self.message(text)]
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn message(&self, content: String) -> ProtocolEvent {
+        ProtocolEvent::Message {
+            session_id: self.session_id.clone(),
+            role: "assistant".to_string(),
+            content,
+        }
+    }
+
+    fn assistant(&mut self, record: &Value) -> Vec<ProtocolEvent> {
+        let mut events = Vec::new();
+        for block in content_blocks(record) {
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("text") if !self.streamed_text => {
+                    if let Some(text) = str_field(block, "text").filter(|t| !t.is_empty()) {
+                        events.push(self.message(text));
+                    }
+                }
+                Some("tool_use") => {
+                    let Some(tool_id) = str_field(block, "id") else { continue };
+                    let name = str_field(block, "name").unwrap_or_else(|| "tool".to_string());
+                    self.tools.insert(tool_id.clone(), (name.clone(), Instant::now()));
+                    events.push(ProtocolEvent::ToolStart {
+                        session_id: self.session_id.clone(),
+                        tool_id,
+                        tool_kind: claude_tool_kind(&name),
+                        tool_name: name,
+                        args: block.get("input").cloned(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn tool_results(&mut self, record: &Value) -> Vec<ProtocolEvent> {
+        let mut events = Vec::new();
+        for block in content_blocks(record) {
+            if block.get("type").and_then(|t| t.as_str()) != Some("tool_result") {
+                continue;
+            }
+            let Some(tool_id) = str_field(block, "tool_use_id") else { continue };
+            let (tool_name, duration_ms) = match self.tools.remove(&tool_id) {
+                Some((name, started)) => (name, Some(started.elapsed().as_millis() as u64)),
+                None => ("tool".to_string(), None),
+            };
+            let output = tool_result_text(block.get("content").unwrap_or(&Value::Null));
+            events.push(ProtocolEvent::ToolEnd {
+                session_id: self.session_id.clone(),
+                tool_id,
+                tool_name,
+                output: (!output.is_empty()).then_some(output),
+                success: !block.get("is_error").and_then(|e| e.as_bool()).unwrap_or(false),
+                duration_ms,
+            });
+        }
+        events
+    }
+
+    /// The final record of a run: its status, plus what it cost.
+    fn result(&mut self, record: &Value) -> Vec<ProtocolEvent> {
+        if let Some(id) = str_field(record, "session_id") {
+            self.agent_session_id = Some(id);
+        }
+        let usage = record.get("usage").unwrap_or(&Value::Null);
+        let tokens = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let turn_usage = TurnUsage {
+            model: self.model.clone(),
+            input_tokens: tokens("input_tokens"),
+            output_tokens: tokens("output_tokens"),
+            cache_read_tokens: tokens("cache_read_input_tokens"),
+            cache_creation_tokens: tokens("cache_creation_input_tokens"),
+            cost_usd: record.get("total_cost_usd").and_then(|c| c.as_f64()),
+        };
+
+        let subtype = str_field(record, "subtype").unwrap_or_else(|| "success".to_string());
+        let is_error = record.get("is_error").and_then(|e| e.as_bool()).unwrap_or(false);
+        let mut events = Vec::new();
+        if is_error {
+            events.push(ProtocolEvent::Error {
+                session_id: self.session_id.clone(),
+                message: str_field(record, "result")
+                    .filter(|r| !r.is_empty())
+                    .unwrap_or_else(|| subtype.clone()),
+                failure: None,
+            });
+        }
+        let status = match (is_error, subtype.as_str()) {
+            (false, "success") => "idle".to_string(),
+            (true, "success") => "error".to_string(),
+            (_, other) => other.to_string(),
+        };
+        events.push(self.state_change(status, Some(turn_usage)));
+        events
+    }
+}
+
+impl AgentStreamParser for ClaudeStreamParser {
+    fn agent(&self) -> &'static str {
+        "claude"
+    }
+
+    fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<Vec<ProtocolEvent>> {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('{') {
+            return None;
+        }
+        let record: Value = serde_json::from_str(trimmed).ok()?;
+        let kind = record.get("type")?.as_str()?;
+        Some(match kind {
+            "system" => self.system(&record),
+            "stream_event" => self.stream_event(record.get("event").unwrap_or(&Value::Null)),
+            "assistant" => self.assistant(&record),
+            "user" => self.tool_results(&record),
+            "result" => self.result(&record),
+            _ => Vec::new(),
+        })
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| v.as_str()).map(String::from)
+}
+
+fn content_blocks(record: &Value) -> impl Iterator<Item = &Value> {
+    record
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+}
+
+/// Tool results are either a string or a list of content blocks.
+fn tool_result_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
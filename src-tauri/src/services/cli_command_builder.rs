@@ -34,6 +34,8 @@ pub fn build_codex_command_args(
     }
 
     args.push("--skip-git-repo-check".to_string());
+    // One JSON thread event per line, parsed by CodexEventParser.
+    args.push("--json".to_string());
 
     args
 }
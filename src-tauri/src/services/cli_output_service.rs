@@ -1,3 +1,9 @@
+use crate::models::ai_agent::StreamChunk;
+use crate::models::protocol::ProtocolEvent;
+use crate::services::claude_stream_service::ClaudeStreamParser;
+use crate::services::codex_stream_service::CodexEventParser;
+use crate::services::event_sink::SharedEventSink;
+
 pub fn sanitize_cli_output_line(agent: &str, line: &str) -> Option<String> {
     let trimmed = line.trim();
 
@@ -129,3 +135,49 @@ impl CodexStreamAccumulator {
         results.push(trimmed.to_string());
     }
 }
+
+/// Turns an agent CLI's structured output into protocol events, one line
+/// at a time.
+pub trait AgentStreamParser: Send {
+    /// The agent whose output this parses, for sanitizing plain lines.
+    fn agent(&self) -> &'static str;
+
+    fn session_id(&self) -> &str;
+
+    /// Events for one line of output, or `None` when the line is not one of
+    /// the agent's records.
+    fn parse_line(&mut self, line: &str) -> Option<Vec<ProtocolEvent>>;
+
+    /// Emit a line of output: records as protocol events, anything else as
+    /// text.
+    fn emit_line(&mut self, sink: &SharedEventSink, line: &str) {
+        match self.parse_line(line) {
+            Some(events) => {
+                for event in events {
+                    sink.emit_event(event);
+                }
+            }
+            None => {
+                if let Some(filtered) = sanitize_cli_output_line(self.agent(), line) {
+                    sink.emit_chunk(StreamChunk {
+                        session_id: self.session_id().to_string(),
+                        content: format!("{}\n", filtered),
+                        finished: false,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// The parser for `agent`'s structured output, if its CLI is run in a JSON
+/// output mode.
+pub fn stream_parser_for(agent: &str, session_id: &str) -> Option<Box<dyn AgentStreamParser>> {
+    if agent.eq_ignore_ascii_case("claude") {
+        Some(Box::new(ClaudeStreamParser::new(session_id)))
+    } else if agent.eq_ignore_ascii_case("codex") {
+        Some(Box::new(CodexEventParser::new(session_id)))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde_json::{json, Value};
+
+use crate::models::protocol::{PlanEntry, ProtocolEvent, ToolKind, TurnUsage};
+use crate::services::cli_output_service::AgentStreamParser;
+
+struct RunningItem {
+    name: String,
+    started: Instant,
+    /// Bytes of `aggregated_output` already sent as updates.
+    output_sent: usize,
+}
+
+/// Turns Codex thread events — `codex exec --json` lines, or the events the
+/// SDK runner forwards — into protocol events for one session.
+///
+/// Commands, file changes, MCP calls and web searches become tool events,
+/// reasoning becomes a `Think` tool, and `turn.completed` reports usage.
+pub struct CodexEventParser {
+    session_id: String,
+    thread_id: Option<String>,
+    model: Option<String>,
+    items: HashMap<String, RunningItem>,
+    /// Whether the turn already produced a message; later ones are appended
+    /// to it after a blank line.
+    turn_has_message: bool,
+}
+
+impl CodexEventParser {
+    pub fn new(session_id: &str) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            thread_id: None,
+            model: None,
+            items: HashMap::new(),
+            turn_has_message: false,
+        }
+    }
+
+    /// Codex events do not name the model, so usage is attributed to the one
+    /// the run was started with.
+    pub fn with_model(mut self, model: Option<String>) -> Self {
+        self.model = model.filter(|m| !m.is_empty());
+        self
+    }
+
+    fn state_change(&self, status: &str, usage: Option<TurnUsage>) -> ProtocolEvent {
+        ProtocolEvent::StateChange {
+            session_id: self.session_id.clone(),
+            status: status.to_string(),
+            context_percent: None,
+            usage,
+            agent_session_id: self.thread_id.clone(),
+        }
+    }
+
+    fn error(&self, message: String) -> ProtocolEvent {
+        ProtocolEvent::Error {
+            session_id: self.session_id.clone(),
+            message,
+            failure: None,
+        }
+    }
+
+    fn item(&mut self, phase: &str, item: &Value) -> Vec<ProtocolEvent> {
+        let Some(item_id) = str_field(item, "id") else {
+            return Vec::new();
+        };
+        let completed = phase == "item.completed";
+        match str_field(item, "type").as_deref() {
+            Some("agent_message") if completed => match str_field(item, "text") {
+                Some(text) if !text.is_empty() => {
+                    let content = if self.turn_has_message {
+                        format!("\n\n{}", text)
+                    } else {
+                        text
+                    };
+                    self.turn_has_message = true;
+                    vec![ProtocolEvent::Message {
+                        session_id: self.session_id.clone(),
+                        role: "assistant".to_string(),
+                        content,
+                    }]
+                }
+                _ => Vec::new(),
+            },
+            Some("reasoning") if completed => {
+                let text = str_field(item, "text").unwrap_or_default();
+                if text.trim().is_empty() {
+                    return Vec::new();
+                }
+                let mut events =
+                    self.start_tool(&item_id, "Reasoning".to_string(), ToolKind::Think, None);
+                events.push(self.end_tool(&item_id, Some(text), true));
+                events
+            }
+            Some("command_execution") => {
+                let command = str_field(item, "command").unwrap_or_default();
+                let mut events = self.start_tool(
+                    &item_id,
+                    command.clone(),
+                    ToolKind::Execute,
+                    Some(json!({ "command": command })),
+                );
+                let output = str_field(item, "aggregated_output").unwrap_or_default();
+                if completed {
+                    let success = match item.get("exit_code").and_then(|c| c.as_i64()) {
+                        Some(code) => code == 0,
+                        None => str_field(item, "status").as_deref() != Some("failed"),
+                    };
+                    events.push(self.end_tool(&item_id, Some(output), success));
+                } else if let Some(update) = self.output_update(&item_id, &output) {
+                    events.push(update);
+                }
+                events
+            }
+            Some("file_change") => {
+                let changes: Vec<(String, String)> = item
+                    .get("changes")
+                    .and_then(|c| c.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|c| {
+                        Some((
+                            str_field(c, "kind").unwrap_or_default(),
+                            str_field(c, "path")?,
+                        ))
+                    })
+                    .collect();
+                let kind = file_change_kind(&changes);
+                let verb = match kind {
+                    ToolKind::Write => "Create",
+                    ToolKind::Delete => "Delete",
+                    _ => "Edit",
+                };
+                let paths: Vec<&str> = changes.iter().map(|(_, path)| path.as_str()).collect();
+                let name = format!("{} {}", verb, paths.join(", "));
+                let mut events =
+                    self.start_tool(&item_id, name, kind, item.get("changes").cloned());
+                if completed {
+                    let summary = changes
+                        .iter()
+                        .map(|(kind, path)| format!("{} {}", kind, path))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let success = str_field(item, "status").as_deref() != Some("failed");
+                    events.push(self.end_tool(&item_id, Some(summary), success));
+                }
+                events
+            }
+            Some("mcp_tool_call") => {
+                let name = format!(
+                    "{}/{}",
+                    str_field(item, "server").unwrap_or_default(),
+                    str_field(item, "tool").unwrap_or_default()
+                );
+                let mut events = self.start_tool(
+                    &item_id,
+                    name,
+                    ToolKind::Other,
+                    item.get("arguments").cloned(),
+                );
+                if completed {
+                    let success = str_field(item, "status").as_deref() != Some("failed");
+                    events.push(self.end_tool(&item_id, None, success));
+                }
+                events
+            }
+            Some("web_search") => {
+                let query = str_field(item, "query").unwrap_or_default();
+                let mut events = self.start_tool(
+                    &item_id,
+                    format!("Search {}", query),
+                    ToolKind::Fetch,
+                    Some(json!({ "query": query })),
+                );
+                if completed {
+                    events.push(self.end_tool(&item_id, None, true));
+                }
+                events
+            }
+            Some("todo_list") => {
+                let entries = item
+                    .get("items")
+                    .and_then(|i| i.as_array())
+                    .into_iter()
+                    .flatten()
+                    .map(|todo| PlanEntry {
+                        content: str_field(todo, "text").unwrap_or_default(),
+                        priority: "medium".to_string(),
+                        status: if todo.get("completed").and_then(|c| c.as_bool()) == Some(true) {
+                            "completed".to_string()
+                        } else {
+                            "pending".to_string()
+                        },
+                    })
+                    .collect();
+                vec![ProtocolEvent::Plan {
+                    session_id: self.session_id.clone(),
+                    entries,
+                }]
+            }
+            Some("error") if completed => match str_field(item, "message") {
+                Some(message) => vec![self.error(message)],
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    /// A ToolStart the first time an item is seen; nothing after that.
+    fn start_tool(
+        &mut self,
+        item_id: &str,
+        name: String,
+        kind: ToolKind,
+        args: Option<Value>,
+    ) -> Vec<ProtocolEvent> {
+        if self.items.contains_key(item_id) {
+            return Vec::new();
+        }
+        self.items.insert(
+            item_id.to_string(),
+            RunningItem {
+                name: name.clone(),
+                started: Instant::now(),
+                output_sent: 0,
+            },
+        );
+        vec![ProtocolEvent::ToolStart {
+            session_id: self.session_id.clone(),
+            tool_id: item_id.to_string(),
+            tool_name: name,
+            tool_kind: kind,
+            args,
+        }]
+    }
+
+    /// The part of a command's output not yet sent.
+    fn output_update(&mut self, item_id: &str, output: &str) -> Option<ProtocolEvent> {
+        let running = self.items.get_mut(item_id)?;
+        let delta = output
+            .get(running.output_sent..)
+            .filter(|d| !d.is_empty())?;
+        running.output_sent = output.len();
+        Some(ProtocolEvent::ToolUpdate {
+            session_id: self.session_id.clone(),
+            tool_id: item_id.to_string(),
+            tool_name: running.name.clone(),
+            output: Some(delta.to_string()),
+        })
+    }
+
+    fn end_tool(&mut self, item_id: &str, output: Option<String>, success: bool) -> ProtocolEvent {
+        let (tool_name, duration_ms) = match self.items.remove(item_id) {
+            Some(running) => (
+                running.name,
+                Some(running.started.elapsed().as_millis() as u64),
+            ),
+            None => ("tool".to_string(), None),
+        };
+        ProtocolEvent::ToolEnd {
+            session_id: self.session_id.clone(),
+            tool_id: item_id.to_string(),
+            tool_name,
+            output: output.filter(|o| !o.is_empty()),
+            success,
+            duration_ms,
+        }
+    }
+
+    /// Codex counts cached tokens as part of `input_tokens`; they are split
+    /// out here so input and cache reads can be priced separately.
+    fn turn_completed(&self, event: &Value) -> Vec<ProtocolEvent> {
+        let usage = event.get("usage").unwrap_or(&Value::Null);
+        let tokens = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let cached = tokens("cached_input_tokens");
+        let turn_usage = TurnUsage {
+            model: self.model.clone(),
+            input_tokens: tokens("input_tokens").saturating_sub(cached),
+            output_tokens: tokens("output_tokens"),
+            cache_read_tokens: cached,
+            cache_creation_tokens: 0,
+            cost_usd: None,
+        };
+        vec![self.state_change("idle", Some(turn_usage))]
+    }
+}
+
+impl AgentStreamParser for CodexEventParser {
+    fn agent(&self) -> &'static str {
+        "codex"
+    }
+
+    fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<Vec<ProtocolEvent>> {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('{') {
+            return None;
+        }
+        let event: Value = serde_json::from_str(trimmed).ok()?;
+        let kind = event.get("type")?.as_str()?;
+        Some(match kind {
+            "thread.started" => {
+                self.thread_id = str_field(&event, "thread_id");
+                vec![self.state_change("running", None)]
+            }
+            "turn.started" => {
+                self.turn_has_message = false;
+                Vec::new()
+            }
+            "item.started" | "item.updated" | "item.completed" => {
+                self.item(kind, event.get("item").unwrap_or(&Value::Null))
+            }
+            "turn.completed" => self.turn_completed(&event),
+            "turn.failed" => {
+                let message = event
+                    .get("error")
+                    .and_then(|e| str_field(e, "message"))
+                    .unwrap_or_else(|| "Codex turn failed".to_string());
+                vec![self.error(message), self.state_change("error", None)]
+            }
+            "error" => match str_field(&event, "message") {
+                Some(message) => vec![self.error(message)],
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        })
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| v.as_str()).map(String::from)
+}
+
+/// `Write` when every change adds a file, `Delete` when every change
+/// removes one, `Edit` otherwise.
+fn file_change_kind(changes: &[(String, String)]) -> ToolKind {
+    if !changes.is_empty() && changes.iter().all(|(kind, _)| kind == "add") {
+        ToolKind::Write
+    } else if !changes.is_empty() && changes.iter().all(|(kind, _)| kind == "delete") {
+        ToolKind::Delete
+    } else {
+        ToolKind::Edit
+    }
+}
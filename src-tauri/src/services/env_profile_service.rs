@@ -0,0 +1,473 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::models::ai_agent::AgentSettings;
+use crate::models::environment::{EnvPreview, EnvPreviewEntry, EnvProfile, EnvSource, EnvVariable};
+use crate::services::project_settings_service::load_project_settings;
+use crate::services::secret_store_service::{self, is_handle, SecretStore};
+
+const PROFILES_FILE: &str = "env-profiles.json";
+
+/// Shown in place of secret values.
+const MASK: &str = "••••••••";
+
+/// Inherited variables an allow list keeps without naming them; agents do
+/// not start without them. A deny list can still drop them.
+const ALWAYS_INHERITED: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "TERM",
+    "LANG",
+    "TMPDIR",
+    "SystemRoot",
+    "USERPROFILE",
+];
+
+static NAME_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").expect("valid name pattern"));
+
+/// Names imported as secrets.
+static SECRET_NAME_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(^|_)(KEY|APIKEY|TOKEN|SECRET|PASSWORD|PASSWD|CREDENTIALS?|AUTH)($|_)")
+        .expect("valid secret name pattern")
+});
+
+/// `~/.commander/env-profiles.json`
+pub fn env_profiles_path() -> Result<PathBuf, String> {
+    let home =
+        dirs::home_dir().ok_or_else(|| "Could not determine user home directory".to_string())?;
+    Ok(home.join(".commander").join(PROFILES_FILE))
+}
+
+/// The profiles in `path`; a missing file has none.
+pub fn load_env_profiles_from(path: &Path) -> Result<Vec<EnvProfile>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read environment profiles: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse environment profiles: {}", e))
+}
+
+pub fn save_env_profiles_to(path: &Path, profiles: &[EnvProfile]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(profiles)
+        .map_err(|e| format!("Failed to serialize environment profiles: {}", e))?;
+    fs::write(path, content).map_err(|e| format!("Failed to write environment profiles: {}", e))
+}
+
+pub fn load_env_profiles() -> Result<Vec<EnvProfile>, String> {
+    load_env_profiles_from(&env_profiles_path()?)
+}
+
+pub fn save_env_profiles(profiles: &[EnvProfile]) -> Result<(), String> {
+    save_env_profiles_to(&env_profiles_path()?, profiles)
+}
+
+/// Whether a variable called `name` is imported as a secret.
+pub fn looks_secret(name: &str) -> bool {
+    SECRET_NAME_RE.is_match(name)
+}
+
+/// Check the profile's names and move its secret values into `store`,
+/// reusing the handles `previous` held under the same names. Handles
+/// `previous` no longer uses are removed.
+pub fn seal_profile(
+    profile: &mut EnvProfile,
+    previous: Option<&EnvProfile>,
+    store: &SecretStore,
+) -> Result<(), String> {
+    if profile.name.trim().is_empty() {
+        return Err("Environment profiles need a name".to_string());
+    }
+    let previous_handle = |name: &str| {
+        previous
+            .and_then(|p| p.variables.iter().find(|v| v.name == name))
+            .filter(|v| v.secret && is_handle(&v.value))
+            .map(|v| v.value.clone())
+    };
+    for var in &mut profile.variables {
+        if !NAME_RE.is_match(&var.name) {
+            return Err(format!("'{}' is not a valid variable name", var.name));
+        }
+        if var.secret && !is_handle(&var.value) {
+            var.value = store.replace(previous_handle(&var.name).as_deref(), &var.value)?;
+        }
+    }
+    for old in previous.into_iter().flat_map(|p| &p.variables) {
+        let kept = profile.variables.iter().any(|v| v.value == old.value);
+        if old.secret && !kept {
+            store.remove(&old.value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Variables from `.env` content: `NAME=value` lines with optional
+/// `export`, `#` comments and single- or double-quoted values. Single
+/// quotes are taken literally; double quotes take `\n`, `\t`, `\"`, `\\`
+/// and `\$` escapes and may span lines.
+pub fn parse_dotenv(content: &str) -> Result<Vec<EnvVariable>, String> {
+    let mut variables: Vec<EnvVariable> = Vec::new();
+    let mut lines = content.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (name, raw) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected NAME=value", index + 1))?;
+        let name = name.trim();
+        if !NAME_RE.is_match(name) {
+            return Err(format!(
+                "line {}: '{}' is not a valid variable name",
+                index + 1,
+                name
+            ));
+        }
+        let raw = raw.trim_start();
+        let value = if let Some(rest) = raw.strip_prefix('\'') {
+            let end = rest
+                .find('\'')
+                .ok_or_else(|| format!("line {}: unterminated single quote", index + 1))?;
+            // Keep it literal through expansion.
+            rest[..end].replace('$', "$$")
+        } else if let Some(rest) = raw.strip_prefix('"') {
+            let mut text = rest.to_string();
+            loop {
+                if let Some(value) = double_quoted(&text) {
+                    break value;
+                }
+                let (_, next) = lines
+                    .next()
+                    .ok_or_else(|| format!("line {}: unterminated double quote", index + 1))?;
+                text.push('\n');
+                text.push_str(next);
+            }
+        } else {
+            let end = raw.find(" #").unwrap_or(raw.len());
+            raw[..end].trim_end().to_string()
+        };
+        // Secrets are never expanded, so they keep their `$` as is.
+        let secret = looks_secret(name);
+        let value = if secret {
+            value.replace("$$", "$")
+        } else {
+            value
+        };
+        variables.retain(|v| v.name != name);
+        variables.push(EnvVariable {
+            name: name.to_string(),
+            value,
+            secret,
+        });
+    }
+    Ok(variables)
+}
+
+/// The value of a double-quoted string up to its closing quote, or `None`
+/// when `text` does not close it.
+fn double_quoted(text: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                '$' => value.push_str("$$"),
+                other @ ('"' | '\\') => value.push(other),
+                other => {
+                    value.push('\\');
+                    value.push(other);
+                }
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Merge `.env` content into `profile`, replacing variables of the same
+/// name. Returns how many were read.
+pub fn import_dotenv(profile: &mut EnvProfile, content: &str) -> Result<usize, String> {
+    let imported = parse_dotenv(content)?;
+    let count = imported.len();
+    for var in imported {
+        match profile.variables.iter_mut().find(|v| v.name == var.name) {
+            Some(existing) => *existing = var,
+            None => profile.variables.push(var),
+        }
+    }
+    Ok(count)
+}
+
+/// `value` with `$NAME`, `${NAME}` and `${NAME:-default}` replaced through
+/// `lookup`. Unset variables expand to nothing, `$$` to `$`.
+pub fn expand(value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(tail) = after.strip_prefix('$') {
+            out.push('$');
+            rest = tail;
+        } else if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| format!("unterminated ${{ in '{}'", value))?;
+            let inner = &braced[..end];
+            let (name, default) = match inner.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (inner, None),
+            };
+            if !NAME_RE.is_match(name) {
+                return Err(format!("'{}' is not a valid variable name", name));
+            }
+            match (lookup(name).filter(|v| !v.is_empty()), default) {
+                (Some(found), _) => out.push_str(&found),
+                (None, Some(default)) => out.push_str(&expand(default, lookup)?),
+                (None, None) => {}
+            }
+            rest = &braced[end + 1..];
+        } else {
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            let name = &after[..len];
+            if NAME_RE.is_match(name) {
+                out.push_str(&lookup(name).unwrap_or_default());
+            } else {
+                out.push('$');
+                out.push_str(name);
+            }
+            rest = &after[len..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct EnvValue {
+    value: String,
+    secret: bool,
+    source: EnvSource,
+}
+
+/// The environment an agent process starts with in place of Commander's
+/// own: the inherited variables the profiles let through plus the
+/// profiles' variables.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgentEnvironment {
+    /// Profiles applied, project first.
+    pub profiles: Vec<String>,
+    vars: BTreeMap<String, EnvValue>,
+    /// Inherited variables the allow and deny lists dropped.
+    pub removed: Vec<String>,
+}
+
+impl AgentEnvironment {
+    /// Apply `profiles` in order over `inherited`. `resolve_secret` reads
+    /// secret handles.
+    pub fn build(
+        profiles: &[EnvProfile],
+        inherited: impl IntoIterator<Item = (String, String)>,
+        resolve_secret: &dyn Fn(&str) -> Result<String, String>,
+    ) -> Result<Self, String> {
+        let allow: Vec<&str> = profiles
+            .iter()
+            .flat_map(|p| &p.inherit_allow)
+            .map(String::as_str)
+            .collect();
+        let deny: Vec<&str> = profiles
+            .iter()
+            .flat_map(|p| &p.inherit_deny)
+            .map(String::as_str)
+            .collect();
+
+        let mut env = Self {
+            profiles: profiles.iter().map(|p| p.name.clone()).collect(),
+            ..Self::default()
+        };
+        for (name, value) in inherited {
+            let allowed = allow.is_empty()
+                || ALWAYS_INHERITED.contains(&name.as_str())
+                || allow.iter().any(|p| name_matches(p, &name));
+            if allowed && !deny.iter().any(|p| name_matches(p, &name)) {
+                env.vars.insert(
+                    name,
+                    EnvValue {
+                        value,
+                        secret: false,
+                        source: EnvSource::Inherited,
+                    },
+                );
+            } else {
+                env.removed.push(name);
+            }
+        }
+        env.removed.sort();
+
+        for profile in profiles {
+            for var in &profile.variables {
+                let context = |e: String| format!("{}: {}: {}", profile.name, var.name, e);
+                let (value, secret) = if var.secret && is_handle(&var.value) {
+                    (resolve_secret(&var.value).map_err(context)?, true)
+                } else {
+                    // A value built from a secret is a secret too.
+                    let uses_secret = Cell::new(var.secret);
+                    let lookup = |name: &str| {
+                        env.vars.get(name).map(|found| {
+                            if found.secret {
+                                uses_secret.set(true);
+                            }
+                            found.value.clone()
+                        })
+                    };
+                    let value = expand(&var.value, &lookup).map_err(context)?;
+                    (value, uses_secret.get())
+                };
+                env.vars.insert(
+                    var.name.clone(),
+                    EnvValue {
+                        value,
+                        secret,
+                        source: EnvSource::Profile(profile.name.clone()),
+                    },
+                );
+            }
+        }
+        Ok(env)
+    }
+
+    /// The environment for a run of an agent with `settings` in
+    /// `working_dir`, or `None` when neither the project nor the agent has
+    /// a profile and the agent inherits Commander's environment.
+    pub fn for_run(settings: &AgentSettings, working_dir: &str) -> Result<Option<Self>, String> {
+        let mut names = Vec::new();
+        if !working_dir.is_empty() {
+            names.extend(load_project_settings(working_dir)?.env_profile);
+        }
+        names.extend(settings.env_profile.clone());
+        names.retain(|name| !name.trim().is_empty());
+        names.dedup();
+        if names.is_empty() {
+            return Ok(None);
+        }
+
+        let all = load_env_profiles()?;
+        let profiles = names
+            .iter()
+            .map(|name| {
+                all.iter()
+                    .find(|p| &p.name == name)
+                    .cloned()
+                    .ok_or_else(|| format!("environment profile '{}' does not exist", name))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Self::build(&profiles, inherited_vars(), &|handle| {
+            secret_store_service::global()?.resolve(handle)
+        })
+        .map(Some)
+    }
+
+    /// What a run of an agent with `settings` in `working_dir` would start
+    /// with, Commander's own environment when no profile applies.
+    pub fn preview_for_run(
+        settings: &AgentSettings,
+        working_dir: &str,
+    ) -> Result<EnvPreview, String> {
+        match Self::for_run(settings, working_dir)? {
+            Some(environment) => Ok(environment.preview()),
+            None => Ok(Self::build(&[], inherited_vars(), &|h| Ok(h.to_string()))?.preview()),
+        }
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars
+            .iter()
+            .map(|(name, v)| (name.as_str(), v.value.as_str()))
+    }
+
+    /// Start `cmd` with exactly this environment.
+    pub fn apply(&self, cmd: &mut tokio::process::Command) {
+        cmd.env_clear();
+        cmd.envs(self.vars());
+    }
+
+    /// Start the PTY command `cmd` with exactly this environment.
+    pub fn apply_pty(&self, cmd: &mut portable_pty::CommandBuilder) {
+        cmd.env_clear();
+        for (name, value) in self.vars() {
+            cmd.env(name, value);
+        }
+    }
+
+    /// The environment as shown to the user, secrets masked.
+    pub fn preview(&self) -> EnvPreview {
+        EnvPreview {
+            profiles: self.profiles.clone(),
+            variables: self
+                .vars
+                .iter()
+                .map(|(name, v)| EnvPreviewEntry {
+                    name: name.clone(),
+                    value: if v.secret {
+                        MASK.to_string()
+                    } else {
+                        v.value.clone()
+                    },
+                    secret: v.secret,
+                    source: v.source.clone(),
+                })
+                .collect(),
+            removed: self.removed.clone(),
+        }
+    }
+
+    /// One-line summary for the session output.
+    pub fn describe(&self) -> String {
+        let set = self
+            .vars
+            .values()
+            .filter(|v| v.source != EnvSource::Inherited)
+            .count();
+        format!(
+            "🌱 Environment: {} ({} set, {} inherited dropped)\n",
+            self.profiles.join(" + "),
+            set,
+            self.removed.len()
+        )
+    }
+}
+
+/// Commander's own environment, minus variables that are not valid UTF-8.
+fn inherited_vars() -> Vec<(String, String)> {
+    std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
+}
+
+/// Whether `name` matches `pattern`, an exact name or a `PREFIX*` glob.
+fn name_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+
+use crate::models::ai_agent::StreamChunk;
+use crate::models::protocol::ProtocolEvent;
+use crate::services::secret_store_service::redact;
+
+/// Event channel carrying raw agent output (`StreamChunk`).
+pub const CLI_STREAM_EVENT: &str = "cli-stream";
+/// Event channel carrying structured protocol traffic (`ProtocolEvent`).
+pub const PROTOCOL_EVENT: &str = "protocol-event";
+
+/// Destination for everything an agent session produces.
+///
+/// Executors never talk to the webview directly; they push raw terminal
+/// output as `StreamChunk`s and structured traffic as `ProtocolEvent`s into a
+/// sink. The GUI forwards both to the frontend, the headless CLI prints them.
+pub trait EventSink: Send + Sync {
+    fn emit_chunk(&self, chunk: StreamChunk);
+
+    fn emit_event(&self, event: ProtocolEvent);
+
+    /// Report that the agent could not be started or failed, as an `Error`
+    /// event; that is what tells a failed run apart. `finished` also closes
+    /// the chunk stream for consumers waiting on it.
+    fn emit_failure(&self, session_id: &str, message: String, finished: bool) {
+        self.emit_event(ProtocolEvent::Error {
+            session_id: session_id.to_string(),
+            message,
+            failure: None,
+        });
+        if finished {
+            self.emit_chunk(StreamChunk {
+                session_id: session_id.to_string(),
+                content: String::new(),
+                finished: true,
+            });
+        }
+    }
+}
+
+/// Shared handle passed to executors and their background reader tasks.
+pub type SharedEventSink = Arc<dyn EventSink>;
+
+/// One item emitted into a sink, in emission order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedEvent {
+    Chunk(StreamChunk),
+    Protocol(ProtocolEvent),
+}
+
+impl RecordedEvent {
+    /// Channel name the GUI would have used for this item.
+    pub fn channel(&self) -> &'static str {
+        match self {
+            RecordedEvent::Chunk(_) => CLI_STREAM_EVENT,
+            RecordedEvent::Protocol(_) => PROTOCOL_EVENT,
+        }
+    }
+}
+
+/// A single JSONL line: `{"ts": <unix ms>, "event": "<channel>", "payload": {...}}`.
+///
+/// Used for event log files and for `commander-cli --format jsonl`, so both
+/// can be read back with [`read_event_log`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub ts: i64,
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+impl EventRecord {
+    pub fn new(ts: i64, recorded: &RecordedEvent) -> Self {
+        let payload = match recorded {
+            RecordedEvent::Chunk(chunk) => serde_json::to_value(chunk),
+            RecordedEvent::Protocol(event) => serde_json::to_value(event),
+        }
+        .unwrap_or(serde_json::Value::Null);
+
+        Self {
+            ts,
+            event: recorded.channel().to_string(),
+            payload,
+        }
+    }
+
+    pub fn now(recorded: &RecordedEvent) -> Self {
+        Self::new(chrono::Utc::now().timestamp_millis(), recorded)
+    }
+
+    /// Decode the payload back into the item that produced this record.
+    pub fn to_recorded(&self) -> Result<RecordedEvent, String> {
+        match self.event.as_str() {
+            CLI_STREAM_EVENT => serde_json::from_value(self.payload.clone())
+                .map(RecordedEvent::Chunk)
+                .map_err(|e| format!("Invalid stream chunk: {}", e)),
+            PROTOCOL_EVENT => serde_json::from_value(self.payload.clone())
+                .map(RecordedEvent::Protocol)
+                .map_err(|e| format!("Invalid protocol event: {}", e)),
+            other => Err(format!("Unknown event channel: {}", other)),
+        }
+    }
+}
+
+/// Read an event log written by [`FileEventSink`].
+///
+/// Lines that fail to parse are skipped: a crash mid-write can leave a
+/// truncated final line, and the rest of the log is still worth having.
+pub fn read_event_log(path: &Path) -> Result<Vec<EventRecord>, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open event log {}: {}", path.display(), e))?;
+
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Failed to read event log: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(record) = serde_json::from_str::<EventRecord>(&line) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Forwards session output to the frontend over the `cli-stream` and
+/// `protocol-event` channels.
+pub struct TauriEventSink {
+    app: tauri::AppHandle,
+}
+
+impl TauriEventSink {
+    pub fn new(app: tauri::AppHandle) -> Self {
+        Self { app }
+    }
+
+    pub fn shared(app: tauri::AppHandle) -> SharedEventSink {
+        Arc::new(Self::new(app))
+    }
+}
+
+impl EventSink for TauriEventSink {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        let _ = self.app.emit(CLI_STREAM_EVENT, chunk);
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        let _ = self.app.emit(PROTOCOL_EVENT, event);
+    }
+}
+
+/// Keeps every emitted item in memory, in order, for tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingEventSink {
+    events: Mutex<Vec<RecordedEvent>>,
+}
+
+#[cfg(test)]
+impl RecordingEventSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything emitted so far.
+    pub fn events(&self) -> Vec<RecordedEvent> {
+        self.events.lock().map(|e| e.clone()).unwrap_or_default()
+    }
+
+    pub fn chunks(&self) -> Vec<StreamChunk> {
+        self.events()
+            .into_iter()
+            .filter_map(|e| match e {
+                RecordedEvent::Chunk(chunk) => Some(chunk),
+                RecordedEvent::Protocol(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn protocol_events(&self) -> Vec<ProtocolEvent> {
+        self.events()
+            .into_iter()
+            .filter_map(|e| match e {
+                RecordedEvent::Protocol(event) => Some(event),
+                RecordedEvent::Chunk(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut events) = self.events.lock() {
+            events.clear();
+        }
+    }
+
+    fn push(&self, event: RecordedEvent) {
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+}
+
+#[cfg(test)]
+impl EventSink for RecordingEventSink {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        self.push(RecordedEvent::Chunk(chunk));
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        self.push(RecordedEvent::Protocol(event));
+    }
+}
+
+/// Appends every emitted item to a JSONL file as an [`EventRecord`].
+///
+/// Each line is flushed as it is written so the log survives a crash of the
+/// app or the agent.
+pub struct FileEventSink {
+    file: Mutex<File>,
+}
+
+impl FileEventSink {
+    /// Open `path` for appending, creating it and its parent directories.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create event log directory: {}", e))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open event log {}: {}", path.display(), e))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    fn append(&self, recorded: RecordedEvent) {
+        let record = EventRecord::now(&recorded);
+        let Ok(line) = serde_json::to_string(&record) else {
+            return;
+        };
+        let mut line = redact(&line);
+        line.push('\n');
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.write_all(line.as_bytes());
+            let _ = file.flush();
+        }
+    }
+}
+
+impl EventSink for FileEventSink {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        self.append(RecordedEvent::Chunk(chunk));
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        self.append(RecordedEvent::Protocol(event));
+    }
+}
+
+/// Fans every item out to several sinks, in the order they were given, so a
+/// session can be shown, logged and persisted at the same time.
+pub struct TeeEventSink {
+    sinks: Vec<SharedEventSink>,
+}
+
+impl TeeEventSink {
+    pub fn new(sinks: Vec<SharedEventSink>) -> Self {
+        Self { sinks }
+    }
+
+    pub fn shared(sinks: Vec<SharedEventSink>) -> SharedEventSink {
+        Arc::new(Self::new(sinks))
+    }
+}
+
+impl EventSink for TeeEventSink {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        for sink in &self.sinks {
+            sink.emit_chunk(chunk.clone());
+        }
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        for sink in &self.sinks {
+            sink.emit_event(event.clone());
+        }
+    }
+}
@@ -1,22 +1,41 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use serde_json::json;
 use tokio::sync::Mutex;
-use tokio::process::{Child, ChildStdin};
+use tokio::process::{Child, ChildStdin, ChildStdout};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use async_trait::async_trait;
-use tauri::Emitter;
 use crate::error::CommanderError;
 use crate::models::ai_agent::AgentSettings;
-use crate::models::protocol::{ProtocolMode, ProtocolEvent, SessionEventKind, ToolKind};
-use super::AgentExecutor;
+use crate::models::protocol::{
+    PermissionTarget, ProtocolError, ProtocolMode, ProtocolEvent, SessionEventKind, ToolKind,
+};
+use crate::services::autohand::rpc_client::HeadlessConfig;
+use crate::services::event_sink::SharedEventSink;
+use crate::services::env_profile_service::AgentEnvironment;
+use crate::services::sandbox_service::SandboxProfile;
+use super::acp_host::AcpHost;
+use super::acp_jsonrpc::{
+    initialize_params, methods, parse_acp_rpc_line, permission_event, permission_outcome,
+    prompt_params, select_permission_option, stop_reason_status, AcpConnection, AcpIncoming,
+    AgentCapabilities, PendingPermission, SessionUpdateMapper, ACP_HANDSHAKE_TIMEOUT,
+    ACP_PROTOCOL_VERSION, METHOD_NOT_FOUND,
+};
+use super::{
+    autohand_config, drain_stderr, own_process_group, terminate_child, AgentExecutor, ExitWatch,
+    SharedTurnTarget, TurnTarget, TERMINATE_GRACE,
+};
+
+pub use super::acp_jsonrpc::AcpDialect;
 
 // ---------------------------------------------------------------------------
 // AcpMessage enum -- classified ACP ndJSON messages
 // ---------------------------------------------------------------------------
 
-/// A classified ACP message received from the agent CLI via ndJSON.
+/// A classified message received from an agent CLI speaking the legacy
+/// ndJSON envelope ([`AcpDialect::Legacy`]).
 ///
-/// ACP uses a `{"type": ..., "data": ...}` envelope.
+/// The envelope is `{"type": ..., "data": ...}`.
 /// This enum maps the known `type` values to structured variants.
 #[derive(Debug, Clone)]
 pub enum AcpMessage {
@@ -207,37 +226,64 @@ async fn write_stdin_line(
 // AcpExecutor struct
 // ---------------------------------------------------------------------------
 
+/// How long the agent gets to exit on its own before it is killed.
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+
 pub struct AcpExecutor {
     flag_variant: Option<String>,
+    dialect: AcpDialect,
     stdin: Arc<Mutex<Option<ChildStdin>>>,
     child: Arc<Mutex<Option<Child>>>,
     alive: Arc<AtomicBool>,
+    busy: Arc<AtomicBool>,
+    connection: Option<Arc<AcpConnection>>,
+    acp_session_id: Option<String>,
+    turn: Option<SharedTurnTarget>,
+    host: Option<Arc<AcpHost>>,
+    exit: Arc<ExitWatch>,
+    /// Sandbox the agent and the terminals it opens run in.
+    sandbox: Option<SandboxProfile>,
+    /// Environment the agent and its terminals start with.
+    environment: Option<AgentEnvironment>,
+    /// Config file with resolved API keys the process was started with.
+    headless_config: Option<HeadlessConfig>,
 }
 
 impl AcpExecutor {
     pub fn new(flag_variant: Option<String>) -> Self {
         Self {
             flag_variant,
+            dialect: AcpDialect::default(),
             stdin: Arc::new(Mutex::new(None)),
             child: Arc::new(Mutex::new(None)),
             alive: Arc::new(AtomicBool::new(false)),
+            busy: Arc::new(AtomicBool::new(false)),
+            connection: None,
+            acp_session_id: None,
+            turn: None,
+            host: None,
+            exit: Arc::new(ExitWatch::default()),
+            sandbox: None,
+            environment: None,
+            headless_config: None,
         }
     }
-}
 
-#[async_trait]
-impl AgentExecutor for AcpExecutor {
-    async fn execute(
+    /// Speak `dialect` instead of ACP JSON-RPC.
+    pub fn with_dialect(mut self, dialect: AcpDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Resolve and spawn the agent, keeping its stdin and handle. Stderr
+    /// goes to the diagnostics of whichever turn `turn` points at.
+    async fn spawn(
         &mut self,
-        app: &tauri::AppHandle,
-        session_id: &str,
         agent: &str,
-        message: &str,
-        working_dir: &str,
-        _settings: &AgentSettings,
-        resume_session_id: Option<&str>,
-    ) -> Result<(), CommanderError> {
-        // 1. Resolve agent binary path.
+        args: &[String],
+        working_dir: Option<&str>,
+        turn: &SharedTurnTarget,
+    ) -> Result<ChildStdout, CommanderError> {
         // The caller may pass an absolute path (pre-resolved via sidecar module)
         // or a bare command name (resolved via PATH).
         let agent_path = {
@@ -255,7 +301,55 @@ impl AgentExecutor for AcpExecutor {
             }
         };
 
-        // 2. Build spawn args
+        let mut command = SandboxProfile::command(self.sandbox.as_ref(), &agent_path, args)
+            .map_err(|e| CommanderError::command(agent, None, e))?;
+        if let Some(environment) = &self.environment {
+            environment.apply(&mut command);
+        }
+        command
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        own_process_group(&mut command);
+        if let Some(dir) = working_dir {
+            command.current_dir(dir);
+        }
+        self.exit.reset();
+        let mut child = command.spawn().map_err(|e| {
+            CommanderError::command(
+                agent,
+                None,
+                format!("failed to spawn agent in ACP mode: {}", e),
+            )
+        })?;
+
+        let stdin = child.stdin.take().ok_or_else(|| {
+            CommanderError::command(agent, None, "failed to capture stdin for ACP process")
+        })?;
+        let stdout = child.stdout.take().ok_or_else(|| {
+            CommanderError::command(agent, None, "failed to capture stdout for ACP process")
+        })?;
+        if let Some(stderr) = child.stderr.take() {
+            drain_stderr(stderr, Arc::clone(turn));
+        }
+
+        *self.stdin.lock().await = Some(stdin);
+        *self.child.lock().await = Some(child);
+        self.alive.store(true, Ordering::SeqCst);
+        Ok(stdout)
+    }
+
+    /// Legacy envelope: the prompt goes out as a `prompt` message and every
+    /// stdout line is classified on its own.
+    async fn execute_legacy(
+        &mut self,
+        sink: &SharedEventSink,
+        session_id: &str,
+        agent: &str,
+        message: &str,
+        working_dir: &str,
+        resume_session_id: Option<&str>,
+    ) -> Result<(), CommanderError> {
         let mut args: Vec<String> = Vec::new();
 
         // Add flag variant flags (e.g. "--mode acp" → ["--mode", "acp"])
@@ -265,57 +359,26 @@ impl AgentExecutor for AcpExecutor {
             }
         }
 
-        // Add working directory
         args.push("--path".to_string());
         args.push(working_dir.to_string());
 
-        // Add resume session if provided
         if let Some(session) = resume_session_id {
             args.push("--resume".to_string());
             args.push(session.to_string());
         }
+        self.headless_config = autohand_config(agent, working_dir, &mut args)?;
 
-        // 3. Spawn child process
-        let mut child = tokio::process::Command::new(&agent_path)
-            .args(&args)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| {
-                CommanderError::command(
-                    agent,
-                    None,
-                    format!("failed to spawn agent in ACP mode: {}", e),
-                )
-            })?;
+        let stdout = self
+            .spawn(agent, &args, None, &TurnTarget::shared(sink, session_id))
+            .await?;
 
-        // 4. Capture stdin and stdout
-        let stdin = child.stdin.take().ok_or_else(|| {
-            CommanderError::command(agent, None, "failed to capture stdin for ACP process")
-        })?;
-        let stdout = child.stdout.take().ok_or_else(|| {
-            CommanderError::command(agent, None, "failed to capture stdout for ACP process")
-        })?;
-
-        // 5. Store stdin and child
-        *self.stdin.lock().await = Some(stdin);
-        *self.child.lock().await = Some(child);
-
-        // 6. Set alive flag
-        self.alive.store(true, Ordering::SeqCst);
-
-        // 7. Emit Connected event
         let session_id_owned = session_id.to_string();
-        let _ = app.emit(
-            "protocol-event",
-            ProtocolEvent::SessionEvent {
-                session_id: session_id_owned.clone(),
-                event: SessionEventKind::Connected,
-            },
-        );
+        sink.emit_event(ProtocolEvent::SessionEvent {
+            session_id: session_id_owned.clone(),
+            event: SessionEventKind::Connected,
+        });
 
-        // 7.5. Send initial prompt via ndJSON stdin
+        // Send initial prompt via ndJSON stdin
         let prompt_envelope = serde_json::json!({
             "type": "prompt",
             "data": { "message": message }
@@ -325,9 +388,11 @@ impl AgentExecutor for AcpExecutor {
         prompt_line.push('\n');
         write_stdin_line(&self.stdin, &prompt_line).await?;
 
-        // 8. Read stdout line-by-line in a background task
-        let app_handle = app.clone();
+        // Read stdout line-by-line in a background task
+        let sink_task = Arc::clone(sink);
         let alive_flag = Arc::clone(&self.alive);
+        let child = Arc::clone(&self.child);
+        let exit = Arc::clone(&self.exit);
         let session_id_task = session_id_owned.clone();
 
         tokio::spawn(async move {
@@ -337,18 +402,17 @@ impl AgentExecutor for AcpExecutor {
             loop {
                 match lines.next_line().await {
                     Ok(Some(line)) => {
-                        // 9. Classify and emit each message
                         match classify_acp_message(&line) {
                             Ok(msg) => {
                                 let event = acp_message_to_protocol_event(&session_id_task, msg);
-                                let _ = app_handle.emit("protocol-event", event);
+                                sink_task.emit_event(event);
                             }
                             Err(err) => {
-                                let _ = app_handle.emit(
-                                    "protocol-event",
+                                sink_task.emit_event(
                                     ProtocolEvent::Error {
                                         session_id: session_id_task.clone(),
                                         message: err,
+                                        failure: None,
                                     },
                                 );
                             }
@@ -364,21 +428,377 @@ impl AgentExecutor for AcpExecutor {
                 }
             }
 
-            // 10. Emit Disconnected event on EOF/exit
+            // The process serves a single prompt, so only a failing exit is a crash.
+            exit.record(&child, false).await;
+
+            // Emit Disconnected event on EOF/exit
+            sink_task.emit_event(ProtocolEvent::SessionEvent {
+                session_id: session_id_task,
+                event: SessionEventKind::Disconnected,
+            });
             alive_flag.store(false, Ordering::SeqCst);
-            let _ = app_handle.emit(
-                "protocol-event",
-                ProtocolEvent::SessionEvent {
-                    session_id: session_id_task,
-                    event: SessionEventKind::Disconnected,
-                },
-            );
         });
 
         Ok(())
     }
 
+    /// Agent Client Protocol: `initialize`, then `session/new` (or
+    /// `session/load` when resuming), then the first `session/prompt` turn.
+    /// The process stays up afterwards for [`AgentExecutor::send_prompt`].
+    ///
+    /// A failed handshake kills the agent and returns the error, so the
+    /// caller can fall back to PTY before anything was shown.
+    async fn execute_json_rpc(
+        &mut self,
+        sink: &SharedEventSink,
+        session_id: &str,
+        agent: &str,
+        message: &str,
+        working_dir: &str,
+        resume_session_id: Option<&str>,
+    ) -> Result<(), CommanderError> {
+        let mut args: Vec<String> = self
+            .flag_variant
+            .as_deref()
+            .map(|flag| flag.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+        self.headless_config = autohand_config(agent, working_dir, &mut args)?;
+        let turn = TurnTarget::shared(sink, session_id);
+        let stdout = self.spawn(agent, &args, Some(working_dir), &turn).await?;
+
+        let connection = Arc::new(AcpConnection::new(Arc::clone(&self.stdin)));
+        let host = Arc::new(
+            AcpHost::new(working_dir, Arc::clone(&turn))
+                .with_sandbox(self.sandbox.clone())
+                .with_environment(self.environment.clone()),
+        );
+        let announced = Arc::new(AtomicBool::new(false));
+        let replaying = Arc::new(AtomicBool::new(false));
+        spawn_json_rpc_reader(
+            stdout,
+            Arc::clone(&connection),
+            Arc::clone(&host),
+            Arc::clone(&turn),
+            ReaderState {
+                alive: Arc::clone(&self.alive),
+                busy: Arc::clone(&self.busy),
+                child: Arc::clone(&self.child),
+                exit: Arc::clone(&self.exit),
+                announced: Arc::clone(&announced),
+                replaying: Arc::clone(&replaying),
+            },
+        );
+
+        let acp_session_id =
+            match acp_handshake(&connection, working_dir, resume_session_id, &replaying).await {
+                Ok(id) => id,
+                Err(e) => {
+                    self.exit.stopping();
+                    kill_child(&self.child, &self.stdin, &self.alive).await;
+                    return Err(e);
+                }
+            };
+        self.connection = Some(connection);
+        self.acp_session_id = Some(acp_session_id);
+        self.turn = Some(turn);
+        self.host = Some(host);
+
+        announced.store(true, Ordering::SeqCst);
+        sink.emit_event(ProtocolEvent::SessionEvent {
+            session_id: session_id.to_string(),
+            event: SessionEventKind::Connected,
+        });
+
+        self.start_turn(sink, session_id, message)
+    }
+
+    /// Send `session/prompt` in the background, pointing the reader at the
+    /// turn's sink first. The turn is over once the prompt is answered.
+    fn start_turn(
+        &self,
+        sink: &SharedEventSink,
+        session_id: &str,
+        message: &str,
+    ) -> Result<(), CommanderError> {
+        let (Some(connection), Some(acp_session_id), Some(turn)) =
+            (&self.connection, &self.acp_session_id, &self.turn)
+        else {
+            return Err(CommanderError::protocol("unsupported", None, "ACP session is not open"));
+        };
+        if !self.alive.load(Ordering::SeqCst) {
+            return Err(CommanderError::protocol("process_died", None, "ACP agent is no longer running"));
+        }
+        if self.busy.swap(true, Ordering::SeqCst) {
+            return Err(CommanderError::protocol("busy", None, "a turn is already running"));
+        }
+        TurnTarget::retarget(turn, sink, session_id);
+
+        let connection = Arc::clone(connection);
+        let params = prompt_params(acp_session_id, message);
+        let sink_task = Arc::clone(sink);
+        let session_id_task = session_id.to_string();
+        let busy = Arc::clone(&self.busy);
+        tokio::spawn(async move {
+            match connection.request(methods::SESSION_PROMPT, params, None).await {
+                Ok(result) => sink_task.emit_event(ProtocolEvent::StateChange {
+                    session_id: session_id_task,
+                    status: stop_reason_status(&result),
+                    context_percent: None,
+                    usage: None,
+                    agent_session_id: None,
+                }),
+                // The supervisor reports a crash, and restarts the agent if it can.
+                Err(CommanderError::Protocol { kind, .. }) if kind == "process_died" => {}
+                Err(e) => sink_task.emit_event(ProtocolEvent::Error {
+                    session_id: session_id_task,
+                    message: e.to_string(),
+                    failure: None,
+                }),
+            }
+            busy.store(false, Ordering::SeqCst);
+        });
+        Ok(())
+    }
+}
+
+/// Negotiate the protocol version and open (or load) the ACP session,
+/// returning its id.
+async fn acp_handshake(
+    connection: &AcpConnection,
+    working_dir: &str,
+    resume_session_id: Option<&str>,
+    replaying: &AtomicBool,
+) -> Result<String, CommanderError> {
+    let init = connection
+        .request(methods::INITIALIZE, initialize_params(), Some(ACP_HANDSHAKE_TIMEOUT))
+        .await?;
+    let capabilities = AgentCapabilities::from_initialize_result(&init);
+    if capabilities.protocol_version != ACP_PROTOCOL_VERSION {
+        return Err(CommanderError::protocol(
+            "agent_error",
+            None,
+            format!(
+                "agent speaks ACP version {}, expected {}",
+                capabilities.protocol_version, ACP_PROTOCOL_VERSION
+            ),
+        ));
+    }
+
+    if let Some(resume_id) = resume_session_id {
+        if capabilities.load_session {
+            // The agent replays the conversation before answering; that
+            // history is already on screen.
+            replaying.store(true, Ordering::SeqCst);
+            let loaded = connection
+                .request(
+                    methods::SESSION_LOAD,
+                    json!({ "sessionId": resume_id, "cwd": working_dir, "mcpServers": [] }),
+                    Some(ACP_HANDSHAKE_TIMEOUT),
+                )
+                .await;
+            replaying.store(false, Ordering::SeqCst);
+            loaded?;
+            return Ok(resume_id.to_string());
+        }
+        eprintln!(
+            "⚠️ ACP agent cannot load sessions; starting a new one instead of resuming {}",
+            resume_id
+        );
+    }
+
+    let created = connection
+        .request(
+            methods::SESSION_NEW,
+            json!({ "cwd": working_dir, "mcpServers": [] }),
+            Some(ACP_HANDSHAKE_TIMEOUT),
+        )
+        .await?;
+    created
+        .get("sessionId")
+        .and_then(|id| id.as_str())
+        .map(String::from)
+        .ok_or_else(|| CommanderError::protocol("parse_error", None, "session/new returned no sessionId"))
+}
+
+/// Executor state the JSON-RPC reader updates or consults.
+struct ReaderState {
+    alive: Arc<AtomicBool>,
+    busy: Arc<AtomicBool>,
+    child: Arc<Mutex<Option<Child>>>,
+    exit: Arc<ExitWatch>,
+    /// Whether `Connected` went out, so `Disconnected` should too.
+    announced: Arc<AtomicBool>,
+    /// Whether `session/load` is replaying history that is already shown.
+    replaying: Arc<AtomicBool>,
+}
+
+/// Route everything the agent writes: responses to their waiting requests,
+/// `session/update` notifications to the sink, and agent requests to their
+/// handlers.
+fn spawn_json_rpc_reader(
+    stdout: ChildStdout,
+    connection: Arc<AcpConnection>,
+    host: Arc<AcpHost>,
+    turn: SharedTurnTarget,
+    state: ReaderState,
+) {
+    let ReaderState {
+        alive,
+        busy,
+        child,
+        exit,
+        announced,
+        replaying,
+    } = state;
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stdout).lines();
+        let mut mapper = SessionUpdateMapper::new();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let Some(TurnTarget { sink, session_id }) = TurnTarget::current(&turn) else {
+                break;
+            };
+            match parse_acp_rpc_line(&line) {
+                Ok(AcpIncoming::Response { id, result }) => {
+                    connection.resolve(id, result);
+                }
+                Ok(AcpIncoming::Notification { method, params }) => {
+                    if method != methods::SESSION_UPDATE || replaying.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    let update = params.get("update").unwrap_or(&serde_json::Value::Null);
+                    for event in mapper.map(&session_id, update) {
+                        sink.emit_event(event);
+                    }
+                }
+                Ok(AcpIncoming::Request { id, method, params })
+                    if method == methods::SESSION_REQUEST_PERMISSION =>
+                {
+                    let request_id = match &id {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    let options = params
+                        .get("options")
+                        .and_then(|o| o.as_array())
+                        .cloned()
+                        .unwrap_or_default();
+                    let event = permission_event(&session_id, &request_id, &params, &mapper);
+                    connection.hold_permission(request_id, PendingPermission { rpc_id: id, options });
+                    sink.emit_event(event);
+                }
+                Ok(AcpIncoming::Request { id, method, params }) => {
+                    // Terminal waits can take as long as the command runs.
+                    let connection = Arc::clone(&connection);
+                    let host = Arc::clone(&host);
+                    tokio::spawn(async move {
+                        let _ = match host.handle(&method, &params).await {
+                            Some(Ok(result)) => connection.respond(id, result).await,
+                            Some(Err(err)) => connection.respond_error(id, err.code, &err.message).await,
+                            None => {
+                                connection
+                                    .respond_error(id, METHOD_NOT_FOUND, &format!("{} is not supported", method))
+                                    .await
+                            }
+                        };
+                    });
+                }
+                Err(err) => sink.emit_event(ProtocolEvent::Error {
+                    session_id,
+                    message: err,
+                    failure: None,
+                }),
+            }
+        }
+
+        // Recorded before pending requests fail, which ends the turn.
+        exit.record(&child, busy.load(Ordering::SeqCst)).await;
+        connection.fail_pending();
+        host.release_all();
+        if let (true, Some(TurnTarget { sink, session_id })) =
+            (announced.load(Ordering::SeqCst), TurnTarget::current(&turn))
+        {
+            sink.emit_event(ProtocolEvent::SessionEvent {
+                session_id,
+                event: SessionEventKind::Disconnected,
+            });
+        }
+        alive.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Wait up to `grace` for the agent to exit on its own, returning early once
+/// the child has been reaped or its stdout has closed.
+async fn wait_for_exit(child: &Mutex<Option<Child>>, alive: &AtomicBool, grace: std::time::Duration) {
+    let deadline = tokio::time::Instant::now() + grace;
+    while tokio::time::Instant::now() < deadline {
+        let exited = match child.lock().await.as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(Some(_))),
+            None => true,
+        };
+        if exited || !alive.load(Ordering::SeqCst) {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+async fn kill_child(
+    child: &Mutex<Option<Child>>,
+    stdin: &Mutex<Option<ChildStdin>>,
+    alive: &AtomicBool,
+) {
+    let mut guard = child.lock().await;
+    if let Some(ref mut child) = *guard {
+        terminate_child(child, TERMINATE_GRACE).await;
+    }
+    *guard = None;
+    *stdin.lock().await = None;
+    alive.store(false, Ordering::SeqCst);
+}
+
+#[async_trait]
+impl AgentExecutor for AcpExecutor {
+    async fn execute(
+        &mut self,
+        sink: &SharedEventSink,
+        session_id: &str,
+        agent: &str,
+        message: &str,
+        working_dir: &str,
+        settings: &AgentSettings,
+        resume_session_id: Option<&str>,
+    ) -> Result<(), CommanderError> {
+        self.sandbox = SandboxProfile::for_run(settings, agent, working_dir);
+        self.environment = AgentEnvironment::for_run(settings, working_dir)
+            .map_err(|e| CommanderError::command(agent, None, e))?;
+        match self.dialect {
+            AcpDialect::JsonRpc => {
+                self.execute_json_rpc(sink, session_id, agent, message, working_dir, resume_session_id)
+                    .await
+            }
+            AcpDialect::Legacy => {
+                self.execute_legacy(sink, session_id, agent, message, working_dir, resume_session_id)
+                    .await
+            }
+        }
+    }
+
     async fn respond_permission(&self, request_id: &str, approved: bool) -> Result<(), CommanderError> {
+        if let Some(connection) = &self.connection {
+            let permission = connection.take_permission(request_id).ok_or_else(|| {
+                CommanderError::protocol(
+                    "agent_error",
+                    None,
+                    format!("no pending permission request {}", request_id),
+                )
+            })?;
+            let option = select_permission_option(&permission.options, approved);
+            return connection
+                .respond(permission.rpc_id, permission_outcome(option))
+                .await;
+        }
+
         let envelope = serde_json::json!({
             "type": "permission_response",
             "data": {
@@ -393,26 +813,26 @@ impl AgentExecutor for AcpExecutor {
     }
 
     async fn abort(&self) -> Result<(), CommanderError> {
-        // Send graceful shutdown command
-        let envelope = serde_json::json!({
-            "type": "command",
-            "data": { "command": "shutdown" },
-        });
-        let mut line = serde_json::to_string(&envelope)
-            .map_err(|e| CommanderError::protocol("write_failed", None, format!("serialize failed: {}", e)))?;
-        line.push('\n');
-        let _ = write_stdin_line(&self.stdin, &line).await;
-
-        // Wait 2 seconds then kill
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-
-        let mut guard = self.child.lock().await;
-        if let Some(ref mut child) = *guard {
-            let _ = child.kill().await;
+        self.exit.stopping();
+        match (&self.connection, &self.acp_session_id) {
+            (Some(_), Some(_)) => {
+                let _ = self.interrupt().await;
+            }
+            _ => {
+                // Send graceful shutdown command
+                let envelope = serde_json::json!({
+                    "type": "command",
+                    "data": { "command": "shutdown" },
+                });
+                let mut line = serde_json::to_string(&envelope)
+                    .map_err(|e| CommanderError::protocol("write_failed", None, format!("serialize failed: {}", e)))?;
+                line.push('\n');
+                let _ = write_stdin_line(&self.stdin, &line).await;
+            }
         }
-        *guard = None;
-        *self.stdin.lock().await = None;
-        self.alive.store(false, Ordering::SeqCst);
+
+        wait_for_exit(&self.child, &self.alive, SHUTDOWN_GRACE).await;
+        kill_child(&self.child, &self.stdin, &self.alive).await;
         Ok(())
     }
 
@@ -423,6 +843,61 @@ impl AgentExecutor for AcpExecutor {
     fn protocol(&self) -> Option<ProtocolMode> {
         Some(ProtocolMode::Acp)
     }
+
+    fn supports_multi_turn(&self) -> bool {
+        self.dialect == AcpDialect::JsonRpc
+    }
+
+    fn is_busy(&self) -> bool {
+        match self.dialect {
+            AcpDialect::JsonRpc => self.is_alive() && self.busy.load(Ordering::SeqCst),
+            AcpDialect::Legacy => self.is_alive(),
+        }
+    }
+
+    async fn send_prompt(
+        &mut self,
+        sink: &SharedEventSink,
+        session_id: &str,
+        message: &str,
+    ) -> Result<(), CommanderError> {
+        self.start_turn(sink, session_id, message)
+    }
+
+    /// `session/cancel` ends the turn with a `cancelled` stop reason; any
+    /// permission request still waiting is answered as cancelled.
+    async fn interrupt(&self) -> Result<(), CommanderError> {
+        let (Some(connection), Some(acp_session_id)) = (&self.connection, &self.acp_session_id)
+        else {
+            return self.abort().await;
+        };
+        connection
+            .notify(methods::SESSION_CANCEL, json!({ "sessionId": acp_session_id }))
+            .await?;
+        connection.cancel_permissions().await;
+        Ok(())
+    }
+
+    /// Close stdin so the agent can exit on its own, killing it after a
+    /// grace period.
+    async fn shutdown(&self) -> Result<(), CommanderError> {
+        if self.connection.is_none() {
+            return self.abort().await;
+        }
+        self.exit.stopping();
+        *self.stdin.lock().await = None;
+        wait_for_exit(&self.child, &self.alive, SHUTDOWN_GRACE).await;
+        kill_child(&self.child, &self.stdin, &self.alive).await;
+        Ok(())
+    }
+
+    fn exit_error(&self) -> Option<ProtocolError> {
+        self.exit.died()
+    }
+
+    fn agent_session_id(&self) -> Option<String> {
+        self.acp_session_id.clone()
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -471,8 +946,14 @@ fn acp_message_to_protocol_event(session_id: &str, msg: AcpMessage) -> ProtocolE
             ProtocolEvent::PermissionRequest {
                 session_id: session_id.to_string(),
                 request_id,
+                target: Some(PermissionTarget {
+                    tool_kind: resolve_tool_kind(&tool_name),
+                    command: None,
+                    paths: Vec::new(),
+                }),
                 tool_name,
                 description,
+                tool_id: None,
             }
         }
         AcpMessage::StateChange { status, context_percent } => {
@@ -480,11 +961,14 @@ fn acp_message_to_protocol_event(session_id: &str, msg: AcpMessage) -> ProtocolE
                 session_id: session_id.to_string(),
                 status,
                 context_percent,
+                usage: None,
+                agent_session_id: None,
             }
         }
         AcpMessage::Unknown => ProtocolEvent::Error {
             session_id: session_id.to_string(),
             message: "received unknown ACP message type".to_string(),
+            failure: None,
         },
     }
 }
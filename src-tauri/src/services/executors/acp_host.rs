@@ -0,0 +1,536 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, PtySize};
+use serde_json::{json, Value};
+use tokio::sync::watch;
+
+use crate::models::protocol::{ProtocolEvent, ToolKind};
+use crate::services::env_profile_service::AgentEnvironment;
+use crate::services::sandbox_service::SandboxProfile;
+use super::acp_jsonrpc::{methods, AcpRpcError};
+use super::{SharedTurnTarget, TurnTarget};
+
+// ---------------------------------------------------------------------------
+// Client-side ACP methods: fs/* and terminal/*
+// ---------------------------------------------------------------------------
+
+/// Editor contents the UI has not saved yet, keyed by resolved path. An
+/// agent reading one of these files sees what the user sees, and may not
+/// write over it until the edits are saved or discarded.
+static UNSAVED_BUFFERS: Lazy<RwLock<HashMap<PathBuf, String>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+pub fn set_unsaved_buffer(path: &str, content: String) {
+    if let Ok(mut buffers) = UNSAVED_BUFFERS.write() {
+        buffers.insert(resolve_path(Path::new(path)), content);
+    }
+}
+
+pub fn clear_unsaved_buffer(path: &str) {
+    if let Ok(mut buffers) = UNSAVED_BUFFERS.write() {
+        buffers.remove(&resolve_path(Path::new(path)));
+    }
+}
+
+fn unsaved_buffer(path: &Path) -> Option<String> {
+    UNSAVED_BUFFERS.read().ok()?.get(path).cloned()
+}
+
+/// Resolve `.` and `..` without touching the disk, then canonicalize the
+/// longest existing prefix. Symlinks past that prefix (dangling ones) are
+/// left in place; [`AcpHost::resolve`] refuses them.
+pub fn resolve_path(path: &Path) -> PathBuf {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                clean.pop();
+            }
+            Component::CurDir => {}
+            other => clean.push(other.as_os_str()),
+        }
+    }
+
+    let mut existing = clean.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return missing
+                .iter()
+                .rev()
+                .fold(canonical, |resolved, name| resolved.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return clean,
+        }
+    }
+}
+
+/// Lines `line..line + limit` (1-based) of `content`, keeping line endings.
+pub fn slice_lines(content: &str, line: Option<u64>, limit: Option<u64>) -> String {
+    if line.is_none() && limit.is_none() {
+        return content.to_string();
+    }
+    let skip = line.unwrap_or(1).saturating_sub(1) as usize;
+    let take = limit.map(|l| l as usize).unwrap_or(usize::MAX);
+    content.split_inclusive('\n').skip(skip).take(take).collect()
+}
+
+/// Unified diff of a file write, as shown on the write's `ToolEnd`.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let path = Path::new(path);
+    git2::Patch::from_buffers(old.as_bytes(), Some(path), new.as_bytes(), Some(path), None)
+        .and_then(|mut patch| patch.to_buf())
+        .map(|buf| buf.as_str().unwrap_or_default().to_string())
+        .unwrap_or_default()
+}
+
+/// Output a terminal has kept, trimmed from the front to the agent's
+/// `outputByteLimit`.
+#[derive(Debug, Default)]
+pub struct TerminalOutput {
+    pub text: String,
+    pub truncated: bool,
+    limit: Option<usize>,
+}
+
+impl TerminalOutput {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self { limit, ..Self::default() }
+    }
+
+    pub fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+        let Some(limit) = self.limit else { return };
+        if self.text.len() > limit {
+            let mut cut = self.text.len() - limit;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.text.drain(..cut);
+            self.truncated = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerminalExit {
+    pub exit_code: Option<u32>,
+    pub signal: Option<String>,
+}
+
+impl TerminalExit {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0) && self.signal.is_none()
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "exitCode": self.exit_code, "signal": self.signal })
+    }
+}
+
+struct Terminal {
+    output: Arc<Mutex<TerminalOutput>>,
+    exit: watch::Receiver<Option<TerminalExit>>,
+    killer: Mutex<Box<dyn ChildKiller + Send + Sync>>,
+}
+
+impl Terminal {
+    fn kill(&self) {
+        if self.exit.borrow().is_none() {
+            if let Ok(mut killer) = self.killer.lock() {
+                let _ = killer.kill();
+            }
+        }
+    }
+}
+
+/// Serves the methods ACP lets an agent call on its client. Files are
+/// confined to the session's root (the project or its worktree) and
+/// commands run as PTYs whose output streams to the UI as tool events.
+pub struct AcpHost {
+    root: PathBuf,
+    turn: SharedTurnTarget,
+    terminals: Mutex<HashMap<String, Arc<Terminal>>>,
+    sandbox: Option<SandboxProfile>,
+    environment: Option<AgentEnvironment>,
+}
+
+impl AcpHost {
+    pub fn new(root: &str, turn: SharedTurnTarget) -> Self {
+        Self {
+            root: resolve_path(Path::new(root)),
+            turn,
+            terminals: Mutex::new(HashMap::new()),
+            sandbox: None,
+            environment: None,
+        }
+    }
+
+    /// Run terminal commands inside the agent's sandbox.
+    pub fn with_sandbox(mut self, sandbox: Option<SandboxProfile>) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Start terminal commands with the agent's environment, under the
+    /// variables the agent asks for.
+    pub fn with_environment(mut self, environment: Option<AgentEnvironment>) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Answer an agent request, or `None` when the method is not one of ours.
+    pub async fn handle(&self, method: &str, params: &Value) -> Option<Result<Value, AcpRpcError>> {
+        Some(match method {
+            methods::FS_READ_TEXT_FILE => self.read_text_file(params).await,
+            methods::FS_WRITE_TEXT_FILE => self.write_text_file(params).await,
+            methods::TERMINAL_CREATE => self.create_terminal(params),
+            methods::TERMINAL_OUTPUT => self.terminal_output(params),
+            methods::TERMINAL_WAIT_FOR_EXIT => self.wait_for_terminal_exit(params).await,
+            methods::TERMINAL_KILL => self.terminal(params).map(|terminal| {
+                terminal.kill();
+                json!({})
+            }),
+            methods::TERMINAL_RELEASE => self.release_terminal(params),
+            _ => return None,
+        })
+    }
+
+    /// Kill every terminal, e.g. when the agent goes away.
+    pub fn release_all(&self) {
+        let terminals: Vec<Arc<Terminal>> = match self.terminals.lock() {
+            Ok(mut terminals) => terminals.drain().map(|(_, t)| t).collect(),
+            Err(_) => return,
+        };
+        for terminal in terminals {
+            terminal.kill();
+        }
+    }
+
+    /// Resolve a path the agent sent, refusing anything outside the root.
+    /// A symlink left in the resolved path could not be followed, so it is
+    /// dangling and a write would create its target wherever it points.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, AcpRpcError> {
+        let requested = Path::new(path);
+        let absolute = if requested.is_absolute() {
+            requested.to_path_buf()
+        } else {
+            self.root.join(requested)
+        };
+        let resolved = resolve_path(&absolute);
+        let Ok(inside) = resolved.strip_prefix(&self.root) else {
+            return Err(AcpRpcError::invalid_params(format!(
+                "{} is outside the project root",
+                path
+            )));
+        };
+        let mut current = self.root.clone();
+        for component in inside.components() {
+            current.push(component);
+            let is_symlink = std::fs::symlink_metadata(&current)
+                .map(|meta| meta.file_type().is_symlink())
+                .unwrap_or(false);
+            if is_symlink {
+                return Err(AcpRpcError::invalid_params(format!(
+                    "{} goes through a symlink that cannot be followed",
+                    path
+                )));
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn display_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn emit(&self, event: impl FnOnce(String) -> ProtocolEvent) {
+        emit_to_turn(&self.turn, event);
+    }
+
+    async fn read_text_file(&self, params: &Value) -> Result<Value, AcpRpcError> {
+        let path = self.resolve(required_str(params, "path")?)?;
+        let content = match unsaved_buffer(&path) {
+            Some(content) => content,
+            None => tokio::fs::read_to_string(&path).await.map_err(|e| {
+                AcpRpcError::internal(format!("cannot read {}: {}", self.display_path(&path), e))
+            })?,
+        };
+        let line = params.get("line").and_then(|v| v.as_u64());
+        let limit = params.get("limit").and_then(|v| v.as_u64());
+        Ok(json!({ "content": slice_lines(&content, line, limit) }))
+    }
+
+    /// Write the file and record it as a tool call whose output is the diff.
+    /// A file with unsaved edits in the editor is left alone.
+    async fn write_text_file(&self, params: &Value) -> Result<Value, AcpRpcError> {
+        let path = self.resolve(required_str(params, "path")?)?;
+        let content = required_str(params, "content")?;
+        let display = self.display_path(&path);
+        if unsaved_buffer(&path).is_some() {
+            return Err(AcpRpcError::invalid_params(format!(
+                "{} has unsaved edits in the editor; save or discard them first",
+                display
+            )));
+        }
+        let previous = tokio::fs::read_to_string(&path).await.ok();
+
+        let tool_id = uuid::Uuid::new_v4().to_string();
+        let tool_name = format!("Write {}", display);
+        let started = Instant::now();
+        self.emit(|session_id| ProtocolEvent::ToolStart {
+            session_id,
+            tool_id: tool_id.clone(),
+            tool_name: tool_name.clone(),
+            tool_kind: if previous.is_some() { ToolKind::Edit } else { ToolKind::Write },
+            args: Some(json!({ "path": display })),
+        });
+
+        let written = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, content).await
+        }
+        .await;
+
+        let (output, success) = match &written {
+            Ok(()) => (unified_diff(&display, previous.as_deref().unwrap_or(""), content), true),
+            Err(e) => (e.to_string(), false),
+        };
+        self.emit(|session_id| ProtocolEvent::ToolEnd {
+            session_id,
+            tool_id,
+            tool_name,
+            output: Some(output),
+            success,
+            duration_ms: Some(started.elapsed().as_millis() as u64),
+        });
+
+        written
+            .map(|_| json!({}))
+            .map_err(|e| AcpRpcError::internal(format!("cannot write {}: {}", display, e)))
+    }
+
+    fn create_terminal(&self, params: &Value) -> Result<Value, AcpRpcError> {
+        let command = required_str(params, "command")?;
+        let args: Vec<String> = params
+            .get("args")
+            .and_then(|a| a.as_array())
+            .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        let cwd = match params.get("cwd").and_then(|c| c.as_str()) {
+            Some(dir) => self.resolve(dir)?,
+            None => self.root.clone(),
+        };
+        let limit = params
+            .get("outputByteLimit")
+            .and_then(|l| l.as_u64())
+            .map(|l| l as usize);
+
+        let (program, argv) = terminal_argv(command, &args);
+        let (program, argv) = match &self.sandbox {
+            Some(sandbox) => sandbox.wrap(&program, &argv).map_err(AcpRpcError::internal)?,
+            None => (program, argv),
+        };
+        let mut cmd = CommandBuilder::new(program);
+        cmd.args(argv);
+        cmd.cwd(&cwd);
+        if let Some(environment) = &self.environment {
+            environment.apply_pty(&mut cmd);
+        }
+        for var in params.get("env").and_then(|e| e.as_array()).into_iter().flatten() {
+            if let (Some(name), Some(value)) = (
+                var.get("name").and_then(|n| n.as_str()),
+                var.get("value").and_then(|v| v.as_str()),
+            ) {
+                cmd.env(name, value);
+            }
+        }
+
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 32,
+                cols: 120,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| AcpRpcError::internal(format!("Failed to open PTY: {}", e)))?;
+        let mut child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| AcpRpcError::internal(format!("Failed to spawn {}: {}", command, e)))?;
+        // Only the child may hold the slave end, or reads never see EOF.
+        drop(pair.slave);
+        let mut reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| AcpRpcError::internal(format!("Failed to clone PTY reader: {}", e)))?;
+
+        let terminal_id = uuid::Uuid::new_v4().to_string();
+        let title = std::iter::once(command.to_string())
+            .chain(args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let output = Arc::new(Mutex::new(TerminalOutput::new(limit)));
+        let (exit_tx, exit_rx) = watch::channel(None);
+        let terminal = Arc::new(Terminal {
+            output: Arc::clone(&output),
+            exit: exit_rx,
+            killer: Mutex::new(child.clone_killer()),
+        });
+        if let Ok(mut terminals) = self.terminals.lock() {
+            terminals.insert(terminal_id.clone(), terminal);
+        }
+
+        self.emit(|session_id| ProtocolEvent::ToolStart {
+            session_id,
+            tool_id: terminal_id.clone(),
+            tool_name: title.clone(),
+            tool_kind: ToolKind::Execute,
+            args: Some(json!({ "command": title, "cwd": self.display_path(&cwd) })),
+        });
+
+        let turn = Arc::clone(&self.turn);
+        let tool_id = terminal_id.clone();
+        let master = pair.master;
+        let started = Instant::now();
+        tokio::task::spawn_blocking(move || {
+            // Closing the master hangs up the child, so keep it until exit.
+            let _master = master;
+            let mut buf = [0u8; 4096];
+            let mut pending: Vec<u8> = Vec::new();
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        pending.extend_from_slice(&buf[..n]);
+                        let text = take_utf8(&mut pending);
+                        if text.is_empty() {
+                            continue;
+                        }
+                        if let Ok(mut output) = output.lock() {
+                            output.push(&text);
+                        }
+                        emit_to_turn(&turn, |session_id| ProtocolEvent::ToolUpdate {
+                            session_id,
+                            tool_id: tool_id.clone(),
+                            tool_name: title.clone(),
+                            output: Some(text),
+                        });
+                    }
+                }
+            }
+
+            let exit = match child.wait() {
+                Ok(status) => TerminalExit {
+                    exit_code: Some(status.exit_code()),
+                    signal: status.signal().map(String::from),
+                },
+                Err(_) => TerminalExit { exit_code: None, signal: None },
+            };
+            emit_to_turn(&turn, |session_id| ProtocolEvent::ToolEnd {
+                session_id,
+                tool_id,
+                tool_name: title,
+                output: None,
+                success: exit.success(),
+                duration_ms: Some(started.elapsed().as_millis() as u64),
+            });
+            let _ = exit_tx.send(Some(exit));
+        });
+
+        Ok(json!({ "terminalId": terminal_id }))
+    }
+
+    fn terminal(&self, params: &Value) -> Result<Arc<Terminal>, AcpRpcError> {
+        let id = required_str(params, "terminalId")?;
+        self.terminals
+            .lock()
+            .ok()
+            .and_then(|terminals| terminals.get(id).cloned())
+            .ok_or_else(|| AcpRpcError::invalid_params(format!("unknown terminal {}", id)))
+    }
+
+    fn terminal_output(&self, params: &Value) -> Result<Value, AcpRpcError> {
+        let terminal = self.terminal(params)?;
+        let exit = terminal.exit.borrow().clone();
+        let output = terminal
+            .output
+            .lock()
+            .map_err(|_| AcpRpcError::internal("terminal output is unavailable"))?;
+        let mut result = json!({ "output": output.text, "truncated": output.truncated });
+        if let Some(exit) = exit {
+            result["exitStatus"] = exit.to_json();
+        }
+        Ok(result)
+    }
+
+    async fn wait_for_terminal_exit(&self, params: &Value) -> Result<Value, AcpRpcError> {
+        let mut exit = self.terminal(params)?.exit.clone();
+        let status = exit
+            .wait_for(|status| status.is_some())
+            .await
+            .map_err(|_| AcpRpcError::internal("terminal ended without an exit status"))?;
+        Ok(status.as_ref().map(TerminalExit::to_json).unwrap_or(Value::Null))
+    }
+
+    fn release_terminal(&self, params: &Value) -> Result<Value, AcpRpcError> {
+        let terminal = self.terminal(params)?;
+        terminal.kill();
+        if let Ok(mut terminals) = self.terminals.lock() {
+            terminals.retain(|_, t| !Arc::ptr_eq(t, &terminal));
+        }
+        Ok(json!({}))
+    }
+}
+
+fn required_str<'a>(params: &'a Value, key: &str) -> Result<&'a str, AcpRpcError> {
+    params
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| AcpRpcError::invalid_params(format!("missing {}", key)))
+}
+
+fn emit_to_turn(turn: &SharedTurnTarget, event: impl FnOnce(String) -> ProtocolEvent) {
+    if let Some(TurnTarget { sink, session_id }) = TurnTarget::current(turn) {
+        sink.emit_event(event(session_id));
+    }
+}
+
+/// A command line without separate args goes through the shell, which
+/// is how agents usually send them.
+fn terminal_argv(command: &str, args: &[String]) -> (String, Vec<String>) {
+    if args.is_empty() && command.contains(char::is_whitespace) {
+        let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        (shell.to_string(), vec![flag.to_string(), command.to_string()])
+    } else {
+        (command.to_string(), args.to_vec())
+    }
+}
+
+/// Take the complete UTF-8 prefix of `pending`, leaving a character split
+/// across reads for the next one.
+pub(crate) fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+    let text = String::from_utf8_lossy(&pending[..valid]).to_string();
+    pending.drain(..valid);
+    text
+}
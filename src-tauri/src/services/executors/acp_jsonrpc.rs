@@ -0,0 +1,673 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::process::ChildStdin;
+use tokio::sync::{oneshot, Mutex};
+
+use crate::error::CommanderError;
+use crate::models::protocol::{PermissionTarget, PlanEntry, ProtocolEvent, ToolKind};
+
+// ---------------------------------------------------------------------------
+// Agent Client Protocol over JSON-RPC 2.0
+// ---------------------------------------------------------------------------
+
+/// ACP protocol version this client implements.
+pub const ACP_PROTOCOL_VERSION: u64 = 1;
+
+/// How long the agent gets to answer a handshake request.
+pub const ACP_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// JSON-RPC error code for methods the client does not implement.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error code for a request with missing or unusable params.
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// JSON-RPC error code for a request that failed while being served.
+pub const INTERNAL_ERROR: i64 = -32603;
+
+pub mod methods {
+    pub const INITIALIZE: &str = "initialize";
+    pub const SESSION_NEW: &str = "session/new";
+    pub const SESSION_LOAD: &str = "session/load";
+    pub const SESSION_PROMPT: &str = "session/prompt";
+    pub const SESSION_CANCEL: &str = "session/cancel";
+    pub const SESSION_UPDATE: &str = "session/update";
+    pub const SESSION_REQUEST_PERMISSION: &str = "session/request_permission";
+    pub const FS_READ_TEXT_FILE: &str = "fs/read_text_file";
+    pub const FS_WRITE_TEXT_FILE: &str = "fs/write_text_file";
+    pub const TERMINAL_CREATE: &str = "terminal/create";
+    pub const TERMINAL_OUTPUT: &str = "terminal/output";
+    pub const TERMINAL_WAIT_FOR_EXIT: &str = "terminal/wait_for_exit";
+    pub const TERMINAL_KILL: &str = "terminal/kill";
+    pub const TERMINAL_RELEASE: &str = "terminal/release";
+}
+
+/// Wire dialect spoken by an [`AcpExecutor`](super::acp_executor::AcpExecutor).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AcpDialect {
+    /// Agent Client Protocol: JSON-RPC 2.0 with an `initialize` handshake.
+    #[default]
+    JsonRpc,
+    /// The older `{"type": ..., "data": ...}` ndJSON envelope.
+    Legacy,
+}
+
+/// A JSON-RPC error object, from the agent's responses or for ours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcpRpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl AcpRpcError {
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self { code: INVALID_PARAMS, message: message.into() }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self { code: INTERNAL_ERROR, message: message.into() }
+    }
+}
+
+impl From<AcpRpcError> for CommanderError {
+    fn from(err: AcpRpcError) -> Self {
+        CommanderError::protocol("agent_error", Some(err.code as i32), err.message)
+    }
+}
+
+/// A JSON-RPC message read from the agent's stdout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AcpIncoming {
+    /// Answer to one of our requests.
+    Response {
+        id: u64,
+        result: Result<Value, AcpRpcError>,
+    },
+    /// A notification from the agent, e.g. `session/update`.
+    Notification { method: String, params: Value },
+    /// A request the agent expects us to answer, e.g. `session/request_permission`.
+    Request {
+        id: Value,
+        method: String,
+        params: Value,
+    },
+}
+
+/// Parse a single ndJSON line into a JSON-RPC message.
+pub fn parse_acp_rpc_line(line: &str) -> Result<AcpIncoming, String> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return Err("empty or whitespace-only line".to_string());
+    }
+    let value: Value =
+        serde_json::from_str(trimmed).map_err(|e| format!("invalid JSON: {}", e))?;
+    if value.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+        return Err("not a JSON-RPC 2.0 message".to_string());
+    }
+
+    let params = value.get("params").cloned().unwrap_or(Value::Null);
+    match (value.get("method").and_then(|m| m.as_str()), value.get("id")) {
+        (Some(method), Some(id)) if !id.is_null() => Ok(AcpIncoming::Request {
+            id: id.clone(),
+            method: method.to_string(),
+            params,
+        }),
+        (Some(method), _) => Ok(AcpIncoming::Notification {
+            method: method.to_string(),
+            params,
+        }),
+        (None, Some(id)) => {
+            let id = id
+                .as_u64()
+                .ok_or_else(|| format!("unexpected response id: {}", id))?;
+            let result = match value.get("error") {
+                Some(error) => Err(AcpRpcError {
+                    code: error.get("code").and_then(|c| c.as_i64()).unwrap_or(-32603),
+                    message: error
+                        .get("message")
+                        .and_then(|m| m.as_str())
+                        .unwrap_or("unknown error")
+                        .to_string(),
+                }),
+                None => Ok(value.get("result").cloned().unwrap_or(Value::Null)),
+            };
+            Ok(AcpIncoming::Response { id, result })
+        }
+        (None, None) => Err("message has neither method nor id".to_string()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Capability negotiation
+// ---------------------------------------------------------------------------
+
+/// Params of the `initialize` request.
+///
+/// Commander serves text file access and terminals itself (see
+/// [`AcpHost`](super::acp_host::AcpHost)), so it can confine them to the
+/// session's root and show them in the UI.
+pub fn initialize_params() -> Value {
+    json!({
+        "protocolVersion": ACP_PROTOCOL_VERSION,
+        "clientCapabilities": {
+            "fs": { "readTextFile": true, "writeTextFile": true },
+            "terminal": true,
+        },
+    })
+}
+
+/// What the agent said it supports in its `initialize` response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AgentCapabilities {
+    pub protocol_version: u64,
+    pub load_session: bool,
+    pub image_prompts: bool,
+    pub embedded_context: bool,
+}
+
+impl AgentCapabilities {
+    pub fn from_initialize_result(result: &Value) -> Self {
+        let caps = result.get("agentCapabilities").unwrap_or(&Value::Null);
+        let prompt = caps.get("promptCapabilities").unwrap_or(&Value::Null);
+        let flag = |v: &Value, key: &str| v.get(key).and_then(|b| b.as_bool()).unwrap_or(false);
+        Self {
+            protocol_version: result
+                .get("protocolVersion")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+            load_session: flag(caps, "loadSession"),
+            image_prompts: flag(prompt, "image"),
+            embedded_context: flag(prompt, "embeddedContext"),
+        }
+    }
+}
+
+/// Params of `session/prompt` for a plain text message.
+pub fn prompt_params(session_id: &str, message: &str) -> Value {
+    json!({
+        "sessionId": session_id,
+        "prompt": [{ "type": "text", "text": message }],
+    })
+}
+
+/// Status reported once a prompt turn ends, from its `stopReason`.
+pub fn stop_reason_status(result: &Value) -> String {
+    match result.get("stopReason").and_then(|r| r.as_str()) {
+        Some("end_turn") | None => "idle".to_string(),
+        Some(other) => other.to_string(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// session/update mapping
+// ---------------------------------------------------------------------------
+
+/// Map an ACP tool kind onto ours.
+pub fn acp_tool_kind(kind: &str) -> ToolKind {
+    match kind {
+        "read" => ToolKind::Read,
+        "edit" | "move" => ToolKind::Edit,
+        "delete" => ToolKind::Delete,
+        "search" => ToolKind::Search,
+        "execute" => ToolKind::Execute,
+        "think" => ToolKind::Think,
+        "fetch" => ToolKind::Fetch,
+        _ => ToolKind::Other,
+    }
+}
+
+/// Text carried by a content block, if it is a text block.
+fn content_block_text(block: &Value) -> Option<&str> {
+    match block.get("type").and_then(|t| t.as_str()) {
+        Some("text") => block.get("text").and_then(|t| t.as_str()),
+        _ => None,
+    }
+}
+
+/// Readable output of a tool call: its text content, or the raw output.
+fn tool_call_output(update: &Value) -> Option<String> {
+    let texts: Vec<&str> = update
+        .get("content")
+        .and_then(|c| c.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| match item.get("type").and_then(|t| t.as_str()) {
+                    Some("content") => item.get("content").and_then(content_block_text),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if !texts.is_empty() {
+        return Some(texts.join("\n"));
+    }
+    match update.get("rawOutput") {
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(v) if !v.is_null() => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+struct TrackedTool {
+    title: String,
+    started: Instant,
+    target: PermissionTarget,
+}
+
+/// Turns `session/update` notifications into protocol events.
+///
+/// Tool call updates only carry the fields that changed, so the title and
+/// start time of every open tool call are remembered here.
+#[derive(Default)]
+pub struct SessionUpdateMapper {
+    tools: HashMap<String, TrackedTool>,
+}
+
+impl SessionUpdateMapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Title of an open tool call, if it is known.
+    pub fn tool_title(&self, tool_id: &str) -> Option<&str> {
+        self.tools.get(tool_id).map(|t| t.title.as_str())
+    }
+
+    /// What an open tool call would touch, as reported when it started.
+    pub fn tool_target(&self, tool_id: &str) -> Option<&PermissionTarget> {
+        self.tools.get(tool_id).map(|t| &t.target)
+    }
+
+    /// Map the `update` object of a `session/update` notification.
+    ///
+    /// Thought chunks, command lists and mode changes produce no events.
+    pub fn map(&mut self, session_id: &str, update: &Value) -> Vec<ProtocolEvent> {
+        let kind = update.get("sessionUpdate").and_then(|k| k.as_str()).unwrap_or("");
+        match kind {
+            "agent_message_chunk" | "user_message_chunk" => {
+                let role = if kind == "agent_message_chunk" { "assistant" } else { "user" };
+                update
+                    .get("content")
+                    .and_then(content_block_text)
+                    .map(|text| ProtocolEvent::Message {
+                        session_id: session_id.to_string(),
+                        role: role.to_string(),
+                        content: text.to_string(),
+                    })
+                    .into_iter()
+                    .collect()
+            }
+            "tool_call" => self.tool_call(session_id, update),
+            "tool_call_update" => self.tool_call_update(session_id, update),
+            "plan" => {
+                let entries = update
+                    .get("entries")
+                    .and_then(|e| e.as_array())
+                    .map(|items| items.iter().map(plan_entry).collect())
+                    .unwrap_or_default();
+                vec![ProtocolEvent::Plan {
+                    session_id: session_id.to_string(),
+                    entries,
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn tool_call(&mut self, session_id: &str, update: &Value) -> Vec<ProtocolEvent> {
+        let tool_id = str_field(update, "toolCallId");
+        let title = update
+            .get("title")
+            .and_then(|t| t.as_str())
+            .unwrap_or("tool")
+            .to_string();
+        let tool_kind = acp_tool_kind(update.get("kind").and_then(|k| k.as_str()).unwrap_or("other"));
+        self.tools.insert(
+            tool_id.clone(),
+            TrackedTool {
+                title: title.clone(),
+                started: Instant::now(),
+                target: tool_call_target(update, None),
+            },
+        );
+
+        let mut events = vec![ProtocolEvent::ToolStart {
+            session_id: session_id.to_string(),
+            tool_id,
+            tool_name: title,
+            tool_kind,
+            args: update.get("rawInput").cloned(),
+        }];
+        // Tool calls that finished before they were reported arrive complete.
+        events.extend(self.tool_call_update(session_id, update).into_iter().filter(
+            |event| matches!(event, ProtocolEvent::ToolEnd { .. }),
+        ));
+        events
+    }
+
+    fn tool_call_update(&mut self, session_id: &str, update: &Value) -> Vec<ProtocolEvent> {
+        let tool_id = str_field(update, "toolCallId");
+        if let Some(title) = update.get("title").and_then(|t| t.as_str()) {
+            if let Some(tool) = self.tools.get_mut(&tool_id) {
+                tool.title = title.to_string();
+            }
+        }
+        let output = tool_call_output(update);
+
+        match update.get("status").and_then(|s| s.as_str()) {
+            Some(status @ ("completed" | "failed")) => {
+                let tool = self.tools.remove(&tool_id);
+                let tool_name = tool
+                    .as_ref()
+                    .map(|t| t.title.clone())
+                    .or_else(|| update.get("title").and_then(|t| t.as_str()).map(String::from))
+                    .unwrap_or_else(|| "tool".to_string());
+                vec![ProtocolEvent::ToolEnd {
+                    session_id: session_id.to_string(),
+                    tool_id,
+                    tool_name,
+                    output,
+                    success: status == "completed",
+                    duration_ms: tool.map(|t| t.started.elapsed().as_millis() as u64),
+                }]
+            }
+            _ => vec![ProtocolEvent::ToolUpdate {
+                session_id: session_id.to_string(),
+                tool_name: self.tool_title(&tool_id).unwrap_or("tool").to_string(),
+                tool_id,
+                output,
+            }],
+        }
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn plan_entry(entry: &Value) -> PlanEntry {
+    PlanEntry {
+        content: str_field(entry, "content"),
+        priority: str_field(entry, "priority"),
+        status: str_field(entry, "status"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Permission requests
+// ---------------------------------------------------------------------------
+
+/// A `session/request_permission` request waiting for the user's answer.
+#[derive(Debug, Clone)]
+pub struct PendingPermission {
+    pub rpc_id: Value,
+    pub options: Vec<Value>,
+}
+
+/// Pick the option matching the user's answer, preferring the one-off kinds
+/// so approving a single call never grants blanket access.
+pub fn select_permission_option(options: &[Value], approved: bool) -> Option<String> {
+    let preferred: &[&str] = if approved {
+        &["allow_once", "allow_always"]
+    } else {
+        &["reject_once", "reject_always"]
+    };
+    preferred.iter().find_map(|kind| {
+        options
+            .iter()
+            .find(|option| option.get("kind").and_then(|k| k.as_str()) == Some(kind))
+            .and_then(|option| option.get("optionId").and_then(|id| id.as_str()))
+            .map(String::from)
+    })
+}
+
+/// Result of `session/request_permission` for a chosen option, or the
+/// cancelled outcome when there is none.
+pub fn permission_outcome(option_id: Option<String>) -> Value {
+    match option_id {
+        Some(option_id) => json!({ "outcome": { "outcome": "selected", "optionId": option_id } }),
+        None => json!({ "outcome": { "outcome": "cancelled" } }),
+    }
+}
+
+/// Turn a permission request into the event shown to the user, keyed by the
+/// JSON-RPC id so the answer can be routed back.
+pub fn permission_event(session_id: &str, request_id: &str, params: &Value, mapper: &SessionUpdateMapper) -> ProtocolEvent {
+    let tool_call = params.get("toolCall").unwrap_or(&Value::Null);
+    let tool_id = str_field(tool_call, "toolCallId");
+    let tool_name = tool_call
+        .get("title")
+        .and_then(|t| t.as_str())
+        .or_else(|| mapper.tool_title(&tool_id))
+        .unwrap_or("tool")
+        .to_string();
+    let description = match tool_call.get("rawInput") {
+        Some(input) if !input.is_null() => format!("{} {}", tool_name, input),
+        _ => tool_name.clone(),
+    };
+    ProtocolEvent::PermissionRequest {
+        session_id: session_id.to_string(),
+        request_id: request_id.to_string(),
+        tool_name,
+        description,
+        target: Some(tool_call_target(tool_call, mapper.tool_target(&tool_id))),
+        tool_id: (!tool_id.is_empty()).then_some(tool_id),
+    }
+}
+
+/// What a tool call would touch: its kind, the command it runs and the
+/// paths in its locations and raw input. Permission requests often only
+/// carry the fields that changed, so the rest comes from `known`.
+pub fn tool_call_target(tool_call: &Value, known: Option<&PermissionTarget>) -> PermissionTarget {
+    let input = tool_call.get("rawInput").unwrap_or(&Value::Null);
+    let command = match input.get("command") {
+        Some(Value::String(command)) => Some(command.clone()),
+        Some(Value::Array(parts)) => Some(
+            parts
+                .iter()
+                .filter_map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        _ => None,
+    };
+    let mut paths: Vec<String> = tool_call
+        .get("locations")
+        .and_then(|l| l.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|location| location.get("path").and_then(|p| p.as_str()))
+        .map(String::from)
+        .collect();
+    for key in ["path", "file_path", "filePath", "abs_path"] {
+        if let Some(path) = input.get(key).and_then(|p| p.as_str()) {
+            if !paths.iter().any(|p| p == path) {
+                paths.push(path.to_string());
+            }
+        }
+    }
+
+    PermissionTarget {
+        tool_kind: tool_call
+            .get("kind")
+            .and_then(|k| k.as_str())
+            .map(acp_tool_kind)
+            .or_else(|| known.map(|t| t.tool_kind.clone()))
+            .unwrap_or(ToolKind::Other),
+        command: command.or_else(|| known.and_then(|t| t.command.clone())),
+        paths: if paths.is_empty() {
+            known.map(|t| t.paths.clone()).unwrap_or_default()
+        } else {
+            paths
+        },
+    }
+}
+
+// ---------------------------------------------------------------------------
+// AcpConnection -- request/response bookkeeping over the child's stdin
+// ---------------------------------------------------------------------------
+
+type ResponseSender = oneshot::Sender<Result<Value, AcpRpcError>>;
+
+/// The client side of a JSON-RPC connection to an ACP agent.
+///
+/// Requests are written to the agent's stdin and their responses are routed
+/// back by the stdout reader through [`AcpConnection::resolve`].
+pub struct AcpConnection {
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+    next_id: AtomicU64,
+    pending: std::sync::Mutex<HashMap<u64, ResponseSender>>,
+    permissions: std::sync::Mutex<HashMap<String, PendingPermission>>,
+}
+
+impl AcpConnection {
+    pub fn new(stdin: Arc<Mutex<Option<ChildStdin>>>) -> Self {
+        Self {
+            stdin,
+            next_id: AtomicU64::new(0),
+            pending: std::sync::Mutex::new(HashMap::new()),
+            permissions: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn write(&self, message: Value) -> Result<(), CommanderError> {
+        let mut line = serde_json::to_string(&message).map_err(|e| {
+            CommanderError::protocol("write_failed", None, format!("serialize failed: {}", e))
+        })?;
+        line.push('\n');
+
+        let mut guard = self.stdin.lock().await;
+        let writer = guard.as_mut().ok_or_else(|| {
+            CommanderError::protocol("write_failed", None, "ACP process stdin not available")
+        })?;
+        writer
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| CommanderError::protocol("write_failed", None, format!("write failed: {}", e)))?;
+        writer
+            .flush()
+            .await
+            .map_err(|e| CommanderError::protocol("write_failed", None, format!("flush failed: {}", e)))
+    }
+
+    /// Send a request and wait for its result, optionally bounded by `timeout`.
+    pub async fn request(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Option<Duration>,
+    ) -> Result<Value, CommanderError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id, tx);
+        }
+
+        let sent = self
+            .write(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await;
+        if let Err(e) = sent {
+            self.forget(id);
+            return Err(e);
+        }
+
+        let response = match timeout {
+            Some(limit) => match tokio::time::timeout(limit, rx).await {
+                Ok(response) => response,
+                Err(_) => {
+                    self.forget(id);
+                    return Err(CommanderError::protocol(
+                        "timeout",
+                        None,
+                        format!("{} timed out after {}s", method, limit.as_secs()),
+                    ));
+                }
+            },
+            None => rx.await,
+        };
+        match response {
+            Ok(result) => result.map_err(CommanderError::from),
+            Err(_) => Err(CommanderError::protocol(
+                "process_died",
+                None,
+                format!("agent exited before answering {}", method),
+            )),
+        }
+    }
+
+    pub async fn notify(&self, method: &str, params: Value) -> Result<(), CommanderError> {
+        self.write(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await
+    }
+
+    /// Answer a request the agent sent us.
+    pub async fn respond(&self, id: Value, result: Value) -> Result<(), CommanderError> {
+        self.write(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+            .await
+    }
+
+    pub async fn respond_error(&self, id: Value, code: i64, message: &str) -> Result<(), CommanderError> {
+        self.write(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }))
+        .await
+    }
+
+    /// Hand a response to the request waiting for it. Returns false for
+    /// unknown ids.
+    pub fn resolve(&self, id: u64, result: Result<Value, AcpRpcError>) -> bool {
+        let sender = self.pending.lock().ok().and_then(|mut p| p.remove(&id));
+        match sender {
+            Some(tx) => tx.send(result).is_ok(),
+            None => false,
+        }
+    }
+
+    fn forget(&self, id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+
+    /// Drop every outstanding request, waking their callers with an error.
+    pub fn fail_pending(&self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+    }
+
+    /// Remember a permission request until the user answers it.
+    pub fn hold_permission(&self, request_id: String, permission: PendingPermission) {
+        if let Ok(mut permissions) = self.permissions.lock() {
+            permissions.insert(request_id, permission);
+        }
+    }
+
+    pub fn take_permission(&self, request_id: &str) -> Option<PendingPermission> {
+        self.permissions.lock().ok().and_then(|mut p| p.remove(request_id))
+    }
+
+    /// Answer every outstanding permission request with `cancelled`, as the
+    /// protocol requires once the turn is cancelled.
+    pub async fn cancel_permissions(&self) {
+        let held: Vec<PendingPermission> = match self.permissions.lock() {
+            Ok(mut permissions) => permissions.drain().map(|(_, p)| p).collect(),
+            Err(_) => Vec::new(),
+        };
+        for permission in held {
+            let _ = self.respond(permission.rpc_id, permission_outcome(None)).await;
+        }
+    }
+}
@@ -1,22 +1,34 @@
 pub mod pty_executor;
 pub mod acp_executor;
+pub mod acp_host;
+pub mod acp_jsonrpc;
 pub mod rpc_executor;
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
 use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, ChildStderr};
 use crate::error::CommanderError;
 use crate::models::ai_agent::AgentSettings;
-use crate::models::protocol::ProtocolMode;
+use crate::models::protocol::{ProtocolError, ProtocolEvent, ProtocolMode};
+use crate::services::agent_diagnostics_service;
 use crate::services::agent_status_service::ProtocolCache;
+use crate::services::autohand::rpc_client::{write_headless_config_with_mode, HeadlessConfig};
+use crate::services::event_sink::SharedEventSink;
 
 use self::pty_executor::PtyExecutor;
 use self::acp_executor::AcpExecutor;
+use self::acp_jsonrpc::AcpDialect;
 use self::rpc_executor::RpcExecutor;
 
 #[async_trait]
 pub trait AgentExecutor: Send + Sync {
     async fn execute(
         &mut self,
-        app: &tauri::AppHandle,
+        sink: &SharedEventSink,
         session_id: &str,
         agent: &str,
         message: &str,
@@ -32,17 +44,280 @@ pub trait AgentExecutor: Send + Sync {
     fn is_alive(&self) -> bool;
 
     fn protocol(&self) -> Option<ProtocolMode>;
+
+    /// Whether the agent process outlives a turn, so follow-ups can go
+    /// through `send_prompt` instead of a fresh spawn.
+    fn supports_multi_turn(&self) -> bool {
+        false
+    }
+
+    /// Whether a prompt turn is still running. Single-shot executors are
+    /// busy until their process exits.
+    fn is_busy(&self) -> bool {
+        self.is_alive()
+    }
+
+    /// Start another turn on the running process, streaming it under
+    /// `session_id`.
+    async fn send_prompt(
+        &mut self,
+        _sink: &SharedEventSink,
+        _session_id: &str,
+        _message: &str,
+    ) -> Result<(), CommanderError> {
+        Err(CommanderError::protocol(
+            "unsupported",
+            None,
+            "executor does not keep its process between turns",
+        ))
+    }
+
+    /// Stop the current turn but keep the process for the next one.
+    async fn interrupt(&self) -> Result<(), CommanderError> {
+        self.abort().await
+    }
+
+    /// End the process once no more turns are coming.
+    async fn shutdown(&self) -> Result<(), CommanderError> {
+        self.abort().await
+    }
+
+    /// Set when the agent process ended on its own in a way that counts as
+    /// a crash. Stops the caller asked for never do.
+    fn exit_error(&self) -> Option<ProtocolError> {
+        None
+    }
+
+    /// The agent's own id for the conversation, to resume it after a restart.
+    fn agent_session_id(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Restarts tried for an agent that died during a turn.
+pub const MAX_RESTARTS: u32 = 3;
+
+/// Backoff before restart `attempt` (1-based): 1s, 2s, 4s, … capped at 30s.
+pub fn restart_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1u64 << attempt.saturating_sub(1).min(5)).min(Duration::from_secs(30))
+}
+
+/// Exit code as a shell reports it: death by signal `n` is `128 + n`.
+pub fn exit_code(status: &std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(-1)
+}
+
+/// For an autohand run, a config with the API keys its own config files
+/// only keep as secret store handles, added to `args` as `--config`. Other
+/// agents get `None`.
+pub fn autohand_config(
+    agent: &str,
+    working_dir: &str,
+    args: &mut Vec<String>,
+) -> Result<Option<HeadlessConfig>, CommanderError> {
+    let name = std::path::Path::new(agent)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if name != "autohand" {
+        return Ok(None);
+    }
+    let config = write_headless_config_with_mode(working_dir, None)?;
+    args.push("--config".to_string());
+    args.push(config.path().to_string_lossy().to_string());
+    Ok(Some(config))
+}
+
+/// How long a process group gets to exit after SIGTERM before it is killed.
+pub const TERMINATE_GRACE: Duration = Duration::from_secs(3);
+
+/// Start the child as the leader of a new process group, so stopping it
+/// also stops everything it spawned.
+pub fn own_process_group(command: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+}
+
+#[cfg(unix)]
+fn signal_group(pgid: u32, signal: libc::c_int) -> bool {
+    // SAFETY: killpg only sends a signal; a stale group id fails with ESRCH.
+    unsafe { libc::killpg(pgid as libc::pid_t, signal) == 0 }
+}
+
+#[cfg(unix)]
+async fn wait_for_group(pgid: u32, deadline: tokio::time::Instant) {
+    while tokio::time::Instant::now() < deadline && signal_group(pgid, 0) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Stop a child along with its process group: SIGTERM, up to `grace` to
+/// exit, then SIGKILL for whatever is left. A child without a group of its
+/// own is simply killed.
+pub async fn terminate_child(child: &mut Child, grace: Duration) {
+    #[cfg(unix)]
+    if let Some(pgid) = child.id() {
+        if signal_group(pgid, libc::SIGTERM) {
+            let deadline = tokio::time::Instant::now() + grace;
+            let _ = tokio::time::timeout_at(deadline, child.wait()).await;
+            wait_for_group(pgid, deadline).await;
+            signal_group(pgid, libc::SIGKILL);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = grace;
+    let _ = child.kill().await;
+}
+
+/// [`terminate_child`] for a process group whose leader is waited on
+/// elsewhere, such as a PTY child.
+pub async fn terminate_process_group(pgid: u32, grace: Duration) {
+    #[cfg(unix)]
+    if signal_group(pgid, libc::SIGTERM) {
+        wait_for_group(pgid, tokio::time::Instant::now() + grace).await;
+        signal_group(pgid, libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = (pgid, grace);
+}
+
+/// How a protocol agent's process ended, shared between its executor and
+/// the task reading its stdout.
+#[derive(Default)]
+pub struct ExitWatch {
+    stopping: AtomicBool,
+    died: std::sync::Mutex<Option<ProtocolError>>,
+}
+
+impl ExitWatch {
+    /// Forget the previous process before spawning a new one.
+    pub fn reset(&self) {
+        self.stopping.store(false, Ordering::SeqCst);
+        if let Ok(mut died) = self.died.lock() {
+            *died = None;
+        }
+    }
+
+    /// The caller is ending the process; its exit is not a crash.
+    pub fn stopping(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+    }
+
+    /// Record the exit once stdout has closed. A non-zero exit is a crash,
+    /// and so is any exit while a turn was running on a process meant to
+    /// outlive it.
+    pub async fn record(&self, child: &tokio::sync::Mutex<Option<Child>>, during_turn: bool) {
+        if self.stopping.load(Ordering::SeqCst) {
+            return;
+        }
+        let code = {
+            let mut guard = child.lock().await;
+            let Some(child) = guard.as_mut() else {
+                return;
+            };
+            match tokio::time::timeout(Duration::from_secs(2), child.wait()).await {
+                Ok(Ok(status)) => exit_code(&status),
+                // Stdout closed but the process lingers: it is of no further use.
+                _ => {
+                    let _ = child.start_kill();
+                    -1
+                }
+            }
+        };
+        if self.stopping.load(Ordering::SeqCst) || (code == 0 && !during_turn) {
+            return;
+        }
+        if let Ok(mut died) = self.died.lock() {
+            *died = Some(ProtocolError::ProcessDied(code));
+        }
+    }
+
+    pub fn died(&self) -> Option<ProtocolError> {
+        self.died.lock().ok().and_then(|died| died.clone())
+    }
+}
+
+/// Where the events of a protocol executor's running turn go. A long-lived
+/// agent process serves several turns, each streaming under its own
+/// session id, so its stdout reader looks the target up per message.
+#[derive(Clone)]
+pub struct TurnTarget {
+    pub sink: SharedEventSink,
+    pub session_id: String,
+}
+
+pub type SharedTurnTarget = Arc<std::sync::Mutex<TurnTarget>>;
+
+impl TurnTarget {
+    pub fn shared(sink: &SharedEventSink, session_id: &str) -> SharedTurnTarget {
+        Arc::new(std::sync::Mutex::new(TurnTarget {
+            sink: Arc::clone(sink),
+            session_id: session_id.to_string(),
+        }))
+    }
+
+    pub fn current(turn: &SharedTurnTarget) -> Option<TurnTarget> {
+        turn.lock().ok().map(|target| target.clone())
+    }
+
+    pub fn retarget(turn: &SharedTurnTarget, sink: &SharedEventSink, session_id: &str) {
+        if let Ok(mut target) = turn.lock() {
+            target.sink = Arc::clone(sink);
+            target.session_id = session_id.to_string();
+        }
+    }
+}
+
+/// Drain an agent's stderr into the diagnostics of the turn it is serving,
+/// so a full pipe never stalls the agent. Recognised failures are reported
+/// on the turn's sink as they show up.
+pub fn drain_stderr(stderr: ChildStderr, turn: SharedTurnTarget) {
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(stderr);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let Some(TurnTarget { sink, session_id }) = TurnTarget::current(&turn) else {
+                continue;
+            };
+            if let Some(failure) = agent_diagnostics_service::record_stderr_line(&session_id, &line) {
+                sink.emit_event(ProtocolEvent::Error {
+                    session_id,
+                    message: line.trim().to_string(),
+                    failure: Some(failure),
+                });
+            }
+        }
+    });
 }
 
 pub struct ExecutorFactory;
 
 impl ExecutorFactory {
-    pub fn create(agent: &str, protocol_cache: &ProtocolCache) -> Box<dyn AgentExecutor> {
+    /// Build the executor for the protocol cached for `agent`; an ACP
+    /// executor speaks `dialect`.
+    pub fn create(
+        agent: &str,
+        protocol_cache: &ProtocolCache,
+        dialect: AcpDialect,
+    ) -> Box<dyn AgentExecutor> {
         let entry = protocol_cache.get(agent);
         match entry.and_then(|e| e.protocol) {
             Some(ProtocolMode::Acp) => {
                 let flag = entry.and_then(|e| e.flag_variant.clone());
-                Box::new(AcpExecutor::new(flag))
+                Box::new(AcpExecutor::new(flag).with_dialect(dialect))
             }
             Some(ProtocolMode::Rpc) => {
                 let flag = entry.and_then(|e| e.flag_variant.clone());
@@ -1,7 +1,6 @@
 use std::process::Stdio;
 use std::sync::Arc;
 use async_trait::async_trait;
-use tauri::Emitter;
 use tokio::io::{AsyncReadExt, AsyncBufReadExt, BufReader};
 use tokio::process::Child;
 use tokio::sync::Mutex;
@@ -12,8 +11,15 @@ use crate::commands::cli_commands::{
 use crate::error::CommanderError;
 use crate::models::ai_agent::{AgentSettings, StreamChunk};
 use crate::models::protocol::ProtocolMode;
-use crate::services::cli_output_service::{sanitize_cli_output_line, CodexStreamAccumulator};
-use super::AgentExecutor;
+use crate::services::cli_output_service::{
+    sanitize_cli_output_line, stream_parser_for, CodexStreamAccumulator,
+};
+use crate::services::event_sink::SharedEventSink;
+use crate::services::env_profile_service::AgentEnvironment;
+use crate::services::sandbox_service::SandboxProfile;
+use crate::services::session_limits_service::register_process_group;
+use super::acp_host::take_utf8;
+use super::{own_process_group, AgentExecutor};
 
 pub struct PtyExecutor {
     child: Arc<Mutex<Option<Child>>>,
@@ -31,7 +37,7 @@ impl PtyExecutor {
 impl AgentExecutor for PtyExecutor {
     async fn execute(
         &mut self,
-        app: &tauri::AppHandle,
+        sink: &SharedEventSink,
         session_id: &str,
         agent: &str,
         message: &str,
@@ -39,31 +45,39 @@ impl AgentExecutor for PtyExecutor {
         settings: &AgentSettings,
         resume_session_id: Option<&str>,
     ) -> Result<(), CommanderError> {
-        let app = app.clone();
+        let sink = Arc::clone(sink);
         let session_id = session_id.to_string();
         let agent = agent.to_string();
         let message = message.to_string();
         let working_dir = working_dir.to_string();
-        let _settings = settings.clone();
+        let settings = settings.clone();
         let child_handle = self.child.clone();
 
-        // Build args — build_agent_command_args loads its own settings internally
         let command_args = build_agent_command_args(
             &agent,
             &message,
-            &app,
+            &settings,
             None,  // execution_mode — not threaded through AgentExecutor yet
             false, // dangerous_bypass
             None,  // permission_mode
             resume_session_id.map(|s| s.to_string()),  // resume_session_id
-        )
-        .await;
+        );
 
         // Resolve absolute path of the executable to avoid PATH issues in GUI contexts
         let resolved_prog = which::which(&agent)
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_else(|_| agent.clone());
 
+        let sandbox = SandboxProfile::for_run(&settings, &agent, &working_dir);
+        let (resolved_prog, command_args) = match &sandbox {
+            Some(sandbox) => sandbox
+                .wrap(&resolved_prog, &command_args)
+                .map_err(|e| CommanderError::command(&agent, None, e))?,
+            None => (resolved_prog, command_args),
+        };
+        let environment = AgentEnvironment::for_run(&settings, &working_dir)
+            .map_err(|e| CommanderError::command(&agent, None, e))?;
+
         let working_dir_opt = if working_dir.is_empty() {
             None
         } else {
@@ -84,27 +98,25 @@ impl AgentExecutor for PtyExecutor {
 
         if prefer_pty {
             if let Err(e) = try_spawn_with_pty(
-                app.clone(),
+                Arc::clone(&sink),
                 session_id.clone(),
                 &agent,
                 &resolved_prog,
                 &command_args,
                 working_dir_opt.clone(),
+                environment.clone(),
             )
             .await
             {
                 // Inform about PTY fallback
-                let _ = app.emit(
-                    "cli-stream",
-                    StreamChunk {
-                        session_id: session_id.clone(),
-                        content: format!(
-                            "ℹ️ PTY unavailable ({}). Falling back to pipe streaming...\n",
-                            e
-                        ),
-                        finished: false,
-                    },
-                );
+                sink.emit_chunk(StreamChunk {
+                    session_id: session_id.clone(),
+                    content: format!(
+                        "ℹ️ PTY unavailable ({}). Falling back to pipe streaming...\n",
+                        e
+                    ),
+                    finished: false,
+                });
             } else {
                 return Ok(()); // PTY path handled end-to-end
             }
@@ -115,44 +127,51 @@ impl AgentExecutor for PtyExecutor {
         cmd.args(&command_args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+        if let Some(environment) = &environment {
+            environment.apply(&mut cmd);
+        }
 
         if let Some(dir) = &working_dir_opt {
-            println!("📁 PIPE: Setting working directory to: {}", dir);
+            eprintln!("📁 PIPE: Setting working directory to: {}", dir);
             cmd.current_dir(dir);
         } else {
-            println!("⚠️  PIPE: No working directory - using system default");
+            eprintln!("⚠️  PIPE: No working directory - using system default");
         }
+        own_process_group(&mut cmd);
 
         match cmd.spawn() {
             Ok(mut child_process) => {
+                let _group = child_process
+                    .id()
+                    .map(|pid| register_process_group(&session_id, pid));
+                let mut readers = Vec::new();
+
                 // Stream stdout
                 if let Some(stdout) = child_process.stdout.take() {
-                    let app_for_stdout = app.clone();
+                    let sink_for_stdout = Arc::clone(&sink);
                     let session_id_for_stdout = session_id.clone();
                     let agent_for_stdout = agent.clone();
-                    tokio::spawn(async move {
-                        if agent_for_stdout.eq_ignore_ascii_case("codex") {
+                    readers.push(tokio::spawn(async move {
+                        if let Some(mut parser) =
+                            stream_parser_for(&agent_for_stdout, &session_id_for_stdout)
+                        {
+                            // Codex separates records with bare carriage
+                            // returns, so split on those as well as newlines.
+                            // A character split across reads waits in
+                            // `pending` for the rest of its bytes.
                             let mut reader = BufReader::new(stdout);
                             let mut buf = vec![0u8; 4096];
+                            let mut pending: Vec<u8> = Vec::new();
                             let mut accumulator = CodexStreamAccumulator::new();
 
                             loop {
                                 match reader.read(&mut buf).await {
                                     Ok(0) => break,
                                     Ok(n) => {
-                                        let text = String::from_utf8_lossy(&buf[..n]);
-                                        for segment in accumulator.push_chunk(text.as_ref()) {
-                                            if let Some(filtered) = sanitize_cli_output_line(
-                                                &agent_for_stdout,
-                                                &segment,
-                                            ) {
-                                                let chunk = StreamChunk {
-                                                    session_id: session_id_for_stdout.clone(),
-                                                    content: filtered,
-                                                    finished: false,
-                                                };
-                                                let _ = app_for_stdout.emit("cli-stream", chunk);
-                                            }
+                                        pending.extend_from_slice(&buf[..n]);
+                                        let text = take_utf8(&mut pending);
+                                        for line in accumulator.push_chunk(&text) {
+                                            parser.emit_line(&sink_for_stdout, &line);
                                         }
                                     }
                                     Err(e) => {
@@ -161,23 +180,14 @@ impl AgentExecutor for PtyExecutor {
                                             content: format!("ERROR: {}\n", e),
                                             finished: false,
                                         };
-                                        let _ = app_for_stdout.emit("cli-stream", chunk);
+                                        sink_for_stdout.emit_chunk(chunk);
                                         break;
                                     }
                                 }
                             }
 
                             if let Some(remaining) = accumulator.flush() {
-                                if let Some(filtered) =
-                                    sanitize_cli_output_line(&agent_for_stdout, &remaining)
-                                {
-                                    let chunk = StreamChunk {
-                                        session_id: session_id_for_stdout,
-                                        content: filtered,
-                                        finished: false,
-                                    };
-                                    let _ = app_for_stdout.emit("cli-stream", chunk);
-                                }
+                                parser.emit_line(&sink_for_stdout, &remaining);
                             }
                         } else {
                             let reader = BufReader::new(stdout);
@@ -192,30 +202,32 @@ impl AgentExecutor for PtyExecutor {
                                         content: filtered + "\n",
                                         finished: false,
                                     };
-                                    let _ = app_for_stdout.emit("cli-stream", chunk);
+                                    sink_for_stdout.emit_chunk(chunk);
                                 }
                             }
                         }
-                    });
+                    }));
                 }
 
                 // Stream stderr
                 if let Some(stderr) = child_process.stderr.take() {
-                    let app_for_stderr = app.clone();
+                    let sink_for_stderr = Arc::clone(&sink);
                     let session_id_for_stderr = session_id.clone();
                     let agent_for_stderr = agent.clone();
-                    tokio::spawn(async move {
+                    readers.push(tokio::spawn(async move {
                         if agent_for_stderr.eq_ignore_ascii_case("codex") {
                             let mut reader = BufReader::new(stderr);
                             let mut buf = vec![0u8; 4096];
+                            let mut pending: Vec<u8> = Vec::new();
                             let mut accumulator = CodexStreamAccumulator::new();
 
                             loop {
                                 match reader.read(&mut buf).await {
                                     Ok(0) => break,
                                     Ok(n) => {
-                                        let text = String::from_utf8_lossy(&buf[..n]);
-                                        for segment in accumulator.push_chunk(text.as_ref()) {
+                                        pending.extend_from_slice(&buf[..n]);
+                                        let text = take_utf8(&mut pending);
+                                        for segment in accumulator.push_chunk(&text) {
                                             if let Some(filtered) = sanitize_cli_output_line(
                                                 &agent_for_stderr,
                                                 &segment,
@@ -225,7 +237,7 @@ impl AgentExecutor for PtyExecutor {
                                                     content: format!("ERROR: {}\n", filtered),
                                                     finished: false,
                                                 };
-                                                let _ = app_for_stderr.emit("cli-stream", chunk);
+                                                sink_for_stderr.emit_chunk(chunk);
                                             }
                                         }
                                     }
@@ -235,7 +247,7 @@ impl AgentExecutor for PtyExecutor {
                                             content: format!("ERROR: {}\n", e),
                                             finished: false,
                                         };
-                                        let _ = app_for_stderr.emit("cli-stream", chunk);
+                                        sink_for_stderr.emit_chunk(chunk);
                                         break;
                                     }
                                 }
@@ -250,7 +262,7 @@ impl AgentExecutor for PtyExecutor {
                                         content: format!("ERROR: {}\n", filtered),
                                         finished: false,
                                     };
-                                    let _ = app_for_stderr.emit("cli-stream", chunk);
+                                    sink_for_stderr.emit_chunk(chunk);
                                 }
                             }
                         } else {
@@ -266,11 +278,11 @@ impl AgentExecutor for PtyExecutor {
                                         content: format!("ERROR: {}\n", filtered),
                                         finished: false,
                                     };
-                                    let _ = app_for_stderr.emit("cli-stream", chunk);
+                                    sink_for_stderr.emit_chunk(chunk);
                                 }
                             }
                         }
-                    });
+                    }));
                 }
 
                 // Store child for abort capability
@@ -279,31 +291,27 @@ impl AgentExecutor for PtyExecutor {
                     *guard = None; // pipe fallback child is consumed by wait below
                 }
 
-                // Wait for completion
-                match child_process.wait().await {
-                    Ok(status) => {
-                        let final_chunk = StreamChunk {
-                            session_id: session_id.clone(),
-                            content: if status.success() {
-                                String::new()
-                            } else {
-                                format!(
-                                    "\n❌ Command failed with exit code: {}\n",
-                                    status.code().unwrap_or(-1)
-                                )
-                            },
-                            finished: true,
-                        };
-                        let _ = app.emit("cli-stream", final_chunk);
-                    }
-                    Err(e) => {
-                        let error_chunk = StreamChunk {
+                // Wait for completion, then drain the readers so the final
+                // chunk is always the last one emitted
+                let exit = child_process.wait().await;
+                for reader in readers {
+                    let _ = reader.await;
+                }
+
+                match exit {
+                    Ok(status) if status.success() => {
+                        sink.emit_chunk(StreamChunk {
                             session_id: session_id.clone(),
-                            content: format!("❌ Process error: {}\n", e),
+                            content: String::new(),
                             finished: true,
-                        };
-                        let _ = app.emit("cli-stream", error_chunk);
+                        });
                     }
+                    Ok(status) => sink.emit_failure(
+                        &session_id,
+                        format!("Command failed with exit code: {}", status.code().unwrap_or(-1)),
+                        true,
+                    ),
+                    Err(e) => sink.emit_failure(&session_id, format!("Process error: {}", e), true),
                 }
             }
             Err(e) => {
@@ -313,12 +321,7 @@ impl AgentExecutor for PtyExecutor {
                     format!("Failed to start {}: {}", agent, e)
                 };
 
-                let error_chunk = StreamChunk {
-                    session_id: session_id.clone(),
-                    content: format!("❌ {}\n", error_message),
-                    finished: true,
-                };
-                let _ = app.emit("cli-stream", error_chunk);
+                sink.emit_failure(&session_id, error_message, true);
             }
         }
 
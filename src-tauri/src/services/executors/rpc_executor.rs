@@ -5,11 +5,20 @@ use tokio::process::{Child, ChildStdin};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use tauri::Emitter;
 use crate::error::CommanderError;
 use crate::models::ai_agent::AgentSettings;
-use crate::models::protocol::{ProtocolMode, ProtocolEvent, SessionEventKind};
-use super::AgentExecutor;
+use crate::models::protocol::{
+    PermissionTarget, ProtocolError, ProtocolMode, ProtocolEvent, SessionEventKind,
+};
+use crate::services::autohand::types::{rpc_methods, rpc_notifications};
+use crate::services::autohand::rpc_client::HeadlessConfig;
+use crate::services::event_sink::SharedEventSink;
+use crate::services::env_profile_service::AgentEnvironment;
+use crate::services::sandbox_service::SandboxProfile;
+use super::{
+    autohand_config, drain_stderr, own_process_group, terminate_child, AgentExecutor, ExitWatch,
+    SharedTurnTarget, TurnTarget, TERMINATE_GRACE,
+};
 use super::acp_executor::resolve_tool_kind;
 
 // ---------------------------------------------------------------------------
@@ -139,6 +148,15 @@ pub struct RpcExecutor {
     stdin: Arc<Mutex<Option<ChildStdin>>>,
     child: Arc<Mutex<Option<Child>>>,
     alive: Arc<AtomicBool>,
+    busy: Arc<AtomicBool>,
+    /// Id of the `autohand.prompt` request of the running turn.
+    prompt_id: Arc<std::sync::Mutex<Option<String>>>,
+    /// The agent's own session id, as reported by `autohand.stateChange`.
+    agent_session: Arc<std::sync::Mutex<Option<String>>>,
+    turn: Option<SharedTurnTarget>,
+    exit: Arc<ExitWatch>,
+    /// Config file with resolved API keys the process was started with.
+    headless_config: Option<HeadlessConfig>,
 }
 
 impl RpcExecutor {
@@ -148,20 +166,60 @@ impl RpcExecutor {
             stdin: Arc::new(Mutex::new(None)),
             child: Arc::new(Mutex::new(None)),
             alive: Arc::new(AtomicBool::new(false)),
+            busy: Arc::new(AtomicBool::new(false)),
+            prompt_id: Arc::new(std::sync::Mutex::new(None)),
+            agent_session: Arc::new(std::sync::Mutex::new(None)),
+            turn: None,
+            exit: Arc::new(ExitWatch::default()),
+            headless_config: None,
         }
     }
+
+    /// Point the reader at the turn's sink and send `autohand.prompt`. The
+    /// turn ends with the prompt's response or an `autohand.agentEnd`.
+    async fn start_turn(
+        &self,
+        sink: &SharedEventSink,
+        session_id: &str,
+        message: &str,
+    ) -> Result<(), CommanderError> {
+        let turn = self.turn.as_ref().ok_or_else(|| {
+            CommanderError::protocol("unsupported", None, "RPC process is not running")
+        })?;
+        if !self.alive.load(Ordering::SeqCst) {
+            return Err(CommanderError::protocol("process_died", None, "RPC agent is no longer running"));
+        }
+        if self.busy.swap(true, Ordering::SeqCst) {
+            return Err(CommanderError::protocol("busy", None, "a turn is already running"));
+        }
+        TurnTarget::retarget(turn, sink, session_id);
+
+        let prompt_req = build_rpc_request(
+            "autohand.prompt",
+            Some(build_prompt_params(message, None)),
+        );
+        if let Ok(mut prompt_id) = self.prompt_id.lock() {
+            *prompt_id = prompt_req.id.clone();
+        }
+        let prompt_line = serialize_rpc_to_line(&prompt_req);
+        let written = write_stdin_line(&self.stdin, &prompt_line).await;
+        if written.is_err() {
+            self.busy.store(false, Ordering::SeqCst);
+        }
+        written
+    }
 }
 
 #[async_trait]
 impl AgentExecutor for RpcExecutor {
     async fn execute(
         &mut self,
-        app: &tauri::AppHandle,
+        sink: &SharedEventSink,
         session_id: &str,
         agent: &str,
         message: &str,
         working_dir: &str,
-        _settings: &AgentSettings,
+        settings: &AgentSettings,
         resume_session_id: Option<&str>,
     ) -> Result<(), CommanderError> {
         // 1. Resolve agent binary path
@@ -188,13 +246,24 @@ impl AgentExecutor for RpcExecutor {
             args.push("--resume".to_string());
             args.push(session.to_string());
         }
+        self.headless_config = autohand_config(agent, working_dir, &mut args)?;
 
         // 3. Spawn child process
-        let mut child = tokio::process::Command::new(&agent_path)
-            .args(&args)
+        self.exit.reset();
+        let sandbox = SandboxProfile::for_run(settings, agent, working_dir);
+        let mut command = SandboxProfile::command(sandbox.as_ref(), &agent_path, &args)
+            .map_err(|e| CommanderError::command(agent, None, e))?;
+        if let Some(environment) = AgentEnvironment::for_run(settings, working_dir)
+            .map_err(|e| CommanderError::command(agent, None, e))?
+        {
+            environment.apply(&mut command);
+        }
+        command
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        own_process_group(&mut command);
+        let mut child = command
             .spawn()
             .map_err(|e| {
                 CommanderError::command(
@@ -211,6 +280,10 @@ impl AgentExecutor for RpcExecutor {
         let stdout = child.stdout.take().ok_or_else(|| {
             CommanderError::command(agent, None, "failed to capture stdout for RPC process")
         })?;
+        let turn = TurnTarget::shared(sink, session_id);
+        if let Some(stderr) = child.stderr.take() {
+            drain_stderr(stderr, Arc::clone(&turn));
+        }
 
         // 5. Store stdin and child
         *self.stdin.lock().await = Some(stdin);
@@ -220,27 +293,19 @@ impl AgentExecutor for RpcExecutor {
         self.alive.store(true, Ordering::SeqCst);
 
         // 7. Emit Connected event
-        let session_id_owned = session_id.to_string();
-        let _ = app.emit(
-            "protocol-event",
-            ProtocolEvent::SessionEvent {
-                session_id: session_id_owned.clone(),
-                event: SessionEventKind::Connected,
-            },
-        );
-
-        // 8. Send initial prompt via JSON-RPC
-        let prompt_req = build_rpc_request(
-            "autohand.prompt",
-            Some(build_prompt_params(message, None)),
-        );
-        let prompt_line = serialize_rpc_to_line(&prompt_req);
-        write_stdin_line(&self.stdin, &prompt_line).await?;
+        sink.emit_event(ProtocolEvent::SessionEvent {
+            session_id: session_id.to_string(),
+            event: SessionEventKind::Connected,
+        });
 
-        // 9. Read stdout line-by-line in a background task
-        let app_handle = app.clone();
+        // 8. Read stdout line-by-line in a background task
+        self.turn = Some(Arc::clone(&turn));
         let alive_flag = Arc::clone(&self.alive);
-        let session_id_task = session_id_owned.clone();
+        let busy = Arc::clone(&self.busy);
+        let prompt_id = Arc::clone(&self.prompt_id);
+        let agent_session = Arc::clone(&self.agent_session);
+        let child = Arc::clone(&self.child);
+        let exit = Arc::clone(&self.exit);
 
         tokio::spawn(async move {
             let reader = BufReader::new(stdout);
@@ -253,21 +318,46 @@ impl AgentExecutor for RpcExecutor {
                         if trimmed.is_empty() {
                             continue;
                         }
+                        let Some(TurnTarget { sink: sink_task, session_id: session_id_task }) =
+                            TurnTarget::current(&turn)
+                        else {
+                            break;
+                        };
                         match parse_rpc_line(&trimmed) {
+                            Ok(RpcMessage::Notification(req)) if req.method == rpc_notifications::AGENT_END => {
+                                busy.store(false, Ordering::SeqCst);
+                            }
                             Ok(RpcMessage::Notification(req)) => {
                                 let event =
                                     rpc_notification_to_protocol_event(&session_id_task, &req);
-                                let _ = app_handle.emit("protocol-event", event);
+                                if let ProtocolEvent::StateChange {
+                                    agent_session_id: Some(ref id),
+                                    ..
+                                } = event
+                                {
+                                    if let Ok(mut agent_session) = agent_session.lock() {
+                                        *agent_session = Some(id.clone());
+                                    }
+                                }
+                                sink_task.emit_event(event);
                             }
-                            Ok(RpcMessage::Response(_resp)) => {
-                                // Responses are acknowledged but not forwarded to frontend
+                            Ok(RpcMessage::Response(resp)) => {
+                                // Responses are not forwarded to the frontend; the
+                                // prompt's response only marks the end of the turn.
+                                let ends_turn = prompt_id
+                                    .lock()
+                                    .map(|id| id.is_some() && *id == resp.id)
+                                    .unwrap_or(false);
+                                if ends_turn {
+                                    busy.store(false, Ordering::SeqCst);
+                                }
                             }
                             Err(err) => {
-                                let _ = app_handle.emit(
-                                    "protocol-event",
+                                sink_task.emit_event(
                                     ProtocolEvent::Error {
-                                        session_id: session_id_task.clone(),
+                                        session_id: session_id_task,
                                         message: err,
+                                        failure: None,
                                     },
                                 );
                             }
@@ -283,18 +373,20 @@ impl AgentExecutor for RpcExecutor {
                 }
             }
 
-            // 10. Emit Disconnected event on EOF/exit
-            alive_flag.store(false, Ordering::SeqCst);
-            let _ = app_handle.emit(
-                "protocol-event",
-                ProtocolEvent::SessionEvent {
-                    session_id: session_id_task,
+            // 9. Emit Disconnected event on EOF/exit
+            exit.record(&child, busy.load(Ordering::SeqCst)).await;
+            if let Some(TurnTarget { sink, session_id }) = TurnTarget::current(&turn) {
+                sink.emit_event(ProtocolEvent::SessionEvent {
+                    session_id,
                     event: SessionEventKind::Disconnected,
-                },
-            );
+                });
+            }
+            busy.store(false, Ordering::SeqCst);
+            alive_flag.store(false, Ordering::SeqCst);
         });
 
-        Ok(())
+        // 10. Send the first prompt
+        self.start_turn(sink, session_id, message).await
     }
 
     async fn respond_permission(&self, request_id: &str, approved: bool) -> Result<(), CommanderError> {
@@ -307,6 +399,7 @@ impl AgentExecutor for RpcExecutor {
     }
 
     async fn abort(&self) -> Result<(), CommanderError> {
+        self.exit.stopping();
         // Send graceful shutdown request
         let req = build_rpc_request("autohand.shutdown", None);
         let line = serialize_rpc_to_line(&req);
@@ -317,7 +410,7 @@ impl AgentExecutor for RpcExecutor {
 
         let mut guard = self.child.lock().await;
         if let Some(ref mut child) = *guard {
-            let _ = child.kill().await;
+            terminate_child(child, TERMINATE_GRACE).await;
         }
         *guard = None;
         *self.stdin.lock().await = None;
@@ -332,6 +425,36 @@ impl AgentExecutor for RpcExecutor {
     fn protocol(&self) -> Option<ProtocolMode> {
         Some(ProtocolMode::Rpc)
     }
+
+    fn supports_multi_turn(&self) -> bool {
+        true
+    }
+
+    fn is_busy(&self) -> bool {
+        self.is_alive() && self.busy.load(Ordering::SeqCst)
+    }
+
+    async fn send_prompt(
+        &mut self,
+        sink: &SharedEventSink,
+        session_id: &str,
+        message: &str,
+    ) -> Result<(), CommanderError> {
+        self.start_turn(sink, session_id, message).await
+    }
+
+    async fn interrupt(&self) -> Result<(), CommanderError> {
+        let req = build_rpc_request(rpc_methods::ABORT, None);
+        write_stdin_line(&self.stdin, &serialize_rpc_to_line(&req)).await
+    }
+
+    fn exit_error(&self) -> Option<ProtocolError> {
+        self.exit.died()
+    }
+
+    fn agent_session_id(&self) -> Option<String> {
+        self.agent_session.lock().ok().and_then(|id| id.clone())
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -438,24 +561,43 @@ fn rpc_notification_to_protocol_event(session_id: &str, req: &JsonRpcRequest) ->
                 .and_then(|v| v.as_str())
                 .unwrap_or("")
                 .to_string();
+            let paths = ["file_path", "filePath"]
+                .iter()
+                .find_map(|key| params.get(*key).and_then(|v| v.as_str()))
+                .map(|path| vec![path.to_string()])
+                .unwrap_or_default();
+            let target = PermissionTarget {
+                tool_kind: resolve_tool_kind(&tool_name),
+                command: params.get("command").and_then(|v| v.as_str()).map(String::from),
+                paths,
+            };
             ProtocolEvent::PermissionRequest {
                 session_id: session_id.to_string(),
                 request_id,
                 tool_name,
                 description,
+                target: Some(target),
+                tool_id: params.get("tool_id").and_then(|v| v.as_str()).map(String::from),
             }
         }
-        "autohand.state_change" => {
+        rpc_notifications::STATE_CHANGE => {
             let status = params
                 .get("status")
                 .and_then(|v| v.as_str())
                 .unwrap_or("idle")
                 .to_string();
-            let context_percent = params.get("context_percent").and_then(|v| v.as_f64());
+            let context_percent = ["contextPercent", "context_percent"]
+                .iter()
+                .find_map(|key| params.get(*key).and_then(|v| v.as_f64()));
             ProtocolEvent::StateChange {
                 session_id: session_id.to_string(),
                 status,
                 context_percent,
+                usage: None,
+                agent_session_id: ["sessionId", "session_id"]
+                    .iter()
+                    .find_map(|key| params.get(*key).and_then(|v| v.as_str()))
+                    .map(String::from),
             }
         }
         "autohand.error" => {
@@ -467,11 +609,13 @@ fn rpc_notification_to_protocol_event(session_id: &str, req: &JsonRpcRequest) ->
             ProtocolEvent::Error {
                 session_id: session_id.to_string(),
                 message,
+                failure: None,
             }
         }
         _ => ProtocolEvent::Error {
             session_id: session_id.to_string(),
             message: format!("received unknown RPC notification: {}", req.method),
+            failure: None,
         },
     }
 }
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::models::protocol::{ProtocolEvent, RunOutcome};
+use crate::services::event_sink::{EventRecord, RecordedEvent};
+
+/// One agent's share of a fan-out, announced before the runs start so the
+/// frontend can attach to each session's stream.
+#[derive(Debug, Clone, Serialize)]
+pub struct FanOutRun {
+    pub agent: String,
+    pub session_id: String,
+    pub branch: String,
+    pub worktree_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FanOutStatus {
+    Completed,
+    Failed,
+    /// The run never started (worktree setup failed or it was cancelled
+    /// while queued).
+    NotRun,
+}
+
+/// What a single run did, read back from its transcript.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RunStats {
+    pub duration_ms: i64,
+    pub tool_calls: usize,
+    pub errors: usize,
+    pub failed: bool,
+}
+
+/// Files changed on a workspace branch, from `diff_workspace_vs_main`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DiffStats {
+    pub files_changed: usize,
+    pub added: usize,
+    pub modified: usize,
+    pub deleted: usize,
+    pub files: Vec<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FanOutResult {
+    pub agent: String,
+    pub session_id: String,
+    pub branch: String,
+    pub worktree_path: Option<String>,
+    pub status: FanOutStatus,
+    #[serde(flatten)]
+    pub stats: RunStats,
+    pub diff: DiffStats,
+    /// Whether the agent left changes that were committed to its branch.
+    pub committed: bool,
+    pub error: Option<String>,
+}
+
+/// Returned by `fan_out_prompt`: one result per agent, in the order requested.
+#[derive(Debug, Clone, Serialize)]
+pub struct FanOutComparison {
+    pub fan_out_id: String,
+    pub prompt: String,
+    pub results: Vec<FanOutResult>,
+}
+
+/// Worktree (and `workspace/` branch) name for one agent of a fan-out.
+pub fn fan_out_worktree_name(agent: &str, fan_out_id: &str) -> String {
+    format!("{}-{}", agent, fan_out_id)
+}
+
+/// Validate and de-duplicate the requested agents, keeping their order.
+pub fn normalize_fan_out_agents(agents: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for agent in agents {
+        let agent = agent.trim().to_lowercase();
+        if agent.is_empty() {
+            continue;
+        }
+        if !agent
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("Invalid agent name for fan-out: {}", agent));
+        }
+        if !normalized.contains(&agent) {
+            normalized.push(agent);
+        }
+    }
+    if normalized.is_empty() {
+        return Err("Select at least one agent to fan out to".to_string());
+    }
+    Ok(normalized)
+}
+
+/// Summarise a run from its transcript: wall-clock span, tool calls, errors and
+/// whether the run failed, by the last [`ProtocolEvent::outcome`].
+pub fn run_stats(records: &[EventRecord]) -> RunStats {
+    let mut stats = RunStats {
+        duration_ms: match (records.first(), records.last()) {
+            (Some(first), Some(last)) => (last.ts - first.ts).max(0),
+            _ => 0,
+        },
+        ..RunStats::default()
+    };
+
+    for record in records {
+        let Ok(RecordedEvent::Protocol(event)) = record.to_recorded() else {
+            continue;
+        };
+        match event {
+            ProtocolEvent::ToolStart { .. } => stats.tool_calls += 1,
+            ProtocolEvent::Error { .. } => stats.errors += 1,
+            _ => {}
+        }
+        if let Some(outcome) = event.outcome() {
+            stats.failed = outcome == RunOutcome::Failed;
+        }
+    }
+    stats
+}
+
+/// Count `diff_workspace_vs_main` rows by status letter.
+pub fn diff_stats(files: Vec<HashMap<String, String>>) -> DiffStats {
+    let mut stats = DiffStats {
+        files_changed: files.len(),
+        ..DiffStats::default()
+    };
+    for file in &files {
+        match file.get("status").and_then(|s| s.chars().next()) {
+            Some('A') => stats.added += 1,
+            Some('D') => stats.deleted += 1,
+            Some(_) => stats.modified += 1,
+            None => {}
+        }
+    }
+    stats.files = files;
+    stats
+}
+
+/// Commit whatever the agent left in its worktree so the branch can be
+/// diffed and merged. Commander's own `.commander/` data is left out.
+/// Returns `false` when there was nothing to commit.
+pub async fn commit_worktree_changes(worktree_path: &str, message: &str) -> Result<bool, String> {
+    let add = tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(worktree_path)
+        .args(["add", "-A", "--", ".", ":(exclude).commander"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git add: {}", e))?;
+    if !add.status.success() {
+        return Err(String::from_utf8_lossy(&add.stderr).to_string());
+    }
+
+    let staged = tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(worktree_path)
+        .args(["diff", "--cached", "--quiet"])
+        .status()
+        .await
+        .map_err(|e| format!("Failed to run git diff: {}", e))?;
+    if staged.success() {
+        return Ok(false);
+    }
+
+    let commit = tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(worktree_path)
+        .args(["commit", "-m", message])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git commit: {}", e))?;
+    if !commit.status.success() {
+        return Err(String::from_utf8_lossy(&commit.stderr).to_string());
+    }
+    Ok(true)
+}
@@ -0,0 +1,544 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use git2::{
+    BranchType, ConfigLevel, Delta, DiffFindOptions, ErrorCode, Oid, Repository, Sort, Tree,
+};
+use serde::Serialize;
+
+/// Environment variable that forces a backend: `cli` or `libgit2`. Anything
+/// else (or unset) uses libgit2 with the CLI as fallback.
+pub const GIT_BACKEND_ENV: &str = "COMMANDER_GIT_BACKEND";
+
+/// Prefixes of the errors that mean something asked for does not exist,
+/// which another backend would not find either.
+const UNKNOWN_REVISION: &str = "Unknown revision";
+const NOT_FOUND: &str = "Not found";
+
+/// git's well-known empty tree, used as the "parent" of root commits.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// One commit as shown in the history views.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitInfo {
+    pub hash: String,
+    pub parents: Vec<String>,
+    pub author: String,
+    /// Author date in `git log --date=iso` format.
+    pub date: String,
+    pub subject: String,
+    /// Decorations in `%D` form: `HEAD -> main`, `tag: v1`, `origin/main`.
+    pub refs: Vec<String>,
+}
+
+/// One file in a name-status diff. `status` is a single letter
+/// (A, M, D, R, C, T); renames and copies also carry the old path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    pub status: String,
+    pub path: String,
+    pub old_path: Option<String>,
+}
+
+impl FileChange {
+    /// The `{status, path[, old_path]}` row shape the frontend consumes.
+    pub fn to_row(&self) -> HashMap<String, String> {
+        let mut row = HashMap::new();
+        row.insert("status".to_string(), self.status.clone());
+        row.insert("path".to_string(), self.path.clone());
+        if let Some(old_path) = &self.old_path {
+            row.insert("old_path".to_string(), old_path.clone());
+        }
+        row
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigScope<'a> {
+    Global,
+    /// The repository containing this path.
+    Local(&'a str),
+}
+
+/// Read-side git operations used by the branch, history and diff views.
+///
+/// Calls are blocking; async callers should go through `spawn_blocking`.
+/// Paths are accepted for any directory inside a repository or worktree.
+pub trait GitBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Branch HEAD points at; `None` when detached.
+    fn current_branch(&self, repo: &str) -> Result<Option<String>, String>;
+
+    /// Local branch names, sorted.
+    fn list_branches(&self, repo: &str) -> Result<Vec<String>, String>;
+
+    /// Up to `limit` commits reachable from `rev` (HEAD when `None`), newest first.
+    fn log(&self, repo: &str, rev: Option<&str>, limit: usize) -> Result<Vec<CommitInfo>, String>;
+
+    /// Files changed on `head` since it forked from `base` (`base...head`).
+    fn diff_range(&self, repo: &str, base: &str, head: &str) -> Result<Vec<FileChange>, String>;
+
+    /// Files a commit changed relative to its first parent.
+    fn commit_changes(&self, repo: &str, commit: &str) -> Result<Vec<FileChange>, String>;
+
+    /// Contents of `path` (relative to the repository root) at `commit`.
+    fn file_at_commit(&self, repo: &str, commit: &str, path: &str) -> Result<Vec<u8>, String>;
+
+    /// Every `key=value` entry of one config file, in file order.
+    fn config_entries(&self, scope: ConfigScope<'_>) -> Result<Vec<(String, String)>, String>;
+}
+
+/// The process-wide backend: libgit2 first, falling back to the `git` binary.
+pub fn git_backend() -> &'static dyn GitBackend {
+    static BACKEND: OnceLock<Box<dyn GitBackend>> = OnceLock::new();
+    BACKEND
+        .get_or_init(|| match std::env::var(GIT_BACKEND_ENV).as_deref() {
+            Ok("cli") => Box::new(CliGitBackend),
+            Ok("libgit2") => Box::new(LibGit2Backend),
+            _ => Box::new(FallbackGitBackend {
+                primary: Box::new(LibGit2Backend),
+                fallback: Box::new(CliGitBackend),
+            }),
+        })
+        .as_ref()
+}
+
+// ---------------- libgit2 ----------------
+
+/// In-process backend built on libgit2.
+pub struct LibGit2Backend;
+
+fn open(repo: &str) -> Result<Repository, String> {
+    Repository::discover(repo).map_err(|e| format!("Failed to open repository {}: {}", repo, e.message()))
+}
+
+fn git2_err(e: git2::Error) -> String {
+    match e.code() {
+        ErrorCode::NotFound => format!("{}: {}", NOT_FOUND, e.message()),
+        _ => e.message().to_string(),
+    }
+}
+
+fn resolve_commit<'r>(repo: &'r Repository, rev: &str) -> Result<git2::Commit<'r>, String> {
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|e| match e.code() {
+            ErrorCode::NotFound | ErrorCode::InvalidSpec | ErrorCode::Ambiguous | ErrorCode::Peel => {
+                format!("{} '{}': {}", UNKNOWN_REVISION, rev, e.message())
+            }
+            _ => git2_err(e),
+        })
+}
+
+/// Whether `error` says a revision, ref or path does not exist, as opposed
+/// to the backend failing.
+pub fn is_lookup_error(error: &str) -> bool {
+    error.starts_with(UNKNOWN_REVISION) || error.starts_with(NOT_FOUND)
+}
+
+fn format_git_time(time: git2::Time) -> String {
+    let offset = chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
+    chrono::DateTime::from_timestamp(time.seconds(), 0)
+        .map(|utc| utc.with_timezone(&offset).format("%Y-%m-%d %H:%M:%S %z").to_string())
+        .unwrap_or_default()
+}
+
+/// `%D`-style decorations keyed by the commit they point at.
+fn decorations(repo: &Repository) -> Result<HashMap<Oid, Vec<String>>, String> {
+    let mut by_commit: HashMap<Oid, Vec<String>> = HashMap::new();
+
+    let head_branch = repo
+        .find_reference("HEAD")
+        .ok()
+        .and_then(|head| head.symbolic_target().map(str::to_string));
+    if let Ok(head) = repo.head() {
+        if let Ok(commit) = head.peel_to_commit() {
+            let label = match head_branch.as_deref().and_then(|t| t.strip_prefix("refs/heads/")) {
+                Some(branch) => format!("HEAD -> {}", branch),
+                None => "HEAD".to_string(),
+            };
+            by_commit.entry(commit.id()).or_default().push(label);
+        }
+    }
+
+    for reference in repo.references().map_err(git2_err)?.flatten() {
+        let Some(name) = reference.name() else { continue };
+        if Some(name) == head_branch.as_deref() {
+            continue;
+        }
+        let label = if let Some(tag) = name.strip_prefix("refs/tags/") {
+            format!("tag: {}", tag)
+        } else if let Some(branch) = name.strip_prefix("refs/heads/") {
+            branch.to_string()
+        } else if let Some(remote) = name.strip_prefix("refs/remotes/") {
+            remote.to_string()
+        } else {
+            continue;
+        };
+        if let Ok(commit) = reference.peel_to_commit() {
+            by_commit.entry(commit.id()).or_default().push(label);
+        }
+    }
+    Ok(by_commit)
+}
+
+fn delta_status(delta: Delta) -> Option<&'static str> {
+    match delta {
+        Delta::Added => Some("A"),
+        Delta::Deleted => Some("D"),
+        Delta::Modified => Some("M"),
+        Delta::Renamed => Some("R"),
+        Delta::Copied => Some("C"),
+        Delta::Typechange => Some("T"),
+        _ => None,
+    }
+}
+
+fn tree_changes(repo: &Repository, old: Option<&Tree<'_>>, new: &Tree<'_>) -> Result<Vec<FileChange>, String> {
+    let mut diff = repo.diff_tree_to_tree(old, Some(new), None).map_err(git2_err)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(git2_err)?;
+
+    let path_of = |file: git2::DiffFile<'_>| file.path().map(|p| p.to_string_lossy().to_string());
+    let mut changes = Vec::new();
+    for delta in diff.deltas() {
+        let Some(status) = delta_status(delta.status()) else { continue };
+        let new_path = path_of(delta.new_file());
+        let old_path = path_of(delta.old_file());
+        let (path, old_path) = match delta.status() {
+            Delta::Renamed | Delta::Copied => (new_path, old_path),
+            Delta::Deleted => (old_path, None),
+            _ => (new_path, None),
+        };
+        if let Some(path) = path {
+            changes.push(FileChange {
+                status: status.to_string(),
+                path,
+                old_path,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+impl GitBackend for LibGit2Backend {
+    fn name(&self) -> &'static str {
+        "libgit2"
+    }
+
+    fn current_branch(&self, repo: &str) -> Result<Option<String>, String> {
+        let repo = open(repo)?;
+        let head = repo.find_reference("HEAD").map_err(git2_err)?;
+        Ok(head
+            .symbolic_target()
+            .and_then(|target| target.strip_prefix("refs/heads/"))
+            .map(str::to_string))
+    }
+
+    fn list_branches(&self, repo: &str) -> Result<Vec<String>, String> {
+        let repo = open(repo)?;
+        let mut names = Vec::new();
+        for branch in repo.branches(Some(BranchType::Local)).map_err(git2_err)? {
+            let (branch, _) = branch.map_err(git2_err)?;
+            if let Some(name) = branch.name().map_err(git2_err)? {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn log(&self, repo: &str, rev: Option<&str>, limit: usize) -> Result<Vec<CommitInfo>, String> {
+        let repo = open(repo)?;
+        let start = resolve_commit(&repo, rev.unwrap_or("HEAD"))?;
+        let decorations = decorations(&repo)?;
+
+        let mut walk = repo.revwalk().map_err(git2_err)?;
+        walk.set_sorting(Sort::TIME).map_err(git2_err)?;
+        walk.push(start.id()).map_err(git2_err)?;
+
+        let mut commits = Vec::new();
+        for oid in walk.take(limit) {
+            let commit = repo.find_commit(oid.map_err(git2_err)?).map_err(git2_err)?;
+            let author = commit.author();
+            commits.push(CommitInfo {
+                hash: commit.id().to_string(),
+                parents: commit.parent_ids().map(|p| p.to_string()).collect(),
+                author: String::from_utf8_lossy(author.name_bytes()).to_string(),
+                date: format_git_time(author.when()),
+                subject: String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default()).to_string(),
+                refs: decorations.get(&commit.id()).cloned().unwrap_or_default(),
+            });
+        }
+        Ok(commits)
+    }
+
+    fn diff_range(&self, repo: &str, base: &str, head: &str) -> Result<Vec<FileChange>, String> {
+        let repo = open(repo)?;
+        let base = resolve_commit(&repo, base)?;
+        let head = resolve_commit(&repo, head)?;
+        let fork = repo.merge_base(base.id(), head.id()).map_err(git2_err)?;
+        let fork_tree = repo.find_commit(fork).and_then(|c| c.tree()).map_err(git2_err)?;
+        let head_tree = head.tree().map_err(git2_err)?;
+        tree_changes(&repo, Some(&fork_tree), &head_tree)
+    }
+
+    fn commit_changes(&self, repo: &str, commit: &str) -> Result<Vec<FileChange>, String> {
+        let repo = open(repo)?;
+        let commit = resolve_commit(&repo, commit)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().map_err(git2_err)?),
+            Err(_) => None,
+        };
+        let tree = commit.tree().map_err(git2_err)?;
+        tree_changes(&repo, parent_tree.as_ref(), &tree)
+    }
+
+    fn file_at_commit(&self, repo: &str, commit: &str, path: &str) -> Result<Vec<u8>, String> {
+        let repo = open(repo)?;
+        let tree = resolve_commit(&repo, commit)?.tree().map_err(git2_err)?;
+        let entry = tree
+            .get_path(Path::new(path))
+            .map_err(|_| format!("{}: path '{}' does not exist in '{}'", NOT_FOUND, path, commit))?;
+        let blob = entry
+            .to_object(&repo)
+            .and_then(|object| object.peel_to_blob())
+            .map_err(git2_err)?;
+        Ok(blob.content().to_vec())
+    }
+
+    fn config_entries(&self, scope: ConfigScope<'_>) -> Result<Vec<(String, String)>, String> {
+        let config = match scope {
+            ConfigScope::Global => git2::Config::find_global()
+                .and_then(|path| git2::Config::open(&path))
+                .map_err(git2_err)?,
+            ConfigScope::Local(repo) => open(repo)?
+                .config()
+                .and_then(|config| config.open_level(ConfigLevel::Local))
+                .map_err(git2_err)?,
+        };
+        let mut entries = Vec::new();
+        let mut iter = config.entries(None).map_err(git2_err)?;
+        while let Some(entry) = iter.next() {
+            let entry = entry.map_err(git2_err)?;
+            if let Some(name) = entry.name() {
+                let value = String::from_utf8_lossy(entry.value_bytes()).to_string();
+                entries.push((name.to_string(), value));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// ---------------- git CLI ----------------
+
+/// Shells out to the `git` binary. Output is requested NUL/record-separated
+/// so paths and subjects with spaces, tabs or `|` survive parsing.
+pub struct CliGitBackend;
+
+fn run_git(dir: Option<&str>, args: &[&str]) -> Result<Vec<u8>, String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git {}: {}", args.first().unwrap_or(&""), e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(output.stdout)
+}
+
+/// Parse `--name-status -z` output.
+pub fn parse_name_status_z(output: &str) -> Vec<FileChange> {
+    let mut tokens = output.split('\0').filter(|t| !t.is_empty());
+    let mut changes = Vec::new();
+    while let Some(status) = tokens.next() {
+        // Rename/copy scores (R100) are dropped; the views only use the letter.
+        let letter = status.chars().next().map(String::from).unwrap_or_default();
+        let change = if letter == "R" || letter == "C" {
+            match (tokens.next(), tokens.next()) {
+                (Some(old), Some(new)) => FileChange {
+                    status: letter,
+                    path: new.to_string(),
+                    old_path: Some(old.to_string()),
+                },
+                _ => break,
+            }
+        } else {
+            match tokens.next() {
+                Some(path) => FileChange {
+                    status: letter,
+                    path: path.to_string(),
+                    old_path: None,
+                },
+                None => break,
+            }
+        };
+        changes.push(change);
+    }
+    changes
+}
+
+/// Parse `git log` output written with [`CLI_LOG_FORMAT`].
+pub fn parse_log_records(output: &str) -> Vec<CommitInfo> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let fields: Vec<&str> = record.trim_start_matches('\n').splitn(6, '\x1f').collect();
+            if fields.len() < 6 || fields[0].is_empty() {
+                return None;
+            }
+            Some(CommitInfo {
+                hash: fields[0].to_string(),
+                parents: fields[1].split_whitespace().map(str::to_string).collect(),
+                author: fields[2].to_string(),
+                date: fields[3].to_string(),
+                subject: fields[4].to_string(),
+                refs: fields[5]
+                    .split(", ")
+                    .map(|r| r.trim().to_string())
+                    .filter(|r| !r.is_empty())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Unit-separated fields, record-separated commits.
+pub const CLI_LOG_FORMAT: &str = "--pretty=format:%H%x1f%P%x1f%an%x1f%ad%x1f%s%x1f%D%x1e";
+
+impl GitBackend for CliGitBackend {
+    fn name(&self) -> &'static str {
+        "cli"
+    }
+
+    fn current_branch(&self, repo: &str) -> Result<Option<String>, String> {
+        let out = run_git(Some(repo), &["branch", "--show-current"])?;
+        let branch = String::from_utf8_lossy(&out).trim().to_string();
+        Ok((!branch.is_empty()).then_some(branch))
+    }
+
+    fn list_branches(&self, repo: &str) -> Result<Vec<String>, String> {
+        let out = run_git(Some(repo), &["for-each-ref", "--format=%(refname:short)", "refs/heads"])?;
+        let mut names: Vec<String> = String::from_utf8_lossy(&out)
+            .lines()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn log(&self, repo: &str, rev: Option<&str>, limit: usize) -> Result<Vec<CommitInfo>, String> {
+        let limit = format!("-n{}", limit);
+        let mut args = vec!["log", "--date=iso", CLI_LOG_FORMAT, &limit];
+        if let Some(rev) = rev {
+            args.extend(["--end-of-options", rev]);
+        }
+        let out = run_git(Some(repo), &args)?;
+        Ok(parse_log_records(&String::from_utf8_lossy(&out)))
+    }
+
+    fn diff_range(&self, repo: &str, base: &str, head: &str) -> Result<Vec<FileChange>, String> {
+        let range = format!("{}...{}", base, head);
+        let out = run_git(Some(repo), &["diff", "--name-status", "-z", "-M", &range, "--"])?;
+        Ok(parse_name_status_z(&String::from_utf8_lossy(&out)))
+    }
+
+    fn commit_changes(&self, repo: &str, commit: &str) -> Result<Vec<FileChange>, String> {
+        let parent = run_git(Some(repo), &["rev-parse", "--verify", "--quiet", &format!("{}^1", commit)])
+            .map(|out| String::from_utf8_lossy(&out).trim().to_string())
+            .unwrap_or_else(|_| EMPTY_TREE.to_string());
+        let out = run_git(Some(repo), &["diff", "--name-status", "-z", "-M", &parent, commit, "--"])?;
+        Ok(parse_name_status_z(&String::from_utf8_lossy(&out)))
+    }
+
+    fn file_at_commit(&self, repo: &str, commit: &str, path: &str) -> Result<Vec<u8>, String> {
+        run_git(Some(repo), &["show", &format!("{}:{}", commit, path)])
+    }
+
+    fn config_entries(&self, scope: ConfigScope<'_>) -> Result<Vec<(String, String)>, String> {
+        let out = match scope {
+            ConfigScope::Global => run_git(None, &["config", "--global", "--list", "-z"])?,
+            ConfigScope::Local(repo) => run_git(Some(repo), &["config", "--local", "--list", "-z"])?,
+        };
+        // With -z each entry is `key\nvalue\0`.
+        Ok(String::from_utf8_lossy(&out)
+            .split('\0')
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.split_once('\n') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (entry.to_string(), String::new()),
+            })
+            .collect())
+    }
+}
+
+// ---------------- fallback ----------------
+
+/// Tries `primary` and retries with `fallback` when it fails, e.g. for
+/// repository formats or extensions libgit2 does not support. Lookups of
+/// things that do not exist are answered by `primary` alone.
+pub struct FallbackGitBackend {
+    pub primary: Box<dyn GitBackend>,
+    pub fallback: Box<dyn GitBackend>,
+}
+
+impl FallbackGitBackend {
+    fn attempt<T>(&self, op: &str, call: impl Fn(&dyn GitBackend) -> Result<T, String>) -> Result<T, String> {
+        call(self.primary.as_ref()).or_else(|e| {
+            if is_lookup_error(&e) {
+                return Err(e);
+            }
+            eprintln!(
+                "⚠️ {} git backend failed for {} ({}); retrying with {}",
+                self.primary.name(),
+                op,
+                e,
+                self.fallback.name()
+            );
+            call(self.fallback.as_ref())
+        })
+    }
+}
+
+impl GitBackend for FallbackGitBackend {
+    fn name(&self) -> &'static str {
+        self.primary.name()
+    }
+
+    fn current_branch(&self, repo: &str) -> Result<Option<String>, String> {
+        self.attempt("current_branch", |b| b.current_branch(repo))
+    }
+
+    fn list_branches(&self, repo: &str) -> Result<Vec<String>, String> {
+        self.attempt("list_branches", |b| b.list_branches(repo))
+    }
+
+    fn log(&self, repo: &str, rev: Option<&str>, limit: usize) -> Result<Vec<CommitInfo>, String> {
+        self.attempt("log", |b| b.log(repo, rev, limit))
+    }
+
+    fn diff_range(&self, repo: &str, base: &str, head: &str) -> Result<Vec<FileChange>, String> {
+        self.attempt("diff_range", |b| b.diff_range(repo, base, head))
+    }
+
+    fn commit_changes(&self, repo: &str, commit: &str) -> Result<Vec<FileChange>, String> {
+        self.attempt("commit_changes", |b| b.commit_changes(repo, commit))
+    }
+
+    fn file_at_commit(&self, repo: &str, commit: &str, path: &str) -> Result<Vec<u8>, String> {
+        self.attempt("file_at_commit", |b| b.file_at_commit(repo, commit, path))
+    }
+
+    fn config_entries(&self, scope: ConfigScope<'_>) -> Result<Vec<(String, String)>, String> {
+        self.attempt("config_entries", |b| b.config_entries(scope))
+    }
+}
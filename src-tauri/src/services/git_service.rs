@@ -2,6 +2,8 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use crate::services::git_backend::git_backend;
+
 /// Check if a directory is a valid Git repository by looking for .git folder
 pub fn is_valid_git_repository(project_path: &str) -> bool {
     let git_path = Path::new(project_path).join(".git");
@@ -14,18 +16,11 @@ pub fn get_git_branch(project_path: &str) -> Option<String> {
         return None;
     }
 
-    let output = Command::new("git")
-        .args(["branch", "--show-current"])
-        .current_dir(project_path)
-        .output()
-        .ok()?;
-
-    if output.status.success() {
-        let branch = String::from_utf8(output.stdout).ok()?;
-        Some(branch.trim().to_string())
-    } else {
-        None
-    }
+    // Detached HEAD reads as an empty branch name, like `git branch --show-current`.
+    git_backend()
+        .current_branch(project_path)
+        .ok()
+        .map(Option::unwrap_or_default)
 }
 
 /// Get the Git status for a repository (short format)
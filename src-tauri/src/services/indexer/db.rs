@@ -1,6 +1,8 @@
 use crate::models::indexer::{
     AgentRecord, DailyAgentStats, IndexedSession, ScanRecord,
 };
+use crate::models::budget::BudgetState;
+use crate::models::usage::{CostBreakdown, CostDimension, SessionUsage, UsageRecord, UsageSource};
 use rusqlite::{params, Connection, OptionalExtension};
 use std::path::Path;
 use std::sync::Mutex;
@@ -13,6 +15,83 @@ fn sqlite_u64(value: i64, field: &str) -> Result<u64, String> {
     u64::try_from(value).map_err(|_| format!("{} is negative in SQLite storage", field))
 }
 
+/// Usage rows that count: a session's transcript rows once it has any,
+/// otherwise what its live runs recorded.
+const EFFECTIVE_USAGE: &str = "WITH effective AS (
+    SELECT * FROM token_usage u
+    WHERE u.source = 'transcript' OR NOT EXISTS (
+        SELECT 1 FROM token_usage t
+        WHERE t.source = 'transcript'
+          AND t.agent_id = u.agent_id
+          AND t.session_key = u.session_key
+    )
+)";
+
+const USAGE_TOKENS: &str = "input_tokens + output_tokens + cache_read_tokens + cache_write_tokens";
+
+/// Write a usage row, adding to the tokens and cost already recorded under
+/// the same key when `accumulate` is set.
+fn insert_usage(conn: &Connection, record: &UsageRecord, accumulate: bool) -> Result<(), String> {
+    let conflict = if accumulate {
+        "ON CONFLICT(agent_id, session_key, source, model, date) DO UPDATE SET
+            project_path = COALESCE(excluded.project_path, project_path),
+            input_tokens = input_tokens + excluded.input_tokens,
+            output_tokens = output_tokens + excluded.output_tokens,
+            cache_read_tokens = cache_read_tokens + excluded.cache_read_tokens,
+            cache_write_tokens = cache_write_tokens + excluded.cache_write_tokens,
+            reported_cost_usd = CASE
+                WHEN reported_cost_usd IS NULL AND excluded.reported_cost_usd IS NULL THEN NULL
+                ELSE COALESCE(reported_cost_usd, 0) + COALESCE(excluded.reported_cost_usd, 0)
+            END,
+            cost_usd = CASE
+                WHEN cost_usd IS NULL AND excluded.cost_usd IS NULL THEN NULL
+                ELSE COALESCE(cost_usd, 0) + COALESCE(excluded.cost_usd, 0)
+            END,
+            updated_at = excluded.updated_at"
+    } else {
+        "ON CONFLICT(agent_id, session_key, source, model, date) DO UPDATE SET
+            project_path = excluded.project_path,
+            input_tokens = excluded.input_tokens,
+            output_tokens = excluded.output_tokens,
+            cache_read_tokens = excluded.cache_read_tokens,
+            cache_write_tokens = excluded.cache_write_tokens,
+            reported_cost_usd = excluded.reported_cost_usd,
+            cost_usd = excluded.cost_usd,
+            updated_at = excluded.updated_at"
+    };
+    conn.execute(
+        &format!(
+            "INSERT INTO token_usage (agent_id, session_key, source, model, date, project_path,
+                input_tokens, output_tokens, cache_read_tokens, cache_write_tokens,
+                reported_cost_usd, cost_usd, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             {}",
+            conflict
+        ),
+        params![
+            record.agent_id,
+            record.session_key,
+            record.source.as_str(),
+            record.model,
+            record.date,
+            record.project_path,
+            sqlite_i64(record.input_tokens, "input_tokens")?,
+            sqlite_i64(record.output_tokens, "output_tokens")?,
+            sqlite_i64(record.cache_read_tokens, "cache_read_tokens")?,
+            sqlite_i64(record.cache_write_tokens, "cache_write_tokens")?,
+            record.reported_cost_usd,
+            record.cost_usd,
+            chrono::Utc::now().timestamp(),
+        ],
+    )
+    .map_err(|e| format!("Failed to record token usage: {}", e))?;
+    Ok(())
+}
+
+/// File name of the index database in the app's data directory. The audit
+/// log keeps its table here too.
+pub const INDEX_DB_FILE: &str = "commander_index.db";
+
 pub struct IndexDb {
     conn: Mutex<Connection>,
 }
@@ -37,6 +116,9 @@ impl IndexDb {
 
     fn init_schema(&self) -> Result<(), String> {
         let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let had_usage: bool = conn
+            .prepare("SELECT 1 FROM token_usage LIMIT 0")
+            .is_ok();
         conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS schema_version (
                 version INTEGER PRIMARY KEY
@@ -82,10 +164,42 @@ impl IndexDb {
                 PRIMARY KEY (source_file, agent_id)
             );
 
+            CREATE TABLE IF NOT EXISTS token_usage (
+                agent_id TEXT NOT NULL,
+                session_key TEXT NOT NULL,
+                source TEXT NOT NULL,
+                model TEXT NOT NULL,
+                date TEXT NOT NULL,
+                project_path TEXT,
+                input_tokens INTEGER NOT NULL DEFAULT 0,
+                output_tokens INTEGER NOT NULL DEFAULT 0,
+                cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+                cache_write_tokens INTEGER NOT NULL DEFAULT 0,
+                reported_cost_usd REAL,
+                cost_usd REAL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (agent_id, session_key, source, model, date)
+            );
+
+            CREATE TABLE IF NOT EXISTS usage_runs (
+                run_id TEXT PRIMARY KEY,
+                agent_id TEXT NOT NULL,
+                session_key TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS budget_state (
+                budget_id TEXT NOT NULL,
+                period_start TEXT NOT NULL,
+                warned_at REAL,
+                exceeded_at INTEGER,
+                PRIMARY KEY (budget_id, period_start)
+            );
+
             CREATE INDEX IF NOT EXISTS idx_sessions_agent ON sessions(agent_id);
             CREATE INDEX IF NOT EXISTS idx_sessions_start ON sessions(session_start);
             CREATE INDEX IF NOT EXISTS idx_sessions_project ON sessions(project_path);
-            CREATE INDEX IF NOT EXISTS idx_daily_stats_date ON daily_stats(date);",
+            CREATE INDEX IF NOT EXISTS idx_daily_stats_date ON daily_stats(date);
+            CREATE INDEX IF NOT EXISTS idx_token_usage_date ON token_usage(date);",
         )
         .map_err(|e| format!("Failed to init schema: {}", e))?;
 
@@ -114,6 +228,13 @@ impl IndexDb {
         )
         .map_err(|e| format!("Failed to clear stale scan records: {}", e))?;
 
+        // Re-scan everything once so transcripts indexed before token usage
+        // was recorded get their usage.
+        if !had_usage {
+            conn.execute_batch("DELETE FROM scan_metadata")
+                .map_err(|e| format!("Failed to clear scan records: {}", e))?;
+        }
+
         Ok(())
     }
 
@@ -370,6 +491,8 @@ impl IndexDb {
         Ok(sum as u64)
     }
 
+    /// Tokens from the pre-aggregated daily stats, plus recorded usage for
+    /// the days and agents those do not count tokens for.
     pub fn get_total_tokens(&self) -> Result<u64, String> {
         let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
         let sum: i64 = conn
@@ -379,7 +502,31 @@ impl IndexDb {
                 |row| row.get(0),
             )
             .map_err(|e| format!("Query error: {}", e))?;
-        Ok(sum as u64)
+        let usage: i64 = conn
+            .query_row(
+                &format!(
+                    "{} SELECT COALESCE(SUM({}), 0) FROM effective e
+                     WHERE NOT EXISTS (
+                        SELECT 1 FROM daily_stats d
+                        WHERE d.date = e.date AND d.agent_id = e.agent_id AND d.total_tokens > 0
+                     )",
+                    EFFECTIVE_USAGE, USAGE_TOKENS
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Query error: {}", e))?;
+        Ok((sum + usage) as u64)
+    }
+
+    pub fn get_total_cost(&self) -> Result<f64, String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        conn.query_row(
+            &format!("{} SELECT COALESCE(SUM(cost_usd), 0) FROM effective", EFFECTIVE_USAGE),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {}", e))
     }
 
     pub fn get_agents_used(&self) -> Result<std::collections::HashMap<String, usize>, String> {
@@ -475,6 +622,31 @@ impl IndexDb {
             }
         }
 
+        // Recorded usage fills in tokens where daily_stats has none
+        let mut stmt3 = conn
+            .prepare(&format!(
+                "{} SELECT date, SUM({}) FROM effective e
+                 WHERE date >= ?1
+                   AND NOT EXISTS (
+                      SELECT 1 FROM daily_stats d
+                      WHERE d.date = e.date AND d.agent_id = e.agent_id AND d.total_tokens > 0
+                   )
+                 GROUP BY date",
+                EFFECTIVE_USAGE, USAGE_TOKENS
+            ))
+            .map_err(|e| format!("Prepare error: {}", e))?;
+        let rows3 = stmt3
+            .query_map(params![start_str], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })
+            .map_err(|e| format!("Query error: {}", e))?;
+        for row in rows3 {
+            let (date, tokens) = row.map_err(|e| format!("Row error: {}", e))?;
+            if let Some(entry) = day_map.get_mut(&date) {
+                entry.1 += tokens as u64;
+            }
+        }
+
         let mut result: Vec<crate::models::dashboard::DailyActivity> = day_map
             .into_iter()
             .map(|(date, (count, tokens))| crate::models::dashboard::DailyActivity {
@@ -487,6 +659,267 @@ impl IndexDb {
         Ok(result)
     }
 
+    // --- Token usage operations ---
+
+    /// Add a live run's turn to its session's usage. `run_id` is
+    /// Commander's session id, remembered so the session can be looked up
+    /// by it.
+    pub fn add_live_usage(&self, run_id: &str, record: &UsageRecord) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        insert_usage(&conn, record, true)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO usage_runs (run_id, agent_id, session_key) VALUES (?1, ?2, ?3)",
+            params![run_id, record.agent_id, record.session_key],
+        )
+        .map_err(|e| format!("Failed to record usage run: {}", e))?;
+        Ok(())
+    }
+
+    /// Replace what a session's transcript was last found to have used.
+    pub fn replace_transcript_usage(
+        &self,
+        agent_id: &str,
+        session_key: &str,
+        records: &[UsageRecord],
+    ) -> Result<(), String> {
+        let mut conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        tx.execute(
+            "DELETE FROM token_usage WHERE agent_id = ?1 AND session_key = ?2 AND source = ?3",
+            params![agent_id, session_key, UsageSource::Transcript.as_str()],
+        )
+        .map_err(|e| format!("Failed to clear token usage: {}", e))?;
+        for record in records {
+            insert_usage(&tx, record, false)?;
+        }
+        tx.commit()
+            .map_err(|e| format!("Failed to commit token usage: {}", e))
+    }
+
+    /// Recompute the cost of every usage row with `price`.
+    pub fn reprice_usage(
+        &self,
+        price: &dyn Fn(&UsageRecord) -> Option<f64>,
+    ) -> Result<usize, String> {
+        let mut conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let records = {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT agent_id, session_key, source, model, date, project_path,
+                            input_tokens, output_tokens, cache_read_tokens, cache_write_tokens,
+                            reported_cost_usd, cost_usd
+                     FROM token_usage",
+                )
+                .map_err(|e| format!("Prepare error: {}", e))?;
+            let rows = stmt
+                .query_map([], usage_record_from_row)
+                .map_err(|e| format!("Query error: {}", e))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Row error: {}", e))?
+        };
+        let mut changed = 0;
+        for record in &records {
+            let cost = price(record);
+            if cost == record.cost_usd {
+                continue;
+            }
+            tx.execute(
+                "UPDATE token_usage SET cost_usd = ?1
+                 WHERE agent_id = ?2 AND session_key = ?3 AND source = ?4 AND model = ?5 AND date = ?6",
+                params![
+                    cost,
+                    record.agent_id,
+                    record.session_key,
+                    record.source.as_str(),
+                    record.model,
+                    record.date,
+                ],
+            )
+            .map_err(|e| format!("Failed to reprice token usage: {}", e))?;
+            changed += 1;
+        }
+        tx.commit()
+            .map_err(|e| format!("Failed to commit token usage: {}", e))?;
+        Ok(changed)
+    }
+
+    /// Tokens and cost since `since` (`YYYY-MM-DD`), grouped by `dimension`
+    /// and optionally narrowed to one project and agent. Days come in
+    /// order, everything else most expensive first.
+    pub fn get_cost_breakdown(
+        &self,
+        dimension: CostDimension,
+        since: &str,
+        project_path: Option<&str>,
+        agent_id: Option<&str>,
+    ) -> Result<Vec<CostBreakdown>, String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let (key, order) = match dimension {
+            CostDimension::Project => ("COALESCE(project_path, '')", "cost DESC, k"),
+            CostDimension::Agent => ("agent_id", "cost DESC, k"),
+            CostDimension::Model => ("model", "cost DESC, k"),
+            CostDimension::Day => ("date", "k"),
+        };
+        let mut stmt = conn
+            .prepare(&format!(
+                "{effective}
+                 SELECT {key} AS k,
+                        COUNT(DISTINCT agent_id || char(31) || session_key),
+                        SUM(input_tokens), SUM(output_tokens),
+                        SUM(cache_read_tokens), SUM(cache_write_tokens),
+                        COALESCE(SUM(cost_usd), 0) AS cost,
+                        SUM(CASE WHEN cost_usd IS NULL THEN {tokens} ELSE 0 END)
+                 FROM effective
+                 WHERE date >= ?1
+                   AND (?2 IS NULL OR project_path = ?2)
+                   AND (?3 IS NULL OR agent_id = ?3)
+                 GROUP BY k
+                 ORDER BY {order}",
+                effective = EFFECTIVE_USAGE,
+                key = key,
+                tokens = USAGE_TOKENS,
+                order = order,
+            ))
+            .map_err(|e| format!("Prepare error: {}", e))?;
+        let rows = stmt
+            .query_map(params![since, project_path, agent_id], |row| {
+                Ok(CostBreakdown {
+                    key: row.get(0)?,
+                    sessions: row.get::<_, i64>(1)? as u64,
+                    input_tokens: row.get::<_, i64>(2)? as u64,
+                    output_tokens: row.get::<_, i64>(3)? as u64,
+                    cache_read_tokens: row.get::<_, i64>(4)? as u64,
+                    cache_write_tokens: row.get::<_, i64>(5)? as u64,
+                    cost_usd: row.get(6)?,
+                    unpriced_tokens: row.get::<_, i64>(7)? as u64,
+                })
+            })
+            .map_err(|e| format!("Query error: {}", e))?;
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {}", e))?);
+        }
+        Ok(results)
+    }
+
+    /// Cost of everything used since `since` (`YYYY-MM-DD`), optionally in
+    /// one project or by one agent only.
+    pub fn get_cost_since(
+        &self,
+        since: &str,
+        project_path: Option<&str>,
+        agent_id: Option<&str>,
+    ) -> Result<f64, String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        conn.query_row(
+            &format!(
+                "{} SELECT COALESCE(SUM(cost_usd), 0) FROM effective
+                 WHERE date >= ?1
+                   AND (?2 IS NULL OR project_path = ?2)
+                   AND (?3 IS NULL OR agent_id = ?3)",
+                EFFECTIVE_USAGE
+            ),
+            params![since, project_path, agent_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {}", e))
+    }
+
+    // --- Budget operations ---
+
+    pub fn get_budget_state(&self, budget_id: &str, period_start: &str) -> Result<BudgetState, String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        conn.query_row(
+            "SELECT warned_at, exceeded_at FROM budget_state
+             WHERE budget_id = ?1 AND period_start = ?2",
+            params![budget_id, period_start],
+            |row| {
+                Ok(BudgetState {
+                    warned_at: row.get(0)?,
+                    exceeded_at: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+        .map(Option::unwrap_or_default)
+        .map_err(|e| format!("Query error: {}", e))
+    }
+
+    pub fn save_budget_state(
+        &self,
+        budget_id: &str,
+        period_start: &str,
+        state: &BudgetState,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        conn.execute(
+            "INSERT INTO budget_state (budget_id, period_start, warned_at, exceeded_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(budget_id, period_start) DO UPDATE SET
+                warned_at = excluded.warned_at,
+                exceeded_at = excluded.exceeded_at",
+            params![budget_id, period_start, state.warned_at, state.exceeded_at],
+        )
+        .map_err(|e| format!("Failed to save budget state: {}", e))?;
+        Ok(())
+    }
+
+    /// Usage of one session, looked up by Commander's session id or the
+    /// agent's own.
+    pub fn get_session_usage(&self, session_id: &str) -> Result<Option<SessionUsage>, String> {
+        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let run: Option<(String, String)> = conn
+            .query_row(
+                "SELECT agent_id, session_key FROM usage_runs WHERE run_id = ?1",
+                params![session_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Query error: {}", e))?;
+        let (agent_id, session_key) = match &run {
+            Some((agent_id, key)) => (Some(agent_id.as_str()), key.as_str()),
+            None => (None, session_id),
+        };
+        let mut stmt = conn
+            .prepare(&format!(
+                "{} SELECT agent_id, session_key, source, model, date, project_path,
+                          input_tokens, output_tokens, cache_read_tokens, cache_write_tokens,
+                          reported_cost_usd, cost_usd
+                   FROM effective
+                   WHERE session_key = ?1 AND (?2 IS NULL OR agent_id = ?2)
+                   ORDER BY date, model",
+                EFFECTIVE_USAGE
+            ))
+            .map_err(|e| format!("Prepare error: {}", e))?;
+        let rows = stmt
+            .query_map(params![session_key, agent_id], usage_record_from_row)
+            .map_err(|e| format!("Query error: {}", e))?;
+
+        let mut usage: Option<SessionUsage> = None;
+        for row in rows {
+            let record = row.map_err(|e| format!("Row error: {}", e))?;
+            let total = usage.get_or_insert_with(|| SessionUsage {
+                agent_id: record.agent_id.clone(),
+                session_key: record.session_key.clone(),
+                ..SessionUsage::default()
+            });
+            if !total.models.contains(&record.model) {
+                total.models.push(record.model.clone());
+            }
+            total.input_tokens += record.input_tokens;
+            total.output_tokens += record.output_tokens;
+            total.cache_read_tokens += record.cache_read_tokens;
+            total.cache_write_tokens += record.cache_write_tokens;
+            total.cost_usd += record.cost_usd.unwrap_or(0.0);
+        }
+        Ok(usage)
+    }
+
     pub fn get_indexed_agents(&self) -> Result<Vec<String>, String> {
         let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
         let mut stmt = conn
@@ -503,6 +936,28 @@ impl IndexDb {
     }
 }
 
+fn usage_record_from_row(row: &rusqlite::Row) -> rusqlite::Result<UsageRecord> {
+    let source: String = row.get(2)?;
+    Ok(UsageRecord {
+        agent_id: row.get(0)?,
+        session_key: row.get(1)?,
+        source: if source == UsageSource::Transcript.as_str() {
+            UsageSource::Transcript
+        } else {
+            UsageSource::Live
+        },
+        model: row.get(3)?,
+        date: row.get(4)?,
+        project_path: row.get(5)?,
+        input_tokens: row.get::<_, i64>(6)? as u64,
+        output_tokens: row.get::<_, i64>(7)? as u64,
+        cache_read_tokens: row.get::<_, i64>(8)? as u64,
+        cache_write_tokens: row.get::<_, i64>(9)? as u64,
+        reported_cost_usd: row.get(10)?,
+        cost_usd: row.get(11)?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -747,4 +1202,142 @@ mod tests {
         let result = db.get_sessions_for_project(Some("/no/such/project"), None, 100, 0).unwrap();
         assert!(result.is_empty());
     }
+
+    fn usage(source: UsageSource, key: &str, model: &str, date: &str, input: u64, cost: Option<f64>) -> UsageRecord {
+        UsageRecord {
+            agent_id: "claude".into(),
+            session_key: key.into(),
+            source,
+            model: model.into(),
+            date: date.into(),
+            project_path: Some("/projects/app".into()),
+            input_tokens: input,
+            output_tokens: 10,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+            reported_cost_usd: None,
+            cost_usd: cost,
+        }
+    }
+
+    #[test]
+    fn test_live_usage_accumulates_per_session() {
+        let (db, _dir) = test_db();
+        let turn = usage(UsageSource::Live, "agent-sess", "claude-sonnet-4", "2026-03-04", 100, Some(0.5));
+        db.add_live_usage("run-1", &turn).unwrap();
+        db.add_live_usage("run-1", &turn).unwrap();
+
+        let by_run = db.get_session_usage("run-1").unwrap().unwrap();
+        assert_eq!(by_run.session_key, "agent-sess");
+        assert_eq!(by_run.input_tokens, 200);
+        assert_eq!(by_run.output_tokens, 20);
+        assert!((by_run.cost_usd - 1.0).abs() < 1e-9);
+        assert_eq!(db.get_session_usage("agent-sess").unwrap(), Some(by_run));
+        assert_eq!(db.get_session_usage("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_transcript_usage_replaces_live_usage() {
+        let (db, _dir) = test_db();
+        db.add_live_usage(
+            "run-1",
+            &usage(UsageSource::Live, "s1", "claude-sonnet-4", "2026-03-04", 100, Some(1.0)),
+        )
+        .unwrap();
+        db.add_live_usage(
+            "run-2",
+            &usage(UsageSource::Live, "s2", "claude-sonnet-4", "2026-03-04", 50, Some(0.5)),
+        )
+        .unwrap();
+        assert!((db.get_total_cost().unwrap() - 1.5).abs() < 1e-9);
+
+        let transcript = vec![
+            usage(UsageSource::Transcript, "s1", "claude-sonnet-4", "2026-03-04", 300, Some(3.0)),
+            usage(UsageSource::Transcript, "s1", "claude-haiku-4-5", "2026-03-05", 40, None),
+        ];
+        db.replace_transcript_usage("claude", "s1", &transcript).unwrap();
+        // Re-indexing the same file must not double count.
+        db.replace_transcript_usage("claude", "s1", &transcript).unwrap();
+
+        assert!((db.get_total_cost().unwrap() - 3.5).abs() < 1e-9);
+        let session = db.get_session_usage("run-1").unwrap().unwrap();
+        assert_eq!(session.input_tokens, 340);
+        assert_eq!(session.models, vec!["claude-sonnet-4", "claude-haiku-4-5"]);
+
+        let models = db
+            .get_cost_breakdown(CostDimension::Model, "2026-03-01", None, None)
+            .unwrap();
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].key, "claude-sonnet-4");
+        assert_eq!(models[0].sessions, 2);
+        assert_eq!(models[0].input_tokens, 350);
+        assert_eq!(models[1].unpriced_tokens, 50);
+
+        let days = db
+            .get_cost_breakdown(CostDimension::Day, "2026-03-05", Some("/projects/app"), Some("claude"))
+            .unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].key, "2026-03-05");
+        assert!(db
+            .get_cost_breakdown(CostDimension::Project, "2026-03-01", Some("/elsewhere"), None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_reprice_usage() {
+        let (db, _dir) = test_db();
+        db.add_live_usage(
+            "run-1",
+            &usage(UsageSource::Live, "s1", "custom-model", "2026-03-04", 1000, None),
+        )
+        .unwrap();
+        let changed = db
+            .reprice_usage(&|record| Some(record.input_tokens as f64 / 1000.0))
+            .unwrap();
+        assert_eq!(changed, 1);
+        assert!((db.get_total_cost().unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(db.reprice_usage(&|_| Some(1.0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_budget_state_and_spend() {
+        let (db, _dir) = test_db();
+        assert_eq!(db.get_budget_state("project:daily:/p", "2026-03-04").unwrap(), BudgetState::default());
+        let state = BudgetState {
+            warned_at: Some(0.8),
+            exceeded_at: None,
+        };
+        db.save_budget_state("project:daily:/p", "2026-03-04", &state).unwrap();
+        assert_eq!(db.get_budget_state("project:daily:/p", "2026-03-04").unwrap(), state);
+        assert_eq!(db.get_budget_state("project:daily:/p", "2026-03-05").unwrap(), BudgetState::default());
+
+        db.add_live_usage("run-1", &usage(UsageSource::Live, "s1", "m", "2026-03-03", 10, Some(2.0)))
+            .unwrap();
+        db.add_live_usage("run-2", &usage(UsageSource::Live, "s2", "m", "2026-03-04", 10, Some(0.5)))
+            .unwrap();
+        assert!((db.get_cost_since("2026-03-01", Some("/projects/app"), None).unwrap() - 2.5).abs() < 1e-9);
+        assert!((db.get_cost_since("2026-03-04", None, Some("claude")).unwrap() - 0.5).abs() < 1e-9);
+        assert_eq!(db.get_cost_since("2026-03-01", None, Some("codex")).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_usage_tokens_fill_days_without_daily_stats() {
+        let (db, _dir) = test_db();
+        db.upsert_daily_stats(&DailyAgentStats {
+            date: "2026-03-04".into(),
+            agent_id: "claude".into(),
+            message_count: 10,
+            session_count: 1,
+            total_tokens: 5000,
+        })
+        .unwrap();
+        // Counted by daily_stats already
+        db.add_live_usage("run-1", &usage(UsageSource::Live, "s1", "m", "2026-03-04", 90, None))
+            .unwrap();
+        // Not counted anywhere else
+        db.add_live_usage("run-2", &usage(UsageSource::Live, "s2", "m", "2026-03-05", 190, None))
+            .unwrap();
+        assert_eq!(db.get_total_tokens().unwrap(), 5200);
+    }
 }
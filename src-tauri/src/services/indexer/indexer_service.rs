@@ -2,6 +2,7 @@ use crate::models::indexer::{AgentRecord, ScanRecord};
 use crate::services::indexer::db::IndexDb;
 use crate::services::indexer::scanner::AgentScanner;
 use crate::services::indexer::scanners::build_scanner_registry;
+use crate::services::usage_service;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tauri::Emitter;
@@ -79,6 +80,7 @@ async fn run_parallel_scan(
     db: &IndexDb,
     scanners: &[Box<dyn AgentScanner>],
 ) -> Result<(), String> {
+    let pricing = usage_service::load_pricing_or_default();
     for scanner in scanners {
         if !scanner.is_available() {
             continue;
@@ -174,6 +176,25 @@ async fn run_parallel_scan(
                                 }
                             }
                             db.upsert_session(session)?;
+
+                            let usage: Vec<_> = result
+                                .usage
+                                .iter()
+                                .filter(|u| {
+                                    u.agent_id == session.agent_id
+                                        && u.session_key == session.original_id
+                                })
+                                .map(|u| {
+                                    let mut record = u.clone();
+                                    record.cost_usd = usage_service::cost_of(&record, &pricing);
+                                    record
+                                })
+                                .collect();
+                            db.replace_transcript_usage(
+                                &session.agent_id,
+                                &session.original_id,
+                                &usage,
+                            )?;
                         }
 
                         db.upsert_scan_record(&ScanRecord {
@@ -11,6 +11,7 @@ pub fn get_dashboard_stats_from_db(
     let total_messages = db.get_total_messages()? as usize;
     let total_sessions = db.get_total_sessions()? as usize;
     let total_tokens = db.get_total_tokens()?;
+    let total_cost_usd = db.get_total_cost()?;
     let agents_used = db.get_agents_used()?;
     let daily_activity = db.get_daily_activity(days)?;
     let (current_streak, longest_streak) = compute_streaks(&daily_activity);
@@ -21,6 +22,7 @@ pub fn get_dashboard_stats_from_db(
         total_messages,
         total_sessions,
         total_tokens,
+        total_cost_usd,
         agents_used,
         daily_activity,
         current_streak,
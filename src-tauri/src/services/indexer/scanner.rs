@@ -1,5 +1,8 @@
 use crate::models::indexer::{DailyAgentStats, IndexedSession};
+use crate::models::usage::{UsageRecord, UsageSource};
+use crate::services::usage_service::canonical_project_path;
 use async_trait::async_trait;
+use std::collections::BTreeMap;
 
 /// A file discovered during a scan pass
 #[derive(Debug, Clone)]
@@ -10,9 +13,11 @@ pub struct DiscoveredFile {
 }
 
 /// Result of parsing a single file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ParseResult {
     pub sessions: Vec<IndexedSession>,
+    /// Token usage the file records, replacing what it recorded before.
+    pub usage: Vec<UsageRecord>,
 }
 
 /// Trait that each agent scanner must implement
@@ -60,3 +65,56 @@ pub fn truncate_summary(content: &str) -> String {
         format!("{}...", truncated)
     }
 }
+
+/// Tokens a transcript records, summed per model and day.
+#[derive(Debug, Default)]
+pub struct UsageTally {
+    rows: BTreeMap<(String, String), ([u64; 4], Option<f64>)>,
+}
+
+impl UsageTally {
+    /// Count one response's input, output, cache read and cache write
+    /// tokens. `timestamp` is RFC 3339; responses without one are skipped.
+    pub fn add(&mut self, model: &str, timestamp: &str, tokens: [u64; 4], cost_usd: Option<f64>) {
+        let Ok(at) = chrono::DateTime::parse_from_rfc3339(timestamp) else {
+            return;
+        };
+        let date = at.with_timezone(&chrono::Utc).format("%Y-%m-%d").to_string();
+        let (total, cost) = self
+            .rows
+            .entry((model.to_string(), date))
+            .or_insert(([0; 4], None));
+        for (sum, count) in total.iter_mut().zip(tokens) {
+            *sum += count;
+        }
+        if let Some(c) = cost_usd {
+            *cost = Some(cost.unwrap_or(0.0) + c);
+        }
+    }
+
+    pub fn into_records(
+        self,
+        agent_id: &str,
+        session_key: &str,
+        project_path: Option<&str>,
+    ) -> Vec<UsageRecord> {
+        self.rows
+            .into_iter()
+            .filter(|(_, (tokens, _))| tokens.iter().any(|t| *t > 0))
+            .map(|((model, date), (tokens, cost))| UsageRecord {
+                agent_id: agent_id.to_string(),
+                session_key: session_key.to_string(),
+                source: UsageSource::Transcript,
+                model,
+                date,
+                project_path: project_path.map(canonical_project_path),
+                input_tokens: tokens[0],
+                output_tokens: tokens[1],
+                cache_read_tokens: tokens[2],
+                cache_write_tokens: tokens[3],
+                reported_cost_usd: cost,
+                cost_usd: None,
+            })
+            .collect()
+    }
+}
@@ -83,7 +83,7 @@ impl AgentScanner for AutohandScanner {
             .to_string();
 
         if session_id.is_empty() {
-            return Ok(ParseResult { sessions: vec![] });
+            return Ok(ParseResult::default());
         }
 
         let created_at = val
@@ -162,6 +162,7 @@ impl AgentScanner for AutohandScanner {
 
         Ok(ParseResult {
             sessions: vec![session],
+            usage: vec![],
         })
     }
 }
@@ -1,6 +1,7 @@
 use crate::models::indexer::{DailyAgentStats, IndexedSession};
-use crate::services::indexer::scanner::{AgentScanner, DiscoveredFile, ParseResult, truncate_summary};
+use crate::services::indexer::scanner::{AgentScanner, DiscoveredFile, ParseResult, UsageTally, truncate_summary};
 use async_trait::async_trait;
+use std::collections::HashSet;
 use std::path::PathBuf;
 
 pub struct ClaudeScanner {
@@ -96,7 +97,7 @@ impl AgentScanner for ClaudeScanner {
 
         // stats-cache.json is handled via parse_aggregate_stats
         if path_buf.file_name().map_or(false, |n| n == "stats-cache.json") {
-            return Ok(ParseResult { sessions: vec![] });
+            return Ok(ParseResult::default());
         }
 
         // Parse JSONL transcript files from projects/
@@ -117,6 +118,10 @@ impl AgentScanner for ClaudeScanner {
         let mut model: Option<String> = None;
         let mut cwd: Option<String> = None;
         let mut summary: Option<String> = None;
+        let mut usage = UsageTally::default();
+        // Each content block of a response is its own line, repeating the
+        // response's usage.
+        let mut counted_responses: HashSet<String> = HashSet::new();
 
         for line in content.lines() {
             if line.trim().is_empty() {
@@ -185,6 +190,36 @@ impl AgentScanner for ClaudeScanner {
                     }
                 }
 
+                // Token usage of assistant responses
+                if msg_type == Some("assistant") {
+                    if let Some(message) = val.get("message") {
+                        let response_model = message.get("model").and_then(|m| m.as_str());
+                        let first_sighting = match message.get("id").and_then(|i| i.as_str()) {
+                            Some(id) => counted_responses.insert(id.to_string()),
+                            None => true,
+                        };
+                        if let (Some(m), Some(u), Some(ts), true) = (
+                            response_model.filter(|m| *m != "<synthetic>"),
+                            message.get("usage"),
+                            val.get("timestamp").and_then(|t| t.as_str()),
+                            first_sighting,
+                        ) {
+                            let tokens = |key: &str| u.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+                            usage.add(
+                                m,
+                                ts,
+                                [
+                                    tokens("input_tokens"),
+                                    tokens("output_tokens"),
+                                    tokens("cache_read_input_tokens"),
+                                    tokens("cache_creation_input_tokens"),
+                                ],
+                                val.get("costUSD").and_then(|c| c.as_f64()),
+                            );
+                        }
+                    }
+                }
+
                 // Extract cwd
                 if cwd.is_none() {
                     if let Some(c) = val.get("cwd").and_then(|c| c.as_str()) {
@@ -195,7 +230,7 @@ impl AgentScanner for ClaudeScanner {
         }
 
         if message_count == 0 {
-            return Ok(ParseResult { sessions: vec![] });
+            return Ok(ParseResult::default());
         }
 
         // Use filename as original_id
@@ -204,6 +239,7 @@ impl AgentScanner for ClaudeScanner {
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_else(|| path.to_string());
 
+        let usage = usage.into_records("claude", &original_id, cwd.as_deref());
         let sessions = vec![IndexedSession {
             id: 0,
             agent_id: "claude".into(),
@@ -219,7 +255,7 @@ impl AgentScanner for ClaudeScanner {
             summary,
         }];
 
-        Ok(ParseResult { sessions })
+        Ok(ParseResult { sessions, usage })
     }
 
     async fn parse_aggregate_stats(&self) -> Option<Vec<DailyAgentStats>> {
@@ -1,5 +1,6 @@
 use crate::models::indexer::IndexedSession;
-use crate::services::indexer::scanner::{AgentScanner, DiscoveredFile, ParseResult, truncate_summary};
+use crate::services::indexer::scanner::{AgentScanner, DiscoveredFile, ParseResult, UsageTally, truncate_summary};
+use crate::services::usage_service::UNKNOWN_MODEL;
 use async_trait::async_trait;
 use std::path::PathBuf;
 
@@ -63,6 +64,10 @@ impl AgentScanner for CodexScanner {
         let mut model: Option<String> = None;
         let mut message_count: u32 = 0;
         let mut summary: Option<String> = None;
+        let mut usage = UsageTally::default();
+        // token_count events carry running totals; usage is what each adds.
+        let mut turn_model: Option<String> = None;
+        let mut totals = [0u64; 3];
 
         for line in content.lines() {
             if line.trim().is_empty() {
@@ -107,6 +112,51 @@ impl AgentScanner for CodexScanner {
                             }
                         }
                     }
+                    "turn_context" => {
+                        if let Some(m) = val
+                            .get("payload")
+                            .and_then(|p| p.get("model"))
+                            .and_then(|m| m.as_str())
+                        {
+                            turn_model = Some(m.to_string());
+                            if model.is_none() {
+                                model = Some(m.to_string());
+                            }
+                        }
+                    }
+                    "event_msg" => {
+                        let payload = val.get("payload");
+                        let total = payload
+                            .filter(|p| p.get("type").and_then(|t| t.as_str()) == Some("token_count"))
+                            .and_then(|p| p.get("info"))
+                            .and_then(|i| i.get("total_token_usage"));
+                        if let (Some(total), Some(ts)) =
+                            (total, val.get("timestamp").and_then(|t| t.as_str()))
+                        {
+                            let count = |key: &str| total.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+                            let current = [
+                                count("input_tokens"),
+                                count("cached_input_tokens"),
+                                count("output_tokens"),
+                            ];
+                            // Totals only shrink when the counter restarts.
+                            let base = if current.iter().zip(totals).any(|(c, t)| *c < t) {
+                                [0; 3]
+                            } else {
+                                totals
+                            };
+                            let [input, cached, output] =
+                                [0, 1, 2].map(|i| current[i] - base[i]);
+                            totals = current;
+                            // Cached tokens are part of Codex's input count.
+                            usage.add(
+                                turn_model.as_deref().or(model.as_deref()).unwrap_or(UNKNOWN_MODEL),
+                                ts,
+                                [input.saturating_sub(cached), output, cached, 0],
+                                None,
+                            );
+                        }
+                    }
                     _ => {}
                 }
 
@@ -130,9 +180,10 @@ impl AgentScanner for CodexScanner {
         });
 
         if message_count == 0 && session_start.is_none() {
-            return Ok(ParseResult { sessions: vec![] });
+            return Ok(ParseResult::default());
         }
 
+        let usage = usage.into_records("codex", &original_id, cwd.as_deref());
         let sessions = vec![IndexedSession {
             id: 0,
             agent_id: "codex".into(),
@@ -148,7 +199,7 @@ impl AgentScanner for CodexScanner {
             summary,
         }];
 
-        Ok(ParseResult { sessions })
+        Ok(ParseResult { sessions, usage })
     }
 }
 
@@ -99,6 +99,7 @@ impl AgentScanner for GeminiScanner {
 
         Ok(ParseResult {
             sessions: vec![session],
+            usage: vec![],
         })
     }
 }
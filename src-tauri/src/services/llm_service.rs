@@ -1,4 +1,5 @@
 use crate::models::*;
+use crate::services::secret_store_service::{self, SecretStore};
 use std::collections::HashMap;
 use tauri_plugin_store::StoreExt;
 
@@ -168,7 +169,29 @@ pub async fn fetch_openai_models(api_key: &str) -> Result<Vec<LLMModel>, String>
     Ok(models)
 }
 
-/// Save LLM settings to store
+fn has_plaintext_api_keys(settings: &LLMSettings) -> bool {
+    settings.providers.values().any(|provider| {
+        provider
+            .api_key
+            .as_deref()
+            .is_some_and(|key| !key.is_empty() && !secret_store_service::is_handle(key))
+    })
+}
+
+/// Move plaintext provider API keys into `secrets`, leaving their handles.
+pub fn seal_api_keys(settings: &mut LLMSettings, secrets: &SecretStore) -> Result<(), String> {
+    for provider in settings.providers.values_mut() {
+        if let Some(key) = provider.api_key.as_mut() {
+            if !key.is_empty() && !secret_store_service::is_handle(key) {
+                *key = secrets.store(key)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Save LLM settings to store. API keys are kept in the secret store and
+/// the settings only hold their handles.
 pub async fn save_llm_settings(
     app: &tauri::AppHandle,
     settings: &LLMSettings,
@@ -177,7 +200,12 @@ pub async fn save_llm_settings(
         .store("settings.json")
         .map_err(|e| format!("Failed to access store: {}", e))?;
 
-    let serialized = serde_json::to_value(settings)
+    let mut settings = settings.clone();
+    if has_plaintext_api_keys(&settings) {
+        seal_api_keys(&mut settings, secret_store_service::global()?)?;
+    }
+
+    let serialized = serde_json::to_value(&settings)
         .map_err(|e| format!("Failed to serialize LLM settings: {}", e))?;
 
     store.set("llm_settings", serialized);
@@ -188,16 +216,35 @@ pub async fn save_llm_settings(
     Ok(())
 }
 
-/// Load LLM settings from store
+/// Load LLM settings from store. Plaintext API keys saved before the secret
+/// store existed are moved into it.
 pub async fn load_llm_settings(app: &tauri::AppHandle) -> Result<LLMSettings, String> {
     let store = app
         .store("settings.json")
         .map_err(|e| format!("Failed to access store: {}", e))?;
 
-    let settings = store
+    let mut settings = store
         .get("llm_settings")
         .and_then(|v| serde_json::from_value(v.clone()).ok())
         .unwrap_or_else(|| get_default_llm_settings());
 
+    if has_plaintext_api_keys(&settings) {
+        let sealed = secret_store_service::global()
+            .and_then(|secrets| seal_api_keys(&mut settings, secrets))
+            .and_then(|_| {
+                serde_json::to_value(&settings)
+                    .map_err(|e| format!("Failed to serialize LLM settings: {}", e))
+            });
+        match sealed {
+            Ok(serialized) => {
+                store.set("llm_settings", serialized);
+                if let Err(e) = store.save() {
+                    eprintln!("⚠️ Failed to save LLM settings with sealed API keys: {}", e);
+                }
+            }
+            Err(e) => eprintln!("⚠️ Left plaintext LLM API keys in settings: {}", e),
+        }
+    }
+
     Ok(settings)
 }
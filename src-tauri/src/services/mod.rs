@@ -1,20 +1,41 @@
+pub mod agent_diagnostics_service;
+pub mod agent_process_pool;
 pub mod agent_status_service;
+pub mod audit_log_service;
 pub mod auth_service;
+pub mod budget_service;
+pub mod checkpoint_service;
 pub mod chat_history_service;
+pub mod claude_stream_service;
 pub mod cli_command_builder;
 pub mod cli_output_service;
 pub mod dashboard_service;
+pub mod env_profile_service;
 pub mod codex_sdk_service;
+pub mod codex_stream_service;
+pub mod event_sink;
 pub mod execution_mode_service;
 pub mod executors;
+pub mod fan_out_service;
 pub mod file_service;
+pub mod git_backend;
 pub mod git_service;
 pub mod indexer;
 pub mod llm_service;
+pub mod permission_policy_service;
 pub mod project_service;
+pub mod project_settings_service;
 pub mod prompt_service;
+pub mod sandbox_service;
+pub mod secret_store_service;
+pub mod session_limits_service;
 pub mod session_manager;
+pub mod replay_service;
+pub mod run_queue_service;
 pub mod sub_agent_service;
+pub mod transcript_service;
+pub mod usage_service;
+pub mod workspace_merge_service;
 pub mod autohand;
 pub mod docs_service;
 pub mod sidecar;
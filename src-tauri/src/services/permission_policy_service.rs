@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use regex::Regex;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::models::ai_agent::StreamChunk;
+use crate::models::permission::{PermissionDecision, PermissionPolicy, PermissionRule};
+use crate::models::protocol::{PermissionTarget, ProtocolEvent};
+use crate::services::event_sink::{EventSink, SharedEventSink};
+use crate::services::session_manager::PermissionResponse;
+
+/// Rules the user allowed for the rest of a conversation.
+pub type SessionAllowances = Arc<Mutex<Vec<PermissionRule>>>;
+
+/// Shell syntax that chains, substitutes or redirects commands.
+const SHELL_METACHARACTERS: &[&str] = &[";", "&", "|", "`", "$(", ">", "<", "\n"];
+
+/// Shell syntax that starts another command, longest first so `&&` is not
+/// taken for two `&`.
+const COMMAND_SEPARATORS: &[&str] = &["&&", "||", "$(", ";", "&", "|", "\n", "`", "(", ")"];
+
+/// Translate a glob into an anchored regex. In paths `*` and `?` stop at
+/// `/` and `**` crosses directories; in commands `*` matches anything. A
+/// backslash makes the next character literal.
+fn glob_regex(pattern: &str, paths: bool) -> Option<Regex> {
+    let mut out = String::from("^");
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if paths && chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    out.push_str("(?:.*/)?");
+                    i += 1;
+                } else {
+                    out.push_str(".*");
+                }
+                i += 1;
+            }
+            '*' if paths => out.push_str("[^/]*"),
+            '*' => out.push_str(".*"),
+            '?' if paths => out.push_str("[^/]"),
+            '?' => out.push('.'),
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                out.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    out.push('$');
+    Regex::new(&out).ok()
+}
+
+/// `text` as a glob that matches only itself.
+pub fn escape_glob(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub fn command_matches(pattern: &str, command: &str) -> bool {
+    glob_regex(pattern.trim(), false).is_some_and(|re| re.is_match(command.trim()))
+}
+
+/// The commands a command line runs: its pieces between separators and
+/// inside substitutions, trimmed, without empty ones. Quotes are not
+/// parsed, so a quoted separator splits too.
+pub fn split_commands(command: &str) -> Vec<&str> {
+    let mut commands = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < command.len() {
+        match COMMAND_SEPARATORS
+            .iter()
+            .find(|separator| command[i..].starts_with(*separator))
+        {
+            Some(separator) => {
+                commands.push(command[start..i].trim());
+                i += separator.len();
+                start = i;
+            }
+            None => i += command[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    commands.push(command[start..].trim());
+    commands.retain(|c| !c.is_empty());
+    commands
+}
+
+/// Whether `pattern` spells out every piece of shell syntax in `command`,
+/// so none of it came from a `*` that could hide a second command.
+pub fn spells_out_metacharacters(pattern: &str, command: &str) -> bool {
+    SHELL_METACHARACTERS
+        .iter()
+        .all(|m| command.matches(m).count() <= pattern.matches(m).count())
+}
+
+pub fn path_matches(pattern: &str, relative_path: &str) -> bool {
+    glob_regex(pattern.trim_start_matches("./"), true).is_some_and(|re| re.is_match(relative_path))
+}
+
+/// `path` relative to the project root, with `/` separators, or `None` when
+/// it lies outside the root.
+pub fn project_relative(path: &str, root: Option<&Path>) -> Option<String> {
+    let path = Path::new(path);
+    let relative = match (path.is_absolute(), root) {
+        (true, Some(root)) => path.strip_prefix(root).ok()?.to_path_buf(),
+        (true, None) => return None,
+        (false, _) => path.to_path_buf(),
+    };
+    let mut parts: Vec<String> = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            _ => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+impl PermissionRule {
+    /// Whether the rule applies to a request for `tool_name` touching
+    /// `target`, with paths taken relative to `root`. An allow rule must
+    /// cover every path and every chained command the request runs, other
+    /// rules any of them.
+    pub fn matches(
+        &self,
+        tool_name: &str,
+        target: Option<&PermissionTarget>,
+        root: Option<&Path>,
+    ) -> bool {
+        if let Some(name) = &self.tool_name {
+            if !name.eq_ignore_ascii_case(tool_name) {
+                return false;
+            }
+        }
+        if let Some(kind) = &self.tool_kind {
+            if target.map(|t| &t.tool_kind) != Some(kind) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.command {
+            let Some(command) = target.and_then(|t| t.command.as_deref()) else {
+                return false;
+            };
+            // A wildcard never allows more than the one command, while any
+            // one chained command is enough to deny or ask.
+            let allows = |command: &str| {
+                command_matches(pattern, command) && spells_out_metacharacters(pattern, command)
+            };
+            let covered = match self.decision {
+                PermissionDecision::Allow => {
+                    let parts = split_commands(command);
+                    allows(command) || (!parts.is_empty() && parts.into_iter().all(allows))
+                }
+                PermissionDecision::Deny | PermissionDecision::Ask => {
+                    command_matches(pattern, command)
+                        || split_commands(command)
+                            .into_iter()
+                            .any(|part| command_matches(pattern, part))
+                }
+            };
+            if !covered {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.path {
+            let paths = target.map(|t| t.paths.as_slice()).unwrap_or_default();
+            if paths.is_empty() {
+                return false;
+            }
+            let in_pattern = |path: &String| {
+                project_relative(path, root).is_some_and(|rel| path_matches(pattern, &rel))
+            };
+            // Allowing needs every path covered; one path is enough to deny
+            // or ask.
+            let covered = match self.decision {
+                PermissionDecision::Allow => paths.iter().all(in_pattern),
+                PermissionDecision::Deny | PermissionDecision::Ask => paths.iter().any(in_pattern),
+            };
+            if !covered {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Short description for the UI, e.g. `deny execute "rm -rf *"`.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![match self.decision {
+            PermissionDecision::Allow => "allow".to_string(),
+            PermissionDecision::Deny => "deny".to_string(),
+            PermissionDecision::Ask => "ask".to_string(),
+        }];
+        if let Some(kind) = &self.tool_kind {
+            let kind = serde_json::to_value(kind)
+                .ok()
+                .and_then(|v| v.as_str().map(String::from))
+                .unwrap_or_default();
+            parts.push(kind);
+        }
+        if let Some(name) = &self.tool_name {
+            parts.push(name.clone());
+        }
+        if let Some(command) = &self.command {
+            parts.push(format!("\"{}\"", command));
+        }
+        if let Some(path) = &self.path {
+            parts.push(format!("in {}", path));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Whether a request can be allowed for the rest of the session: only one
+/// naming a command or paths, since a rule without either would cover the
+/// tool whatever it does.
+pub fn can_remember(target: Option<&PermissionTarget>) -> bool {
+    target.is_some_and(|t| t.command.is_some() || !t.paths.is_empty())
+}
+
+/// The rules to remember when the user allows a request for the rest of the
+/// session: the same tool running exactly the same command on each of the
+/// same paths. Paths outside `root` are not remembered, so requests
+/// touching them are asked about again, and a request that
+/// [cannot be remembered](can_remember) gives no rules.
+pub fn session_rules(
+    tool_name: &str,
+    target: Option<&PermissionTarget>,
+    root: Option<&Path>,
+) -> Vec<PermissionRule> {
+    if !can_remember(target) {
+        return Vec::new();
+    }
+    let rule = PermissionRule {
+        decision: PermissionDecision::Allow,
+        tool_name: Some(tool_name.to_string()),
+        tool_kind: target.map(|t| t.tool_kind.clone()),
+        command: target.and_then(|t| t.command.as_deref()).map(escape_glob),
+        ..PermissionRule::default()
+    };
+    match target {
+        Some(target) if !target.paths.is_empty() => target
+            .paths
+            .iter()
+            .filter_map(|path| project_relative(path, root))
+            .map(|relative| PermissionRule {
+                path: Some(escape_glob(&relative)),
+                ..rule.clone()
+            })
+            .collect(),
+        _ => vec![rule],
+    }
+}
+
+/// Whether the session's allowances cover a request. Each path it touches
+/// may be allowed by a different one.
+fn allowed_for_session(
+    allowed: &[PermissionRule],
+    tool_name: &str,
+    target: Option<&PermissionTarget>,
+    root: Option<&Path>,
+) -> bool {
+    let covers = |target: Option<&PermissionTarget>| {
+        allowed
+            .iter()
+            .any(|rule| rule.matches(tool_name, target, root))
+    };
+    match target {
+        Some(target) if target.paths.len() > 1 => target.paths.iter().all(|path| {
+            covers(Some(&PermissionTarget {
+                paths: vec![path.clone()],
+                ..target.clone()
+            }))
+        }),
+        _ => covers(target),
+    }
+}
+
+/// The global and project policies combined: project rules are checked
+/// first, and a project default overrides the global one.
+#[derive(Debug, Clone, Default)]
+pub struct EffectivePolicy {
+    pub rules: Vec<PermissionRule>,
+    pub default_decision: PermissionDecision,
+}
+
+impl EffectivePolicy {
+    pub fn new(global: &PermissionPolicy, project: Option<&PermissionPolicy>) -> Self {
+        let mut rules = project.map(|p| p.rules.clone()).unwrap_or_default();
+        rules.extend(global.rules.iter().cloned());
+        let default_decision = project
+            .and_then(|p| p.default_decision)
+            .or(global.default_decision)
+            .unwrap_or_default();
+        Self {
+            rules,
+            default_decision,
+        }
+    }
+
+    /// Decide a request. Deny rules always win; then what was allowed for
+    /// the session; then the first matching rule; then the default.
+    pub fn decide(
+        &self,
+        tool_name: &str,
+        target: Option<&PermissionTarget>,
+        root: Option<&Path>,
+        allowed: &[PermissionRule],
+    ) -> (PermissionDecision, String) {
+        let matching = |rule: &&PermissionRule| rule.matches(tool_name, target, root);
+        if let Some(rule) = self
+            .rules
+            .iter()
+            .filter(|r| r.decision == PermissionDecision::Deny)
+            .find(matching)
+        {
+            return (PermissionDecision::Deny, format!("rule: {}", rule.describe()));
+        }
+        if allowed_for_session(allowed, tool_name, target, root) {
+            return (PermissionDecision::Allow, "allowed for this session".to_string());
+        }
+        if let Some(rule) = self.rules.iter().find(matching) {
+            return (rule.decision, format!("rule: {}", rule.describe()));
+        }
+        (self.default_decision, "default".to_string())
+    }
+}
+
+/// Sits between executors and the UI. Permission requests a rule decides
+/// are answered on the session's permission channel and reported as
+/// `PermissionResolved`; the rest are passed on for the user to answer
+/// through [`PermissionGate::answer`].
+pub struct PermissionGate {
+    inner: SharedEventSink,
+    session_id: String,
+    policy: EffectivePolicy,
+    root: Option<PathBuf>,
+    allowances: SessionAllowances,
+    responder: UnboundedSender<PermissionResponse>,
+    pending: Mutex<HashMap<String, PendingRequest>>,
+}
+
+/// A request waiting for the user, kept to report and remember the answer.
+struct PendingRequest {
+    tool_name: String,
+    target: Option<PermissionTarget>,
+    tool_id: Option<String>,
+}
+
+impl PermissionGate {
+    pub fn new(
+        inner: SharedEventSink,
+        session_id: &str,
+        policy: EffectivePolicy,
+        project_root: Option<&str>,
+        allowances: SessionAllowances,
+        responder: UnboundedSender<PermissionResponse>,
+    ) -> Self {
+        Self {
+            inner,
+            session_id: session_id.to_string(),
+            policy,
+            root: project_root.map(PathBuf::from),
+            allowances,
+            responder,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn resolve(
+        &self,
+        request_id: String,
+        request: PendingRequest,
+        approved: bool,
+        reason: String,
+        automatic: bool,
+    ) -> Result<(), String> {
+        self.inner.emit_event(ProtocolEvent::PermissionResolved {
+            session_id: self.session_id.clone(),
+            request_id: request_id.clone(),
+            tool_name: request.tool_name,
+            approved,
+            reason,
+            automatic,
+            target: request.target,
+            tool_id: request.tool_id,
+        });
+        self.responder
+            .send(PermissionResponse {
+                request_id,
+                approved,
+            })
+            .map_err(|_| format!("Session {} executor not running", self.session_id))
+    }
+
+    /// Forward the user's answer to the agent. `remember` allows requests
+    /// like this one for the rest of the session, when the request names a
+    /// command or paths to tie that to; otherwise only this one is allowed.
+    pub fn answer(&self, request_id: String, approved: bool, remember: bool) -> Result<(), String> {
+        let request = self
+            .pending
+            .lock()
+            .ok()
+            .and_then(|mut pending| pending.remove(&request_id))
+            .ok_or_else(|| {
+                format!(
+                    "No pending permission request {} in session {}",
+                    request_id, self.session_id
+                )
+            })?;
+        let reason = if approved && remember && can_remember(request.target.as_ref()) {
+            if let Ok(mut allowances) = self.allowances.lock() {
+                allowances.extend(session_rules(
+                    &request.tool_name,
+                    request.target.as_ref(),
+                    self.root.as_deref(),
+                ));
+            }
+            "allowed for this session"
+        } else {
+            "user"
+        };
+        self.resolve(request_id, request, approved, reason.to_string(), false)
+    }
+}
+
+impl EventSink for PermissionGate {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        self.inner.emit_chunk(chunk);
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        let ProtocolEvent::PermissionRequest {
+            request_id,
+            tool_name,
+            target,
+            tool_id,
+            ..
+        } = &event
+        else {
+            self.inner.emit_event(event);
+            return;
+        };
+
+        let allowed = self
+            .allowances
+            .lock()
+            .map(|a| a.clone())
+            .unwrap_or_default();
+        let (decision, reason) =
+            self.policy
+                .decide(tool_name, target.as_ref(), self.root.as_deref(), &allowed);
+        let request = PendingRequest {
+            tool_name: tool_name.clone(),
+            target: target.clone(),
+            tool_id: tool_id.clone(),
+        };
+        match decision {
+            PermissionDecision::Ask => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.insert(request_id.clone(), request);
+                }
+                self.inner.emit_event(event);
+            }
+            PermissionDecision::Allow | PermissionDecision::Deny => {
+                let approved = decision == PermissionDecision::Allow;
+                let _ = self.resolve(request_id.clone(), request, approved, reason, true);
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::models::project::ProjectSettings;
+
+const COMMANDER_DIR: &str = ".commander";
+const PROJECT_SETTINGS_FILE: &str = "settings.json";
+
+/// `<project>/.commander/settings.json`
+pub fn project_settings_path(project_path: &str) -> PathBuf {
+    Path::new(project_path)
+        .join(COMMANDER_DIR)
+        .join(PROJECT_SETTINGS_FILE)
+}
+
+/// Load a project's settings; a missing file yields the defaults.
+pub fn load_project_settings(project_path: &str) -> Result<ProjectSettings, String> {
+    let path = project_settings_path(project_path);
+    if !path.exists() {
+        return Ok(ProjectSettings::default());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read project settings: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse project settings: {}", e))
+}
+
+pub fn save_project_settings(project_path: &str, settings: &ProjectSettings) -> Result<(), String> {
+    let path = project_settings_path(project_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create .commander directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize project settings: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write project settings: {}", e))
+}
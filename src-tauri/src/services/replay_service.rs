@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::services::event_sink::{EventRecord, RecordedEvent, SharedEventSink};
+
+/// How a recorded session is paced when played back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayPace {
+    /// Keep the gaps between events exactly as recorded.
+    Original,
+    /// Divide every recorded gap by the given factor.
+    Speed(f64),
+    /// Emit one event per [`ReplayCommand::Step`].
+    Step,
+}
+
+impl ReplayPace {
+    /// Parse the `mode` argument of `replay_session`: `original`, `speed`
+    /// (requires a positive `speed` factor) or `step`.
+    pub fn parse(mode: &str, speed: Option<f64>) -> Result<Self, String> {
+        match mode {
+            "original" => Ok(ReplayPace::Original),
+            "speed" => match speed {
+                Some(factor) if factor.is_finite() && factor > 0.0 => Ok(ReplayPace::Speed(factor)),
+                Some(factor) => Err(format!("Replay speed must be a positive number, got {}", factor)),
+                None => Err("Replay mode 'speed' requires a speed factor".to_string()),
+            },
+            "step" => Ok(ReplayPace::Step),
+            other => Err(format!(
+                "Unknown replay mode '{}'. Expected original, speed or step",
+                other
+            )),
+        }
+    }
+
+    /// Wait before an event recorded `gap_ms` after the previous one.
+    /// `None` means the replay waits for an explicit step instead.
+    pub fn delay(&self, gap_ms: i64) -> Option<Duration> {
+        let gap_ms = gap_ms.max(0) as f64;
+        match self {
+            ReplayPace::Original => Some(Duration::from_secs_f64(gap_ms / 1000.0)),
+            ReplayPace::Speed(factor) => Some(Duration::from_secs_f64(gap_ms / 1000.0 / factor)),
+            ReplayPace::Step => None,
+        }
+    }
+}
+
+/// Control messages for a running replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayCommand {
+    /// Emit the next event now. In timed modes this skips the current wait.
+    Step,
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayOutcome {
+    Completed,
+    Stopped,
+}
+
+/// Returned to the frontend when a replay starts.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayInfo {
+    pub replay_id: String,
+    /// Session id the replayed events are emitted under.
+    pub session_id: String,
+    pub total_events: usize,
+    /// Recorded length of the run in milliseconds.
+    pub duration_ms: i64,
+}
+
+/// Decode a record, emitting it under `session_id` instead of the recorded id
+/// when one is given so a replay does not collide with a live session.
+pub fn retarget_record(record: &EventRecord, session_id: Option<&str>) -> Result<RecordedEvent, String> {
+    let Some(session_id) = session_id else {
+        return record.to_recorded();
+    };
+
+    let mut record = record.clone();
+    let target = match record.payload.get_mut("data") {
+        // Protocol events are serialized as {"type": ..., "data": {...}}.
+        Some(data) if data.is_object() => data,
+        _ => &mut record.payload,
+    };
+    if let Some(obj) = target.as_object_mut() {
+        if obj.contains_key("session_id") {
+            obj.insert("session_id".to_string(), serde_json::Value::String(session_id.to_string()));
+        }
+    }
+    record.to_recorded()
+}
+
+/// Play `records` into `sink` with the given pacing until they run out or a
+/// `Stop` arrives. Records that cannot be decoded are skipped.
+///
+/// The replay also stops when every command sender has been dropped.
+pub async fn play_records(
+    sink: &SharedEventSink,
+    records: &[EventRecord],
+    pace: ReplayPace,
+    session_id: Option<&str>,
+    commands: &mut mpsc::UnboundedReceiver<ReplayCommand>,
+) -> ReplayOutcome {
+    let mut previous_ts: Option<i64> = None;
+
+    for record in records {
+        let Ok(event) = retarget_record(record, session_id) else {
+            continue;
+        };
+        let gap_ms = previous_ts.map(|ts| record.ts - ts).unwrap_or(0);
+        previous_ts = Some(record.ts);
+
+        match pace.delay(gap_ms) {
+            None => match commands.recv().await {
+                Some(ReplayCommand::Step) => {}
+                Some(ReplayCommand::Stop) | None => return ReplayOutcome::Stopped,
+            },
+            Some(delay) if !delay.is_zero() => {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    command = commands.recv() => match command {
+                        Some(ReplayCommand::Step) => {}
+                        Some(ReplayCommand::Stop) | None => return ReplayOutcome::Stopped,
+                    },
+                }
+            }
+            Some(_) => {
+                if let Ok(ReplayCommand::Stop) = commands.try_recv() {
+                    return ReplayOutcome::Stopped;
+                }
+            }
+        }
+
+        match event {
+            RecordedEvent::Chunk(chunk) => sink.emit_chunk(chunk),
+            RecordedEvent::Protocol(event) => sink.emit_event(event),
+        }
+    }
+
+    ReplayOutcome::Completed
+}
+
+/// Control channels of the replays currently running, keyed by replay id.
+#[derive(Default)]
+pub struct ReplayRegistry {
+    replays: HashMap<String, mpsc::UnboundedSender<ReplayCommand>>,
+}
+
+impl ReplayRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a replay and return the receiving end of its control channel.
+    pub fn start(&mut self, replay_id: &str) -> mpsc::UnboundedReceiver<ReplayCommand> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.replays.insert(replay_id.to_string(), tx);
+        rx
+    }
+
+    pub fn send(&self, replay_id: &str, command: ReplayCommand) -> Result<(), String> {
+        self.replays
+            .get(replay_id)
+            .ok_or_else(|| format!("No active replay: {}", replay_id))?
+            .send(command)
+            .map_err(|_| format!("Replay {} has already finished", replay_id))
+    }
+
+    /// Forget a replay once its task has ended.
+    pub fn finish(&mut self, replay_id: &str) {
+        self.replays.remove(replay_id);
+    }
+}
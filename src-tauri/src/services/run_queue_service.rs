@@ -0,0 +1,333 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+use crate::models::ai_agent::AllAgentSettings;
+
+/// How many finished runs feed the ETA estimate.
+const DURATION_HISTORY: usize = 20;
+
+/// Concurrency caps applied when starting queued runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyLimits {
+    pub global: usize,
+    pub per_project: Option<usize>,
+    pub per_agent: Option<usize>,
+}
+
+impl ConcurrencyLimits {
+    pub fn from_settings(settings: &AllAgentSettings) -> Self {
+        Self {
+            // A cap of 0 would stall the queue forever; treat it as 1.
+            global: settings.max_concurrent_sessions.max(1) as usize,
+            per_project: settings
+                .max_concurrent_sessions_per_project
+                .map(|n| n.max(1) as usize),
+            per_agent: settings
+                .max_concurrent_sessions_per_agent
+                .map(|n| n.max(1) as usize),
+        }
+    }
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self::from_settings(&AllAgentSettings::default())
+    }
+}
+
+/// A prompt waiting for a free slot.
+struct QueuedRun {
+    session_id: String,
+    agent: String,
+    project: Option<String>,
+    priority: i32,
+    seq: u64,
+    enqueued_at: i64,
+    start: oneshot::Sender<()>,
+}
+
+struct RunningRun {
+    agent: String,
+    project: Option<String>,
+    started_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningRunStatus {
+    pub session_id: String,
+    pub agent: String,
+    pub project: Option<String>,
+    pub started_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedRunStatus {
+    pub session_id: String,
+    pub agent: String,
+    pub project: Option<String>,
+    pub priority: i32,
+    /// 1-based position in the queue.
+    pub position: usize,
+    pub enqueued_at: i64,
+    /// Estimated seconds until the run starts; `None` until a run has
+    /// finished and there is a duration to estimate from.
+    pub eta_seconds: Option<i64>,
+}
+
+/// What the frontend gets from `get_run_queue` and the `run-queue-updated` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunQueueSnapshot {
+    pub max_concurrent_sessions: usize,
+    pub max_concurrent_sessions_per_project: Option<usize>,
+    pub max_concurrent_sessions_per_agent: Option<usize>,
+    pub running: Vec<RunningRunStatus>,
+    pub queued: Vec<QueuedRunStatus>,
+}
+
+/// Sits in front of the executors and decides when each prompt may start.
+///
+/// Runs are started in priority order (higher first, FIFO within the same
+/// priority). A run blocked by its project or agent cap does not hold up runs
+/// for other projects or agents behind it.
+pub struct RunQueue {
+    limits: ConcurrencyLimits,
+    queued: Vec<QueuedRun>,
+    running: HashMap<String, RunningRun>,
+    durations: VecDeque<i64>,
+    next_seq: u64,
+}
+
+impl RunQueue {
+    pub fn new(limits: ConcurrencyLimits) -> Self {
+        Self {
+            limits,
+            queued: Vec::new(),
+            running: HashMap::new(),
+            durations: VecDeque::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Apply new caps (e.g. after the settings changed) and start whatever
+    /// they now allow.
+    pub fn set_limits(&mut self, limits: ConcurrencyLimits) {
+        self.limits = limits;
+        self.dispatch();
+    }
+
+    /// Queue a run. The returned receiver resolves once the run may start;
+    /// it errors if the run is cancelled while still queued.
+    pub fn enqueue(
+        &mut self,
+        session_id: &str,
+        agent: &str,
+        project: Option<&str>,
+        priority: i32,
+    ) -> Result<oneshot::Receiver<()>, String> {
+        if self.running.contains_key(session_id) || self.position(session_id).is_some() {
+            return Err(format!("Session {} is already queued or running", session_id));
+        }
+
+        let (start, started) = oneshot::channel();
+        self.queued.push(QueuedRun {
+            session_id: session_id.to_string(),
+            agent: agent.to_string(),
+            project: project.map(str::to_string),
+            priority,
+            seq: self.next_seq,
+            enqueued_at: chrono::Utc::now().timestamp(),
+            start,
+        });
+        self.next_seq += 1;
+        self.sort();
+        self.dispatch();
+        Ok(started)
+    }
+
+    /// Mark a running session as done, freeing its slot for the next run.
+    pub fn complete(&mut self, session_id: &str) {
+        if let Some(run) = self.running.remove(session_id) {
+            let duration = chrono::Utc::now().timestamp() - run.started_at;
+            self.durations.push_back(duration.max(0));
+            if self.durations.len() > DURATION_HISTORY {
+                self.durations.pop_front();
+            }
+        }
+        self.dispatch();
+    }
+
+    /// Drop a queued run. Running sessions must be terminated instead.
+    pub fn cancel(&mut self, session_id: &str) -> Result<(), String> {
+        match self.queued.iter().position(|r| r.session_id == session_id) {
+            Some(index) => {
+                // Dropping the sender wakes the waiting task with an error.
+                self.queued.remove(index);
+                self.dispatch();
+                Ok(())
+            }
+            None if self.running.contains_key(session_id) => Err(format!(
+                "Session {} is already running; terminate it instead",
+                session_id
+            )),
+            None => Err(format!("Session {} is not queued", session_id)),
+        }
+    }
+
+    /// Change the priority of a queued run.
+    pub fn reprioritize(&mut self, session_id: &str, priority: i32) -> Result<(), String> {
+        let run = self
+            .queued
+            .iter_mut()
+            .find(|r| r.session_id == session_id)
+            .ok_or_else(|| format!("Session {} is not queued", session_id))?;
+        run.priority = priority;
+        self.sort();
+        self.dispatch();
+        Ok(())
+    }
+
+    /// 1-based queue position of a waiting session.
+    pub fn position(&self, session_id: &str) -> Option<usize> {
+        self.queued
+            .iter()
+            .position(|r| r.session_id == session_id)
+            .map(|i| i + 1)
+    }
+
+    /// Drop every queued run; running sessions are left alone.
+    pub fn cancel_all(&mut self) {
+        self.queued.clear();
+    }
+
+    pub fn snapshot(&self) -> RunQueueSnapshot {
+        let now = chrono::Utc::now().timestamp();
+        let etas = self.estimate_starts(now);
+
+        let mut running: Vec<RunningRunStatus> = self
+            .running
+            .iter()
+            .map(|(id, run)| RunningRunStatus {
+                session_id: id.clone(),
+                agent: run.agent.clone(),
+                project: run.project.clone(),
+                started_at: run.started_at,
+            })
+            .collect();
+        running.sort_by_key(|r| r.started_at);
+
+        let queued = self
+            .queued
+            .iter()
+            .enumerate()
+            .map(|(i, run)| QueuedRunStatus {
+                session_id: run.session_id.clone(),
+                agent: run.agent.clone(),
+                project: run.project.clone(),
+                priority: run.priority,
+                position: i + 1,
+                enqueued_at: run.enqueued_at,
+                eta_seconds: etas.as_ref().map(|e| e[i]),
+            })
+            .collect();
+
+        RunQueueSnapshot {
+            max_concurrent_sessions: self.limits.global,
+            max_concurrent_sessions_per_project: self.limits.per_project,
+            max_concurrent_sessions_per_agent: self.limits.per_agent,
+            running,
+            queued,
+        }
+    }
+
+    fn sort(&mut self) {
+        self.queued
+            .sort_by(|a, b| b.priority.cmp(&a.priority).then(a.seq.cmp(&b.seq)));
+    }
+
+    fn count_running(&self, matches: impl Fn(&RunningRun) -> bool) -> usize {
+        self.running.values().filter(|r| matches(r)).count()
+    }
+
+    fn has_slot(&self, run: &QueuedRun) -> bool {
+        if self.running.len() >= self.limits.global {
+            return false;
+        }
+        if let (Some(cap), Some(project)) = (self.limits.per_project, run.project.as_deref()) {
+            if self.count_running(|r| r.project.as_deref() == Some(project)) >= cap {
+                return false;
+            }
+        }
+        if let Some(cap) = self.limits.per_agent {
+            if self.count_running(|r| r.agent == run.agent) >= cap {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Start every queued run the caps allow, in queue order.
+    fn dispatch(&mut self) {
+        let mut index = 0;
+        while index < self.queued.len() {
+            if self.running.len() >= self.limits.global {
+                break;
+            }
+            if !self.has_slot(&self.queued[index]) {
+                index += 1;
+                continue;
+            }
+
+            let run = self.queued.remove(index);
+            // The waiting task is gone (e.g. the app is shutting down); skip it.
+            if run.start.send(()).is_err() {
+                continue;
+            }
+            self.running.insert(
+                run.session_id,
+                RunningRun {
+                    agent: run.agent,
+                    project: run.project,
+                    started_at: chrono::Utc::now().timestamp(),
+                },
+            );
+        }
+    }
+
+    /// Seconds until each queued run is expected to start, assuming every run
+    /// takes the average recent duration and ignoring per-project/agent caps.
+    fn estimate_starts(&self, now: i64) -> Option<Vec<i64>> {
+        if self.durations.is_empty() {
+            return None;
+        }
+        let average = self.durations.iter().sum::<i64>() / self.durations.len() as i64;
+
+        // When each slot frees up, relative to now.
+        let mut slots: Vec<i64> = self
+            .running
+            .values()
+            .map(|r| (average - (now - r.started_at)).max(0))
+            .collect();
+        slots.resize(self.limits.global.max(slots.len()).max(1), 0);
+
+        let mut etas = Vec::with_capacity(self.queued.len());
+        for _ in &self.queued {
+            let (slot, free_at) = slots
+                .iter()
+                .copied()
+                .enumerate()
+                .min_by_key(|(_, t)| *t)
+                .unwrap_or((0, 0));
+            etas.push(free_at);
+            slots[slot] = free_at + average;
+        }
+        Some(etas)
+    }
+}
+
+impl Default for RunQueue {
+    fn default() -> Self {
+        Self::new(ConcurrencyLimits::default())
+    }
+}
@@ -0,0 +1,307 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::models::ai_agent::{AgentSettings, StreamChunk};
+use crate::models::protocol::{ProtocolEvent, SandboxAccess};
+use crate::services::event_sink::{EventSink, SharedEventSink};
+
+/// Bubblewrap sets up the namespaces; it runs unprivileged where user
+/// namespaces are enabled.
+const BWRAP: &str = "bwrap";
+
+/// Blocked accesses reported per run; the rest only show in the output.
+const MAX_BLOCKED_REPORTS: usize = 20;
+
+/// Longest output line quoted in a blocked access report, in characters.
+const MAX_DETAIL_CHARS: usize = 300;
+
+/// State the agents write under the home directory on every run, keyed by
+/// the start of the agent's binary name.
+const AGENT_STATE_PATHS: &[(&str, &[&str])] = &[
+    ("claude", &[".claude", ".claude.json"]),
+    ("codex", &[".codex"]),
+    ("gemini", &[".gemini"]),
+    ("cursor", &[".cursor"]),
+    ("copilot", &[".copilot"]),
+    ("opencode", &[".local/share/opencode"]),
+    ("autohand", &[".autohand"]),
+    ("pi", &[".pi"]),
+    ("vibe", &[".vibe"]),
+    ("amp", &[".config/amp", ".local/share/amp", ".cache/amp"]),
+];
+
+static WRITE_BLOCKED_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)read-only file system|\bEROFS\b").expect("valid write pattern"));
+
+static NETWORK_BLOCKED_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)network is unreachable|\b(ENETUNREACH|EAI_AGAIN)\b|temporary failure in name resolution|could not resolve host|getaddrinfo",
+    )
+    .expect("valid network pattern")
+});
+
+/// Where a sandboxed agent may write and whether it may reach the network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxProfile {
+    /// Existing paths bound writable; everything else is read-only.
+    pub writable: Vec<PathBuf>,
+    /// Paths kept read-only even inside a writable path: the project's git
+    /// directories, whose config and hooks run whenever Commander calls git.
+    pub read_only: Vec<PathBuf>,
+    pub allow_network: bool,
+}
+
+impl SandboxProfile {
+    /// The sandbox a run of `agent` in `working_dir` gets, or `None` when
+    /// the agent's settings leave it off.
+    pub fn for_run(settings: &AgentSettings, agent: &str, working_dir: &str) -> Option<Self> {
+        let sandbox = &settings.process_sandbox;
+        if !sandbox.enabled {
+            return None;
+        }
+
+        let mut candidates = Vec::new();
+        let mut git = Vec::new();
+        if !working_dir.is_empty() {
+            let project = PathBuf::from(working_dir);
+            git = git_dirs(&project);
+            candidates.push(project);
+        }
+        if let Some(home) = dirs::home_dir() {
+            candidates.extend(agent_state_paths(agent).iter().map(|p| home.join(p)));
+        }
+        candidates.extend(sandbox.writable_paths.iter().map(|p| expand_home(p)));
+
+        Some(Self {
+            writable: existing(candidates),
+            read_only: existing(git),
+            allow_network: sandbox.allow_network,
+        })
+    }
+
+    /// Bubblewrap options that set up the sandbox, up to the command.
+    pub fn bwrap_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "--die-with-parent",
+            "--new-session",
+            "--unshare-user",
+            "--unshare-ipc",
+            "--unshare-pid",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        if !self.allow_network {
+            args.push("--unshare-net".to_string());
+        }
+        // Later mounts win, so the writable binds come after the read-only root.
+        for arg in [
+            "--ro-bind",
+            "/",
+            "/",
+            "--dev-bind",
+            "/dev",
+            "/dev",
+            "--proc",
+            "/proc",
+            "--tmpfs",
+            "/tmp",
+        ] {
+            args.push(arg.to_string());
+        }
+        for path in &self.writable {
+            let path = path.to_string_lossy().to_string();
+            args.extend(["--bind".to_string(), path.clone(), path]);
+        }
+        for path in &self.read_only {
+            let path = path.to_string_lossy().to_string();
+            args.extend(["--ro-bind".to_string(), path.clone(), path]);
+        }
+        args.extend(["--setenv", "COMMANDER_SANDBOX", "1"].map(String::from));
+        args
+    }
+
+    /// `program args` as the program and arguments that run it inside the
+    /// sandbox. The working directory carries over.
+    pub fn wrap(&self, program: &str, args: &[String]) -> Result<(String, Vec<String>), String> {
+        if !cfg!(target_os = "linux") {
+            return Err("the process sandbox is only available on Linux".to_string());
+        }
+        let bwrap = which::which(BWRAP)
+            .map_err(|_| "the process sandbox needs bubblewrap (bwrap) installed".to_string())?;
+        let mut wrapped = self.bwrap_args();
+        wrapped.push("--".to_string());
+        wrapped.push(program.to_string());
+        wrapped.extend(args.iter().cloned());
+        Ok((bwrap.to_string_lossy().to_string(), wrapped))
+    }
+
+    /// A tokio command running `program args` inside the sandbox, or as is
+    /// without one.
+    pub fn command(
+        sandbox: Option<&SandboxProfile>,
+        program: &Path,
+        args: &[String],
+    ) -> Result<tokio::process::Command, String> {
+        let (program, args) = match sandbox {
+            Some(sandbox) => sandbox.wrap(&program.to_string_lossy(), args)?,
+            None => (program.to_string_lossy().to_string(), args.to_vec()),
+        };
+        let mut command = tokio::process::Command::new(program);
+        command.args(args);
+        Ok(command)
+    }
+
+    /// One line for the run's output saying what the agent may do.
+    pub fn describe(&self) -> String {
+        let writable = if self.writable.is_empty() {
+            "nothing".to_string()
+        } else {
+            self.writable
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let network = if self.allow_network { "on" } else { "off" };
+        format!("🔒 Sandboxed: writable {}; network {}\n", writable, network)
+    }
+}
+
+/// Paths under the home directory where `agent` keeps its own state, which
+/// stay writable inside the sandbox.
+pub fn agent_state_paths(agent: &str) -> &'static [&'static str] {
+    let name = Path::new(agent)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    AGENT_STATE_PATHS
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map_or(&[], |(_, paths)| paths)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Canonical forms of the `paths` that exist, without duplicates.
+fn existing(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let Ok(path) = std::fs::canonicalize(&path) else {
+            continue;
+        };
+        if !found.contains(&path) {
+            found.push(path);
+        }
+    }
+    found
+}
+
+/// The git directories of the repository at `project`. A git worktree keeps
+/// its own in the main repository's git directory, which it also reads.
+fn git_dirs(project: &Path) -> Vec<PathBuf> {
+    let dot_git = project.join(".git");
+    if dot_git.is_dir() {
+        return vec![dot_git];
+    }
+    let Ok(dot_git) = std::fs::read_to_string(dot_git) else {
+        return Vec::new();
+    };
+    let Some(git_dir) = dot_git.trim().strip_prefix("gitdir:") else {
+        return Vec::new();
+    };
+    let git_dir = project.join(git_dir.trim());
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+        .map(|common| git_dir.join(common.trim()))
+        .ok();
+    std::iter::once(git_dir).chain(common_dir).collect()
+}
+
+/// Recognise an access the sandbox blocked in a line of agent output.
+/// Network failures only count when the sandbox turned the network off.
+pub fn blocked_access(line: &str, network_off: bool) -> Option<SandboxAccess> {
+    if WRITE_BLOCKED_RE.is_match(line) {
+        Some(SandboxAccess::Write)
+    } else if network_off && NETWORK_BLOCKED_RE.is_match(line) {
+        Some(SandboxAccess::Network)
+    } else {
+        None
+    }
+}
+
+/// Watches a sandboxed run's output and tool results for blocked writes and
+/// connects, reporting each distinct one as `SandboxBlocked`.
+pub struct SandboxMonitor {
+    inner: SharedEventSink,
+    session_id: String,
+    network_off: bool,
+    reported: Mutex<HashSet<(SandboxAccess, String)>>,
+}
+
+impl SandboxMonitor {
+    pub fn shared(
+        inner: SharedEventSink,
+        session_id: &str,
+        profile: &SandboxProfile,
+    ) -> SharedEventSink {
+        Arc::new(Self {
+            inner,
+            session_id: session_id.to_string(),
+            network_off: !profile.allow_network,
+            reported: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn inspect(&self, text: &str) {
+        for line in text.lines() {
+            let Some(access) = blocked_access(line, self.network_off) else {
+                continue;
+            };
+            let detail: String = line.trim().chars().take(MAX_DETAIL_CHARS).collect();
+            let first = match self.reported.lock() {
+                Ok(mut reported) => {
+                    reported.len() < MAX_BLOCKED_REPORTS
+                        && reported.insert((access, detail.clone()))
+                }
+                Err(_) => false,
+            };
+            if first {
+                self.inner.emit_event(ProtocolEvent::SandboxBlocked {
+                    session_id: self.session_id.clone(),
+                    access,
+                    detail,
+                });
+            }
+        }
+    }
+}
+
+impl EventSink for SandboxMonitor {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        let content = chunk.content.clone();
+        self.inner.emit_chunk(chunk);
+        self.inspect(&content);
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        let text = match &event {
+            ProtocolEvent::ToolUpdate { output, .. } | ProtocolEvent::ToolEnd { output, .. } => {
+                output.clone()
+            }
+            ProtocolEvent::Error { message, .. } => Some(message.clone()),
+            _ => None,
+        };
+        self.inner.emit_event(event);
+        if let Some(text) = text {
+            self.inspect(&text);
+        }
+    }
+}
@@ -0,0 +1,699 @@
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+use crate::APP_IDENTIFIER;
+
+/// Configs hold `secret://<id>` in place of the secret itself.
+pub const HANDLE_PREFIX: &str = "secret://";
+
+/// File name of the encrypted store, kept in the app's data directory.
+pub const SECRETS_FILE: &str = "secrets.json";
+
+/// Random key material mixed into the machine key, next to the store.
+const MACHINE_KEY_FILE: &str = "machine.key";
+
+/// Derive the store key from this passphrase instead of the machine key.
+pub const PASSPHRASE_ENV: &str = "COMMANDER_SECRET_PASSPHRASE";
+
+/// `keyring` keeps secrets in the desktop keyring (Secret Service) on Linux.
+pub const BACKEND_ENV: &str = "COMMANDER_SECRET_BACKEND";
+
+/// Config keys whose values are secrets.
+pub const SECRET_KEYS: &[&str] = &["apiKey", "api_key"];
+
+/// Encrypted with every store key, to tell a wrong key from a corrupt entry.
+const CHECK_PLAINTEXT: &[u8] = b"commander-secret-store";
+const STORE_VERSION: u32 = 1;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Shorter values are not redacted by value; they would match ordinary text.
+const MIN_REDACTED_LEN: usize = 8;
+
+static SECRET_PATTERNS: Lazy<Vec<(Regex, &'static str)>> = Lazy::new(|| {
+    [
+        (r"\b(sk|rk|pk)-[A-Za-z0-9_\-]{16,}", REDACTED),
+        (
+            r"(?i)\b(bearer)\s+[A-Za-z0-9._~+/\-]{16,}=*",
+            "$1 [REDACTED]",
+        ),
+        (r"\bAIza[0-9A-Za-z_\-]{35}\b", REDACTED),
+        (r"\bgh[pousr]_[A-Za-z0-9]{36,}\b", REDACTED),
+    ]
+    .into_iter()
+    .map(|(pattern, with)| (Regex::new(pattern).expect("valid secret pattern"), with))
+    .collect()
+});
+
+/// Every secret value the process has stored or resolved, longest first.
+static KNOWN_SECRETS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+static GLOBAL_STORE: OnceCell<SecretStore> = OnceCell::new();
+
+/// Whether a config value is a handle rather than the secret itself.
+pub fn is_handle(value: &str) -> bool {
+    value.starts_with(HANDLE_PREFIX)
+}
+
+fn handle_id(handle: &str) -> Result<&str, String> {
+    handle
+        .strip_prefix(HANDLE_PREFIX)
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| "Not a secret handle".to_string())
+}
+
+/// Redact `value` from everything [`redact`] sees from now on.
+pub fn register_secret(value: &str) {
+    let value = value.trim();
+    if value.len() < MIN_REDACTED_LEN || is_handle(value) {
+        return;
+    }
+    if let Ok(mut known) = KNOWN_SECRETS.write() {
+        if !known.iter().any(|k| k == value) {
+            known.push(value.to_string());
+            known.sort_by_key(|k| std::cmp::Reverse(k.len()));
+        }
+    }
+}
+
+/// `text` with known secrets and anything shaped like an API key or bearer
+/// token replaced. Used on every error message and log line that can carry
+/// agent or provider output.
+pub fn redact(text: &str) -> String {
+    let mut redacted = text.to_string();
+    if let Ok(known) = KNOWN_SECRETS.read() {
+        for secret in known.iter() {
+            if redacted.contains(secret.as_str()) {
+                redacted = redacted.replace(secret.as_str(), REDACTED);
+            }
+        }
+    }
+    for (pattern, with) in SECRET_PATTERNS.iter() {
+        if pattern.is_match(&redacted) {
+            redacted = pattern.replace_all(&redacted, *with).into_owned();
+        }
+    }
+    redacted
+}
+
+/// A short digest of a secret, for telling values apart without keeping them.
+pub fn fingerprint(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Where the encrypted store key comes from.
+#[derive(Clone)]
+pub enum KeySource {
+    /// Derived from a passphrase the user supplies.
+    Passphrase(String),
+    /// Derived from the machine id and a random key file only this user can
+    /// read, so the store opens without a prompt on this machine alone.
+    Machine,
+}
+
+impl KeySource {
+    /// The passphrase in `COMMANDER_SECRET_PASSPHRASE`, or the machine key.
+    pub fn from_env() -> Self {
+        match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) if !passphrase.is_empty() => KeySource::Passphrase(passphrase),
+            _ => KeySource::Machine,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            KeySource::Passphrase(_) => "passphrase",
+            KeySource::Machine => "machine",
+        }
+    }
+
+    fn key_material(&self, store_dir: &Path) -> Result<Vec<u8>, String> {
+        match self {
+            KeySource::Passphrase(passphrase) => Ok(passphrase.as_bytes().to_vec()),
+            KeySource::Machine => {
+                let mut material = machine_id().into_bytes();
+                material.extend(machine_key(store_dir)?);
+                Ok(material)
+            }
+        }
+    }
+}
+
+fn machine_id() -> String {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn machine_key(store_dir: &Path) -> Result<Vec<u8>, String> {
+    let path = store_dir.join(MACHINE_KEY_FILE);
+    match std::fs::read(&path) {
+        Ok(key) if !key.is_empty() => Ok(key),
+        _ => {
+            let mut key = vec![0u8; 32];
+            OsRng.fill_bytes(&mut key);
+            write_private(&path, &key)?;
+            Ok(key)
+        }
+    }
+}
+
+/// Write a file only the current user can read, replacing it atomically.
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let tmp = path.with_extension("tmp");
+    // A leftover file would keep its permissions through the rewrite.
+    let _ = std::fs::remove_file(&tmp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&tmp)
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    file.write_all(contents)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Somewhere secrets are kept, by id.
+trait SecretBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn get(&self, id: &str) -> Result<Option<String>, String>;
+    fn set(&self, id: &str, value: &str) -> Result<(), String>;
+    fn delete(&self, id: &str) -> Result<(), String>;
+    /// Every stored value, where the backend can list them.
+    fn values(&self) -> Result<Vec<String>, String>;
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SecretsFile {
+    version: u32,
+    key_source: String,
+    salt: String,
+    check: String,
+    #[serde(default)]
+    secrets: BTreeMap<String, String>,
+}
+
+/// Secrets sealed with ChaCha20-Poly1305 under an Argon2id-derived key, in a
+/// file only the current user can read. Each entry is bound to its id.
+struct EncryptedFile {
+    path: PathBuf,
+    cipher: ChaCha20Poly1305,
+    /// Serialises read-modify-write cycles within the process.
+    lock: Mutex<()>,
+}
+
+impl EncryptedFile {
+    fn open(path: &Path, key_source: &KeySource) -> Result<Self, String> {
+        let store_dir = path.parent().unwrap_or(Path::new("."));
+        let existing = Self::read_file(path)?;
+        let salt = match &existing {
+            Some(file) => BASE64
+                .decode(&file.salt)
+                .map_err(|_| format!("Secret store at {} is corrupt", path.display()))?,
+            None => {
+                let mut salt = vec![0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                salt
+            }
+        };
+
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(&key_source.key_material(store_dir)?, &salt, &mut key)
+            .map_err(|e| format!("Failed to derive the secret store key: {}", e))?;
+        let store = Self {
+            path: path.to_path_buf(),
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            lock: Mutex::new(()),
+        };
+
+        match existing {
+            Some(file) => {
+                if store.open_sealed(&file.check, "").as_deref() != Some(CHECK_PLAINTEXT) {
+                    return Err(format!(
+                        "Secret store at {} is locked with a different {} key; set {} to the passphrase it was created with",
+                        path.display(),
+                        file.key_source,
+                        PASSPHRASE_ENV
+                    ));
+                }
+            }
+            None => store.write_file(&SecretsFile {
+                version: STORE_VERSION,
+                key_source: key_source.name().to_string(),
+                salt: BASE64.encode(&salt),
+                check: store.seal(CHECK_PLAINTEXT, "")?,
+                secrets: BTreeMap::new(),
+            })?,
+        }
+        Ok(store)
+    }
+
+    fn read_file(path: &Path) -> Result<Option<SecretsFile>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .map(Some)
+                .map_err(|e| format!("Failed to parse secret store at {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!(
+                "Failed to read secret store at {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    fn load(&self) -> Result<SecretsFile, String> {
+        Self::read_file(&self.path)?
+            .ok_or_else(|| format!("Secret store at {} is missing", self.path.display()))
+    }
+
+    fn write_file(&self, file: &SecretsFile) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(file)
+            .map_err(|e| format!("Failed to serialize secret store: {}", e))?;
+        write_private(&self.path, json.as_bytes())
+    }
+
+    fn seal(&self, plaintext: &[u8], id: &str) -> Result<String, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: id.as_bytes(),
+                },
+            )
+            .map_err(|_| "Failed to encrypt secret".to_string())?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend(sealed);
+        Ok(BASE64.encode(bytes))
+    }
+
+    fn open_sealed(&self, sealed: &str, id: &str) -> Option<Vec<u8>> {
+        let bytes = BASE64.decode(sealed).ok()?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: id.as_bytes(),
+                },
+            )
+            .ok()
+    }
+
+    fn decrypt(&self, id: &str, sealed: &str) -> Result<String, String> {
+        self.open_sealed(sealed, id)
+            .and_then(|plain| String::from_utf8(plain).ok())
+            .ok_or_else(|| format!("Secret {}{} could not be decrypted", HANDLE_PREFIX, id))
+    }
+}
+
+impl SecretBackend for EncryptedFile {
+    fn name(&self) -> &'static str {
+        "encrypted file"
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, String> {
+        let _guard = self.lock.lock().map_err(|e| format!("Lock error: {}", e))?;
+        match self.load()?.secrets.get(id) {
+            Some(sealed) => self.decrypt(id, sealed).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, id: &str, value: &str) -> Result<(), String> {
+        let _guard = self.lock.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let mut file = self.load()?;
+        file.secrets
+            .insert(id.to_string(), self.seal(value.as_bytes(), id)?);
+        self.write_file(&file)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        let _guard = self.lock.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let mut file = self.load()?;
+        if file.secrets.remove(id).is_some() {
+            self.write_file(&file)?;
+        }
+        Ok(())
+    }
+
+    fn values(&self) -> Result<Vec<String>, String> {
+        let _guard = self.lock.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let file = self.load()?;
+        file.secrets
+            .iter()
+            .map(|(id, sealed)| self.decrypt(id, sealed))
+            .collect()
+    }
+}
+
+/// The desktop keyring over the freedesktop Secret Service, through
+/// `secret-tool` from libsecret.
+struct SecretService {
+    secret_tool: PathBuf,
+}
+
+impl SecretService {
+    fn run(&self, args: &[&str], stdin: Option<&str>) -> Result<std::process::Output, String> {
+        let mut child = std::process::Command::new(&self.secret_tool)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run secret-tool: {}", e))?;
+        if let (Some(value), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(value.as_bytes())
+                .map_err(|e| format!("Failed to pass secret to secret-tool: {}", e))?;
+        }
+        child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to run secret-tool: {}", e))
+    }
+}
+
+impl SecretBackend for SecretService {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, String> {
+        let output = self.run(
+            &["lookup", "application", APP_IDENTIFIER, "secret", id],
+            None,
+        )?;
+        let value = String::from_utf8_lossy(&output.stdout).to_string();
+        // `lookup` exits non-zero with no output when nothing matches.
+        Ok((output.status.success() && !value.is_empty()).then_some(value))
+    }
+
+    fn set(&self, id: &str, value: &str) -> Result<(), String> {
+        let label = format!("Commander secret {}", id);
+        let output = self.run(
+            &[
+                "store",
+                "--label",
+                &label,
+                "application",
+                APP_IDENTIFIER,
+                "secret",
+                id,
+            ],
+            Some(value),
+        )?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "The keyring refused the secret: {}",
+                redact(String::from_utf8_lossy(&output.stderr).trim())
+            ))
+        }
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        self.run(
+            &["clear", "application", APP_IDENTIFIER, "secret", id],
+            None,
+        )?;
+        Ok(())
+    }
+
+    fn values(&self) -> Result<Vec<String>, String> {
+        Ok(Vec::new())
+    }
+}
+
+/// Where provider API keys and auth tokens are kept. Configs reference a
+/// secret by its `secret://` handle and the value is looked up when needed.
+pub struct SecretStore {
+    backend: Box<dyn SecretBackend>,
+}
+
+impl SecretStore {
+    /// The encrypted file at `path`, created on first use.
+    pub fn open_encrypted(path: &Path, key_source: &KeySource) -> Result<Self, String> {
+        Ok(Self {
+            backend: Box::new(EncryptedFile::open(path, key_source)?),
+        })
+    }
+
+    /// The desktop keyring. Linux only, and needs `secret-tool` installed.
+    pub fn open_keyring() -> Result<Self, String> {
+        if !cfg!(target_os = "linux") {
+            return Err("the keyring secret store is only available on Linux".to_string());
+        }
+        let secret_tool = which::which("secret-tool").map_err(|_| {
+            "the keyring secret store needs secret-tool (libsecret-tools) installed".to_string()
+        })?;
+        Ok(Self {
+            backend: Box::new(SecretService { secret_tool }),
+        })
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    /// Keep `value` under a new handle, which is returned.
+    pub fn store(&self, value: &str) -> Result<String, String> {
+        let handle = format!("{}{}", HANDLE_PREFIX, uuid::Uuid::new_v4());
+        self.store_at(&handle, value)?;
+        Ok(handle)
+    }
+
+    /// Keep `value` under `handle`, replacing what it held.
+    pub fn store_at(&self, handle: &str, value: &str) -> Result<(), String> {
+        register_secret(value);
+        self.backend.set(handle_id(handle)?, value)
+    }
+
+    /// Keep `value` under `previous` when that is a handle, or a new one.
+    pub fn replace(&self, previous: Option<&str>, value: &str) -> Result<String, String> {
+        match previous.filter(|p| is_handle(p)) {
+            Some(handle) => self.store_at(handle, value).map(|_| handle.to_string()),
+            None => self.store(value),
+        }
+    }
+
+    /// The value behind `handle`, if the store has it.
+    pub fn lookup(&self, handle: &str) -> Result<Option<String>, String> {
+        let value = self.backend.get(handle_id(handle)?)?;
+        if let Some(value) = &value {
+            register_secret(value);
+        }
+        Ok(value)
+    }
+
+    /// The secret a config value stands for: the value behind a handle, or
+    /// the value itself when it is not one.
+    pub fn resolve(&self, value: &str) -> Result<String, String> {
+        if !is_handle(value) {
+            register_secret(value);
+            return Ok(value.to_string());
+        }
+        self.lookup(value)?.ok_or_else(|| {
+            format!(
+                "Secret {} is missing from the {} store",
+                value,
+                self.backend_name()
+            )
+        })
+    }
+
+    /// Forget the secret behind a handle; other values are left alone.
+    pub fn remove(&self, value: &str) -> Result<(), String> {
+        if is_handle(value) {
+            self.backend.delete(handle_id(value)?)?;
+        }
+        Ok(())
+    }
+
+    /// Move plaintext secrets under [`SECRET_KEYS`] into the store, leaving
+    /// handles in their place. A value found twice gets one handle. Returns
+    /// how many secrets were moved.
+    pub fn seal_json(&self, value: &mut Value) -> Result<usize, String> {
+        let mut sealed = HashMap::new();
+        self.seal_json_into(value, &mut sealed)?;
+        Ok(sealed.len())
+    }
+
+    fn seal_json_into(
+        &self,
+        value: &mut Value,
+        sealed: &mut HashMap<String, String>,
+    ) -> Result<(), String> {
+        match value {
+            Value::Object(map) => {
+                for (key, entry) in map.iter_mut() {
+                    match entry {
+                        Value::String(secret)
+                            if SECRET_KEYS.contains(&key.as_str())
+                                && !secret.is_empty()
+                                && !is_handle(secret) =>
+                        {
+                            let handle = match sealed.get(secret.as_str()) {
+                                Some(handle) => handle.clone(),
+                                None => {
+                                    let handle = self.store(secret)?;
+                                    sealed.insert(secret.clone(), handle.clone());
+                                    handle
+                                }
+                            };
+                            *secret = handle;
+                        }
+                        _ => self.seal_json_into(entry, sealed)?,
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.seal_json_into(item, sealed)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Replace every handle in `value` with the secret it stands for.
+    pub fn resolve_json(&self, value: &mut Value) -> Result<(), String> {
+        match value {
+            Value::String(text) if is_handle(text) => *text = self.resolve(text)?,
+            Value::Object(map) => {
+                for entry in map.values_mut() {
+                    self.resolve_json(entry)?;
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.resolve_json(item)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Whether `value` has a plaintext secret under one of [`SECRET_KEYS`].
+pub fn has_plaintext_secrets(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.iter().any(|(key, entry)| match entry {
+            Value::String(secret) if SECRET_KEYS.contains(&key.as_str()) => {
+                !secret.is_empty() && !is_handle(secret)
+            }
+            _ => has_plaintext_secrets(entry),
+        }),
+        Value::Array(items) => items.iter().any(has_plaintext_secrets),
+        _ => false,
+    }
+}
+
+/// [`SecretStore::resolve`] against the app's store, which is only opened
+/// when `value` is a handle.
+pub fn resolve_value(value: &str) -> Result<String, String> {
+    if is_handle(value) {
+        global()?.resolve(value)
+    } else {
+        register_secret(value);
+        Ok(value.to_string())
+    }
+}
+
+/// Whether `value` holds a handle anywhere.
+pub fn contains_handles(value: &Value) -> bool {
+    match value {
+        Value::String(text) => is_handle(text),
+        Value::Object(map) => map.values().any(contains_handles),
+        Value::Array(items) => items.iter().any(contains_handles),
+        _ => false,
+    }
+}
+
+/// Move the plaintext secrets of a JSON config file into the store and
+/// rewrite the file with handles. The store is only opened when there is
+/// something to move. Returns whether the file changed.
+pub fn seal_config_file<'s>(
+    path: &Path,
+    store: impl FnOnce() -> Result<&'s SecretStore, String>,
+) -> Result<bool, String> {
+    let Ok(raw) = std::fs::read_to_string(path) else {
+        return Ok(false);
+    };
+    let Ok(mut root) = serde_json::from_str::<Value>(&raw) else {
+        return Ok(false);
+    };
+    if !has_plaintext_secrets(&root) {
+        return Ok(false);
+    }
+    store()?.seal_json(&mut root)?;
+    let pretty = serde_json::to_string_pretty(&root)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    std::fs::write(path, pretty)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(true)
+}
+
+/// Where the app keeps the encrypted secret store.
+pub fn default_secrets_path() -> Option<PathBuf> {
+    crate::app_data_dir().map(|dir| dir.join(SECRETS_FILE))
+}
+
+/// The store the app keeps its secrets in, opened on first use: the keyring
+/// when `COMMANDER_SECRET_BACKEND=keyring`, otherwise the encrypted file.
+/// Everything already in it is redacted from then on.
+pub fn global() -> Result<&'static SecretStore, String> {
+    GLOBAL_STORE.get_or_try_init(|| {
+        let store = match std::env::var(BACKEND_ENV).as_deref() {
+            Ok("keyring") => SecretStore::open_keyring()?,
+            _ => {
+                let path =
+                    default_secrets_path().ok_or("Could not determine the app data directory")?;
+                SecretStore::open_encrypted(&path, &KeySource::from_env())?
+            }
+        };
+        match store.backend.values() {
+            Ok(values) => values.iter().for_each(|value| register_secret(value)),
+            Err(e) => eprintln!("⚠️ Some stored secrets could not be read: {}", e),
+        }
+        Ok(store)
+    })
+}
@@ -0,0 +1,236 @@
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::models::ai_agent::{AgentSettings, StreamChunk};
+use crate::models::protocol::{ProtocolEvent, SessionEndReason};
+use crate::services::event_sink::{EventSink, SharedEventSink};
+use crate::services::executors::{terminate_process_group, TERMINATE_GRACE};
+
+/// How often a running session is checked against its limits.
+const LIMIT_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What a single run may use before it is stopped. `None` means no limit.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionLimits {
+    pub max_run: Option<Duration>,
+    pub idle: Option<Duration>,
+    pub max_output_bytes: Option<u64>,
+}
+
+impl SessionLimits {
+    pub fn from_settings(settings: &AgentSettings) -> Self {
+        let minutes = |m: u32| (m > 0).then(|| Duration::from_secs(m as u64 * 60));
+        Self {
+            max_run: minutes(settings.max_run_minutes),
+            idle: minutes(settings.session_timeout_minutes),
+            max_output_bytes: (settings.max_output_bytes > 0).then_some(settings.max_output_bytes),
+        }
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_run.is_none() && self.idle.is_none() && self.max_output_bytes.is_none()
+    }
+}
+
+/// Activity of one run, fed by [`ActivitySink`] and checked against the
+/// run's limits.
+#[derive(Debug)]
+pub struct SessionActivity {
+    limits: SessionLimits,
+    started: Instant,
+    last_activity: Mutex<Instant>,
+    output_bytes: AtomicU64,
+    /// Permission requests waiting on the user; the idle clock stops meanwhile.
+    pending_permissions: Mutex<HashSet<String>>,
+    end_reason: Mutex<Option<SessionEndReason>>,
+}
+
+impl SessionActivity {
+    pub fn new(limits: SessionLimits) -> Self {
+        let now = Instant::now();
+        Self {
+            limits,
+            started: now,
+            last_activity: Mutex::new(now),
+            output_bytes: AtomicU64::new(0),
+            pending_permissions: Mutex::new(HashSet::new()),
+            end_reason: Mutex::new(None),
+        }
+    }
+
+    pub fn touch(&self) {
+        if let Ok(mut last) = self.last_activity.lock() {
+            *last = Instant::now();
+        }
+    }
+
+    fn record_output(&self, bytes: usize) {
+        self.output_bytes.fetch_add(bytes as u64, Ordering::SeqCst);
+        self.touch();
+    }
+
+    /// Unix seconds of the last chunk or event.
+    pub fn last_activity_at(&self) -> i64 {
+        let idle = self
+            .last_activity
+            .lock()
+            .map(|last| last.elapsed())
+            .unwrap_or_default();
+        chrono::Utc::now().timestamp() - idle.as_secs() as i64
+    }
+
+    pub fn output_bytes(&self) -> u64 {
+        self.output_bytes.load(Ordering::SeqCst)
+    }
+
+    /// The limit the run has gone past, if any.
+    pub fn exceeded(&self) -> Option<SessionEndReason> {
+        if let Some(max) = self.limits.max_output_bytes {
+            if self.output_bytes() > max {
+                return Some(SessionEndReason::OutputLimit);
+            }
+        }
+        if let Some(max) = self.limits.max_run {
+            if self.started.elapsed() >= max {
+                return Some(SessionEndReason::WallClockTimeout);
+            }
+        }
+        if let Some(idle) = self.limits.idle {
+            let waiting_on_user = self
+                .pending_permissions
+                .lock()
+                .is_ok_and(|pending| !pending.is_empty());
+            let quiet = self
+                .last_activity
+                .lock()
+                .map(|last| last.elapsed())
+                .unwrap_or_default();
+            if !waiting_on_user && quiet >= idle {
+                return Some(SessionEndReason::IdleTimeout);
+            }
+        }
+        None
+    }
+
+    /// Resolves once the run goes past a limit, recording it as the reason
+    /// the run ended. Never resolves when the run has no limits.
+    pub async fn limit_reached(&self) -> SessionEndReason {
+        if self.limits.is_unlimited() {
+            return std::future::pending().await;
+        }
+        loop {
+            if let Some(reason) = self.exceeded() {
+                return self.end(reason);
+            }
+            tokio::time::sleep(LIMIT_CHECK_INTERVAL).await;
+        }
+    }
+
+    /// Record why the run ended. The first reason sticks and is returned.
+    pub fn end(&self, reason: SessionEndReason) -> SessionEndReason {
+        match self.end_reason.lock() {
+            Ok(mut end_reason) => *end_reason.get_or_insert(reason),
+            Err(_) => reason,
+        }
+    }
+}
+
+/// Bytes of agent output an event carries.
+fn event_output_bytes(event: &ProtocolEvent) -> usize {
+    match event {
+        ProtocolEvent::Message { content, .. } => content.len(),
+        ProtocolEvent::ToolUpdate { output, .. } | ProtocolEvent::ToolEnd { output, .. } => {
+            output.as_deref().map_or(0, str::len)
+        }
+        ProtocolEvent::Error { message, .. } => message.len(),
+        _ => 0,
+    }
+}
+
+/// Counts every chunk and event on its way through as session activity.
+pub struct ActivitySink {
+    inner: SharedEventSink,
+    activity: Arc<SessionActivity>,
+}
+
+impl ActivitySink {
+    pub fn shared(inner: SharedEventSink, activity: Arc<SessionActivity>) -> SharedEventSink {
+        Arc::new(Self { inner, activity })
+    }
+}
+
+impl EventSink for ActivitySink {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        self.activity.record_output(chunk.content.len());
+        self.inner.emit_chunk(chunk);
+    }
+
+    fn emit_event(&self, event: ProtocolEvent) {
+        if let Ok(mut pending) = self.activity.pending_permissions.lock() {
+            match &event {
+                ProtocolEvent::PermissionRequest { request_id, .. } => {
+                    pending.insert(request_id.clone());
+                }
+                ProtocolEvent::PermissionResolved { request_id, .. } => {
+                    pending.remove(request_id);
+                }
+                _ => {}
+            }
+        }
+        self.activity.record_output(event_output_bytes(&event));
+        self.inner.emit_event(event);
+    }
+}
+
+/// Process groups of the agents running for each session, for runs whose
+/// executor cannot be stopped through its own handle.
+static PROCESS_GROUPS: Lazy<Mutex<HashMap<String, Vec<u32>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Keeps a process group registered to its session until dropped.
+pub struct ProcessGroupRegistration {
+    session_id: String,
+    pgid: u32,
+}
+
+impl Drop for ProcessGroupRegistration {
+    fn drop(&mut self) {
+        if let Ok(mut groups) = PROCESS_GROUPS.lock() {
+            if let Some(pgids) = groups.get_mut(&self.session_id) {
+                pgids.retain(|pgid| *pgid != self.pgid);
+                if pgids.is_empty() {
+                    groups.remove(&self.session_id);
+                }
+            }
+        }
+    }
+}
+
+/// Register the process group led by `pgid` as running for a session.
+pub fn register_process_group(session_id: &str, pgid: u32) -> ProcessGroupRegistration {
+    if let Ok(mut groups) = PROCESS_GROUPS.lock() {
+        groups.entry(session_id.to_string()).or_default().push(pgid);
+    }
+    ProcessGroupRegistration {
+        session_id: session_id.to_string(),
+        pgid,
+    }
+}
+
+/// Stop every process group registered to a session, gracefully first.
+pub async fn terminate_session_processes(session_id: &str) {
+    let pgids = PROCESS_GROUPS
+        .lock()
+        .ok()
+        .and_then(|groups| groups.get(session_id).cloned())
+        .unwrap_or_default();
+    futures::future::join_all(
+        pgids
+            .into_iter()
+            .map(|pgid| terminate_process_group(pgid, TERMINATE_GRACE)),
+    )
+    .await;
+}
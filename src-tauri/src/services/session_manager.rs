@@ -1,4 +1,7 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::services::permission_policy_service::{PermissionGate, SessionAllowances};
 
 /// Permission response forwarded from frontend to executor task.
 #[derive(Debug)]
@@ -18,18 +21,51 @@ pub struct ActiveSession {
 
 pub struct SessionManager {
     sessions: HashMap<String, ActiveSession>,
+    /// Permission policy gates of running sessions, by session id.
+    gates: HashMap<String, Arc<PermissionGate>>,
+    /// What the user allowed for the rest of a conversation, by conversation id.
+    allowances: HashMap<String, SessionAllowances>,
 }
 
 impl SessionManager {
     pub fn new() -> Self {
-        Self { sessions: HashMap::new() }
+        Self {
+            sessions: HashMap::new(),
+            gates: HashMap::new(),
+            allowances: HashMap::new(),
+        }
     }
 
     pub fn insert(&mut self, session: ActiveSession) {
         self.sessions.insert(session.session_id.clone(), session);
     }
 
+    /// Route the user's permission answers for a session through its gate.
+    pub fn set_permission_gate(&mut self, session_id: &str, gate: Arc<PermissionGate>) {
+        self.gates.insert(session_id.to_string(), gate);
+    }
+
+    /// Allowances shared by every run of a conversation.
+    pub fn session_allowances(&mut self, conversation_id: &str) -> SessionAllowances {
+        self.allowances.entry(conversation_id.to_string()).or_default().clone()
+    }
+
     pub fn send_permission(&self, session_id: &str, request_id: String, approved: bool) -> Result<(), String> {
+        self.answer_permission(session_id, request_id, approved, false)
+    }
+
+    /// Answer a permission request; `remember` allows requests like it for
+    /// the rest of the conversation.
+    pub fn answer_permission(
+        &self,
+        session_id: &str,
+        request_id: String,
+        approved: bool,
+        remember: bool,
+    ) -> Result<(), String> {
+        if let Some(gate) = self.gates.get(session_id) {
+            return gate.answer(request_id, approved, remember);
+        }
         if let Some(session) = self.sessions.get(session_id) {
             session.permission_sender.send(PermissionResponse { request_id, approved })
                 .map_err(|_| format!("Session {} executor not running", session_id))
@@ -39,10 +75,12 @@ impl SessionManager {
     }
 
     pub fn remove(&mut self, session_id: &str) -> Option<ActiveSession> {
+        self.gates.remove(session_id);
         self.sessions.remove(session_id)
     }
 
     pub fn close_session(&mut self, session_id: &str) {
+        self.gates.remove(session_id);
         if let Some(mut session) = self.sessions.remove(session_id) {
             if let Some(sender) = session.abort_sender.take() {
                 let _ = sender.send(());
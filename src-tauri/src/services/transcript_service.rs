@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use crate::models::chat_history::*;
+use crate::models::protocol::ProtocolEvent;
+use crate::services::chat_history_service::{
+    ensure_commander_directory, load_sessions_index, save_chat_session,
+};
+use crate::services::event_sink::{read_event_log, EventRecord, FileEventSink, RecordedEvent};
+use crate::services::git_service::get_git_branch;
+
+const COMMANDER_DIR: &str = ".commander";
+const TRANSCRIPTS_DIR: &str = "transcripts";
+
+/// Prefix of the status line `run_agent_session` emits before the agent
+/// starts; it describes the run rather than being agent output.
+const SESSION_INFO_PREFIX: &str = "🔗 Agent:";
+
+/// Append-only event log for one conversation, every turn included:
+/// `<project>/.commander/transcripts/<session_id>.jsonl`, where the id is
+/// the conversation's. Ids come from the frontend, so anything but letters,
+/// digits, `-` and `_` is refused rather than allowed to leave the
+/// transcripts directory.
+pub fn transcript_path(project_path: &str, session_id: &str) -> Result<PathBuf, String> {
+    let valid = !session_id.is_empty()
+        && session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("Invalid session id: {:?}", session_id));
+    }
+    Ok(Path::new(project_path)
+        .join(COMMANDER_DIR)
+        .join(TRANSCRIPTS_DIR)
+        .join(format!("{}.jsonl", session_id)))
+}
+
+/// Open (or continue) the transcript for a session.
+pub fn open_transcript(project_path: &str, session_id: &str) -> Result<FileEventSink, String> {
+    FileEventSink::open(transcript_path(project_path, session_id)?)
+}
+
+/// Read every recorded event of a session, oldest first.
+pub fn load_transcript(project_path: &str, session_id: &str) -> Result<Vec<EventRecord>, String> {
+    let path = transcript_path(project_path, session_id)?;
+    if !path.exists() {
+        return Err(format!("Transcript not found for session: {}", session_id));
+    }
+    read_event_log(&path)
+}
+
+/// Remove a session's transcript, if it has one.
+pub fn delete_transcript(project_path: &str, session_id: &str) -> Result<(), String> {
+    let path = transcript_path(project_path, session_id)?;
+    if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| format!("Failed to delete transcript: {}", e))?;
+    }
+    Ok(())
+}
+
+/// One prompt and everything the agent answered to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptTurn {
+    pub prompt: String,
+    /// Unix seconds of the prompt.
+    pub started_at: i64,
+    /// Unix seconds of the last event of the turn.
+    pub ended_at: i64,
+    /// Assistant `Message` text, or the raw stream output for agents that
+    /// only produce terminal text.
+    pub response: String,
+    pub tool_calls: usize,
+    pub errors: usize,
+}
+
+/// Split a transcript into turns. A user `Message` event starts a new turn;
+/// events before the first prompt are ignored.
+pub fn transcript_turns(records: &[EventRecord]) -> Vec<TranscriptTurn> {
+    struct Pending {
+        turn: TranscriptTurn,
+        messages: String,
+        stream: String,
+    }
+
+    fn finish(pending: Pending) -> TranscriptTurn {
+        let mut turn = pending.turn;
+        turn.response = if pending.messages.trim().is_empty() {
+            pending.stream.trim().to_string()
+        } else {
+            pending.messages.trim().to_string()
+        };
+        turn
+    }
+
+    let mut turns = Vec::new();
+    let mut current: Option<Pending> = None;
+
+    for record in records {
+        let Ok(recorded) = record.to_recorded() else {
+            continue;
+        };
+        let ts = record.ts / 1000;
+
+        if let RecordedEvent::Protocol(ProtocolEvent::Message { role, content, .. }) = &recorded {
+            if role == "user" {
+                if let Some(pending) = current.take() {
+                    turns.push(finish(pending));
+                }
+                current = Some(Pending {
+                    turn: TranscriptTurn {
+                        prompt: content.clone(),
+                        started_at: ts,
+                        ended_at: ts,
+                        response: String::new(),
+                        tool_calls: 0,
+                        errors: 0,
+                    },
+                    messages: String::new(),
+                    stream: String::new(),
+                });
+                continue;
+            }
+        }
+
+        let Some(pending) = current.as_mut() else {
+            continue;
+        };
+        pending.turn.ended_at = ts;
+
+        match recorded {
+            RecordedEvent::Chunk(chunk) => {
+                if !chunk.content.starts_with(SESSION_INFO_PREFIX) {
+                    pending.stream.push_str(&chunk.content);
+                }
+            }
+            RecordedEvent::Protocol(ProtocolEvent::Message { content, .. }) => {
+                if !pending.messages.is_empty() && !pending.messages.ends_with('\n') {
+                    pending.messages.push('\n');
+                }
+                pending.messages.push_str(&content);
+            }
+            RecordedEvent::Protocol(ProtocolEvent::ToolStart { .. }) => {
+                pending.turn.tool_calls += 1;
+            }
+            RecordedEvent::Protocol(ProtocolEvent::Error { .. }) => {
+                pending.turn.errors += 1;
+            }
+            RecordedEvent::Protocol(_) => {}
+        }
+    }
+
+    if let Some(pending) = current {
+        turns.push(finish(pending));
+    }
+    turns
+}
+
+/// Rebuild the chat history entry for a conversation from its transcript
+/// once a turn of it finishes.
+///
+/// Writes `session_<id>.json` with one user and one assistant message per
+/// turn and upserts the session in the index. Titles, summaries and the
+/// archived flag set by the user on an existing entry are kept.
+pub async fn finalize_transcript(
+    project_path: &str,
+    session_id: &str,
+    agent: &str,
+    model: Option<String>,
+) -> Result<ChatSession, String> {
+    let records = load_transcript(project_path, session_id)?;
+    let turns = transcript_turns(&records);
+    let first = turns
+        .first()
+        .ok_or_else(|| format!("Transcript for session {} has no prompts", session_id))?;
+
+    let branch = get_git_branch(project_path);
+    let mut messages = Vec::new();
+    for turn in &turns {
+        let mut user = EnhancedChatMessage::new("user", &turn.prompt, agent, session_id);
+        user.timestamp = turn.started_at;
+        user.metadata.branch = branch.clone();
+        user.metadata.working_dir = Some(project_path.to_string());
+        user.metadata.file_mentions = extract_file_mentions(&turn.prompt);
+        messages.push(user);
+
+        if !turn.response.is_empty() {
+            let mut assistant = EnhancedChatMessage::new("assistant", &turn.response, agent, session_id);
+            assistant.timestamp = turn.ended_at;
+            assistant.metadata.branch = branch.clone();
+            assistant.metadata.working_dir = Some(project_path.to_string());
+            assistant.metadata.file_mentions = extract_file_mentions(&turn.response);
+            messages.push(assistant);
+        }
+    }
+
+    let mut session = ChatSession::new(agent, first.started_at, &first.prompt);
+    session.id = session_id.to_string();
+    session.end_time = turns.last().map(|t| t.ended_at).unwrap_or(first.started_at);
+    session.message_count = messages.len();
+    session.branch = branch;
+    session.model = model;
+
+    let index_path = ensure_commander_directory(project_path)
+        .await?
+        .join("sessions_index.json");
+    let index = load_sessions_index(&index_path).await.unwrap_or_default();
+    if let Some(existing) = index.sessions.iter().find(|s| s.id == session_id) {
+        session.start_time = session.start_time.min(existing.start_time);
+        session.archived = existing.archived;
+        session.custom_title = existing.custom_title.clone();
+        session.ai_summary = existing.ai_summary.clone();
+        session.forked_from = existing.forked_from.clone();
+    }
+
+    save_chat_session(project_path, &session, &messages).await?;
+    Ok(session)
+}
@@ -0,0 +1,281 @@
+//! Token usage and what it costs.
+//!
+//! Usage reaches the index two ways: [`UsageMeter`] records each turn a
+//! live run reports, and the indexer reads it back from the agents'
+//! transcript files. Both are priced with the table in
+//! `~/.commander/model-pricing.json`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::OnceCell;
+
+use crate::models::ai_agent::StreamChunk;
+use crate::models::protocol::{ProtocolEvent, TurnUsage};
+use crate::models::usage::{ModelPrice, UsageRecord, UsageSource};
+use crate::services::event_sink::{EventSink, SharedEventSink};
+use crate::services::indexer::db::IndexDb;
+
+const PRICING_FILE: &str = "model-pricing.json";
+
+/// Model recorded for usage the agent did not name a model for.
+pub const UNKNOWN_MODEL: &str = "unknown";
+
+static USAGE_DB: OnceCell<Arc<IndexDb>> = OnceCell::new();
+
+/// Make `db` the index live runs record their usage into.
+pub fn install(db: Arc<IndexDb>) {
+    let _ = USAGE_DB.set(db);
+}
+
+pub fn installed() -> Option<Arc<IndexDb>> {
+    USAGE_DB.get().cloned()
+}
+
+fn price(model: &str, input: f64, output: f64, cache_read: f64, cache_write: f64) -> ModelPrice {
+    ModelPrice {
+        model: model.to_string(),
+        input,
+        output,
+        cache_read,
+        cache_write,
+    }
+}
+
+/// List prices, in USD per million tokens, of the models the supported
+/// agents default to.
+pub fn default_pricing() -> Vec<ModelPrice> {
+    vec![
+        price("claude-opus-4", 15.0, 75.0, 1.5, 18.75),
+        price("claude-opus-4-5", 5.0, 25.0, 0.5, 6.25),
+        price("claude-sonnet-4", 3.0, 15.0, 0.3, 3.75),
+        price("claude-3-7-sonnet", 3.0, 15.0, 0.3, 3.75),
+        price("claude-3-5-sonnet", 3.0, 15.0, 0.3, 3.75),
+        price("claude-haiku-4-5", 1.0, 5.0, 0.1, 1.25),
+        price("claude-3-5-haiku", 0.8, 4.0, 0.08, 1.0),
+        price("gpt-5", 1.25, 10.0, 0.125, 0.0),
+        price("gpt-5-mini", 0.25, 2.0, 0.025, 0.0),
+        price("gpt-5-nano", 0.05, 0.4, 0.005, 0.0),
+        price("gpt-4.1", 2.0, 8.0, 0.5, 0.0),
+        price("gpt-4.1-mini", 0.4, 1.6, 0.1, 0.0),
+        price("gpt-4o", 2.5, 10.0, 1.25, 0.0),
+        price("o3", 2.0, 8.0, 0.5, 0.0),
+        price("o3-mini", 1.1, 4.4, 0.55, 0.0),
+        price("o4-mini", 1.1, 4.4, 0.275, 0.0),
+        price("gemini-2.5-pro", 1.25, 10.0, 0.31, 0.0),
+        price("gemini-2.5-flash", 0.3, 2.5, 0.075, 0.0),
+    ]
+}
+
+pub fn pricing_path() -> Result<PathBuf, String> {
+    let home =
+        dirs::home_dir().ok_or_else(|| "Could not determine user home directory".to_string())?;
+    Ok(home.join(".commander").join(PRICING_FILE))
+}
+
+/// The pricing table in `path`; without one the defaults apply.
+pub fn load_pricing_from(path: &Path) -> Result<Vec<ModelPrice>, String> {
+    if !path.exists() {
+        return Ok(default_pricing());
+    }
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read model pricing: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse model pricing: {}", e))
+}
+
+pub fn save_pricing_to(path: &Path, pricing: &[ModelPrice]) -> Result<(), String> {
+    for entry in pricing {
+        if entry.model.trim().is_empty() {
+            return Err("Model prices need a model".to_string());
+        }
+        let rates = [
+            entry.input,
+            entry.output,
+            entry.cache_read,
+            entry.cache_write,
+        ];
+        if rates.iter().any(|rate| !rate.is_finite() || *rate < 0.0) {
+            return Err(format!("{}: prices must be zero or more", entry.model));
+        }
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(pricing)
+        .map_err(|e| format!("Failed to serialize model pricing: {}", e))?;
+    fs::write(path, content).map_err(|e| format!("Failed to write model pricing: {}", e))
+}
+
+pub fn load_pricing() -> Result<Vec<ModelPrice>, String> {
+    load_pricing_from(&pricing_path()?)
+}
+
+pub fn save_pricing(pricing: &[ModelPrice]) -> Result<(), String> {
+    save_pricing_to(&pricing_path()?, pricing)
+}
+
+/// The pricing table, or the defaults when it cannot be read.
+pub fn load_pricing_or_default() -> Vec<ModelPrice> {
+    load_pricing().unwrap_or_else(|e| {
+        eprintln!("⚠️ Using default model pricing: {}", e);
+        default_pricing()
+    })
+}
+
+/// The entry pricing `model`: the longest one it starts with, ignoring case
+/// and any `provider/` prefix.
+pub fn price_for<'a>(pricing: &'a [ModelPrice], model: &str) -> Option<&'a ModelPrice> {
+    let model = model.to_ascii_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    pricing
+        .iter()
+        .filter(|entry| {
+            let prefix = entry.model.to_ascii_lowercase();
+            !prefix.is_empty() && model.starts_with(&prefix)
+        })
+        .max_by_key(|entry| entry.model.len())
+}
+
+/// What `record`'s tokens cost at `pricing`, falling back to what the agent
+/// reported for models the table does not list.
+pub fn cost_of(record: &UsageRecord, pricing: &[ModelPrice]) -> Option<f64> {
+    match price_for(pricing, &record.model) {
+        Some(price) => Some(
+            (record.input_tokens as f64 * price.input
+                + record.output_tokens as f64 * price.output
+                + record.cache_read_tokens as f64 * price.cache_read
+                + record.cache_write_tokens as f64 * price.cache_write)
+                / 1_000_000.0,
+        ),
+        None => record.reported_cost_usd,
+    }
+}
+
+/// The form project paths are recorded and budgeted under: `~` expanded and
+/// symlinks resolved, so a directory matches itself however it was spelled.
+/// Paths that do not exist are only tidied (no trailing separator).
+pub fn canonical_project_path(path: &str) -> String {
+    let expanded = match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    };
+    fs::canonicalize(&expanded)
+        .unwrap_or_else(|_| expanded.components().collect())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The usage row one live turn adds, dated today (UTC).
+pub fn live_record(
+    agent: &str,
+    session_key: &str,
+    project_path: Option<&str>,
+    default_model: Option<&str>,
+    usage: &TurnUsage,
+) -> UsageRecord {
+    UsageRecord {
+        agent_id: agent.to_string(),
+        session_key: session_key.to_string(),
+        source: UsageSource::Live,
+        model: usage
+            .model
+            .as_deref()
+            .or(default_model)
+            .filter(|m| !m.is_empty())
+            .unwrap_or(UNKNOWN_MODEL)
+            .to_string(),
+        date: chrono::Utc::now().format("%Y-%m-%d").to_string(),
+        project_path: project_path.map(canonical_project_path),
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+        cache_read_tokens: usage.cache_read_tokens,
+        cache_write_tokens: usage.cache_creation_tokens,
+        reported_cost_usd: usage.cost_usd,
+        cost_usd: None,
+    }
+}
+
+/// Records the usage each turn of a run reports, and fills in its cost for
+/// agents that do not report one.
+pub struct UsageMeter {
+    inner: SharedEventSink,
+    db: Arc<IndexDb>,
+    pricing: Vec<ModelPrice>,
+    agent: String,
+    session_id: String,
+    project_path: Option<String>,
+    default_model: Option<String>,
+    /// The agent's own session id, once it reports one.
+    session_key: Mutex<Option<String>>,
+}
+
+impl UsageMeter {
+    pub fn shared(
+        inner: SharedEventSink,
+        db: Arc<IndexDb>,
+        pricing: Vec<ModelPrice>,
+        agent: &str,
+        session_id: &str,
+        project_path: Option<&str>,
+        default_model: Option<&str>,
+    ) -> SharedEventSink {
+        Arc::new(Self {
+            inner,
+            db,
+            pricing,
+            agent: agent.to_string(),
+            session_id: session_id.to_string(),
+            project_path: project_path.map(str::to_string),
+            default_model: default_model.map(str::to_string),
+            session_key: Mutex::new(None),
+        })
+    }
+
+    fn session_key(&self, agent_session_id: Option<&String>) -> String {
+        let Ok(mut key) = self.session_key.lock() else {
+            return self.session_id.clone();
+        };
+        if let Some(id) = agent_session_id.filter(|id| !id.is_empty()) {
+            *key = Some(id.clone());
+        }
+        key.clone().unwrap_or_else(|| self.session_id.clone())
+    }
+}
+
+impl EventSink for UsageMeter {
+    fn emit_chunk(&self, chunk: StreamChunk) {
+        self.inner.emit_chunk(chunk);
+    }
+
+    fn emit_event(&self, mut event: ProtocolEvent) {
+        if let ProtocolEvent::StateChange {
+            usage,
+            agent_session_id,
+            ..
+        } = &mut event
+        {
+            let session_key = self.session_key(agent_session_id.as_ref());
+            if let Some(usage) = usage {
+                let mut record = live_record(
+                    &self.agent,
+                    &session_key,
+                    self.project_path.as_deref(),
+                    self.default_model.as_deref(),
+                    usage,
+                );
+                record.cost_usd = cost_of(&record, &self.pricing);
+                if usage.cost_usd.is_none() {
+                    usage.cost_usd = record.cost_usd;
+                }
+                if let Err(e) = self.db.add_live_usage(&self.session_id, &record) {
+                    eprintln!("⚠️ Failed to record usage for {}: {}", self.session_id, e);
+                }
+            }
+        }
+        self.inner.emit_event(event);
+    }
+}
@@ -0,0 +1,602 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::project::MergeStrategy;
+use crate::services::project_settings_service::load_project_settings;
+
+/// Branch names tried, in order, when neither the caller, the project
+/// settings nor `origin/HEAD` name a base branch.
+const FALLBACK_BASE_BRANCHES: &[&str] = &["main", "master", "develop", "trunk"];
+
+/// Unmerged states as reported by `git status --porcelain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    BothModified,
+    BothAdded,
+    BothDeleted,
+    AddedByUs,
+    AddedByThem,
+    DeletedByUs,
+    DeletedByThem,
+}
+
+impl ConflictKind {
+    /// Map a two-letter porcelain status to a conflict kind; `None` for
+    /// entries that are not unmerged.
+    pub fn from_porcelain(code: &str) -> Option<Self> {
+        match code {
+            "UU" => Some(ConflictKind::BothModified),
+            "AA" => Some(ConflictKind::BothAdded),
+            "DD" => Some(ConflictKind::BothDeleted),
+            "AU" => Some(ConflictKind::AddedByUs),
+            "UA" => Some(ConflictKind::AddedByThem),
+            "DU" => Some(ConflictKind::DeletedByUs),
+            "UD" => Some(ConflictKind::DeletedByThem),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeConflict {
+    pub path: String,
+    pub kind: ConflictKind,
+}
+
+/// Result of `merge_workspace_to_main`. When `merged` is false the base
+/// branch was left untouched and `conflicts` lists what blocked the merge.
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeOutcome {
+    pub merged: bool,
+    pub strategy: MergeStrategy,
+    pub base_branch: String,
+    pub workspace_branch: String,
+    /// New tip of the base branch.
+    pub commit: Option<String>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+pub(crate) async fn git(dir: &str, args: &[&str]) -> Result<Output, String> {
+    tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git {}: {}", args.first().unwrap_or(&""), e))
+}
+
+/// Run git and return its trimmed stdout, or its stderr as the error.
+pub(crate) async fn git_stdout(dir: &str, args: &[&str]) -> Result<String, String> {
+    let output = git(dir, args).await?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+async fn local_branch_exists(project_path: &str, branch: &str) -> bool {
+    git(
+        project_path,
+        &["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", branch)],
+    )
+    .await
+    .map(|o| o.status.success())
+    .unwrap_or(false)
+}
+
+async fn ref_exists(project_path: &str, name: &str) -> bool {
+    git(
+        project_path,
+        &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", name)],
+    )
+    .await
+    .map(|o| o.status.success())
+    .unwrap_or(false)
+}
+
+/// Branch checked out in a worktree.
+pub async fn current_branch(worktree_path: &str) -> Result<String, String> {
+    git_stdout(worktree_path, &["rev-parse", "--abbrev-ref", "HEAD"]).await
+}
+
+/// Pick the branch workspaces are compared against and merged into:
+/// the explicit argument, then the project's `base_branch` setting, then the
+/// branch `origin/HEAD` points at, then the first of main/master/develop/trunk
+/// that exists, and finally whatever the project has checked out.
+pub async fn resolve_base_branch(project_path: &str, explicit: Option<&str>) -> Result<String, String> {
+    let configured = match explicit.map(str::trim).filter(|b| !b.is_empty()) {
+        Some(branch) => Some(branch.to_string()),
+        None => load_project_settings(project_path)?
+            .base_branch
+            .filter(|b| !b.trim().is_empty()),
+    };
+    if let Some(branch) = configured {
+        if !ref_exists(project_path, &branch).await {
+            return Err(format!("Base branch '{}' does not exist", branch));
+        }
+        return Ok(branch);
+    }
+
+    if let Ok(remote_head) = git_stdout(
+        project_path,
+        &["symbolic-ref", "--quiet", "--short", "refs/remotes/origin/HEAD"],
+    )
+    .await
+    {
+        let local = remote_head.strip_prefix("origin/").unwrap_or(&remote_head);
+        if local_branch_exists(project_path, local).await {
+            return Ok(local.to_string());
+        }
+        if !remote_head.is_empty() {
+            return Ok(remote_head);
+        }
+    }
+
+    for candidate in FALLBACK_BASE_BRANCHES {
+        if local_branch_exists(project_path, candidate).await {
+            return Ok(candidate.to_string());
+        }
+    }
+
+    current_branch(project_path).await
+}
+
+/// Unmerged entries from `git status --porcelain` output.
+pub fn parse_conflicts(porcelain: &str) -> Vec<MergeConflict> {
+    porcelain
+        .lines()
+        .filter(|line| line.len() > 3)
+        .filter_map(|line| {
+            let kind = ConflictKind::from_porcelain(&line[..2])?;
+            Some(MergeConflict {
+                path: line[3..].trim_matches('"').to_string(),
+                kind,
+            })
+        })
+        .collect()
+}
+
+async fn conflicts_in(worktree: &str) -> Vec<MergeConflict> {
+    git_stdout(worktree, &["status", "--porcelain"])
+        .await
+        .map(|out| parse_conflicts(&out))
+        .unwrap_or_default()
+}
+
+/// Detached scratch worktree outside the project, so merges never touch a
+/// checkout the user is working in.
+pub(crate) struct ScratchWorktree {
+    project_path: String,
+    pub path: String,
+}
+
+impl ScratchWorktree {
+    pub(crate) async fn create(project_path: &str, commit: &str) -> Result<Self, String> {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("commander-merge-{}", uuid::Uuid::new_v4().simple()));
+        let path = path.to_string_lossy().to_string();
+        git_stdout(project_path, &["worktree", "add", "--detach", &path, commit])
+            .await
+            .map_err(|e| format!("Failed to create scratch worktree: {}", e))?;
+        Ok(Self {
+            project_path: project_path.to_string(),
+            path,
+        })
+    }
+
+    pub(crate) async fn remove(self) {
+        let _ = git(&self.project_path, &["worktree", "remove", "--force", &self.path]).await;
+        let _ = std::fs::remove_dir_all(&self.path);
+        let _ = git(&self.project_path, &["worktree", "prune"]).await;
+    }
+}
+
+/// Worktree that has `branch` checked out, if any.
+async fn worktree_with_branch(project_path: &str, branch: &str) -> Option<String> {
+    let list = git_stdout(project_path, &["worktree", "list", "--porcelain"]).await.ok()?;
+    let wanted = format!("branch refs/heads/{}", branch);
+    let mut current: Option<&str> = None;
+    for line in list.lines() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            current = Some(path);
+        } else if line == wanted {
+            return current.map(str::to_string);
+        }
+    }
+    None
+}
+
+/// Move `branch` from `old` to `new`. If the branch is checked out somewhere,
+/// that checkout is fast-forwarded in place (keeping unrelated local edits)
+/// so it does not suddenly show the merge as reverted changes.
+async fn advance_branch(project_path: &str, branch: &str, old: &str, new: &str) -> Result<(), String> {
+    if let Some(checkout) = worktree_with_branch(project_path, branch).await {
+        git_stdout(&checkout, &["read-tree", "-m", "-u", old, new])
+            .await
+            .map_err(|e| {
+                format!(
+                    "'{}' is checked out at {} with local changes that conflict with the merge: {}",
+                    branch, checkout, e
+                )
+            })?;
+    }
+    git_stdout(
+        project_path,
+        &["update-ref", &format!("refs/heads/{}", branch), new, old],
+    )
+    .await
+    .map(|_| ())
+    .map_err(|e| format!("Failed to update {}: {}", branch, e))
+}
+
+/// How to settle one conflicted file. "Ours" is always the base branch side
+/// and "theirs" the workspace side, whichever strategy is in use.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConflictResolution {
+    Ours,
+    Theirs,
+    Manual { content: String },
+    Delete,
+    /// Let an agent edit the file in the scratch worktree.
+    Agent { agent: String },
+}
+
+/// The three sides of a conflicted file plus the working copy with markers.
+/// Sides that do not exist (e.g. a file added on one side) are `None`, as is
+/// everything for binary files.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConflictFileVersions {
+    pub path: String,
+    pub kind: ConflictKind,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    pub working: Option<String>,
+    pub binary: bool,
+}
+
+/// A merge that has been run in a scratch worktree but not yet applied to
+/// the base branch. Nothing outside the scratch worktree changes until
+/// [`PendingMerge::complete`] succeeds.
+pub struct PendingMerge {
+    scratch: ScratchWorktree,
+    pub project_path: String,
+    pub base_branch: String,
+    pub workspace_branch: String,
+    pub strategy: MergeStrategy,
+    old_base: String,
+    message: String,
+}
+
+/// Run `strategy` for the branch checked out in `worktree_path` in a fresh
+/// scratch worktree and stop there, returning the pending merge and whatever
+/// it conflicted on.
+pub async fn start_merge(
+    project_path: &str,
+    worktree_path: &str,
+    base_branch: &str,
+    strategy: MergeStrategy,
+    message: Option<String>,
+) -> Result<(PendingMerge, Vec<MergeConflict>), String> {
+    let workspace_branch = current_branch(worktree_path).await?;
+    if workspace_branch == "HEAD" {
+        return Err("Workspace worktree is not on a branch".to_string());
+    }
+    if !local_branch_exists(project_path, base_branch).await {
+        return Err(format!("Base branch '{}' is not a local branch", base_branch));
+    }
+
+    let old_base = git_stdout(project_path, &["rev-parse", &format!("refs/heads/{}", base_branch)]).await?;
+    let workspace_tip = git_stdout(project_path, &["rev-parse", &format!("refs/heads/{}", workspace_branch)]).await?;
+    let already_merged = git(project_path, &["merge-base", "--is-ancestor", &workspace_tip, &old_base])
+        .await?
+        .status
+        .success();
+    if already_merged {
+        return Err(format!(
+            "{} has nothing that is not already in {}",
+            workspace_branch, base_branch
+        ));
+    }
+
+    let message = message.unwrap_or_else(|| match strategy {
+        MergeStrategy::Squash => format!("Squash workspace {} into {}", workspace_branch, base_branch),
+        _ => format!("Merge workspace {} into {}", workspace_branch, base_branch),
+    });
+
+    let start = match strategy {
+        MergeStrategy::RebaseFastForward => workspace_tip.as_str(),
+        _ => old_base.as_str(),
+    };
+    let scratch = ScratchWorktree::create(project_path, start).await?;
+
+    let output = match strategy {
+        MergeStrategy::MergeCommit => {
+            git(&scratch.path, &["merge", "--no-ff", "--no-commit", "-m", &message, &workspace_tip]).await
+        }
+        MergeStrategy::Squash => git(&scratch.path, &["merge", "--squash", &workspace_tip]).await,
+        MergeStrategy::RebaseFastForward => git(&scratch.path, &["rebase", &old_base]).await,
+    };
+
+    let pending = PendingMerge {
+        scratch,
+        project_path: project_path.to_string(),
+        base_branch: base_branch.to_string(),
+        workspace_branch,
+        strategy,
+        old_base,
+        message,
+    };
+
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            pending.abort().await;
+            return Err(e);
+        }
+    };
+    let conflicts = pending.conflicts().await;
+    if !output.status.success() && conflicts.is_empty() {
+        pending.abort().await;
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok((pending, conflicts))
+}
+
+impl PendingMerge {
+    /// Scratch worktree the merge is happening in.
+    pub fn scratch_path(&self) -> &str {
+        &self.scratch.path
+    }
+
+    pub fn outcome(&self, merged: bool, commit: Option<String>, conflicts: Vec<MergeConflict>) -> MergeOutcome {
+        MergeOutcome {
+            merged,
+            strategy: self.strategy,
+            base_branch: self.base_branch.clone(),
+            workspace_branch: self.workspace_branch.clone(),
+            commit,
+            conflicts,
+        }
+    }
+
+    /// Files that are still unmerged.
+    pub async fn conflicts(&self) -> Vec<MergeConflict> {
+        conflicts_in(&self.scratch.path).await
+    }
+
+    async fn conflict(&self, path: &str) -> Result<MergeConflict, String> {
+        self.conflicts()
+            .await
+            .into_iter()
+            .find(|c| c.path == path)
+            .ok_or_else(|| format!("{} is not in conflict", path))
+    }
+
+    /// Content of index stage 1 (base), 2 (ours) or 3 (theirs), if present.
+    async fn stage(&self, stage: u8, path: &str) -> Option<Vec<u8>> {
+        let output = git(&self.scratch.path, &["show", &format!(":{}:{}", stage, path)]).await.ok()?;
+        output.status.success().then_some(output.stdout)
+    }
+
+    pub async fn conflict_versions(&self, path: &str) -> Result<ConflictFileVersions, String> {
+        let conflict = self.conflict(path).await?;
+        let stages = [
+            self.stage(1, path).await,
+            self.stage(2, path).await,
+            self.stage(3, path).await,
+            std::fs::read(Path::new(&self.scratch.path).join(path)).ok(),
+        ];
+        let binary = stages
+            .iter()
+            .flatten()
+            .any(|bytes| std::str::from_utf8(bytes).is_err() || bytes.contains(&0));
+        let [base, ours, theirs, working] =
+            stages.map(|bytes| bytes.filter(|_| !binary).map(|b| String::from_utf8_lossy(&b).to_string()));
+
+        Ok(ConflictFileVersions {
+            path: conflict.path,
+            kind: conflict.kind,
+            base,
+            ours,
+            theirs,
+            working,
+            binary,
+        })
+    }
+
+    /// Settle one conflicted file. Agent resolutions are run by the caller,
+    /// which then calls [`PendingMerge::mark_resolved`].
+    pub async fn resolve(&self, path: &str, resolution: &ConflictResolution) -> Result<(), String> {
+        self.conflict(path).await?;
+        let file = Path::new(&self.scratch.path).join(path);
+
+        let content = match resolution {
+            ConflictResolution::Ours => self.stage(2, path).await,
+            ConflictResolution::Theirs => self.stage(3, path).await,
+            ConflictResolution::Manual { content } => Some(content.clone().into_bytes()),
+            ConflictResolution::Delete => None,
+            ConflictResolution::Agent { .. } => {
+                return Err("Agent resolutions must be run through resolve_merge_conflict".to_string())
+            }
+        };
+
+        match content {
+            Some(bytes) => {
+                if let Some(parent) = file.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+                }
+                std::fs::write(&file, bytes).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+                git_stdout(&self.scratch.path, &["add", "--", path]).await?;
+            }
+            None => {
+                git_stdout(&self.scratch.path, &["rm", "--quiet", "--force", "--ignore-unmatch", "--", path]).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stage a file someone else (an agent) edited, refusing it while it
+    /// still has conflict markers.
+    pub async fn mark_resolved(&self, path: &str) -> Result<(), String> {
+        self.conflict(path).await?;
+        let content = std::fs::read_to_string(Path::new(&self.scratch.path).join(path))
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if has_conflict_markers(&content) {
+            return Err(format!("{} still contains conflict markers", path));
+        }
+        git_stdout(&self.scratch.path, &["add", "--", path]).await.map(|_| ())
+    }
+
+    /// Finish the merge and move the base branch to the result.
+    ///
+    /// Returns an outcome with `merged: false` and the new conflicts if a
+    /// rebase stopped again on a later commit; the merge stays pending.
+    pub async fn complete(&self) -> Result<MergeOutcome, String> {
+        let remaining = self.conflicts().await;
+        if !remaining.is_empty() {
+            return Err(format!("{} file(s) still have conflicts", remaining.len()));
+        }
+
+        let scratch = self.scratch.path.as_str();
+        match self.strategy {
+            MergeStrategy::MergeCommit => {
+                git_stdout(scratch, &["commit", "--no-edit", "-m", &self.message])
+                    .await
+                    .map_err(|e| format!("Failed to commit merge: {}", e))?;
+            }
+            MergeStrategy::Squash => {
+                git_stdout(scratch, &["commit", "-m", &self.message])
+                    .await
+                    .map_err(|e| format!("Failed to commit squashed changes: {}", e))?;
+            }
+            MergeStrategy::RebaseFastForward => {
+                // A rebase with no stops left has nothing to continue.
+                if rebase_in_progress(scratch).await {
+                    let output = git(scratch, &["-c", "core.editor=true", "rebase", "--continue"]).await?;
+                    if !output.status.success() {
+                        let conflicts = self.conflicts().await;
+                        if conflicts.is_empty() {
+                            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+                        }
+                        return Ok(self.outcome(false, None, conflicts));
+                    }
+                }
+            }
+        }
+
+        let new_tip = git_stdout(scratch, &["rev-parse", "HEAD"]).await?;
+        advance_branch(&self.project_path, &self.base_branch, &self.old_base, &new_tip).await?;
+        Ok(self.outcome(true, Some(new_tip), Vec::new()))
+    }
+
+    /// Throw the merge away. The base branch was never touched.
+    pub async fn abort(self) {
+        let scratch = self.scratch.path.as_str();
+        match self.strategy {
+            MergeStrategy::RebaseFastForward => {
+                let _ = git(scratch, &["rebase", "--abort"]).await;
+            }
+            _ => {
+                let _ = git(scratch, &["merge", "--abort"]).await;
+            }
+        }
+        self.close().await;
+    }
+
+    /// Remove the scratch worktree once the merge has completed.
+    pub async fn close(self) {
+        self.scratch.remove().await;
+    }
+}
+
+async fn rebase_in_progress(scratch: &str) -> bool {
+    for dir in ["rebase-merge", "rebase-apply"] {
+        if let Ok(path) = git_stdout(scratch, &["rev-parse", "--git-path", dir]).await {
+            if Path::new(scratch).join(&path).exists() || Path::new(&path).exists() {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Whether text still contains git conflict markers.
+pub fn has_conflict_markers(content: &str) -> bool {
+    content.lines().any(|line| {
+        line.starts_with("<<<<<<< ") || line.starts_with(">>>>>>> ") || line == "======="
+    })
+}
+
+/// Prompt used when an agent is asked to settle a conflicted file.
+pub fn agent_resolution_prompt(merge: &PendingMerge, path: &str) -> String {
+    format!(
+        "Resolve the git merge conflict in `{}`. The file contains conflict markers between \
+the base branch `{}` (ours) and the workspace branch `{}` (theirs). Edit only this file so \
+the result keeps the intent of both sides, remove every conflict marker, and do not run any \
+git commands.",
+        path, merge.base_branch, merge.workspace_branch
+    )
+}
+
+/// Bring the branch checked out in `worktree_path` into `base_branch` in one
+/// go. On conflicts the scratch worktree is discarded and the base branch is
+/// left untouched; use [`start_merge`] to resolve them instead.
+pub async fn merge_workspace(
+    project_path: &str,
+    worktree_path: &str,
+    base_branch: &str,
+    strategy: MergeStrategy,
+    message: Option<String>,
+) -> Result<MergeOutcome, String> {
+    let (pending, conflicts) =
+        start_merge(project_path, worktree_path, base_branch, strategy, message).await?;
+    if !conflicts.is_empty() {
+        let outcome = pending.outcome(false, None, conflicts);
+        pending.abort().await;
+        return Ok(outcome);
+    }
+
+    // A fresh rebase that completed cleanly has no further stops, so this
+    // only ever sees `merged` outcomes or errors.
+    let result = pending.complete().await;
+    match &result {
+        Ok(outcome) if outcome.merged => pending.close().await,
+        _ => pending.abort().await,
+    }
+    result
+}
+
+/// Merges waiting for conflict resolution, keyed by merge id.
+#[derive(Default)]
+pub struct MergeRegistry {
+    merges: HashMap<String, PendingMerge>,
+}
+
+impl MergeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, merge_id: &str, merge: PendingMerge) {
+        self.merges.insert(merge_id.to_string(), merge);
+    }
+
+    pub fn get(&self, merge_id: &str) -> Result<&PendingMerge, String> {
+        self.merges
+            .get(merge_id)
+            .ok_or_else(|| format!("No pending merge: {}", merge_id))
+    }
+
+    pub fn remove(&mut self, merge_id: &str) -> Result<PendingMerge, String> {
+        self.merges
+            .remove(merge_id)
+            .ok_or_else(|| format!("No pending merge: {}", merge_id))
+    }
+}
@@ -2,6 +2,7 @@
 mod tests {
     use crate::models::autohand::*;
     use crate::services::autohand::hooks_service;
+    use crate::services::secret_store_service::{is_handle, KeySource, SecretStore};
     use std::collections::HashMap;
     use tempfile::TempDir;
 
@@ -482,14 +483,29 @@ mod tests {
             network: None,
         };
 
-        crate::commands::autohand_commands::save_autohand_config_internal(ws_dir, &config).unwrap();
+        let secrets = SecretStore::open_encrypted(
+            &tmp.path().join("secrets.json"),
+            &KeySource::Passphrase("test".to_string()),
+        )
+        .unwrap();
+        crate::commands::autohand_commands::save_autohand_config_with_store(
+            ws_dir,
+            &config,
+            || Ok(&secrets),
+        )
+        .unwrap();
 
         let raw = std::fs::read_to_string(tmp.path().join(".autohand").join("config.json")).unwrap();
+        assert!(!raw.contains("sk-provider-123"));
         let json: serde_json::Value = serde_json::from_str(&raw).unwrap();
         let provider_block = json.get("openrouter").unwrap();
+        let handle = provider_block.get("apiKey").and_then(|v| v.as_str()).unwrap();
+        assert!(is_handle(handle));
+        assert_eq!(secrets.resolve(handle).unwrap(), "sk-provider-123");
+        // The provider_details copy shares the handle
         assert_eq!(
-            provider_block.get("apiKey").and_then(|v| v.as_str()),
-            Some("sk-provider-123")
+            json.pointer("/provider_details/api_key").and_then(|v| v.as_str()),
+            Some(handle)
         );
         assert_eq!(
             provider_block.get("baseUrl").and_then(|v| v.as_str()),
@@ -500,4 +516,24 @@ mod tests {
             Some("anthropic/claude-sonnet-4-20250514")
         );
     }
+
+    #[test]
+    fn test_config_fingerprint_never_holds_the_api_key() {
+        let mut config = AutohandConfig {
+            provider_details: Some(ProviderDetails {
+                api_key: Some("sk-fingerprint-secret-1".to_string()),
+                ..ProviderDetails::default()
+            }),
+            ..AutohandConfig::default()
+        };
+        let first = crate::commands::autohand_commands::config_fingerprint(&config);
+        assert!(!first.contains("sk-fingerprint-secret-1"));
+
+        config.provider_details.as_mut().unwrap().api_key =
+            Some("sk-fingerprint-secret-2".to_string());
+        assert_ne!(
+            crate::commands::autohand_commands::config_fingerprint(&config),
+            first
+        );
+    }
 }
@@ -0,0 +1,206 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::cli_commands::settings_for_agent;
+    use crate::commands::settings_commands::read_all_agent_settings_file;
+    use crate::headless::{
+        agent_settings_mut, ends_turn, format_event_human, parse_args, HeadlessCommand,
+        OutputFormat,
+    };
+    use crate::models::ai_agent::AllAgentSettings;
+    use crate::models::protocol::{ProtocolEvent, SessionEventKind, TurnUsage};
+    use tempfile::TempDir;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn parse_run(list: &[&str]) -> crate::headless::HeadlessArgs {
+        match parse_args(&args(list)).expect("args should parse") {
+            HeadlessCommand::Run(run) => *run,
+            HeadlessCommand::Help => panic!("expected a run command"),
+        }
+    }
+
+    #[test]
+    fn parses_run_with_all_options() {
+        let run = parse_run(&[
+            "run",
+            "--agent",
+            "claude",
+            "--cwd",
+            "/tmp/project",
+            "--format",
+            "jsonl",
+            "--mode",
+            "full",
+            "--model",
+            "sonnet",
+            "--transport",
+            "acp",
+            "--resume",
+            "abc",
+            "--session-id",
+            "ci-1",
+            "--log",
+            "/tmp/run.jsonl",
+            "--dangerous-bypass",
+            "--auto-approve",
+            "fix",
+            "the",
+            "tests",
+        ]);
+
+        assert_eq!(run.agent, "claude");
+        assert_eq!(run.working_dir.as_deref(), Some("/tmp/project"));
+        assert_eq!(run.format, OutputFormat::Jsonl);
+        assert_eq!(run.execution_mode.as_deref(), Some("full"));
+        assert_eq!(run.model.as_deref(), Some("sonnet"));
+        assert_eq!(run.transport.as_deref(), Some("acp"));
+        assert_eq!(run.resume_session_id.as_deref(), Some("abc"));
+        assert_eq!(run.session_id.as_deref(), Some("ci-1"));
+        assert_eq!(run.log_path.as_deref(), Some("/tmp/run.jsonl"));
+        assert!(run.dangerous_bypass);
+        assert!(run.auto_approve);
+        assert_eq!(run.prompt.as_deref(), Some("fix the tests"));
+    }
+
+    #[test]
+    fn defaults_to_human_output_and_stdin_prompt() {
+        let run = parse_run(&["run", "--agent", "codex"]);
+        assert_eq!(run.format, OutputFormat::Human);
+        assert_eq!(run.prompt, None);
+        assert!(!run.auto_approve);
+    }
+
+    #[test]
+    fn double_dash_keeps_flag_like_prompt_words() {
+        let run = parse_run(&["run", "--agent", "gemini", "--", "--explain", "this"]);
+        assert_eq!(run.prompt.as_deref(), Some("--explain this"));
+    }
+
+    #[test]
+    fn rejects_missing_agent_and_unknown_values() {
+        assert!(parse_args(&args(&["run", "hello"])).is_err());
+        assert!(parse_args(&args(&["run", "--agent"])).is_err());
+        assert!(parse_args(&args(&["run", "--agent", "x", "--format", "xml"])).is_err());
+        assert!(parse_args(&args(&["run", "--agent", "x", "--transport", "grpc"])).is_err());
+        assert!(parse_args(&args(&["run", "--agent", "x", "--bogus"])).is_err());
+        assert!(parse_args(&args(&["launch"])).is_err());
+    }
+
+    #[test]
+    fn help_is_returned_for_empty_args_and_help_flags() {
+        assert_eq!(parse_args(&[]).unwrap(), HeadlessCommand::Help);
+        assert_eq!(parse_args(&args(&["--help"])).unwrap(), HeadlessCommand::Help);
+        assert_eq!(
+            parse_args(&args(&["run", "--agent", "claude", "-h"])).unwrap(),
+            HeadlessCommand::Help
+        );
+    }
+
+    #[test]
+    fn human_format_summarises_tool_and_error_events() {
+        let end = ProtocolEvent::ToolEnd {
+            session_id: "s".into(),
+            tool_id: "t1".into(),
+            tool_name: "write_file".into(),
+            output: None,
+            success: true,
+            duration_ms: Some(12),
+        };
+        assert_eq!(format_event_human(&end).as_deref(), Some("✔ write_file (12ms)"));
+
+        let err = ProtocolEvent::Error {
+            session_id: "s".into(),
+            message: "boom".into(),
+            failure: None,
+        };
+        assert_eq!(format_event_human(&err).as_deref(), Some("❌ boom"));
+
+        let connected = ProtocolEvent::SessionEvent {
+            session_id: "s".into(),
+            event: SessionEventKind::Connected,
+        };
+        assert_eq!(format_event_human(&connected), None);
+    }
+
+    #[test]
+    fn human_format_reports_turn_cost() {
+        let done = ProtocolEvent::StateChange {
+            session_id: "s".into(),
+            status: "idle".into(),
+            context_percent: None,
+            usage: Some(TurnUsage {
+                input_tokens: 1200,
+                output_tokens: 340,
+                cost_usd: Some(0.0123),
+                ..TurnUsage::default()
+            }),
+            agent_session_id: None,
+        };
+        assert_eq!(
+            format_event_human(&done).as_deref(),
+            Some("💰 $0.0123 · 1200 in / 340 out tokens")
+        );
+    }
+
+    #[test]
+    fn streamed_messages_end_with_the_turn() {
+        let state = |status: &str| ProtocolEvent::StateChange {
+            session_id: "s".into(),
+            status: status.into(),
+            context_percent: Some(12.0),
+            usage: None,
+            agent_session_id: None,
+        };
+        assert!(!ends_turn(&state("running")));
+        assert!(ends_turn(&state("idle")));
+        assert!(!ends_turn(&ProtocolEvent::Message {
+            session_id: "s".into(),
+            role: "assistant".into(),
+            content: "Hel".into(),
+        }));
+    }
+
+    #[test]
+    fn reads_agent_settings_from_store_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("all-agent-settings.json");
+        let mut saved = AllAgentSettings::default();
+        saved.claude.model = Some("opus".into());
+        saved.claude.transport = Some("acp".into());
+        std::fs::write(
+            &path,
+            serde_json::json!({ "all_agent_settings": saved }).to_string(),
+        )
+        .unwrap();
+
+        let settings = read_all_agent_settings_file(&path).unwrap();
+        assert_eq!(settings.claude.model.as_deref(), Some("opus"));
+        assert_eq!(settings.claude.transport.as_deref(), Some("acp"));
+    }
+
+    #[test]
+    fn missing_store_file_yields_default_settings() {
+        let dir = TempDir::new().unwrap();
+        let settings = read_all_agent_settings_file(&dir.path().join("missing.json")).unwrap();
+        assert_eq!(settings.max_concurrent_sessions, 10);
+    }
+
+    #[test]
+    fn overrides_reach_the_settings_the_run_uses() {
+        for agent in [
+            "autohand", "claude", "codex", "gemini", "cursor", "copilot", "pi", "opencode", "vibe",
+            "amp",
+        ] {
+            let mut settings = AllAgentSettings::default();
+            let slot = agent_settings_mut(&mut settings, agent).expect("built-in agent");
+            slot.model = Some("override".into());
+            slot.transport = Some("acp".into());
+
+            let used = settings_for_agent(&settings, agent);
+            assert_eq!(used.model.as_deref(), Some("override"), "{agent}");
+            assert_eq!(used.transport.as_deref(), Some("acp"), "{agent}");
+        }
+    }
+}
@@ -2,6 +2,7 @@
 pub mod chat_history;
 pub mod commands;
 pub mod error_handling;
+pub mod headless;
 pub mod integration;
 pub mod models;
 pub mod services;
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::models::protocol::{ProtocolMode, ProtocolError, ProtocolEvent, SessionEventKind, ToolKind};
+    use crate::models::protocol::{PermissionTarget, ProtocolMode, ProtocolError, ProtocolEvent, RunOutcome, SessionEndReason, SessionEventKind, ToolKind, TurnUsage};
 
     #[test]
     fn protocol_mode_serializes_to_lowercase() {
@@ -82,6 +82,20 @@ mod tests {
         assert_eq!(json, "\"fallback_to_pty\"");
         let back: SessionEventKind = serde_json::from_str(&json).unwrap();
         assert_eq!(back, kind);
+        assert_eq!(
+            serde_json::to_string(&SessionEventKind::ReconnectFailed).unwrap(),
+            "\"reconnect_failed\""
+        );
+    }
+
+    #[test]
+    fn only_crashes_a_restart_could_fix_are_restartable() {
+        assert!(ProtocolError::ProcessDied(1).is_restartable());
+        assert!(ProtocolError::ProcessDied(137).is_restartable());
+        // Usage errors and a missing or unrunnable binary fail the same way again.
+        assert!(!ProtocolError::ProcessDied(64).is_restartable());
+        assert!(!ProtocolError::ProcessDied(127).is_restartable());
+        assert!(!ProtocolError::Timeout("prompt".into()).is_restartable());
     }
 
     #[test]
@@ -90,6 +104,8 @@ mod tests {
             session_id: "s3".into(),
             status: "running".into(),
             context_percent: Some(42.5),
+            usage: None,
+            agent_session_id: None,
         };
         let json = serde_json::to_value(&event).unwrap();
         assert_eq!(json["type"], "StateChange");
@@ -101,10 +117,44 @@ mod tests {
             session_id: "s4".into(),
             status: "idle".into(),
             context_percent: None,
+            usage: None,
+            agent_session_id: None,
         };
         let json2 = serde_json::to_value(&event_no_pct).unwrap();
         assert_eq!(json2["data"]["status"], "idle");
         assert!(json2["data"]["context_percent"].is_null());
+        assert!(json2["data"].get("usage").is_none());
+        assert!(json2["data"].get("agent_session_id").is_none());
+    }
+
+    #[test]
+    fn state_change_round_trips_usage_and_agent_session_id() {
+        let event = ProtocolEvent::StateChange {
+            session_id: "s5".into(),
+            status: "idle".into(),
+            context_percent: None,
+            usage: Some(TurnUsage {
+                model: Some("claude-sonnet-4-5".into()),
+                input_tokens: 10,
+                output_tokens: 20,
+                cache_read_tokens: 30,
+                cache_creation_tokens: 40,
+                cost_usd: Some(0.5),
+            }),
+            agent_session_id: Some("native-1".into()),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["data"]["usage"]["output_tokens"], 20);
+        assert_eq!(json["data"]["agent_session_id"], "native-1");
+        let back: ProtocolEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(back, event);
+
+        let legacy = serde_json::json!({
+            "type": "StateChange",
+            "data": { "session_id": "s6", "status": "idle", "context_percent": null }
+        });
+        let parsed: ProtocolEvent = serde_json::from_value(legacy).unwrap();
+        assert!(matches!(parsed, ProtocolEvent::StateChange { usage: None, agent_session_id: None, .. }));
     }
 
     #[test]
@@ -114,11 +164,77 @@ mod tests {
             request_id: "req-001".into(),
             tool_name: "write_file".into(),
             description: "Write to /etc/hosts".into(),
+            target: None,
+            tool_id: None,
         };
         let json = serde_json::to_value(&event).unwrap();
         assert_eq!(json["type"], "PermissionRequest");
         assert_eq!(json["data"]["request_id"], "req-001");
         assert_eq!(json["data"]["tool_name"], "write_file");
         assert_eq!(json["data"]["description"], "Write to /etc/hosts");
+        assert!(json["data"].get("target").is_none());
+        assert!(json["data"].get("tool_id").is_none());
+    }
+
+    #[test]
+    fn permission_request_target_round_trips() {
+        let event = ProtocolEvent::PermissionRequest {
+            session_id: "s5".into(),
+            request_id: "req-002".into(),
+            tool_name: "Run tests".into(),
+            description: "cargo test".into(),
+            target: Some(PermissionTarget {
+                tool_kind: ToolKind::Execute,
+                command: Some("cargo test".into()),
+                paths: Vec::new(),
+            }),
+            tool_id: Some("call-7".into()),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["data"]["target"]["tool_kind"], "execute");
+        assert_eq!(json["data"]["target"]["command"], "cargo test");
+        let parsed: ProtocolEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, event);
+    }
+
+    #[test]
+    fn the_last_settling_event_decides_the_outcome() {
+        let state = |status: &str| ProtocolEvent::StateChange {
+            session_id: "s".into(),
+            status: status.into(),
+            context_percent: None,
+            usage: None,
+            agent_session_id: None,
+        };
+        let error = ProtocolEvent::Error {
+            session_id: "s".into(),
+            message: "rate limited, retrying".into(),
+            failure: None,
+        };
+        let last = |events: &[ProtocolEvent]| events.iter().rev().find_map(|e| e.outcome());
+
+        assert_eq!(state("running").outcome(), None);
+        assert_eq!(state("budget_warning").outcome(), None);
+        assert_eq!(state("error_max_turns").outcome(), Some(RunOutcome::Failed));
+        assert_eq!(
+            last(&[state("running"), error.clone(), state("idle")]),
+            Some(RunOutcome::Completed)
+        );
+        assert_eq!(
+            last(&[state("idle"), error.clone()]),
+            Some(RunOutcome::Failed)
+        );
+        let ended = |reason| ProtocolEvent::SessionEnded {
+            session_id: "s".into(),
+            reason,
+        };
+        assert_eq!(
+            last(&[state("idle"), ended(SessionEndReason::Completed)]),
+            Some(RunOutcome::Completed)
+        );
+        assert_eq!(
+            last(&[state("idle"), ended(SessionEndReason::IdleTimeout)]),
+            Some(RunOutcome::Failed)
+        );
     }
 }
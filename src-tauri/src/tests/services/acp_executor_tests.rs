@@ -3,8 +3,22 @@ mod tests {
     use crate::services::executors::acp_executor::{
         classify_acp_message, resolve_tool_kind, AcpMessage, AcpExecutor,
     };
-    use crate::services::executors::AgentExecutor;
-    use crate::models::protocol::{ProtocolMode, ToolKind};
+    use crate::services::executors::acp_jsonrpc::{
+        acp_tool_kind, parse_acp_rpc_line, permission_outcome, select_permission_option,
+        stop_reason_status, AcpIncoming, AcpRpcError, AgentCapabilities, SessionUpdateMapper,
+    };
+    use crate::services::executors::{restart_delay, AgentExecutor};
+    use crate::services::executors::acp_executor::AcpDialect;
+    use crate::services::event_sink::{RecordingEventSink, SharedEventSink};
+    use crate::models::ai_agent::AgentSettings;
+    use crate::models::protocol::{
+        AgentFailure, AgentFailureKind, PermissionTarget, PlanEntry, ProtocolError, ProtocolEvent,
+        ProtocolMode, SessionEventKind, ToolKind,
+    };
+    use crate::services::agent_diagnostics_service;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[test]
     fn classify_message_event() {
@@ -102,4 +116,635 @@ mod tests {
         let executor = AcpExecutor::new(Some("--mode acp".to_string()));
         assert_eq!(executor.protocol(), Some(ProtocolMode::Acp));
     }
+
+    #[test]
+    fn acp_executor_defaults_to_json_rpc_dialect() {
+        assert_eq!(AcpDialect::default(), AcpDialect::JsonRpc);
+    }
+
+    // -----------------------------------------------------------------------
+    // JSON-RPC dialect
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn parse_rpc_line_distinguishes_responses_notifications_and_requests() {
+        let response = parse_acp_rpc_line(r#"{"jsonrpc":"2.0","id":3,"result":{"ok":true}}"#).unwrap();
+        assert_eq!(
+            response,
+            AcpIncoming::Response { id: 3, result: Ok(json!({"ok": true})) }
+        );
+
+        let error = parse_acp_rpc_line(
+            r#"{"jsonrpc":"2.0","id":4,"error":{"code":-32000,"message":"auth required"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            error,
+            AcpIncoming::Response {
+                id: 4,
+                result: Err(AcpRpcError { code: -32000, message: "auth required".into() }),
+            }
+        );
+
+        match parse_acp_rpc_line(r#"{"jsonrpc":"2.0","method":"session/update","params":{"x":1}}"#).unwrap() {
+            AcpIncoming::Notification { method, params } => {
+                assert_eq!(method, "session/update");
+                assert_eq!(params, json!({"x": 1}));
+            }
+            other => panic!("expected Notification, got {:?}", other),
+        }
+
+        match parse_acp_rpc_line(r#"{"jsonrpc":"2.0","id":"p1","method":"session/request_permission","params":{}}"#).unwrap() {
+            AcpIncoming::Request { id, method, .. } => {
+                assert_eq!(id, json!("p1"));
+                assert_eq!(method, "session/request_permission");
+            }
+            other => panic!("expected Request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_rpc_line_rejects_legacy_envelope() {
+        assert!(parse_acp_rpc_line(r#"{"type":"message","data":{}}"#).is_err());
+        assert!(parse_acp_rpc_line("not json").is_err());
+        assert!(parse_acp_rpc_line("  ").is_err());
+    }
+
+    #[test]
+    fn capabilities_are_read_from_initialize_result() {
+        let caps = AgentCapabilities::from_initialize_result(&json!({
+            "protocolVersion": 1,
+            "agentCapabilities": {
+                "loadSession": true,
+                "promptCapabilities": { "image": true }
+            }
+        }));
+        assert_eq!(caps.protocol_version, 1);
+        assert!(caps.load_session);
+        assert!(caps.image_prompts);
+        assert!(!caps.embedded_context);
+
+        let bare = AgentCapabilities::from_initialize_result(&json!({"protocolVersion": 1}));
+        assert!(!bare.load_session);
+    }
+
+    #[test]
+    fn stop_reason_maps_to_status() {
+        assert_eq!(stop_reason_status(&json!({"stopReason": "end_turn"})), "idle");
+        assert_eq!(stop_reason_status(&json!({"stopReason": "cancelled"})), "cancelled");
+        assert_eq!(stop_reason_status(&json!({})), "idle");
+    }
+
+    #[test]
+    fn acp_tool_kinds_map_onto_ours() {
+        assert_eq!(acp_tool_kind("read"), ToolKind::Read);
+        assert_eq!(acp_tool_kind("edit"), ToolKind::Edit);
+        assert_eq!(acp_tool_kind("move"), ToolKind::Edit);
+        assert_eq!(acp_tool_kind("delete"), ToolKind::Delete);
+        assert_eq!(acp_tool_kind("execute"), ToolKind::Execute);
+        assert_eq!(acp_tool_kind("switch_mode"), ToolKind::Other);
+    }
+
+    #[test]
+    fn message_chunks_become_messages() {
+        let mut mapper = SessionUpdateMapper::new();
+        let events = mapper.map(
+            "s1",
+            &json!({"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "Hel"}}),
+        );
+        assert_eq!(
+            events,
+            vec![ProtocolEvent::Message {
+                session_id: "s1".into(),
+                role: "assistant".into(),
+                content: "Hel".into(),
+            }]
+        );
+        assert!(mapper
+            .map("s1", &json!({"sessionUpdate": "agent_thought_chunk", "content": {"type": "text", "text": "hmm"}}))
+            .is_empty());
+    }
+
+    #[test]
+    fn tool_call_lifecycle_keeps_id_and_title() {
+        let mut mapper = SessionUpdateMapper::new();
+        let start = mapper.map(
+            "s1",
+            &json!({
+                "sessionUpdate": "tool_call",
+                "toolCallId": "call_1",
+                "title": "Reading config",
+                "kind": "read",
+                "status": "pending",
+                "rawInput": {"path": "/tmp/a"}
+            }),
+        );
+        assert_eq!(
+            start,
+            vec![ProtocolEvent::ToolStart {
+                session_id: "s1".into(),
+                tool_id: "call_1".into(),
+                tool_name: "Reading config".into(),
+                tool_kind: ToolKind::Read,
+                args: Some(json!({"path": "/tmp/a"})),
+            }]
+        );
+
+        let update = mapper.map(
+            "s1",
+            &json!({"sessionUpdate": "tool_call_update", "toolCallId": "call_1", "status": "in_progress"}),
+        );
+        assert_eq!(
+            update,
+            vec![ProtocolEvent::ToolUpdate {
+                session_id: "s1".into(),
+                tool_id: "call_1".into(),
+                tool_name: "Reading config".into(),
+                output: None,
+            }]
+        );
+
+        let end = mapper.map(
+            "s1",
+            &json!({
+                "sessionUpdate": "tool_call_update",
+                "toolCallId": "call_1",
+                "status": "completed",
+                "content": [{"type": "content", "content": {"type": "text", "text": "key = 1"}}]
+            }),
+        );
+        match &end[..] {
+            [ProtocolEvent::ToolEnd { tool_id, tool_name, output, success, duration_ms, .. }] => {
+                assert_eq!(tool_id, "call_1");
+                assert_eq!(tool_name, "Reading config");
+                assert_eq!(output.as_deref(), Some("key = 1"));
+                assert!(success);
+                assert!(duration_ms.is_some());
+            }
+            other => panic!("expected ToolEnd, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn completed_tool_call_emits_start_and_end() {
+        let mut mapper = SessionUpdateMapper::new();
+        let events = mapper.map(
+            "s1",
+            &json!({
+                "sessionUpdate": "tool_call",
+                "toolCallId": "call_2",
+                "title": "rm -rf build",
+                "kind": "execute",
+                "status": "failed",
+                "rawOutput": "permission denied"
+            }),
+        );
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], ProtocolEvent::ToolStart { ref tool_kind, .. } if *tool_kind == ToolKind::Execute));
+        match &events[1] {
+            ProtocolEvent::ToolEnd { success, output, .. } => {
+                assert!(!success);
+                assert_eq!(output.as_deref(), Some("permission denied"));
+            }
+            other => panic!("expected ToolEnd, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn plan_update_becomes_plan_event() {
+        let mut mapper = SessionUpdateMapper::new();
+        let events = mapper.map(
+            "s1",
+            &json!({
+                "sessionUpdate": "plan",
+                "entries": [
+                    {"content": "Read the code", "priority": "high", "status": "completed"},
+                    {"content": "Fix the bug", "priority": "medium", "status": "pending"}
+                ]
+            }),
+        );
+        assert_eq!(
+            events,
+            vec![ProtocolEvent::Plan {
+                session_id: "s1".into(),
+                entries: vec![
+                    PlanEntry {
+                        content: "Read the code".into(),
+                        priority: "high".into(),
+                        status: "completed".into(),
+                    },
+                    PlanEntry {
+                        content: "Fix the bug".into(),
+                        priority: "medium".into(),
+                        status: "pending".into(),
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn permission_option_prefers_one_off_kinds() {
+        let options = vec![
+            json!({"optionId": "always", "name": "Always", "kind": "allow_always"}),
+            json!({"optionId": "once", "name": "Once", "kind": "allow_once"}),
+            json!({"optionId": "no", "name": "No", "kind": "reject_once"}),
+        ];
+        assert_eq!(select_permission_option(&options, true).as_deref(), Some("once"));
+        assert_eq!(select_permission_option(&options, false).as_deref(), Some("no"));
+        assert_eq!(select_permission_option(&options[..2], false), None);
+
+        assert_eq!(
+            permission_outcome(Some("once".into())),
+            json!({"outcome": {"outcome": "selected", "optionId": "once"}})
+        );
+        assert_eq!(permission_outcome(None), json!({"outcome": {"outcome": "cancelled"}}));
+    }
+
+    #[cfg(unix)]
+    fn write_script(dir: &std::path::Path, name: &str, body: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    async fn wait_until_dead(executor: &AcpExecutor) {
+        for _ in 0..100 {
+            if !executor.is_alive() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("agent still alive after 5s");
+    }
+
+    async fn wait_until_idle(executor: &AcpExecutor) {
+        for _ in 0..100 {
+            if !executor.is_busy() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("turn still running after 5s");
+    }
+
+    /// Answers the handshake with fixed ids: the client numbers its requests
+    /// from zero.
+    const HANDSHAKE: &str = r#"read init
+echo '{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":1,"agentCapabilities":{}}}'
+read new
+echo '{"jsonrpc":"2.0","id":1,"result":{"sessionId":"acp-1"}}'
+read prompt
+"#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn json_rpc_executor_runs_one_prompt_turn() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let body = format!(
+            r#"{}echo '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"acp-1","update":{{"sessionUpdate":"agent_message_chunk","content":{{"type":"text","text":"hi"}}}}}}}}'
+echo '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"acp-1","update":{{"sessionUpdate":"tool_call","toolCallId":"t1","title":"ls","kind":"execute","status":"completed"}}}}}}'
+echo '{{"jsonrpc":"2.0","id":2,"result":{{"stopReason":"end_turn"}}}}'
+cat > /dev/null
+"#,
+            HANDSHAKE
+        );
+        let agent = write_script(dir.path(), "fake-acp", &body);
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = AcpExecutor::new(None);
+        executor
+            .execute(&sink, "s1", &agent, "hello", &dir.path().to_string_lossy(), &AgentSettings::default(), None)
+            .await
+            .unwrap();
+        wait_until_idle(&executor).await;
+        assert!(executor.is_alive(), "agent should outlive the turn");
+        executor.shutdown().await.unwrap();
+        wait_until_dead(&executor).await;
+        assert_eq!(executor.exit_error(), None, "a requested stop is not a crash");
+
+        let events = recorder.protocol_events();
+        let kinds: Vec<&str> = events
+            .iter()
+            .map(|event| match event {
+                ProtocolEvent::SessionEvent { event: SessionEventKind::Connected, .. } => "connected",
+                ProtocolEvent::SessionEvent { event: SessionEventKind::Disconnected, .. } => "disconnected",
+                ProtocolEvent::Message { .. } => "message",
+                ProtocolEvent::ToolStart { .. } => "tool_start",
+                ProtocolEvent::ToolEnd { .. } => "tool_end",
+                ProtocolEvent::StateChange { .. } => "state",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["connected", "message", "tool_start", "tool_end", "state", "disconnected"]
+        );
+        assert_eq!(
+            events[4],
+            ProtocolEvent::StateChange {
+                session_id: "s1".into(),
+                status: "idle".into(),
+                context_percent: None,
+                usage: None,
+                agent_session_id: None,
+            }
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn json_rpc_executor_routes_permission_answer_to_agent() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let body = format!(
+            r#"{}echo '{{"jsonrpc":"2.0","id":"perm-1","method":"session/request_permission","params":{{"sessionId":"acp-1","toolCall":{{"toolCallId":"t1","title":"Write file"}},"options":[{{"optionId":"yes","name":"Allow","kind":"allow_once"}},{{"optionId":"no","name":"Reject","kind":"reject_once"}}]}}}}'
+read answer
+case "$answer" in *'"optionId":"yes"'*) text=approved ;; *) text=denied ;; esac
+echo '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"acp-1","update":{{"sessionUpdate":"agent_message_chunk","content":{{"type":"text","text":"'$text'"}}}}}}}}'
+echo '{{"jsonrpc":"2.0","id":2,"result":{{"stopReason":"end_turn"}}}}'
+cat > /dev/null
+"#,
+            HANDSHAKE
+        );
+        let agent = write_script(dir.path(), "fake-acp", &body);
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = AcpExecutor::new(None);
+        executor
+            .execute(&sink, "s1", &agent, "hello", &dir.path().to_string_lossy(), &AgentSettings::default(), None)
+            .await
+            .unwrap();
+
+        for _ in 0..100 {
+            if recorder
+                .protocol_events()
+                .iter()
+                .any(|e| matches!(e, ProtocolEvent::PermissionRequest { .. }))
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        executor.respond_permission("perm-1", true).await.unwrap();
+        wait_until_idle(&executor).await;
+        executor.shutdown().await.unwrap();
+
+        let events = recorder.protocol_events();
+        assert!(events.contains(&ProtocolEvent::PermissionRequest {
+            session_id: "s1".into(),
+            request_id: "perm-1".into(),
+            tool_name: "Write file".into(),
+            description: "Write file".into(),
+            target: Some(PermissionTarget {
+                tool_kind: ToolKind::Other,
+                command: None,
+                paths: Vec::new(),
+            }),
+            tool_id: Some("t1".into()),
+        }));
+        assert!(events.contains(&ProtocolEvent::Message {
+            session_id: "s1".into(),
+            role: "assistant".into(),
+            content: "approved".into(),
+        }));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn json_rpc_executor_runs_follow_up_turns_on_the_same_process() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let body = format!(
+            r#"{}echo '{{"jsonrpc":"2.0","id":2,"result":{{"stopReason":"end_turn"}}}}'
+read follow_up
+case "$follow_up" in *'"text":"again"'*) text=second ;; *) text=wrong ;; esac
+echo '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"acp-1","update":{{"sessionUpdate":"agent_message_chunk","content":{{"type":"text","text":"'$text'"}}}}}}}}'
+echo '{{"jsonrpc":"2.0","id":3,"result":{{"stopReason":"end_turn"}}}}'
+cat > /dev/null
+"#,
+            HANDSHAKE
+        );
+        let agent = write_script(dir.path(), "fake-acp", &body);
+
+        let first = Arc::new(RecordingEventSink::new());
+        let first_sink: SharedEventSink = first.clone();
+        let mut executor = AcpExecutor::new(None);
+        assert!(executor.supports_multi_turn());
+        executor
+            .execute(&first_sink, "s1", &agent, "hello", &dir.path().to_string_lossy(), &AgentSettings::default(), None)
+            .await
+            .unwrap();
+        wait_until_idle(&executor).await;
+
+        let second = Arc::new(RecordingEventSink::new());
+        let second_sink: SharedEventSink = second.clone();
+        executor.send_prompt(&second_sink, "s2", "again").await.unwrap();
+        wait_until_idle(&executor).await;
+        executor.shutdown().await.unwrap();
+        wait_until_dead(&executor).await;
+
+        let events = second.protocol_events();
+        assert!(events.contains(&ProtocolEvent::Message {
+            session_id: "s2".into(),
+            role: "assistant".into(),
+            content: "second".into(),
+        }));
+        assert!(events.contains(&ProtocolEvent::StateChange {
+            session_id: "s2".into(),
+            status: "idle".into(),
+            context_percent: None,
+            usage: None,
+            agent_session_id: None,
+        }));
+        assert!(!first
+            .protocol_events()
+            .iter()
+            .any(|e| matches!(e, ProtocolEvent::Message { .. })));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn json_rpc_executor_reports_an_agent_dying_mid_turn() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let body = format!("{}exit 3\n", HANDSHAKE);
+        let agent = write_script(dir.path(), "fake-acp", &body);
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = AcpExecutor::new(None);
+        executor
+            .execute(&sink, "s1", &agent, "hello", &dir.path().to_string_lossy(), &AgentSettings::default(), None)
+            .await
+            .unwrap();
+        wait_until_dead(&executor).await;
+        wait_until_idle(&executor).await;
+
+        assert_eq!(executor.exit_error(), Some(ProtocolError::ProcessDied(3)));
+        assert_eq!(executor.agent_session_id().as_deref(), Some("acp-1"));
+        // The supervisor reports the crash once it knows whether it can restart.
+        assert!(!recorder
+            .protocol_events()
+            .iter()
+            .any(|e| matches!(e, ProtocolEvent::Error { .. })));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn json_rpc_executor_reports_recognised_stderr_failures() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let body = format!(
+            "{}echo 'Error: ANTHROPIC_API_KEY is not set' >&2\nexit 1\n",
+            HANDSHAKE
+        );
+        let agent = write_script(dir.path(), "fake-acp", &body);
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = AcpExecutor::new(None);
+        executor
+            .execute(&sink, "diag-acp", &agent, "hello", &dir.path().to_string_lossy(), &AgentSettings::default(), None)
+            .await
+            .unwrap();
+        wait_until_dead(&executor).await;
+        for _ in 0..100 {
+            if agent_diagnostics_service::session_diagnostics("diag-acp").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let diagnostics = agent_diagnostics_service::session_diagnostics("diag-acp").unwrap();
+        assert_eq!(diagnostics.lines, vec!["Error: ANTHROPIC_API_KEY is not set".to_string()]);
+        let failures: Vec<AgentFailure> = recorder
+            .protocol_events()
+            .into_iter()
+            .filter_map(|e| match e {
+                ProtocolEvent::Error { failure, .. } => failure,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(failures, vec![AgentFailure::from(AgentFailureKind::MissingApiKey)]);
+    }
+
+    #[test]
+    fn restart_delay_doubles_up_to_a_cap() {
+        let delays: Vec<u64> = (1..=7).map(|n| restart_delay(n).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn json_rpc_executor_interrupt_cancels_turn_and_keeps_agent() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let body = format!(
+            r#"{}echo '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"acp-1","update":{{"sessionUpdate":"agent_message_chunk","content":{{"type":"text","text":"working"}}}}}}}}'
+read cancel
+case "$cancel" in *'"method":"session/cancel"'*) reason=cancelled ;; *) reason=end_turn ;; esac
+echo '{{"jsonrpc":"2.0","id":2,"result":{{"stopReason":"'$reason'"}}}}'
+cat > /dev/null
+"#,
+            HANDSHAKE
+        );
+        let agent = write_script(dir.path(), "fake-acp", &body);
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = AcpExecutor::new(None);
+        executor
+            .execute(&sink, "s1", &agent, "hello", &dir.path().to_string_lossy(), &AgentSettings::default(), None)
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if recorder
+                .protocol_events()
+                .iter()
+                .any(|e| matches!(e, ProtocolEvent::Message { .. }))
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(executor.is_busy());
+        executor.interrupt().await.unwrap();
+        wait_until_idle(&executor).await;
+
+        assert!(executor.is_alive());
+        assert!(recorder.protocol_events().contains(&ProtocolEvent::StateChange {
+            session_id: "s1".into(),
+            status: "cancelled".into(),
+            context_percent: None,
+            usage: None,
+            agent_session_id: None,
+        }));
+        executor.shutdown().await.unwrap();
+        wait_until_dead(&executor).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn json_rpc_executor_serves_file_writes_inside_working_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let body = format!(
+            r#"{}echo '{{"jsonrpc":"2.0","id":"w1","method":"fs/write_text_file","params":{{"sessionId":"acp-1","path":"'"$PWD"'/notes.txt","content":"hello"}}}}'
+read written
+echo '{{"jsonrpc":"2.0","id":"w2","method":"fs/write_text_file","params":{{"sessionId":"acp-1","path":"/tmp/../etc/escape.txt","content":"x"}}}}'
+read refused
+stop=refusal
+case "$written" in *'"result"'*) case "$refused" in *'"error"'*) stop=end_turn ;; esac ;; esac
+echo '{{"jsonrpc":"2.0","id":2,"result":{{"stopReason":"'$stop'"}}}}'
+cat > /dev/null
+"#,
+            HANDSHAKE
+        );
+        let agent = write_script(dir.path(), "fake-acp", &body);
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = AcpExecutor::new(None);
+        executor
+            .execute(&sink, "s1", &agent, "hello", &dir.path().to_string_lossy(), &AgentSettings::default(), None)
+            .await
+            .unwrap();
+        wait_until_idle(&executor).await;
+        executor.shutdown().await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(dir.path().join("notes.txt")).unwrap(), "hello");
+        let events = recorder.protocol_events();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ProtocolEvent::ToolEnd { tool_name, output: Some(diff), success: true, .. }
+                if tool_name == "Write notes.txt" && diff.contains("+hello")
+        )));
+        assert!(events.contains(&ProtocolEvent::StateChange {
+            session_id: "s1".into(),
+            status: "idle".into(),
+            context_percent: None,
+            usage: None,
+            agent_session_id: None,
+        }));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn json_rpc_executor_fails_when_agent_skips_handshake() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let agent = write_script(dir.path(), "not-acp", "echo 'plain text output'\n");
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = AcpExecutor::new(None);
+        let result = executor
+            .execute(&sink, "s1", &agent, "hello", &dir.path().to_string_lossy(), &AgentSettings::default(), None)
+            .await;
+
+        assert!(result.is_err());
+        assert!(!executor.is_alive());
+        assert!(!recorder
+            .protocol_events()
+            .iter()
+            .any(|e| matches!(e, ProtocolEvent::SessionEvent { .. })));
+    }
 }
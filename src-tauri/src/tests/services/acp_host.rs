@@ -0,0 +1,285 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::protocol::{ProtocolEvent, ToolKind};
+    use crate::services::event_sink::{RecordingEventSink, SharedEventSink};
+    use crate::services::executors::acp_host::{
+        clear_unsaved_buffer, set_unsaved_buffer, slice_lines, take_utf8, unified_diff, AcpHost,
+        TerminalOutput,
+    };
+    use crate::services::executors::acp_jsonrpc::INVALID_PARAMS;
+    use crate::services::executors::TurnTarget;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn host(dir: &tempfile::TempDir) -> (AcpHost, Arc<RecordingEventSink>) {
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let host = AcpHost::new(&dir.path().to_string_lossy(), TurnTarget::shared(&sink, "s1"));
+        (host, recorder)
+    }
+
+    #[test]
+    fn resolve_confines_paths_to_root() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (host, _) = host(&dir);
+        let root = dir.path().canonicalize().unwrap();
+
+        assert_eq!(host.resolve("src/main.rs").unwrap(), root.join("src/main.rs"));
+        assert_eq!(
+            host.resolve(&root.join("a/../b.txt").to_string_lossy()).unwrap(),
+            root.join("b.txt")
+        );
+        assert_eq!(host.resolve("../outside.txt").unwrap_err().code, INVALID_PARAMS);
+        assert!(host.resolve("/etc/passwd").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_refuses_symlink_out_of_root() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let outside = tempfile::TempDir::new().unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("escape")).unwrap();
+        let (host, _) = host(&dir);
+
+        assert!(host.resolve("escape/secret.txt").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn write_through_dangling_symlink_is_refused() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let outside = tempfile::TempDir::new().unwrap();
+        let target = outside.path().join("new.txt");
+        std::os::unix::fs::symlink(&target, dir.path().join("evil")).unwrap();
+        let (host, _) = host(&dir);
+
+        let result = host
+            .handle(
+                "fs/write_text_file",
+                &json!({ "sessionId": "acp-1", "path": "evil", "content": "x" }),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.unwrap_err().code, INVALID_PARAMS);
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn slice_lines_is_one_based_and_keeps_endings() {
+        let content = "one\ntwo\nthree\n";
+        assert_eq!(slice_lines(content, None, None), content);
+        assert_eq!(slice_lines(content, Some(2), None), "two\nthree\n");
+        assert_eq!(slice_lines(content, Some(1), Some(2)), "one\ntwo\n");
+        assert_eq!(slice_lines(content, Some(9), Some(1)), "");
+    }
+
+    #[test]
+    fn unified_diff_shows_changed_lines() {
+        let diff = unified_diff("notes.txt", "a\nb\n", "a\nc\n");
+        assert!(diff.contains("notes.txt"));
+        assert!(diff.contains("-b"));
+        assert!(diff.contains("+c"));
+    }
+
+    #[test]
+    fn terminal_output_drops_oldest_bytes_past_limit() {
+        let mut output = TerminalOutput::new(Some(4));
+        output.push("ab");
+        assert!(!output.truncated);
+        output.push("cdef");
+        assert_eq!(output.text, "cdef");
+        assert!(output.truncated);
+
+        let mut output = TerminalOutput::new(Some(3));
+        output.push("é€");
+        assert_eq!(output.text, "€");
+    }
+
+    #[test]
+    fn characters_split_across_reads_wait_for_their_rest() {
+        let bytes = "{\"text\":\"é€\"}\n".as_bytes();
+        let mut pending = bytes[..10].to_vec();
+        assert_eq!(take_utf8(&mut pending), "{\"text\":\"");
+        assert_eq!(pending.len(), 1);
+        pending.extend_from_slice(&bytes[10..]);
+        assert_eq!(take_utf8(&mut pending), "é€\"}\n");
+        assert!(pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_text_file_prefers_unsaved_buffer() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("draft.txt");
+        std::fs::write(&file, "on disk\n").unwrap();
+        let (host, _) = host(&dir);
+        let path = file.to_string_lossy().to_string();
+
+        let read = host
+            .handle("fs/read_text_file", &json!({ "sessionId": "acp-1", "path": path }))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read, json!({ "content": "on disk\n" }));
+
+        set_unsaved_buffer(&path, "in editor\n".into());
+        let read = host
+            .handle("fs/read_text_file", &json!({ "sessionId": "acp-1", "path": path }))
+            .await
+            .unwrap()
+            .unwrap();
+        clear_unsaved_buffer(&path);
+        assert_eq!(read, json!({ "content": "in editor\n" }));
+    }
+
+    #[tokio::test]
+    async fn write_text_file_leaves_unsaved_edits_alone() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("open.txt");
+        std::fs::write(&file, "on disk\n").unwrap();
+        let (host, _) = host(&dir);
+        let path = file.to_string_lossy().to_string();
+
+        set_unsaved_buffer(&path, "in editor\n".into());
+        let result = host
+            .handle(
+                "fs/write_text_file",
+                &json!({ "sessionId": "acp-1", "path": path, "content": "agent\n" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.unwrap_err().code, INVALID_PARAMS);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "on disk\n");
+
+        clear_unsaved_buffer(&path);
+        let result = host
+            .handle(
+                "fs/write_text_file",
+                &json!({ "sessionId": "acp-1", "path": path, "content": "agent\n" }),
+            )
+            .await
+            .unwrap();
+        assert!(result.is_ok());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "agent\n");
+    }
+
+    #[tokio::test]
+    async fn write_text_file_records_tool_end_with_diff() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "old\n").unwrap();
+        let (host, recorder) = host(&dir);
+
+        host.handle(
+            "fs/write_text_file",
+            &json!({ "sessionId": "acp-1", "path": "a.txt", "content": "new\n" }),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "new\n");
+        let events = recorder.protocol_events();
+        assert!(matches!(
+            &events[0],
+            ProtocolEvent::ToolStart { tool_kind: ToolKind::Edit, tool_name, .. } if tool_name == "Write a.txt"
+        ));
+        match &events[1] {
+            ProtocolEvent::ToolEnd { output: Some(diff), success: true, .. } => {
+                assert!(diff.contains("-old"));
+                assert!(diff.contains("+new"));
+            }
+            other => panic!("expected ToolEnd with diff, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn write_text_file_outside_root_is_refused() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (host, recorder) = host(&dir);
+
+        let result = host
+            .handle(
+                "fs/write_text_file",
+                &json!({ "sessionId": "acp-1", "path": "../escape.txt", "content": "x" }),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.unwrap_err().code, INVALID_PARAMS);
+        assert!(!dir.path().parent().unwrap().join("escape.txt").exists());
+        assert!(recorder.protocol_events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_method_is_not_handled() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (host, _) = host(&dir);
+        assert!(host.handle("editor/open", &json!({})).await.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn terminal_runs_command_in_pty_and_streams_output() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (host, recorder) = host(&dir);
+
+        let created = host
+            .handle(
+                "terminal/create",
+                &json!({ "sessionId": "acp-1", "command": "echo", "args": ["hello"] }),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let terminal = json!({ "sessionId": "acp-1", "terminalId": created["terminalId"] });
+
+        let exit = host
+            .handle("terminal/wait_for_exit", &terminal)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(exit["exitCode"], json!(0));
+
+        let output = host.handle("terminal/output", &terminal).await.unwrap().unwrap();
+        assert!(output["output"].as_str().unwrap().contains("hello"));
+        assert_eq!(output["truncated"], json!(false));
+        assert_eq!(output["exitStatus"]["exitCode"], json!(0));
+
+        let events = recorder.protocol_events();
+        assert!(matches!(
+            events.first(),
+            Some(ProtocolEvent::ToolStart { tool_kind: ToolKind::Execute, tool_name, .. }) if tool_name == "echo hello"
+        ));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, ProtocolEvent::ToolEnd { success: true, .. })));
+
+        host.handle("terminal/release", &terminal).await.unwrap().unwrap();
+        assert!(host.handle("terminal/output", &terminal).await.unwrap().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn terminal_kill_stops_command() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (host, _) = host(&dir);
+
+        let created = host
+            .handle("terminal/create", &json!({ "sessionId": "acp-1", "command": "sleep 30" }))
+            .await
+            .unwrap()
+            .unwrap();
+        let terminal = json!({ "sessionId": "acp-1", "terminalId": created["terminalId"] });
+
+        host.handle("terminal/kill", &terminal).await.unwrap().unwrap();
+        let exit = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            host.handle("terminal/wait_for_exit", &terminal),
+        )
+        .await
+        .expect("terminal did not exit after kill")
+        .unwrap()
+        .unwrap();
+        assert_ne!(exit["exitCode"], json!(0));
+    }
+}
@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::protocol::AgentFailureKind;
+    use crate::services::agent_diagnostics_service::{
+        classify_stderr_line, clear_failures, last_failure, record_stderr_line,
+        session_diagnostics, MAX_DIAGNOSTIC_LINES,
+    };
+
+    #[test]
+    fn stderr_lines_are_classified_into_known_failures() {
+        let cases = [
+            (
+                "Error: ANTHROPIC_API_KEY is not set",
+                Some(AgentFailureKind::MissingApiKey),
+            ),
+            (
+                "Request failed with status code 401",
+                Some(AgentFailureKind::MissingApiKey),
+            ),
+            (
+                "You are not logged in. Please run `codex login`.",
+                Some(AgentFailureKind::MissingApiKey),
+            ),
+            (
+                "429 Too Many Requests: rate limit reached",
+                Some(AgentFailureKind::RateLimit),
+            ),
+            (
+                "API error: overloaded_error (overloaded)",
+                Some(AgentFailureKind::RateLimit),
+            ),
+            (
+                "connect ECONNREFUSED 127.0.0.1:443",
+                Some(AgentFailureKind::Network),
+            ),
+            (
+                "curl: (6) Could not resolve host: api.openai.com",
+                Some(AgentFailureKind::Network),
+            ),
+            (
+                "Unsupported protocol version 3",
+                Some(AgentFailureKind::VersionMismatch),
+            ),
+            (
+                "error: unknown option '--experimental-acp'",
+                Some(AgentFailureKind::VersionMismatch),
+            ),
+            (
+                "Error: request failed: Unauthorized",
+                Some(AgentFailureKind::MissingApiKey),
+            ),
+            (
+                "getaddrinfo failed for api.anthropic.com",
+                Some(AgentFailureKind::Network),
+            ),
+            (
+                "This version is no longer supported, please upgrade.",
+                Some(AgentFailureKind::VersionMismatch),
+            ),
+            ("Update available 1.2.0 → 1.3.0. Please update with npm i -g", None),
+            ("retrying getaddrinfo for api.openai.com (attempt 2)", None),
+            ("skipping unauthorized tool in plugin list", None),
+            ("    at Object.<anonymous> (/app/dist/cli.js:401:17)", None),
+            ("Loaded 12 tools", None),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(classify_stderr_line(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn each_failure_kind_is_reported_once_per_session() {
+        let session = "diag-once";
+        let first =
+            record_stderr_line(session, "\x1b[31mError: OPENAI_API_KEY is missing\x1b[0m\n");
+        assert_eq!(first.map(|f| f.kind), Some(AgentFailureKind::MissingApiKey));
+        assert!(record_stderr_line(session, "Error: OPENAI_API_KEY is missing").is_none());
+        assert!(record_stderr_line(session, "retrying…").is_none());
+        let rate = record_stderr_line(session, "rate limit exceeded");
+        assert_eq!(rate.map(|f| f.kind), Some(AgentFailureKind::RateLimit));
+
+        let diagnostics = session_diagnostics(session).unwrap();
+        assert_eq!(diagnostics.lines[0], "Error: OPENAI_API_KEY is missing");
+        assert_eq!(diagnostics.lines.len(), 4);
+        assert_eq!(diagnostics.failures.len(), 2);
+        assert!(!diagnostics.failures[0].hint.is_empty());
+        assert_eq!(last_failure(session), Some(AgentFailureKind::RateLimit));
+        assert!(session_diagnostics("diag-silent").is_none());
+    }
+
+    #[test]
+    fn failures_are_forgotten_before_a_restart() {
+        let session = "diag-restart";
+        record_stderr_line(session, "Error: OPENAI_API_KEY is missing");
+        assert_eq!(last_failure(session), Some(AgentFailureKind::MissingApiKey));
+
+        clear_failures(session);
+        assert_eq!(last_failure(session), None);
+        assert_eq!(session_diagnostics(session).unwrap().lines.len(), 1);
+        let again = record_stderr_line(session, "Error: OPENAI_API_KEY is missing");
+        assert_eq!(again.map(|f| f.kind), Some(AgentFailureKind::MissingApiKey));
+    }
+
+    #[test]
+    fn old_lines_are_dropped_past_the_cap() {
+        let session = "diag-cap";
+        for n in 0..MAX_DIAGNOSTIC_LINES + 5 {
+            record_stderr_line(session, &format!("line {}", n));
+        }
+        let diagnostics = session_diagnostics(session).unwrap();
+        assert_eq!(diagnostics.lines.len(), MAX_DIAGNOSTIC_LINES);
+        assert_eq!(diagnostics.dropped_lines, 5);
+        assert_eq!(diagnostics.lines[0], "line 5");
+    }
+}
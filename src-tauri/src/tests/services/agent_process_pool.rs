@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod tests {
+    use crate::error::CommanderError;
+    use crate::models::ai_agent::AgentSettings;
+    use crate::models::protocol::ProtocolMode;
+    use crate::services::agent_process_pool::{idle_timeout, AgentProcessPool, SharedExecutor};
+    use crate::services::event_sink::SharedEventSink;
+    use crate::services::executors::AgentExecutor;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tokio::sync::Mutex as TokioMutex;
+
+    struct FakeExecutor {
+        alive: bool,
+        busy: bool,
+    }
+
+    #[async_trait]
+    impl AgentExecutor for FakeExecutor {
+        async fn execute(
+            &mut self,
+            _sink: &SharedEventSink,
+            _session_id: &str,
+            _agent: &str,
+            _message: &str,
+            _working_dir: &str,
+            _settings: &AgentSettings,
+            _resume_session_id: Option<&str>,
+        ) -> Result<(), CommanderError> {
+            Ok(())
+        }
+
+        async fn abort(&self) -> Result<(), CommanderError> {
+            Ok(())
+        }
+
+        async fn respond_permission(&self, _request_id: &str, _approved: bool) -> Result<(), CommanderError> {
+            Ok(())
+        }
+
+        fn is_alive(&self) -> bool {
+            self.alive
+        }
+
+        fn is_busy(&self) -> bool {
+            self.busy
+        }
+
+        fn protocol(&self) -> Option<ProtocolMode> {
+            Some(ProtocolMode::Acp)
+        }
+    }
+
+    fn fake(alive: bool, busy: bool) -> SharedExecutor {
+        Arc::new(TokioMutex::new(Box::new(FakeExecutor { alive, busy })))
+    }
+
+    #[test]
+    fn idle_timeout_follows_session_timeout_minutes() {
+        assert_eq!(idle_timeout(0), None);
+        assert_eq!(idle_timeout(5), Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn get_only_returns_process_for_same_agent_and_directory() {
+        let mut pool = AgentProcessPool::new();
+        let key = AgentProcessPool::key("claude", "conv-1");
+        assert_eq!(key, "claude:conv-1");
+        pool.insert(&key, fake(true, false), "claude", "/repo", Duration::from_secs(60));
+
+        assert!(pool.get(&key, "claude", "/repo").is_some());
+        assert!(pool.get(&key, "claude", "/other").is_none());
+        assert!(pool.get(&key, "codex", "/repo").is_none());
+        assert!(pool.get("claude:conv-2", "claude", "/repo").is_none());
+    }
+
+    #[test]
+    fn insert_returns_replaced_process() {
+        let mut pool = AgentProcessPool::new();
+        let first = fake(true, false);
+        assert!(pool
+            .insert("a:c", Arc::clone(&first), "a", "/repo", Duration::from_secs(60))
+            .is_none());
+        let replaced = pool
+            .insert("a:c", fake(true, false), "a", "/repo", Duration::from_secs(60))
+            .unwrap();
+        assert!(Arc::ptr_eq(&replaced, &first));
+    }
+
+    #[test]
+    fn remove_executor_leaves_newer_process_in_place() {
+        let mut pool = AgentProcessPool::new();
+        let stale = fake(true, false);
+        let current = fake(true, false);
+        pool.insert("a:c", Arc::clone(&current), "a", "/repo", Duration::from_secs(60));
+
+        assert!(!pool.remove_executor("a:c", &stale));
+        assert!(pool.get("a:c", "a", "/repo").is_some());
+        assert!(pool.remove_executor("a:c", &current));
+        assert!(pool.get("a:c", "a", "/repo").is_none());
+    }
+
+    #[test]
+    fn take_expired_skips_busy_and_recent_processes() {
+        let mut pool = AgentProcessPool::new();
+        let timeout = Duration::from_secs(60);
+        pool.insert("a:idle", fake(true, false), "a", "/repo", timeout);
+        pool.insert("a:busy", fake(true, true), "a", "/repo", timeout);
+        pool.insert("a:dead", fake(false, false), "a", "/repo", Duration::from_secs(3600));
+
+        let dead = pool.take_expired(Instant::now());
+        assert_eq!(dead.len(), 1);
+        assert!(pool.get("a:dead", "a", "/repo").is_none());
+        assert!(pool.get("a:idle", "a", "/repo").is_some());
+
+        let later = Instant::now() + timeout + Duration::from_secs(1);
+        assert_eq!(pool.take_expired(later).len(), 1);
+        assert!(pool.get("a:idle", "a", "/repo").is_none());
+        assert!(pool.get("a:busy", "a", "/repo").is_some());
+    }
+
+    #[test]
+    fn take_expired_skips_locked_process() {
+        let mut pool = AgentProcessPool::new();
+        let executor = fake(true, false);
+        pool.insert("a:c", Arc::clone(&executor), "a", "/repo", Duration::ZERO);
+
+        let guard = executor.try_lock().unwrap();
+        assert!(pool.take_expired(Instant::now()).is_empty());
+        drop(guard);
+        assert_eq!(pool.take_expired(Instant::now()).len(), 1);
+    }
+
+    #[test]
+    fn drain_empties_pool() {
+        let mut pool = AgentProcessPool::new();
+        pool.insert("a:1", fake(true, false), "a", "/repo", Duration::from_secs(60));
+        pool.insert("a:2", fake(true, false), "a", "/repo", Duration::from_secs(60));
+        assert_eq!(pool.drain().len(), 2);
+        assert!(pool.get("a:1", "a", "/repo").is_none());
+    }
+}
@@ -14,11 +14,16 @@ mod tests {
             sandbox_mode: false,
             auto_approval: false,
             session_timeout_minutes: 30,
+            max_run_minutes: 0,
+            max_output_bytes: 64 * 1024 * 1024,
             output_format: "markdown".to_string(),
             debug_mode: false,
             max_tokens: None,
             temperature: None,
             transport: None,
+            acp_legacy_envelope: false,
+            process_sandbox: Default::default(),
+            env_profile: None,
         }
     }
 
@@ -42,6 +47,8 @@ mod tests {
             amp: base_settings(None),
             custom_agents: vec![],
             max_concurrent_sessions: 12,
+            max_concurrent_sessions_per_project: None,
+            max_concurrent_sessions_per_agent: None,
         };
 
         let registry = normalize_legacy_agent_registry(&enablement, &legacy);
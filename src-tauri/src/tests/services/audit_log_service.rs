@@ -0,0 +1,243 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::audit::{AuditEntry, AuditEventKind, AuditQuery};
+    use crate::models::protocol::{PermissionTarget, ProtocolEvent, ToolKind};
+    use crate::services::audit_log_service::{AuditLog, AuditRecorder};
+    use crate::services::event_sink::EventSink;
+    use crate::services::indexer::db::{IndexDb, INDEX_DB_FILE};
+    use serde_json::json;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn open_log() -> (Arc<AuditLog>, TempDir) {
+        let dir = TempDir::new().unwrap();
+        let log = AuditLog::open(&dir.path().join("audit.db")).unwrap();
+        (Arc::new(log), dir)
+    }
+
+    fn entry(session_id: &str, agent: &str, timestamp: i64) -> AuditEntry {
+        AuditEntry {
+            id: 0,
+            timestamp,
+            kind: AuditEventKind::PermissionDecided,
+            project_path: Some("/work/app".into()),
+            agent: agent.into(),
+            session_id: session_id.into(),
+            request_id: Some("r1".into()),
+            tool_id: None,
+            tool_name: Some("Bash".into()),
+            tool_kind: Some(ToolKind::Execute),
+            command: Some("cargo test".into()),
+            paths: vec!["src/lib.rs".into()],
+            approved: Some(true),
+            decided_by: Some("policy".into()),
+            reason: Some("rule: allow execute".into()),
+            success: None,
+            duration_ms: None,
+            output: None,
+        }
+    }
+
+    fn resolved(request_id: &str, tool_id: Option<&str>, automatic: bool) -> ProtocolEvent {
+        ProtocolEvent::PermissionResolved {
+            session_id: "s1".into(),
+            request_id: request_id.into(),
+            tool_name: "Run tests".into(),
+            approved: true,
+            reason: "rule: allow execute".into(),
+            automatic,
+            target: Some(PermissionTarget {
+                tool_kind: ToolKind::Execute,
+                command: Some("cargo test".into()),
+                paths: Vec::new(),
+            }),
+            tool_id: tool_id.map(String::from),
+        }
+    }
+
+    fn tool_start(tool_id: &str, kind: ToolKind, args: serde_json::Value) -> ProtocolEvent {
+        ProtocolEvent::ToolStart {
+            session_id: "s1".into(),
+            tool_id: tool_id.into(),
+            tool_name: "tool".into(),
+            tool_kind: kind,
+            args: Some(args),
+        }
+    }
+
+    fn tool_end(tool_id: &str, success: bool) -> ProtocolEvent {
+        ProtocolEvent::ToolEnd {
+            session_id: "s1".into(),
+            tool_id: tool_id.into(),
+            tool_name: "tool".into(),
+            output: Some("ok".into()),
+            success,
+            duration_ms: Some(12),
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_and_filter() {
+        let (log, _dir) = open_log();
+        log.append(&entry("s1", "claude", 100)).unwrap();
+        log.append(&entry("s2", "codex", 200)).unwrap();
+        let mut tool = entry("s2", "codex", 300);
+        tool.kind = AuditEventKind::ToolCompleted;
+        tool.success = Some(false);
+        tool.duration_ms = Some(40);
+        log.append(&tool).unwrap();
+
+        let all = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].paths, vec!["src/lib.rs".to_string()]);
+        assert_eq!(all[0].tool_kind, Some(ToolKind::Execute));
+        assert_eq!(all[2].success, Some(false));
+        assert_eq!(all[2].duration_ms, Some(40));
+
+        let codex = AuditQuery {
+            agent: Some("codex".into()),
+            ..AuditQuery::default()
+        };
+        assert_eq!(log.query(&codex).unwrap().len(), 2);
+
+        let window = AuditQuery {
+            since: Some(150),
+            until: Some(250),
+            ..AuditQuery::default()
+        };
+        let rows = log.query(&window).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].session_id, "s2");
+
+        let tools = AuditQuery {
+            session_id: Some("s2".into()),
+            kind: Some(AuditEventKind::ToolCompleted),
+            ..AuditQuery::default()
+        };
+        assert_eq!(log.query(&tools).unwrap().len(), 1);
+
+        let other_project = AuditQuery {
+            project_path: Some("/elsewhere".into()),
+            ..AuditQuery::default()
+        };
+        assert!(log.query(&other_project).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rows_cannot_be_changed_or_removed() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.db");
+        let log = AuditLog::open(&path).unwrap();
+        log.append(&entry("s1", "claude", 100)).unwrap();
+
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        assert!(conn
+            .execute("UPDATE audit_events SET approved = 0", [])
+            .is_err());
+        assert!(conn.execute("DELETE FROM audit_events", []).is_err());
+
+        drop(log);
+        let reopened = AuditLog::open(&path).unwrap();
+        assert_eq!(reopened.query(&AuditQuery::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn audit_table_lives_in_the_index_database() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(INDEX_DB_FILE);
+        let index = IndexDb::open(&path).unwrap();
+        let log = AuditLog::open(&path).unwrap();
+        log.append(&entry("s1", "claude", 100)).unwrap();
+
+        drop(index);
+        let index = IndexDb::open(&path).unwrap();
+        assert!(index.get_budget_state("b", "2026-01-01").is_ok());
+        assert_eq!(log.query(&AuditQuery::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn recorder_links_tool_results_to_their_approval() {
+        let (log, _dir) = open_log();
+        let recorder = AuditRecorder::new(Arc::clone(&log), Some("/work/app"), "claude", "s1");
+
+        recorder.emit_event(tool_start(
+            "t1",
+            ToolKind::Execute,
+            json!({ "command": "cargo test" }),
+        ));
+        recorder.emit_event(resolved("r1", Some("t1"), true));
+        recorder.emit_event(tool_end("t1", true));
+
+        // A request that does not name its tool call covers the next tool.
+        recorder.emit_event(resolved("r2", None, false));
+        recorder.emit_event(tool_start(
+            "t2",
+            ToolKind::Edit,
+            json!({ "file_path": "src/a.rs" }),
+        ));
+        recorder.emit_event(tool_end("t2", false));
+
+        let rows = log.query(&AuditQuery::default()).unwrap();
+        let summary: Vec<(AuditEventKind, Option<String>, Option<String>)> = rows
+            .iter()
+            .map(|r| (r.kind, r.request_id.clone(), r.decided_by.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    AuditEventKind::PermissionDecided,
+                    Some("r1".into()),
+                    Some("policy".into())
+                ),
+                (AuditEventKind::ToolCompleted, Some("r1".into()), None),
+                (
+                    AuditEventKind::PermissionDecided,
+                    Some("r2".into()),
+                    Some("user".into())
+                ),
+                (AuditEventKind::ToolCompleted, Some("r2".into()), None),
+            ]
+        );
+        assert_eq!(rows[0].command.as_deref(), Some("cargo test"));
+        assert_eq!(rows[1].command.as_deref(), Some("cargo test"));
+        assert_eq!(rows[1].success, Some(true));
+        assert_eq!(rows[3].paths, vec!["src/a.rs".to_string()]);
+        assert_eq!(rows[3].success, Some(false));
+        assert!(rows
+            .iter()
+            .all(|r| r.agent == "claude" && r.project_path.as_deref() == Some("/work/app")));
+    }
+
+    #[test]
+    fn recorder_skips_read_only_tools_and_records_open_requests() {
+        let (log, _dir) = open_log();
+        let recorder = AuditRecorder::new(Arc::clone(&log), None, "codex", "s1");
+
+        recorder.emit_event(tool_start(
+            "t1",
+            ToolKind::Read,
+            json!({ "path": "README.md" }),
+        ));
+        recorder.emit_event(tool_end("t1", true));
+        recorder.emit_event(ProtocolEvent::PermissionRequest {
+            session_id: "s1".into(),
+            request_id: "r1".into(),
+            tool_name: "Delete".into(),
+            description: "Delete build/".into(),
+            target: Some(PermissionTarget {
+                tool_kind: ToolKind::Delete,
+                command: None,
+                paths: vec!["build".into()],
+            }),
+            tool_id: None,
+        });
+
+        let rows = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].kind, AuditEventKind::PermissionRequested);
+        assert_eq!(rows[0].tool_kind, Some(ToolKind::Delete));
+        assert_eq!(rows[0].paths, vec!["build".to_string()]);
+        assert_eq!(rows[0].approved, None);
+    }
+}
@@ -1,11 +1,18 @@
 use crate::models::auth::{AuthUser, StoredAuth};
 use crate::services::auth_service;
+use crate::services::secret_store_service::{is_handle, KeySource, SecretStore};
 use tempfile::TempDir;
 
+fn secret_store(temp: &TempDir) -> SecretStore {
+    let path = temp.path().join("secrets.json");
+    SecretStore::open_encrypted(&path, &KeySource::Passphrase("test".to_string())).unwrap()
+}
+
 #[test]
 fn test_save_and_load_auth_token() {
     let temp = TempDir::new().unwrap();
     let sessions_dir = temp.path().join(".autohand").join("sessions");
+    let secrets = secret_store(&temp);
 
     let stored = StoredAuth {
         token: "test-token-123".to_string(),
@@ -19,8 +26,8 @@ fn test_save_and_load_auth_token() {
         created_at: "2026-02-24T00:00:00Z".to_string(),
     };
 
-    auth_service::save_auth_to_file(&sessions_dir, &stored).unwrap();
-    let loaded = auth_service::load_auth_from_file(&sessions_dir).unwrap();
+    auth_service::save_auth_to_file(&sessions_dir, &stored, &secrets).unwrap();
+    let loaded = auth_service::load_auth_from_file(&sessions_dir, &secrets).unwrap();
 
     assert!(loaded.is_some());
     let loaded = loaded.unwrap();
@@ -29,12 +36,39 @@ fn test_save_and_load_auth_token() {
     assert_eq!(loaded.device_id, "commander-dev-1");
 }
 
+#[test]
+fn test_auth_file_holds_a_handle_not_the_token() {
+    let temp = TempDir::new().unwrap();
+    let sessions_dir = temp.path().join(".autohand").join("sessions");
+    let secrets = secret_store(&temp);
+
+    // Written by a version that kept the token in the file
+    std::fs::create_dir_all(&sessions_dir).unwrap();
+    std::fs::write(
+        sessions_dir.join("commander.json"),
+        r#"{"token":"plain-token-456","user":{"id":"1","email":"a@b.com","name":"A","avatar_url":null},"device_id":"dev","created_at":"2026-01-01T00:00:00Z"}"#,
+    )
+    .unwrap();
+
+    let loaded = auth_service::load_auth_from_file(&sessions_dir, &secrets).unwrap();
+    assert_eq!(loaded.unwrap().token, "plain-token-456");
+
+    let raw = std::fs::read_to_string(sessions_dir.join("commander.json")).unwrap();
+    assert!(!raw.contains("plain-token-456"));
+    let on_disk: StoredAuth = serde_json::from_str(&raw).unwrap();
+    assert!(is_handle(&on_disk.token));
+
+    auth_service::clear_auth_file(&sessions_dir, Some(&secrets)).unwrap();
+    assert_eq!(secrets.lookup(&on_disk.token).unwrap(), None);
+}
+
 #[test]
 fn test_load_returns_none_when_no_file() {
     let temp = TempDir::new().unwrap();
     let sessions_dir = temp.path().join(".autohand").join("sessions");
+    let secrets = secret_store(&temp);
 
-    let loaded = auth_service::load_auth_from_file(&sessions_dir).unwrap();
+    let loaded = auth_service::load_auth_from_file(&sessions_dir, &secrets).unwrap();
     assert!(loaded.is_none());
 }
 
@@ -42,6 +76,7 @@ fn test_load_returns_none_when_no_file() {
 fn test_clear_auth_file() {
     let temp = TempDir::new().unwrap();
     let sessions_dir = temp.path().join(".autohand").join("sessions");
+    let secrets = secret_store(&temp);
 
     let stored = StoredAuth {
         token: "tok".to_string(),
@@ -55,9 +90,33 @@ fn test_clear_auth_file() {
         created_at: "2026-01-01T00:00:00Z".to_string(),
     };
 
-    auth_service::save_auth_to_file(&sessions_dir, &stored).unwrap();
-    auth_service::clear_auth_file(&sessions_dir).unwrap();
+    auth_service::save_auth_to_file(&sessions_dir, &stored, &secrets).unwrap();
+    auth_service::clear_auth_file(&sessions_dir, Some(&secrets)).unwrap();
 
-    let loaded = auth_service::load_auth_from_file(&sessions_dir).unwrap();
+    let loaded = auth_service::load_auth_from_file(&sessions_dir, &secrets).unwrap();
     assert!(loaded.is_none());
 }
+
+#[test]
+fn test_clear_auth_file_without_the_secret_store() {
+    let temp = TempDir::new().unwrap();
+    let sessions_dir = temp.path().join(".autohand").join("sessions");
+    let secrets = secret_store(&temp);
+
+    let stored = StoredAuth {
+        token: "tok".to_string(),
+        user: AuthUser {
+            id: "1".to_string(),
+            email: "a@b.com".to_string(),
+            name: "A".to_string(),
+            avatar_url: None,
+        },
+        device_id: "dev".to_string(),
+        created_at: "2026-01-01T00:00:00Z".to_string(),
+    };
+    auth_service::save_auth_to_file(&sessions_dir, &stored, &secrets).unwrap();
+
+    // Signing out still removes the file when the store cannot be opened
+    auth_service::clear_auth_file(&sessions_dir, None).unwrap();
+    assert!(!sessions_dir.join("commander.json").exists());
+}
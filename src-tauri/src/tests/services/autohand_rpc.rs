@@ -102,4 +102,29 @@ mod tests {
         assert!(args.contains(&"--model".to_string()));
         assert!(args.contains(&"claude-opus-4-20250514".to_string()));
     }
+
+    #[test]
+    fn test_headless_config_is_per_spawn_and_removed_on_drop() {
+        let workspace = tempfile::tempdir().unwrap();
+        let dir = workspace.path().to_str().unwrap();
+
+        let first = write_headless_config_with_mode(dir, Some("unrestricted")).unwrap();
+        let second = write_headless_config_with_mode(dir, None).unwrap();
+        assert_ne!(first.path(), second.path());
+
+        let path = first.path().to_path_buf();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["permissions"]["mode"], "unrestricted");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o077, 0);
+        }
+
+        drop(first);
+        assert!(!path.exists());
+        assert!(second.path().exists());
+    }
 }
@@ -0,0 +1,304 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use tokio::sync::Mutex as TokioMutex;
+
+    use crate::models::budget::{Budget, BudgetAlertKind, BudgetPeriod, BudgetScope};
+    use crate::models::protocol::{ProtocolEvent, TurnUsage};
+    use crate::models::usage::{ModelPrice, UsageSource};
+    use crate::services::budget_service::{
+        check_budgets, describe_refusal, exhausted_budgets, load_budgets_from, period_start,
+        save_budgets_to, BudgetGuard,
+    };
+    use crate::services::event_sink::{RecordingEventSink, SharedEventSink};
+    use crate::services::indexer::db::IndexDb;
+    use crate::services::session_manager::{ActiveSession, SessionManager};
+    use crate::services::usage_service::{canonical_project_path, live_record, UsageMeter};
+
+    fn budget(scope: BudgetScope, target: &str, period: BudgetPeriod, limit_usd: f64) -> Budget {
+        Budget {
+            scope,
+            target: target.to_string(),
+            period,
+            limit_usd,
+            warn_at: vec![0.5, 0.8],
+            hard_stop: true,
+        }
+    }
+
+    /// Record `cost` of live usage today by `agent` in `/projects/app`.
+    fn spend(db: &IndexDb, agent: &str, cost: f64) {
+        let mut record = live_record(
+            agent,
+            "s1",
+            Some("/projects/app"),
+            None,
+            &TurnUsage::default(),
+        );
+        record.input_tokens = 1;
+        record.cost_usd = Some(cost);
+        assert_eq!(record.source, UsageSource::Live);
+        db.add_live_usage("run-1", &record).unwrap();
+    }
+
+    fn kinds(alerts: &[crate::models::budget::BudgetAlert]) -> Vec<BudgetAlertKind> {
+        alerts.iter().map(|a| a.kind.clone()).collect()
+    }
+
+    #[test]
+    fn budgets_round_trip_and_reject_invalid_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("budgets.json");
+        assert!(load_budgets_from(&path).unwrap().is_empty());
+
+        let budgets = vec![
+            budget(
+                BudgetScope::Project,
+                "/projects/app",
+                BudgetPeriod::Daily,
+                10.0,
+            ),
+            budget(BudgetScope::Agent, "claude", BudgetPeriod::Monthly, 100.0),
+        ];
+        save_budgets_to(&path, &budgets).unwrap();
+        assert_eq!(load_budgets_from(&path).unwrap(), budgets);
+
+        let mut zero = budgets[0].clone();
+        zero.limit_usd = 0.0;
+        assert!(save_budgets_to(&path, &[zero]).is_err());
+        let mut threshold = budgets[0].clone();
+        threshold.warn_at = vec![1.5];
+        assert!(save_budgets_to(&path, &[threshold]).is_err());
+        assert!(save_budgets_to(&path, &[budgets[0].clone(), budgets[0].clone()]).is_err());
+    }
+
+    #[test]
+    fn project_budgets_match_the_directory_however_it_is_spelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("app");
+        std::fs::create_dir(&project).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&project, &link).unwrap();
+        let canonical = canonical_project_path(&project.to_string_lossy());
+        assert_eq!(canonical_project_path("/projects/app/"), "/projects/app");
+
+        let saved = save_budgets_to(
+            &dir.path().join("budgets.json"),
+            &[budget(
+                BudgetScope::Project,
+                &format!("{}/", project.display()),
+                BudgetPeriod::Daily,
+                1.0,
+            )],
+        )
+        .unwrap();
+        assert_eq!(saved[0].target, canonical);
+
+        let db = IndexDb::open(&dir.path().join("index.db")).unwrap();
+        let mut record = live_record(
+            "claude",
+            "s1",
+            Some(&link.to_string_lossy()),
+            None,
+            &TurnUsage::default(),
+        );
+        assert_eq!(record.project_path.as_deref(), Some(canonical.as_str()));
+        record.input_tokens = 1;
+        record.cost_usd = Some(2.0);
+        db.add_live_usage("run-1", &record).unwrap();
+
+        let today = chrono::Utc::now().date_naive();
+        let working_dir = format!("{}/.", link.display());
+        let exhausted =
+            exhausted_budgets(&db, &saved, "claude", Some(&working_dir), today).unwrap();
+        assert_eq!(exhausted.len(), 1);
+        assert!((exhausted[0].spent_usd - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn periods_start_on_the_day_or_the_first_of_the_month() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 17).unwrap();
+        assert_eq!(period_start(BudgetPeriod::Daily, day), day);
+        assert_eq!(
+            period_start(BudgetPeriod::Monthly, day),
+            NaiveDate::from_ymd_opt(2026, 3, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn thresholds_warn_once_per_period_even_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        let today = chrono::Utc::now().date_naive();
+        let mut soft = budget(BudgetScope::Agent, "codex", BudgetPeriod::Monthly, 10.0);
+        soft.hard_stop = false;
+        let budgets = vec![
+            soft,
+            budget(BudgetScope::Project, "/elsewhere", BudgetPeriod::Daily, 1.0),
+        ];
+
+        {
+            let db = IndexDb::open(&path).unwrap();
+            spend(&db, "codex", 5.5);
+            let alerts =
+                check_budgets(&db, &budgets, "codex", Some("/projects/app"), today).unwrap();
+            assert_eq!(
+                kinds(&alerts),
+                vec![BudgetAlertKind::Warning { threshold: 0.5 }]
+            );
+            assert!((alerts[0].status.spent_usd - 5.5).abs() < 1e-9);
+            assert!(
+                check_budgets(&db, &budgets, "codex", Some("/projects/app"), today)
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+
+        let db = IndexDb::open(&path).unwrap();
+        assert!(
+            check_budgets(&db, &budgets, "codex", Some("/projects/app"), today)
+                .unwrap()
+                .is_empty()
+        );
+        spend(&db, "codex", 3.0);
+        assert_eq!(
+            kinds(&check_budgets(&db, &budgets, "codex", None, today).unwrap()),
+            vec![BudgetAlertKind::Warning { threshold: 0.8 }]
+        );
+        // A budget that does not stop sessions reports going over once.
+        spend(&db, "codex", 2.0);
+        assert_eq!(
+            kinds(&check_budgets(&db, &budgets, "codex", None, today).unwrap()),
+            vec![BudgetAlertKind::Exceeded]
+        );
+        assert!(check_budgets(&db, &budgets, "codex", None, today)
+            .unwrap()
+            .is_empty());
+        // Other agents are not covered.
+        assert!(check_budgets(&db, &budgets, "claude", None, today)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn spent_hard_budgets_refuse_new_runs_without_recording_alerts() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = IndexDb::open(&dir.path().join("index.db")).unwrap();
+        let today = chrono::Utc::now().date_naive();
+        let mut soft = budget(BudgetScope::Agent, "claude", BudgetPeriod::Daily, 1.0);
+        soft.hard_stop = false;
+        let budgets = vec![
+            soft,
+            budget(BudgetScope::Project, "/projects/app", BudgetPeriod::Monthly, 2.0),
+        ];
+
+        spend(&db, "claude", 1.5);
+        assert!(exhausted_budgets(&db, &budgets, "claude", Some("/projects/app"), today)
+            .unwrap()
+            .is_empty());
+
+        spend(&db, "claude", 1.0);
+        let exhausted =
+            exhausted_budgets(&db, &budgets, "claude", Some("/projects/app"), today).unwrap();
+        assert_eq!(exhausted.len(), 1);
+        assert_eq!(exhausted[0].budget.target, "/projects/app");
+        assert!(describe_refusal(&exhausted[0]).contains("not starting"));
+        assert!(exhausted_budgets(&db, &budgets, "claude", Some("/elsewhere"), today)
+            .unwrap()
+            .is_empty());
+        // The first turn of a run still hears about the budget going over.
+        assert!(kinds(
+            &check_budgets(&db, &budgets, "claude", Some("/projects/app"), today).unwrap()
+        )
+        .contains(&BudgetAlertKind::Exceeded));
+    }
+
+    #[tokio::test]
+    async fn sessions_that_go_over_a_hard_budget_are_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(IndexDb::open(&dir.path().join("index.db")).unwrap());
+        let sessions = Arc::new(TokioMutex::new(SessionManager::new()));
+        let (permission_tx, _permission_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (abort_tx, abort_rx) = tokio::sync::oneshot::channel();
+        sessions.lock().await.insert(ActiveSession {
+            session_id: "run-1".into(),
+            permission_sender: permission_tx,
+            abort_sender: Some(abort_tx),
+        });
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let guard = BudgetGuard::shared(
+            recorder.clone() as SharedEventSink,
+            Arc::clone(&db),
+            vec![budget(
+                BudgetScope::Project,
+                "/projects/app",
+                BudgetPeriod::Daily,
+                2.0,
+            )],
+            "claude",
+            "run-1",
+            Some("/projects/app"),
+            Arc::clone(&sessions),
+        );
+        let pricing = vec![ModelPrice {
+            model: "m".to_string(),
+            input: 1.0,
+            output: 0.0,
+            cache_read: 0.0,
+            cache_write: 0.0,
+        }];
+        let sink = UsageMeter::shared(
+            guard,
+            Arc::clone(&db),
+            pricing,
+            "claude",
+            "run-1",
+            Some("/projects/app"),
+            Some("m"),
+        );
+
+        for tokens in [1_000_000, 700_000, 1_000_000] {
+            sink.emit_event(ProtocolEvent::StateChange {
+                session_id: "run-1".into(),
+                status: "idle".into(),
+                context_percent: None,
+                usage: Some(TurnUsage {
+                    input_tokens: tokens,
+                    ..TurnUsage::default()
+                }),
+                agent_session_id: None,
+            });
+        }
+
+        tokio::time::timeout(Duration::from_secs(5), abort_rx)
+            .await
+            .expect("the session should be closed")
+            .unwrap();
+
+        let statuses: Vec<String> = recorder
+            .protocol_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                ProtocolEvent::StateChange { status, .. } => Some(status),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                "idle",
+                "budget_warning",
+                "idle",
+                "budget_warning",
+                "idle",
+                "budget_exceeded"
+            ]
+        );
+        let output: String = recorder.chunks().into_iter().map(|c| c.content).collect();
+        assert!(output.contains("🛑 Budget: Project /projects/app has spent $2.70"));
+    }
+}
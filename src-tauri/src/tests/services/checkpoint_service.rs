@@ -0,0 +1,154 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command as StdCommand;
+
+    use crate::models::protocol::{ProtocolEvent, ToolKind};
+    use crate::services::checkpoint_service::{
+        checkpoint_ref, create_checkpoint, diff_checkpoints, list_checkpoints, restore_checkpoint,
+        CheckpointKind, CheckpointRecorder,
+    };
+    use crate::services::event_sink::EventSink;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let out = StdCommand::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(out.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&out.stderr));
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    }
+
+    fn setup(tmp: &TempDir) -> String {
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q"]);
+        git(&repo, &["config", "user.name", "Test"]);
+        git(&repo, &["config", "user.email", "test@example.com"]);
+        fs::write(repo.join("a.txt"), "one\n").unwrap();
+        fs::write(repo.join(".gitignore"), "target/\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-qm", "init"]);
+        repo.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn checkpoint_refs_are_valid_names() {
+        assert_eq!(checkpoint_ref("abc-123_x"), "refs/commander/checkpoints/abc-123_x");
+        assert_eq!(checkpoint_ref("a b:c"), "refs/commander/checkpoints/a_b_c");
+    }
+
+    #[tokio::test]
+    async fn checkpoints_chain_and_skip_unchanged_trees() {
+        let tmp = TempDir::new().unwrap();
+        let repo = setup(&tmp);
+
+        create_checkpoint(&repo, "s1", CheckpointKind::BeforePrompt, "Fix the bug\nmore detail", None)
+            .await
+            .unwrap()
+            .unwrap();
+        // Nothing changed yet.
+        assert!(create_checkpoint(&repo, "s1", CheckpointKind::AfterTool, "Write a.txt", None)
+            .await
+            .unwrap()
+            .is_none());
+
+        fs::write(Path::new(&repo).join("a.txt"), "two\n").unwrap();
+        create_checkpoint(&repo, "s1", CheckpointKind::AfterTool, "Write a.txt", None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let list = list_checkpoints(&repo, "s1").await.unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].kind, CheckpointKind::BeforePrompt);
+        assert_eq!(list[0].label, "Fix the bug");
+        assert_eq!(list[1].kind, CheckpointKind::AfterTool);
+        assert!(list[0].created_at > 0);
+        assert!(list_checkpoints(&repo, "other").await.unwrap().is_empty());
+
+        // HEAD, branches and the index are untouched.
+        let repo_path = Path::new(&repo);
+        assert_eq!(git(repo_path, &["rev-list", "--count", "HEAD"]), "1");
+        assert_eq!(git(repo_path, &["diff", "--cached", "--name-only"]), "");
+    }
+
+    #[tokio::test]
+    async fn diff_and_restore_roll_back_agent_changes() {
+        let tmp = TempDir::new().unwrap();
+        let repo = setup(&tmp);
+        let repo_path = Path::new(&repo);
+
+        let before = create_checkpoint(&repo, "s1", CheckpointKind::BeforePrompt, "prompt", None)
+            .await
+            .unwrap()
+            .unwrap();
+        fs::write(repo_path.join("a.txt"), "changed\n").unwrap();
+        fs::write(repo_path.join("new file.txt"), "new\n").unwrap();
+        fs::create_dir_all(repo_path.join("target")).unwrap();
+        fs::write(repo_path.join("target/build.out"), "ignored\n").unwrap();
+        let after = create_checkpoint(&repo, "s1", CheckpointKind::AfterTool, "Edit", None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let diff = diff_checkpoints(&repo, "s1", &before.id, Some(&after.id), None).await.unwrap();
+        let mut paths: Vec<_> = diff.files.iter().map(|f| (f.status.as_str(), f.path.as_str())).collect();
+        paths.sort();
+        assert_eq!(paths, vec![("A", "new file.txt"), ("M", "a.txt")]);
+        assert!(diff.patch.contains("+changed"));
+
+        fs::remove_file(repo_path.join("a.txt")).unwrap();
+        let live = diff_checkpoints(&repo, "s1", &after.id, None, None).await.unwrap();
+        assert_eq!(live.files.len(), 1);
+        assert_eq!(live.files[0].status, "D");
+
+        let safety = restore_checkpoint(&repo, "s1", &before.id).await.unwrap();
+        assert_eq!(safety.kind, CheckpointKind::BeforeRestore);
+        assert_eq!(fs::read_to_string(repo_path.join("a.txt")).unwrap(), "one\n");
+        assert!(!repo_path.join("new file.txt").exists());
+        // Ignored files are not part of checkpoints and survive a restore.
+        assert!(repo_path.join("target/build.out").exists());
+
+        // Restoring the safety checkpoint undoes the restore.
+        restore_checkpoint(&repo, "s1", &safety.id).await.unwrap();
+        assert!(!repo_path.join("a.txt").exists());
+        assert!(repo_path.join("new file.txt").exists());
+
+        assert!(restore_checkpoint(&repo, "s2", &before.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn recorder_checkpoints_after_mutating_tools() {
+        let tmp = TempDir::new().unwrap();
+        let repo = setup(&tmp);
+        let recorder = CheckpointRecorder::start(&repo, "s1");
+
+        let tool = |id: &str, kind: ToolKind| ProtocolEvent::ToolStart {
+            session_id: "s1".to_string(),
+            tool_id: id.to_string(),
+            tool_name: "tool".to_string(),
+            tool_kind: kind,
+            args: Some(serde_json::json!({ "path": "a.txt" })),
+        };
+        let end = |id: &str| ProtocolEvent::ToolEnd {
+            session_id: "s1".to_string(),
+            tool_id: id.to_string(),
+            tool_name: "tool".to_string(),
+            output: None,
+            success: true,
+            duration_ms: None,
+        };
+
+        recorder.emit_event(tool("read", ToolKind::Read));
+        fs::write(Path::new(&repo).join("a.txt"), "read changed it?\n").unwrap();
+        recorder.emit_event(end("read"));
+        recorder.emit_event(tool("write", ToolKind::Write));
+        recorder.emit_event(end("write"));
+        recorder.finish().await;
+
+        let list = list_checkpoints(&repo, "s1").await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].label, "tool a.txt");
+        assert_eq!(list[0].tool.as_ref().unwrap().tool_id, "write");
+    }
+}
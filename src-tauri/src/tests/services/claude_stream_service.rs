@@ -0,0 +1,211 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::protocol::{ProtocolEvent, ToolKind, TurnUsage};
+    use crate::services::claude_stream_service::{claude_tool_kind, ClaudeStreamParser};
+    use crate::services::cli_output_service::AgentStreamParser;
+    use crate::services::event_sink::{RecordingEventSink, SharedEventSink};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn parse(parser: &mut ClaudeStreamParser, record: serde_json::Value) -> Vec<ProtocolEvent> {
+        parser.parse_line(&record.to_string()).expect("stream-json record")
+    }
+
+    #[test]
+    fn system_init_reports_running_with_native_session_id() {
+        let mut parser = ClaudeStreamParser::new("s1");
+        let events = parse(
+            &mut parser,
+            json!({"type":"system","subtype":"init","session_id":"native-1","model":"claude-sonnet-4-5","tools":["Bash"]}),
+        );
+        assert_eq!(
+            events,
+            vec![ProtocolEvent::StateChange {
+                session_id: "s1".into(),
+                status: "running".into(),
+                context_percent: None,
+                usage: None,
+                agent_session_id: Some("native-1".into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn text_deltas_become_messages_and_full_record_text_is_not_repeated() {
+        let mut parser = ClaudeStreamParser::new("s1");
+        assert!(parse(
+            &mut parser,
+            json!({"type":"stream_event","event":{"type":"message_start","message":{"model":"claude-sonnet-4-5"}}}),
+        )
+        .is_empty());
+        let delta = parse(
+            &mut parser,
+            json!({"type":"stream_event","event":{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}}),
+        );
+        assert_eq!(
+            delta,
+            vec![ProtocolEvent::Message {
+                session_id: "s1".into(),
+                role: "assistant".into(),
+                content: "Hel".into(),
+            }]
+        );
+        let full = parse(
+            &mut parser,
+            json!({"type":"assistant","message":{"content":[{"type":"text","text":"Hello"}]}}),
+        );
+        assert!(full.is_empty());
+    }
+
+    #[test]
+    fn assistant_text_is_used_when_not_streamed() {
+        let mut parser = ClaudeStreamParser::new("s1");
+        let events = parse(
+            &mut parser,
+            json!({"type":"assistant","message":{"content":[{"type":"thinking","thinking":"hmm"},{"type":"text","text":"Done."}]}}),
+        );
+        assert_eq!(
+            events,
+            vec![ProtocolEvent::Message {
+                session_id: "s1".into(),
+                role: "assistant".into(),
+                content: "Done.".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn tool_use_and_result_pair_into_start_and_end() {
+        let mut parser = ClaudeStreamParser::new("s1");
+        let start = parse(
+            &mut parser,
+            json!({"type":"assistant","message":{"content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"ls"}}]}}),
+        );
+        assert_eq!(
+            start,
+            vec![ProtocolEvent::ToolStart {
+                session_id: "s1".into(),
+                tool_id: "toolu_1".into(),
+                tool_name: "Bash".into(),
+                tool_kind: ToolKind::Execute,
+                args: Some(json!({"command":"ls"})),
+            }]
+        );
+
+        let end = parse(
+            &mut parser,
+            json!({"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_1","content":[{"type":"text","text":"a.txt"}],"is_error":false}]}}),
+        );
+        match &end[..] {
+            [ProtocolEvent::ToolEnd { tool_id, tool_name, output, success, duration_ms, .. }] => {
+                assert_eq!(tool_id, "toolu_1");
+                assert_eq!(tool_name, "Bash");
+                assert_eq!(output.as_deref(), Some("a.txt"));
+                assert!(*success);
+                assert!(duration_ms.is_some());
+            }
+            other => panic!("expected one ToolEnd, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failed_tool_result_is_unsuccessful() {
+        let mut parser = ClaudeStreamParser::new("s1");
+        let end = parse(
+            &mut parser,
+            json!({"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_9","content":"permission denied","is_error":true}]}}),
+        );
+        assert!(matches!(
+            &end[..],
+            [ProtocolEvent::ToolEnd { success: false, output: Some(out), .. }] if out == "permission denied"
+        ));
+    }
+
+    #[test]
+    fn result_reports_cost_and_usage() {
+        let mut parser = ClaudeStreamParser::new("s1");
+        parse(
+            &mut parser,
+            json!({"type":"system","subtype":"init","session_id":"native-1","model":"claude-sonnet-4-5"}),
+        );
+        let events = parse(
+            &mut parser,
+            json!({
+                "type":"result","subtype":"success","is_error":false,"session_id":"native-1",
+                "total_cost_usd":0.0421,
+                "usage":{"input_tokens":12,"output_tokens":340,"cache_read_input_tokens":5000,"cache_creation_input_tokens":800}
+            }),
+        );
+        assert_eq!(
+            events,
+            vec![ProtocolEvent::StateChange {
+                session_id: "s1".into(),
+                status: "idle".into(),
+                context_percent: None,
+                usage: Some(TurnUsage {
+                    model: Some("claude-sonnet-4-5".into()),
+                    input_tokens: 12,
+                    output_tokens: 340,
+                    cache_read_tokens: 5000,
+                    cache_creation_tokens: 800,
+                    cost_usd: Some(0.0421),
+                }),
+                agent_session_id: Some("native-1".into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn error_result_emits_error_then_status() {
+        let mut parser = ClaudeStreamParser::new("s1");
+        let events = parse(
+            &mut parser,
+            json!({"type":"result","subtype":"error_max_turns","is_error":true,"usage":{}}),
+        );
+        assert_eq!(
+            events[0],
+            ProtocolEvent::Error {
+                session_id: "s1".into(),
+                message: "error_max_turns".into(),
+                failure: None,
+            }
+        );
+        assert!(matches!(
+            &events[1],
+            ProtocolEvent::StateChange { status, .. } if status == "error_max_turns"
+        ));
+    }
+
+    #[test]
+    fn non_record_lines_are_left_as_text() {
+        let mut parser = ClaudeStreamParser::new("s1");
+        assert!(parser.parse_line("Error: not logged in").is_none());
+        assert!(parser.parse_line("{not json").is_none());
+        assert!(parser.parse_line(r#"{"mcp_servers":[]}"#).is_none());
+        assert_eq!(parser.parse_line(r#"{"type":"rate_limit_event"}"#), Some(Vec::new()));
+    }
+
+    #[test]
+    fn emit_line_sends_events_and_plain_text_to_sink() {
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut parser = ClaudeStreamParser::new("s1");
+
+        parser.emit_line(&sink, r#"{"type":"assistant","message":{"content":[{"type":"text","text":"hi"}]}}"#);
+        parser.emit_line(&sink, "plain output");
+
+        assert_eq!(recorder.protocol_events().len(), 1);
+        let chunks = recorder.chunks();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].content, "plain output\n");
+    }
+
+    #[test]
+    fn tool_kinds_follow_claude_tool_names() {
+        assert_eq!(claude_tool_kind("Read"), ToolKind::Read);
+        assert_eq!(claude_tool_kind("MultiEdit"), ToolKind::Edit);
+        assert_eq!(claude_tool_kind("Glob"), ToolKind::Search);
+        assert_eq!(claude_tool_kind("WebFetch"), ToolKind::Fetch);
+        assert_eq!(claude_tool_kind("Task"), ToolKind::Other);
+    }
+}
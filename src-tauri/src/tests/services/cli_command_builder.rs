@@ -17,6 +17,7 @@ fn codex_args_include_exec_and_prompt() {
         "prompt should be included in args"
     );
     assert!(args.contains(&"--skip-git-repo-check".to_string()));
+    assert!(args.contains(&"--json".to_string()), "output should be JSON events");
 }
 
 #[test]
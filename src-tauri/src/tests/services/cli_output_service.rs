@@ -1,4 +1,6 @@
-use crate::services::cli_output_service::{sanitize_cli_output_line, CodexStreamAccumulator};
+use crate::services::cli_output_service::{
+    sanitize_cli_output_line, stream_parser_for, CodexStreamAccumulator,
+};
 
 #[test]
 fn filters_node_circular_dependency_warnings_for_codex() {
@@ -96,3 +98,10 @@ fn codex_stream_accumulator_strips_sse_envelope() {
     let chunks = acc.push_chunk("data: [DONE]\r\n");
     assert!(chunks.is_empty());
 }
+
+#[test]
+fn structured_parsers_exist_for_claude_and_codex_only() {
+    assert_eq!(stream_parser_for("claude", "s1").map(|p| p.agent()), Some("claude"));
+    assert_eq!(stream_parser_for("Codex", "s1").map(|p| p.agent()), Some("codex"));
+    assert!(stream_parser_for("gemini", "s1").is_none());
+}
@@ -0,0 +1,271 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::protocol::{PlanEntry, ProtocolEvent, ToolKind, TurnUsage};
+    use crate::services::cli_output_service::AgentStreamParser;
+    use crate::services::codex_stream_service::CodexEventParser;
+    use crate::services::event_sink::{RecordingEventSink, SharedEventSink};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn parse(parser: &mut CodexEventParser, event: serde_json::Value) -> Vec<ProtocolEvent> {
+        parser.parse_line(&event.to_string()).expect("thread event")
+    }
+
+    #[test]
+    fn thread_started_reports_running_with_thread_id() {
+        let mut parser = CodexEventParser::new("s1");
+        let events = parse(
+            &mut parser,
+            json!({"type":"thread.started","thread_id":"th_1"}),
+        );
+        assert_eq!(
+            events,
+            vec![ProtocolEvent::StateChange {
+                session_id: "s1".into(),
+                status: "running".into(),
+                context_percent: None,
+                usage: None,
+                agent_session_id: Some("th_1".into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn agent_message_is_emitted_when_completed() {
+        let mut parser = CodexEventParser::new("s1");
+        let item = json!({"id":"item_0","type":"agent_message","text":"All done."});
+        assert!(parse(&mut parser, json!({"type":"item.started","item":item})).is_empty());
+        assert_eq!(
+            parse(&mut parser, json!({"type":"item.completed","item":item})),
+            vec![ProtocolEvent::Message {
+                session_id: "s1".into(),
+                role: "assistant".into(),
+                content: "All done.".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn later_messages_in_a_turn_are_separated() {
+        let mut parser = CodexEventParser::new("s1");
+        let message = |id: &str, text: &str| json!({"type":"item.completed","item":{"id":id,"type":"agent_message","text":text}});
+        parse(&mut parser, json!({"type":"turn.started"}));
+        parse(&mut parser, message("item_0", "Looking."));
+        let second = parse(&mut parser, message("item_1", "Done."));
+        assert!(matches!(
+            &second[..],
+            [ProtocolEvent::Message { content, .. }] if content == "\n\nDone."
+        ));
+
+        parse(&mut parser, json!({"type":"turn.started"}));
+        let next_turn = parse(&mut parser, message("item_2", "Again."));
+        assert!(matches!(
+            &next_turn[..],
+            [ProtocolEvent::Message { content, .. }] if content == "Again."
+        ));
+    }
+
+    #[test]
+    fn command_execution_streams_output_deltas_and_ends_with_exit_code() {
+        let mut parser = CodexEventParser::new("s1");
+        let start = parse(
+            &mut parser,
+            json!({"type":"item.started","item":{"id":"item_1","type":"command_execution","command":"cargo test","aggregated_output":"","status":"in_progress"}}),
+        );
+        assert_eq!(
+            start,
+            vec![ProtocolEvent::ToolStart {
+                session_id: "s1".into(),
+                tool_id: "item_1".into(),
+                tool_name: "cargo test".into(),
+                tool_kind: ToolKind::Execute,
+                args: Some(json!({"command":"cargo test"})),
+            }]
+        );
+
+        let update = parse(
+            &mut parser,
+            json!({"type":"item.updated","item":{"id":"item_1","type":"command_execution","command":"cargo test","aggregated_output":"running 2 tests\n","status":"in_progress"}}),
+        );
+        let update2 = parse(
+            &mut parser,
+            json!({"type":"item.updated","item":{"id":"item_1","type":"command_execution","command":"cargo test","aggregated_output":"running 2 tests\nok\n","status":"in_progress"}}),
+        );
+        assert!(matches!(
+            &update[..],
+            [ProtocolEvent::ToolUpdate { output: Some(out), .. }] if out == "running 2 tests\n"
+        ));
+        assert!(matches!(
+            &update2[..],
+            [ProtocolEvent::ToolUpdate { output: Some(out), .. }] if out == "ok\n"
+        ));
+
+        let end = parse(
+            &mut parser,
+            json!({"type":"item.completed","item":{"id":"item_1","type":"command_execution","command":"cargo test","aggregated_output":"running 2 tests\nok\n","exit_code":101,"status":"failed"}}),
+        );
+        match &end[..] {
+            [ProtocolEvent::ToolEnd {
+                tool_id,
+                tool_name,
+                success,
+                duration_ms,
+                ..
+            }] => {
+                assert_eq!(tool_id, "item_1");
+                assert_eq!(tool_name, "cargo test");
+                assert!(!*success);
+                assert!(duration_ms.is_some());
+            }
+            other => panic!("expected one ToolEnd, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn file_change_becomes_edit_tool_listing_changes() {
+        let mut parser = CodexEventParser::new("s1");
+        let events = parse(
+            &mut parser,
+            json!({"type":"item.completed","item":{"id":"item_2","type":"file_change","status":"completed","changes":[{"path":"src/lib.rs","kind":"update"},{"path":"src/new.rs","kind":"add"}]}}),
+        );
+        assert!(matches!(
+            &events[0],
+            ProtocolEvent::ToolStart { tool_kind: ToolKind::Edit, tool_name, .. }
+                if tool_name == "Edit src/lib.rs, src/new.rs"
+        ));
+        assert!(matches!(
+            &events[1],
+            ProtocolEvent::ToolEnd { success: true, output: Some(out), .. }
+                if out == "update src/lib.rs\nadd src/new.rs"
+        ));
+
+        let created = parse(
+            &mut parser,
+            json!({"type":"item.completed","item":{"id":"item_3","type":"file_change","status":"completed","changes":[{"path":"a.txt","kind":"add"}]}}),
+        );
+        assert!(matches!(
+            &created[0],
+            ProtocolEvent::ToolStart {
+                tool_kind: ToolKind::Write,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn reasoning_becomes_think_tool() {
+        let mut parser = CodexEventParser::new("s1");
+        let events = parse(
+            &mut parser,
+            json!({"type":"item.completed","item":{"id":"item_4","type":"reasoning","text":"**Planning** the change"}}),
+        );
+        assert!(matches!(
+            &events[0],
+            ProtocolEvent::ToolStart {
+                tool_kind: ToolKind::Think,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &events[1],
+            ProtocolEvent::ToolEnd { output: Some(out), .. } if out == "**Planning** the change"
+        ));
+    }
+
+    #[test]
+    fn todo_list_becomes_plan() {
+        let mut parser = CodexEventParser::new("s1");
+        let events = parse(
+            &mut parser,
+            json!({"type":"item.updated","item":{"id":"item_5","type":"todo_list","items":[{"text":"Read code","completed":true},{"text":"Fix bug","completed":false}]}}),
+        );
+        assert_eq!(
+            events,
+            vec![ProtocolEvent::Plan {
+                session_id: "s1".into(),
+                entries: vec![
+                    PlanEntry {
+                        content: "Read code".into(),
+                        priority: "medium".into(),
+                        status: "completed".into(),
+                    },
+                    PlanEntry {
+                        content: "Fix bug".into(),
+                        priority: "medium".into(),
+                        status: "pending".into(),
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn turn_completed_reports_usage_with_cache_split_out() {
+        let mut parser = CodexEventParser::new("s1").with_model(Some("gpt-5-codex".into()));
+        parse(
+            &mut parser,
+            json!({"type":"thread.started","thread_id":"th_1"}),
+        );
+        let events = parse(
+            &mut parser,
+            json!({"type":"turn.completed","usage":{"input_tokens":1200,"cached_input_tokens":1000,"output_tokens":80}}),
+        );
+        assert_eq!(
+            events,
+            vec![ProtocolEvent::StateChange {
+                session_id: "s1".into(),
+                status: "idle".into(),
+                context_percent: None,
+                usage: Some(TurnUsage {
+                    model: Some("gpt-5-codex".into()),
+                    input_tokens: 200,
+                    output_tokens: 80,
+                    cache_read_tokens: 1000,
+                    cache_creation_tokens: 0,
+                    cost_usd: None,
+                }),
+                agent_session_id: Some("th_1".into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn turn_failed_emits_error_then_status() {
+        let mut parser = CodexEventParser::new("s1");
+        let events = parse(
+            &mut parser,
+            json!({"type":"turn.failed","error":{"message":"stream disconnected"}}),
+        );
+        assert_eq!(
+            events[0],
+            ProtocolEvent::Error {
+                session_id: "s1".into(),
+                message: "stream disconnected".into(),
+                failure: None,
+            }
+        );
+        assert!(matches!(
+            &events[1],
+            ProtocolEvent::StateChange { status, .. } if status == "error"
+        ));
+    }
+
+    #[test]
+    fn emit_line_sends_events_and_plain_text_to_sink() {
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut parser = CodexEventParser::new("s1");
+
+        parser.emit_line(&sink, r#"{"type":"turn.started"}"#);
+        parser.emit_line(
+            &sink,
+            r#"{"type":"item.completed","item":{"id":"item_0","type":"agent_message","text":"hi"}}"#,
+        );
+        parser.emit_line(&sink, "Reading prompt from stdin...");
+
+        assert_eq!(recorder.protocol_events().len(), 1);
+        let chunks = recorder.chunks();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].content, "Reading prompt from stdin...\n");
+    }
+}
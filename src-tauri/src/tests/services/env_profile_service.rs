@@ -0,0 +1,248 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::ai_agent::AgentSettings;
+    use crate::models::environment::{EnvProfile, EnvSource, EnvVariable};
+    use crate::services::env_profile_service::{
+        expand, import_dotenv, parse_dotenv, seal_profile, AgentEnvironment,
+    };
+    use crate::services::secret_store_service::{is_handle, KeySource, SecretStore};
+
+    fn var(name: &str, value: &str) -> EnvVariable {
+        EnvVariable {
+            name: name.to_string(),
+            value: value.to_string(),
+            secret: false,
+        }
+    }
+
+    fn profile(name: &str, variables: Vec<EnvVariable>) -> EnvProfile {
+        EnvProfile {
+            name: name.to_string(),
+            variables,
+            ..EnvProfile::default()
+        }
+    }
+
+    fn inherited(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn get<'a>(env: &'a AgentEnvironment, name: &str) -> Option<&'a str> {
+        env.vars().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    fn no_secrets(handle: &str) -> Result<String, String> {
+        Err(format!("unexpected secret {}", handle))
+    }
+
+    #[test]
+    fn dotenv_files_are_parsed_like_the_shell_reads_them() {
+        let content = r#"
+# database
+export DB_HOST=localhost
+DB_PORT = 5432 # default port
+GREETING="hello\n\"world\""
+LITERAL='$HOME stays'
+MULTI="first
+second"
+OPENAI_API_KEY=sk-from-dotenv
+DB_HOST=db.internal
+"#;
+        let vars = parse_dotenv(content).unwrap();
+        let get = |name: &str| vars.iter().find(|v| v.name == name).unwrap();
+
+        assert_eq!(get("DB_HOST").value, "db.internal");
+        assert_eq!(vars.iter().filter(|v| v.name == "DB_HOST").count(), 1);
+        assert_eq!(get("DB_PORT").value, "5432");
+        assert_eq!(get("GREETING").value, "hello\n\"world\"");
+        assert_eq!(get("LITERAL").value, "$$HOME stays");
+        assert_eq!(get("MULTI").value, "first\nsecond");
+        assert!(get("OPENAI_API_KEY").secret);
+        assert!(!get("DB_PORT").secret);
+
+        let err = parse_dotenv("OK=1\nnot a variable\n").unwrap_err();
+        assert!(err.starts_with("line 2"));
+        assert!(parse_dotenv("BAD=\"open").is_err());
+    }
+
+    #[test]
+    fn values_expand_references_defaults_and_escapes() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/dev".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!(
+            expand("$HOME/bin:${HOME}/.local", &lookup).unwrap(),
+            "/home/dev/bin:/home/dev/.local"
+        );
+        assert_eq!(
+            expand("${EMPTY:-fallback} ${MISSING:-$HOME}", &lookup).unwrap(),
+            "fallback /home/dev"
+        );
+        assert_eq!(expand("cost: $$5, $MISSING.", &lookup).unwrap(), "cost: $5, .");
+        assert_eq!(expand("trailing $", &lookup).unwrap(), "trailing $");
+        assert!(expand("${HOME", &lookup).is_err());
+    }
+
+    #[test]
+    fn allow_and_deny_lists_filter_the_inherited_environment() {
+        let mut base = profile("base", vec![]);
+        base.inherit_allow = vec!["AWS_*".to_string(), "EDITOR".to_string()];
+        base.inherit_deny = vec!["AWS_SECRET_ACCESS_KEY".to_string()];
+
+        let env = AgentEnvironment::build(
+            &[base],
+            inherited(&[
+                ("PATH", "/usr/bin"),
+                ("HOME", "/home/dev"),
+                ("AWS_REGION", "eu-west-1"),
+                ("AWS_SECRET_ACCESS_KEY", "hidden"),
+                ("EDITOR", "vim"),
+                ("GITHUB_TOKEN", "ghp_commander"),
+            ]),
+            &no_secrets,
+        )
+        .unwrap();
+
+        let names: Vec<&str> = env.vars().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["AWS_REGION", "EDITOR", "HOME", "PATH"]);
+        assert_eq!(env.removed, vec!["AWS_SECRET_ACCESS_KEY", "GITHUB_TOKEN"]);
+
+        // Without an allow list everything but the denied names is kept.
+        let mut deny_only = profile("deny", vec![]);
+        deny_only.inherit_deny = vec!["GITHUB_*".to_string()];
+        let env = AgentEnvironment::build(
+            &[deny_only],
+            inherited(&[("EDITOR", "vim"), ("GITHUB_TOKEN", "ghp_commander")]),
+            &no_secrets,
+        )
+        .unwrap();
+        assert_eq!(get(&env, "EDITOR"), Some("vim"));
+        assert_eq!(get(&env, "GITHUB_TOKEN"), None);
+    }
+
+    #[test]
+    fn agent_profiles_layer_over_project_profiles() {
+        let project = profile(
+            "project",
+            vec![var("NODE_ENV", "development"), var("PATH", "$PWD/bin:$PATH")],
+        );
+        let agent = profile(
+            "agent",
+            vec![
+                var("NODE_ENV", "test"),
+                var("LOG", "${NODE_ENV}.log"),
+                EnvVariable {
+                    name: "API_TOKEN".to_string(),
+                    value: "secret://token".to_string(),
+                    secret: true,
+                },
+                var("AUTH_HEADER", "Bearer $API_TOKEN"),
+            ],
+        );
+        let env = AgentEnvironment::build(
+            &[project, agent],
+            inherited(&[("PATH", "/usr/bin"), ("PWD", "/work")]),
+            &|handle| {
+                assert_eq!(handle, "secret://token");
+                Ok("tok-123".to_string())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(get(&env, "PATH"), Some("/work/bin:/usr/bin"));
+        assert_eq!(get(&env, "NODE_ENV"), Some("test"));
+        assert_eq!(get(&env, "LOG"), Some("test.log"));
+        assert_eq!(get(&env, "API_TOKEN"), Some("tok-123"));
+        assert_eq!(get(&env, "AUTH_HEADER"), Some("Bearer tok-123"));
+
+        let preview = env.preview();
+        assert_eq!(preview.profiles, vec!["project", "agent"]);
+        let entry = |name: &str| preview.variables.iter().find(|v| v.name == name).unwrap();
+        assert_eq!(entry("NODE_ENV").source, EnvSource::Profile("agent".to_string()));
+        assert_eq!(entry("PWD").source, EnvSource::Inherited);
+        // Secrets and values built from them never show.
+        for name in ["API_TOKEN", "AUTH_HEADER"] {
+            assert!(entry(name).secret);
+            assert!(!entry(name).value.contains("tok-123"));
+        }
+    }
+
+    #[test]
+    fn unresolvable_secrets_fail_the_environment() {
+        let broken = profile(
+            "broken",
+            vec![EnvVariable {
+                name: "API_KEY".to_string(),
+                value: "secret://gone".to_string(),
+                secret: true,
+            }],
+        );
+        let err = AgentEnvironment::build(&[broken], Vec::new(), &no_secrets).unwrap_err();
+        assert!(err.starts_with("broken: API_KEY:"));
+    }
+
+    #[test]
+    fn secret_values_are_sealed_and_released_with_the_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SecretStore::open_encrypted(
+            &dir.path().join("secrets.json"),
+            &KeySource::Passphrase("p".to_string()),
+        )
+        .unwrap();
+
+        let mut first = profile("dev", vec![var("DEBUG", "1")]);
+        import_dotenv(&mut first, "STRIPE_SECRET_KEY=sk_test_123\nDEBUG=0\n").unwrap();
+        seal_profile(&mut first, None, &store).unwrap();
+        let handle = first.variables[1].value.clone();
+        assert!(is_handle(&handle));
+        assert_eq!(first.variables[0].value, "0");
+        assert_eq!(store.resolve(&handle).unwrap(), "sk_test_123");
+
+        // A new value keeps the handle.
+        let mut second = first.clone();
+        second.variables[1].value = "sk_test_456".to_string();
+        seal_profile(&mut second, Some(&first), &store).unwrap();
+        assert_eq!(second.variables[1].value, handle);
+        assert_eq!(store.resolve(&handle).unwrap(), "sk_test_456");
+
+        // Dropping the variable drops the secret.
+        let mut third = profile("dev", vec![]);
+        seal_profile(&mut third, Some(&second), &store).unwrap();
+        assert_eq!(store.lookup(&handle).unwrap(), None);
+
+        let mut invalid = profile("dev", vec![var("NOT-VALID", "x")]);
+        assert!(seal_profile(&mut invalid, None, &store).is_err());
+    }
+
+    #[test]
+    fn runs_without_profiles_inherit_commanders_environment() {
+        assert_eq!(
+            AgentEnvironment::for_run(&AgentSettings::default(), "").unwrap(),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn agents_start_with_exactly_the_built_environment() {
+        let mut scoped = profile("scoped", vec![var("GREETING", "hi $USER")]);
+        scoped.inherit_allow = vec!["NOTHING_ELSE".to_string()];
+        let env = AgentEnvironment::build(
+            &[scoped],
+            inherited(&[("USER", "dev"), ("PATH", "/usr/bin:/bin"), ("LEAK", "x")]),
+            &no_secrets,
+        )
+        .unwrap();
+
+        let mut cmd = tokio::process::Command::new("/bin/sh");
+        cmd.args(["-c", "printf '%s|%s' \"$GREETING\" \"${LEAK:-unset}\""]);
+        env.apply(&mut cmd);
+        let output = cmd.output().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hi dev|unset");
+    }
+}
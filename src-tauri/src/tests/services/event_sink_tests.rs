@@ -0,0 +1,262 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::models::ai_agent::{AgentSettings, StreamChunk};
+    use crate::models::protocol::{ProtocolEvent, SessionEventKind};
+    use crate::services::event_sink::{
+        read_event_log, EventRecord, EventSink, FileEventSink, RecordedEvent,
+        RecordingEventSink, SharedEventSink, TeeEventSink,
+    };
+    use crate::services::executors::acp_executor::{AcpDialect, AcpExecutor};
+    use crate::services::executors::pty_executor::PtyExecutor;
+    use crate::services::executors::AgentExecutor;
+    use tempfile::TempDir;
+
+    fn chunk(content: &str, finished: bool) -> StreamChunk {
+        StreamChunk {
+            session_id: "s1".into(),
+            content: content.into(),
+            finished,
+        }
+    }
+
+    fn session_event(kind: SessionEventKind) -> ProtocolEvent {
+        ProtocolEvent::SessionEvent {
+            session_id: "s1".into(),
+            event: kind,
+        }
+    }
+
+    #[cfg(unix)]
+    fn write_script(dir: &std::path::Path, name: &str, body: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    async fn wait_for<F: Fn() -> bool>(condition: F) {
+        for _ in 0..100 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("condition not met within 5s");
+    }
+
+    #[test]
+    fn recording_sink_keeps_emission_order_across_channels() {
+        let sink = RecordingEventSink::new();
+        sink.emit_event(session_event(SessionEventKind::Connected));
+        sink.emit_chunk(chunk("hello\n", false));
+        sink.emit_chunk(chunk("", true));
+
+        assert_eq!(
+            sink.events(),
+            vec![
+                RecordedEvent::Protocol(session_event(SessionEventKind::Connected)),
+                RecordedEvent::Chunk(chunk("hello\n", false)),
+                RecordedEvent::Chunk(chunk("", true)),
+            ]
+        );
+        assert_eq!(sink.chunks().len(), 2);
+        assert_eq!(sink.protocol_events().len(), 1);
+
+        sink.clear();
+        assert!(sink.events().is_empty());
+    }
+
+    #[test]
+    fn tee_sink_forwards_every_item_to_every_sink() {
+        let first = Arc::new(RecordingEventSink::new());
+        let second = Arc::new(RecordingEventSink::new());
+        let tee = TeeEventSink::new(vec![first.clone(), second.clone()]);
+
+        tee.emit_chunk(chunk("a", false));
+        tee.emit_event(session_event(SessionEventKind::Disconnected));
+
+        let expected = vec![
+            RecordedEvent::Chunk(chunk("a", false)),
+            RecordedEvent::Protocol(session_event(SessionEventKind::Disconnected)),
+        ];
+        assert_eq!(first.events(), expected);
+        assert_eq!(second.events(), expected);
+    }
+
+    #[test]
+    fn file_sink_appends_jsonl_that_reads_back_in_order() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("logs").join("s1.jsonl");
+
+        let sink = FileEventSink::open(&path).unwrap();
+        sink.emit_chunk(chunk("line\n", false));
+        sink.emit_event(ProtocolEvent::Message {
+            session_id: "s1".into(),
+            role: "assistant".into(),
+            content: "done".into(),
+        });
+        drop(sink);
+
+        // Reopening appends instead of truncating.
+        let sink = FileEventSink::open(&path).unwrap();
+        sink.emit_chunk(chunk("", true));
+
+        let records = read_event_log(&path).unwrap();
+        let events: Vec<RecordedEvent> =
+            records.iter().map(|r| r.to_recorded().unwrap()).collect();
+        assert_eq!(
+            events,
+            vec![
+                RecordedEvent::Chunk(chunk("line\n", false)),
+                RecordedEvent::Protocol(ProtocolEvent::Message {
+                    session_id: "s1".into(),
+                    role: "assistant".into(),
+                    content: "done".into(),
+                }),
+                RecordedEvent::Chunk(chunk("", true)),
+            ]
+        );
+        assert_eq!(records[0].event, "cli-stream");
+        assert_eq!(records[1].event, "protocol-event");
+        assert!(records.windows(2).all(|w| w[0].ts <= w[1].ts));
+    }
+
+    #[test]
+    fn event_log_reader_skips_truncated_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("s1.jsonl");
+        let good = serde_json::to_string(&EventRecord::new(
+            1,
+            &RecordedEvent::Chunk(chunk("ok", false)),
+        ))
+        .unwrap();
+        std::fs::write(&path, format!("{}\n{{\"ts\":2,\"event\":\"cli-str", good)).unwrap();
+
+        let records = read_event_log(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].ts, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn acp_executor_emits_exact_event_sequence() {
+        let dir = TempDir::new().unwrap();
+        let agent = write_script(
+            dir.path(),
+            "fake-acp",
+            r#"read prompt
+echo '{"type":"message","data":{"role":"assistant","content":"hi"}}'
+echo '{"type":"state_change","data":{"status":"idle","context_percent":12.5}}'
+"#,
+        );
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = AcpExecutor::new(None).with_dialect(AcpDialect::Legacy);
+        executor
+            .execute(
+                &sink,
+                "s1",
+                &agent,
+                "hello",
+                &dir.path().to_string_lossy(),
+                &AgentSettings::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        wait_for(|| !executor.is_alive()).await;
+
+        assert_eq!(
+            recorder.protocol_events(),
+            vec![
+                session_event(SessionEventKind::Connected),
+                ProtocolEvent::Message {
+                    session_id: "s1".into(),
+                    role: "assistant".into(),
+                    content: "hi".into(),
+                },
+                ProtocolEvent::StateChange {
+                    session_id: "s1".into(),
+                    status: "idle".into(),
+                    context_percent: Some(12.5),
+                    usage: None,
+                    agent_session_id: None,
+                },
+                session_event(SessionEventKind::Disconnected),
+            ]
+        );
+        assert!(recorder.chunks().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pty_executor_pipe_path_emits_output_then_final_chunk() {
+        let dir = TempDir::new().unwrap();
+        let agent = write_script(dir.path(), "fake-agent", "echo \"got: $1\"\n");
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = PtyExecutor::new();
+        executor
+            .execute(
+                &sink,
+                "s1",
+                &agent,
+                "hello",
+                &dir.path().to_string_lossy(),
+                &AgentSettings::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            recorder.events(),
+            vec![
+                RecordedEvent::Chunk(chunk("got: hello\n", false)),
+                RecordedEvent::Chunk(chunk("", true)),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pty_executor_reports_failing_exit_as_error_then_final_chunk() {
+        let dir = TempDir::new().unwrap();
+        let agent = write_script(dir.path(), "failing-agent", "exit 3\n");
+
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let mut executor = PtyExecutor::new();
+        executor
+            .execute(
+                &sink,
+                "s1",
+                &agent,
+                "",
+                &dir.path().to_string_lossy(),
+                &AgentSettings::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            recorder.events(),
+            vec![
+                RecordedEvent::Protocol(ProtocolEvent::Error {
+                    session_id: "s1".into(),
+                    message: "Command failed with exit code: 3".into(),
+                    failure: None,
+                }),
+                RecordedEvent::Chunk(chunk("", true)),
+            ]
+        );
+    }
+}
@@ -3,11 +3,12 @@ mod tests {
     use crate::services::executors::{ExecutorFactory, AgentExecutor};
     use crate::services::agent_status_service::{ProtocolCache, ProtocolCacheEntry};
     use crate::models::protocol::ProtocolMode;
+    use crate::services::executors::acp_jsonrpc::AcpDialect;
 
     #[test]
     fn factory_creates_pty_executor_when_no_protocol() {
         let cache = ProtocolCache::new();
-        let executor = ExecutorFactory::create("claude", &cache);
+        let executor = ExecutorFactory::create("claude", &cache, AcpDialect::default());
         assert_eq!(executor.protocol(), None);
     }
 
@@ -19,10 +20,24 @@ mod tests {
             agent_version: "0.1.0".into(),
             flag_variant: Some("--mode acp".into()),
         });
-        let executor = ExecutorFactory::create("autohand", &cache);
+        let executor = ExecutorFactory::create("autohand", &cache, AcpDialect::default());
         assert_eq!(executor.protocol(), Some(ProtocolMode::Acp));
     }
 
+    #[test]
+    fn factory_passes_the_legacy_dialect_to_acp_executors() {
+        let mut cache = ProtocolCache::new();
+        cache.set("autohand", ProtocolCacheEntry {
+            protocol: Some(ProtocolMode::Acp),
+            agent_version: "0.1.0".into(),
+            flag_variant: Some("--mode acp".into()),
+        });
+        let executor = ExecutorFactory::create("autohand", &cache, AcpDialect::Legacy);
+        assert!(!executor.supports_multi_turn());
+        let executor = ExecutorFactory::create("autohand", &cache, AcpDialect::JsonRpc);
+        assert!(executor.supports_multi_turn());
+    }
+
     #[test]
     fn factory_creates_rpc_executor_when_rpc_cached() {
         let mut cache = ProtocolCache::new();
@@ -31,7 +46,7 @@ mod tests {
             agent_version: "0.1.0".into(),
             flag_variant: Some("--rpc".into()),
         });
-        let executor = ExecutorFactory::create("autohand", &cache);
+        let executor = ExecutorFactory::create("autohand", &cache, AcpDialect::default());
         assert_eq!(executor.protocol(), Some(ProtocolMode::Rpc));
     }
 }
@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::process::Command as StdCommand;
+
+    use crate::models::ai_agent::StreamChunk;
+    use crate::models::protocol::{ProtocolEvent, ToolKind};
+    use crate::services::event_sink::{EventRecord, RecordedEvent};
+    use crate::services::fan_out_service::{
+        commit_worktree_changes, diff_stats, fan_out_worktree_name, normalize_fan_out_agents,
+        run_stats,
+    };
+    use tempfile::TempDir;
+
+    fn init_repo(path: &std::path::Path) {
+        assert!(StdCommand::new("git").arg("init").current_dir(path).status().unwrap().success());
+        let _ = StdCommand::new("git").args(["config", "user.name", "Test"]).current_dir(path).status();
+        let _ = StdCommand::new("git").args(["config", "user.email", "test@example.com"]).current_dir(path).status();
+        fs::write(path.join("file.txt"), b"hello\n").unwrap();
+        assert!(StdCommand::new("git").args(["add", "."]).current_dir(path).status().unwrap().success());
+        assert!(StdCommand::new("git").args(["commit", "-m", "init"]).current_dir(path).status().unwrap().success());
+    }
+
+    fn chunk(content: &str) -> RecordedEvent {
+        RecordedEvent::Chunk(StreamChunk {
+            session_id: "s".into(),
+            content: content.into(),
+            finished: false,
+        })
+    }
+
+    fn row(status: &str, path: &str) -> HashMap<String, String> {
+        HashMap::from([
+            ("status".to_string(), status.to_string()),
+            ("path".to_string(), path.to_string()),
+        ])
+    }
+
+    #[test]
+    fn agents_are_normalized_and_deduplicated() {
+        let agents = vec![" Claude".to_string(), "codex".into(), "claude".into(), "".into()];
+        assert_eq!(normalize_fan_out_agents(&agents).unwrap(), vec!["claude", "codex"]);
+        assert!(normalize_fan_out_agents(&[]).is_err());
+        assert!(normalize_fan_out_agents(&["../evil".to_string()]).is_err());
+        assert_eq!(fan_out_worktree_name("gemini", "ab12cd34"), "gemini-ab12cd34");
+    }
+
+    #[test]
+    fn run_stats_counts_tools_errors_and_failures() {
+        let records = vec![
+            EventRecord::new(1_000, &chunk("🔗 Agent: codex | Command: fix\n")),
+            EventRecord::new(
+                1_200,
+                &RecordedEvent::Protocol(ProtocolEvent::ToolStart {
+                    session_id: "s".into(),
+                    tool_id: "t1".into(),
+                    tool_name: "edit".into(),
+                    tool_kind: ToolKind::Edit,
+                    args: None,
+                }),
+            ),
+            EventRecord::new(3_500, &chunk("done\n")),
+        ];
+        let stats = run_stats(&records);
+        assert_eq!(stats.duration_ms, 2_500);
+        assert_eq!(stats.tool_calls, 1);
+        assert_eq!(stats.errors, 0);
+        assert!(!stats.failed);
+
+        // Only structured events count; output that merely looks like an error does not.
+        let noisy = vec![EventRecord::new(0, &chunk("❌ 2 tests failed, retrying\n"))];
+        assert!(!run_stats(&noisy).failed);
+        let failed = vec![EventRecord::new(
+            0,
+            &RecordedEvent::Protocol(ProtocolEvent::Error {
+                session_id: "s".into(),
+                message: "Command failed with exit code: 1".into(),
+                failure: None,
+            }),
+        )];
+        let stats = run_stats(&failed);
+        assert!(stats.failed);
+        assert_eq!(stats.errors, 1);
+
+        // An error the agent got past does not fail a turn it then finished.
+        let recovered = vec![
+            failed[0].clone(),
+            EventRecord::new(
+                10,
+                &RecordedEvent::Protocol(ProtocolEvent::StateChange {
+                    session_id: "s".into(),
+                    status: "idle".into(),
+                    context_percent: None,
+                    usage: None,
+                    agent_session_id: None,
+                }),
+            ),
+        ];
+        let stats = run_stats(&recovered);
+        assert!(!stats.failed);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(run_stats(&[]).duration_ms, 0);
+    }
+
+    #[test]
+    fn diff_stats_counts_by_status() {
+        let stats = diff_stats(vec![
+            row("A", "new.rs"),
+            row("M", "lib.rs"),
+            row("R100", "moved.rs"),
+            row("D", "old.rs"),
+        ]);
+        assert_eq!(stats.files_changed, 4);
+        assert_eq!((stats.added, stats.modified, stats.deleted), (1, 2, 1));
+        assert_eq!(stats.files.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn commit_worktree_changes_skips_commander_data() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path();
+        init_repo(repo);
+        let worktree = repo.to_string_lossy().to_string();
+
+        assert!(!commit_worktree_changes(&worktree, "nothing").await.unwrap());
+
+        fs::write(repo.join("file.txt"), b"hello\nworld\n").unwrap();
+        fs::create_dir_all(repo.join(".commander/transcripts")).unwrap();
+        fs::write(repo.join(".commander/transcripts/s.jsonl"), b"{}\n").unwrap();
+        assert!(commit_worktree_changes(&worktree, "codex: add world").await.unwrap());
+
+        let show = StdCommand::new("git")
+            .args(["show", "--name-only", "--pretty=%s", "HEAD"])
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        let show = String::from_utf8_lossy(&show.stdout);
+        assert!(show.contains("codex: add world"));
+        assert!(show.contains("file.txt"));
+        assert!(!show.contains(".commander"));
+    }
+}
@@ -0,0 +1,167 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command as StdCommand;
+
+    use crate::services::git_backend::{
+        is_lookup_error, parse_log_records, parse_name_status_z, CliGitBackend, ConfigScope,
+        FallbackGitBackend, FileChange, GitBackend, LibGit2Backend,
+    };
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str], date: &str) -> String {
+        let out = StdCommand::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&out.stderr));
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    }
+
+    /// `master` with a root commit, a tag and a `feature` branch that adds a
+    /// file with spaces in its name, renames another and has a `|` subject.
+    fn setup(tmp: &TempDir) -> String {
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        let at = |n: u32| format!("2024-05-0{} 10:00:00 +0200", n);
+        git(&repo, &["init", "-q"], &at(1));
+        git(&repo, &["config", "user.name", "Test"], &at(1));
+        git(&repo, &["config", "user.email", "test@example.com"], &at(1));
+        git(&repo, &["config", "alias.co", "checkout"], &at(1));
+
+        fs::write(repo.join("old name.txt"), "a fairly long line so rename detection has content\n").unwrap();
+        fs::write(repo.join("keep.txt"), "keep\n").unwrap();
+        git(&repo, &["add", "."], &at(1));
+        git(&repo, &["commit", "-qm", "init"], &at(1));
+        git(&repo, &["branch", "-M", "master"], &at(1));
+        git(&repo, &["tag", "v1"], &at(1));
+
+        git(&repo, &["checkout", "-qb", "feature"], &at(2));
+        fs::write(repo.join("with space.txt"), "new\n").unwrap();
+        git(&repo, &["mv", "old name.txt", "new name.txt"], &at(2));
+        git(&repo, &["add", "."], &at(2));
+        git(&repo, &["commit", "-qm", "add | rename"], &at(2));
+
+        fs::write(repo.join("keep.txt"), "changed\n").unwrap();
+        git(&repo, &["commit", "-qam", "edit keep"], &at(3));
+        repo.to_string_lossy().to_string()
+    }
+
+    fn sorted(mut changes: Vec<FileChange>) -> Vec<FileChange> {
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        changes
+    }
+
+    #[test]
+    fn backends_agree_on_branches_log_and_diffs() {
+        let tmp = TempDir::new().unwrap();
+        let repo = setup(&tmp);
+        let backends: [&dyn GitBackend; 2] = [&LibGit2Backend, &CliGitBackend];
+
+        for backend in backends {
+            let name = backend.name();
+            assert_eq!(backend.current_branch(&repo).unwrap().as_deref(), Some("feature"), "{}", name);
+            assert_eq!(backend.list_branches(&repo).unwrap(), vec!["feature", "master"], "{}", name);
+
+            let log = backend.log(&repo, None, 10).unwrap();
+            assert_eq!(log.len(), 3, "{}", name);
+            assert_eq!(log[0].subject, "edit keep", "{}", name);
+            assert_eq!(log[0].refs, vec!["HEAD -> feature"], "{}", name);
+            assert_eq!(log[1].subject, "add | rename", "{}", name);
+            assert_eq!(log[1].date, "2024-05-02 10:00:00 +0200", "{}", name);
+            assert_eq!(log[1].parents, vec![log[2].hash.clone()], "{}", name);
+            let mut root_refs = log[2].refs.clone();
+            root_refs.sort();
+            assert_eq!(root_refs, vec!["master", "tag: v1"], "{}", name);
+
+            assert_eq!(backend.log(&repo, Some("master"), 10).unwrap().len(), 1, "{}", name);
+            assert_eq!(backend.log(&repo, None, 2).unwrap().len(), 2, "{}", name);
+
+            let range = sorted(backend.diff_range(&repo, "master", "feature").unwrap());
+            assert_eq!(
+                range,
+                vec![
+                    FileChange { status: "M".into(), path: "keep.txt".into(), old_path: None },
+                    FileChange {
+                        status: "R".into(),
+                        path: "new name.txt".into(),
+                        old_path: Some("old name.txt".into()),
+                    },
+                    FileChange { status: "A".into(), path: "with space.txt".into(), old_path: None },
+                ],
+                "{}",
+                name
+            );
+
+            let root = backend.commit_changes(&repo, &log[2].hash).unwrap();
+            assert_eq!(root.len(), 2, "{}", name);
+            assert!(root.iter().all(|c| c.status == "A"), "{}", name);
+            let head = backend.commit_changes(&repo, "HEAD").unwrap();
+            assert_eq!(head, vec![FileChange { status: "M".into(), path: "keep.txt".into(), old_path: None }]);
+
+            assert_eq!(backend.file_at_commit(&repo, "master", "keep.txt").unwrap(), b"keep\n", "{}", name);
+            assert!(backend.file_at_commit(&repo, "master", "with space.txt").is_err(), "{}", name);
+            assert!(backend.log(&repo, Some("no-such-branch"), 10).is_err(), "{}", name);
+
+            let config = backend.config_entries(ConfigScope::Local(&repo)).unwrap();
+            assert!(config.contains(&("user.name".to_string(), "Test".to_string())), "{}", name);
+            assert!(config.contains(&("alias.co".to_string(), "checkout".to_string())), "{}", name);
+        }
+    }
+
+    #[test]
+    fn detached_head_has_no_branch() {
+        let tmp = TempDir::new().unwrap();
+        let repo = setup(&tmp);
+        git(Path::new(&repo), &["checkout", "-q", "--detach", "master"], "2024-05-04 10:00:00 +0200");
+
+        assert_eq!(LibGit2Backend.current_branch(&repo).unwrap(), None);
+        assert_eq!(CliGitBackend.current_branch(&repo).unwrap(), None);
+        assert_eq!(LibGit2Backend.log(&repo, None, 1).unwrap()[0].refs[0], "HEAD");
+    }
+
+    #[test]
+    fn missing_revisions_and_paths_do_not_fall_back() {
+        let tmp = TempDir::new().unwrap();
+        let repo = setup(&tmp);
+        let backend = FallbackGitBackend {
+            primary: Box::new(LibGit2Backend),
+            fallback: Box::new(CliGitBackend),
+        };
+
+        // The CLI would answer with its own stderr had it been asked.
+        let missing_rev = backend.log(&repo, Some("no-such-branch"), 1).unwrap_err();
+        assert!(missing_rev.starts_with("Unknown revision 'no-such-branch'"), "{}", missing_rev);
+        let missing_path = backend.file_at_commit(&repo, "master", "nope.txt").unwrap_err();
+        assert!(is_lookup_error(&missing_path), "{}", missing_path);
+        assert!(!is_lookup_error("Failed to open repository /tmp/x: unsupported extension"));
+    }
+
+    #[test]
+    fn parses_nul_separated_name_status() {
+        let changes = parse_name_status_z("M\0a b.txt\0R087\0old\tname\0new\nname\0D\0gone\0");
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].path, "a b.txt");
+        assert_eq!(changes[1].status, "R");
+        assert_eq!(changes[1].old_path.as_deref(), Some("old\tname"));
+        assert_eq!(changes[1].path, "new\nname");
+        assert_eq!(changes[2].status, "D");
+    }
+
+    #[test]
+    fn parses_log_records_with_pipes_in_subjects() {
+        let output = "abc\x1fp1 p2\x1fAda\x1f2024-01-01 00:00:00 +0000\x1fa | b\x1fHEAD -> main, tag: v1\x1e\n\
+                      def\x1f\x1fAda\x1f2024-01-01 00:00:00 +0000\x1froot\x1f\x1e";
+        let commits = parse_log_records(output);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].subject, "a | b");
+        assert_eq!(commits[0].parents, vec!["p1", "p2"]);
+        assert_eq!(commits[0].refs, vec!["HEAD -> main", "tag: v1"]);
+        assert!(commits[1].parents.is_empty());
+        assert!(commits[1].refs.is_empty());
+    }
+}
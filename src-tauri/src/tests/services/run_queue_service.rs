@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests {
+    use tokio::sync::oneshot::error::TryRecvError;
+
+    use crate::models::ai_agent::AllAgentSettings;
+    use crate::services::run_queue_service::{ConcurrencyLimits, RunQueue};
+
+    fn limits(global: usize, per_project: Option<usize>, per_agent: Option<usize>) -> ConcurrencyLimits {
+        ConcurrencyLimits {
+            global,
+            per_project,
+            per_agent,
+        }
+    }
+
+    fn queued_ids(queue: &RunQueue) -> Vec<String> {
+        queue.snapshot().queued.into_iter().map(|q| q.session_id).collect()
+    }
+
+    #[test]
+    fn limits_come_from_settings_and_never_drop_to_zero() {
+        let settings = AllAgentSettings {
+            max_concurrent_sessions: 0,
+            max_concurrent_sessions_per_project: Some(2),
+            ..AllAgentSettings::default()
+        };
+        assert_eq!(ConcurrencyLimits::from_settings(&settings), limits(1, Some(2), None));
+        assert_eq!(ConcurrencyLimits::default().global, 10);
+    }
+
+    #[test]
+    fn global_cap_queues_extra_runs_until_a_slot_frees() {
+        let mut queue = RunQueue::new(limits(2, None, None));
+        let mut a = queue.enqueue("a", "claude", Some("/p1"), 0).unwrap();
+        let mut b = queue.enqueue("b", "codex", Some("/p2"), 0).unwrap();
+        let mut c = queue.enqueue("c", "gemini", Some("/p3"), 0).unwrap();
+
+        assert_eq!(a.try_recv(), Ok(()));
+        assert_eq!(b.try_recv(), Ok(()));
+        assert_eq!(c.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(queue.position("c"), Some(1));
+
+        let snapshot = queue.snapshot();
+        assert_eq!(snapshot.running.len(), 2);
+        assert_eq!(snapshot.queued[0].position, 1);
+        assert_eq!(snapshot.queued[0].eta_seconds, None);
+
+        queue.complete("a");
+        assert_eq!(c.try_recv(), Ok(()));
+        assert_eq!(queue.position("c"), None);
+        // Once a run has finished there is a duration to estimate from.
+        let mut d = queue.enqueue("d", "claude", None, 0).unwrap();
+        assert_eq!(d.try_recv(), Err(TryRecvError::Empty));
+        assert!(queue.snapshot().queued[0].eta_seconds.is_some());
+    }
+
+    #[test]
+    fn project_cap_does_not_block_other_projects() {
+        let mut queue = RunQueue::new(limits(5, Some(1), None));
+        let mut a = queue.enqueue("a", "claude", Some("/p1"), 0).unwrap();
+        let mut b = queue.enqueue("b", "claude", Some("/p1"), 0).unwrap();
+        let mut c = queue.enqueue("c", "claude", Some("/p2"), 0).unwrap();
+
+        assert_eq!(a.try_recv(), Ok(()));
+        assert_eq!(b.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(c.try_recv(), Ok(()));
+
+        queue.complete("a");
+        assert_eq!(b.try_recv(), Ok(()));
+    }
+
+    #[test]
+    fn agent_cap_limits_runs_of_the_same_agent() {
+        let mut queue = RunQueue::new(limits(5, None, Some(1)));
+        let mut a = queue.enqueue("a", "codex", Some("/p1"), 0).unwrap();
+        let mut b = queue.enqueue("b", "codex", Some("/p2"), 0).unwrap();
+        let mut c = queue.enqueue("c", "claude", Some("/p1"), 0).unwrap();
+
+        assert_eq!(a.try_recv(), Ok(()));
+        assert_eq!(b.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(c.try_recv(), Ok(()));
+    }
+
+    #[test]
+    fn priority_orders_the_queue_and_can_be_changed() {
+        let mut queue = RunQueue::new(limits(1, None, None));
+        let _a = queue.enqueue("a", "claude", None, 0).unwrap();
+        let mut b = queue.enqueue("b", "claude", None, 0).unwrap();
+        let mut c = queue.enqueue("c", "claude", None, 5).unwrap();
+        let _d = queue.enqueue("d", "claude", None, 0).unwrap();
+        assert_eq!(queued_ids(&queue), vec!["c", "b", "d"]);
+
+        queue.reprioritize("b", 10).unwrap();
+        assert_eq!(queued_ids(&queue), vec!["b", "c", "d"]);
+        assert!(queue.reprioritize("a", 1).is_err());
+
+        queue.complete("a");
+        assert_eq!(b.try_recv(), Ok(()));
+        assert_eq!(c.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn cancel_drops_queued_runs_only() {
+        let mut queue = RunQueue::new(limits(1, None, None));
+        let _a = queue.enqueue("a", "claude", None, 0).unwrap();
+        let mut b = queue.enqueue("b", "claude", None, 0).unwrap();
+
+        queue.cancel("b").unwrap();
+        assert_eq!(b.try_recv(), Err(TryRecvError::Closed));
+        assert!(queue.cancel("a").is_err());
+        assert!(queue.cancel("missing").is_err());
+
+        let mut c = queue.enqueue("c", "claude", None, 0).unwrap();
+        queue.cancel_all();
+        assert_eq!(c.try_recv(), Err(TryRecvError::Closed));
+        assert_eq!(queue.snapshot().running.len(), 1);
+    }
+
+    #[test]
+    fn duplicate_sessions_are_rejected_and_dead_waiters_skipped() {
+        let mut queue = RunQueue::new(limits(1, None, None));
+        let _a = queue.enqueue("a", "claude", None, 0).unwrap();
+        assert!(queue.enqueue("a", "claude", None, 0).is_err());
+
+        let b = queue.enqueue("b", "claude", None, 0).unwrap();
+        let mut c = queue.enqueue("c", "claude", None, 0).unwrap();
+        drop(b);
+
+        queue.complete("a");
+        assert_eq!(c.try_recv(), Ok(()));
+    }
+}
@@ -0,0 +1,183 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::models::ai_agent::{AgentSettings, ProcessSandbox, StreamChunk};
+    use crate::models::protocol::{ProtocolEvent, SandboxAccess};
+    use crate::services::event_sink::{RecordingEventSink, SharedEventSink};
+    use crate::services::sandbox_service::{
+        agent_state_paths, blocked_access, SandboxMonitor, SandboxProfile,
+    };
+
+    fn sandboxed(allow_network: bool, writable_paths: Vec<String>) -> AgentSettings {
+        AgentSettings {
+            process_sandbox: ProcessSandbox {
+                enabled: true,
+                allow_network,
+                writable_paths,
+            },
+            ..AgentSettings::default()
+        }
+    }
+
+    #[test]
+    fn runs_are_only_sandboxed_when_enabled() {
+        let project = tempfile::tempdir().unwrap();
+        let dir = project.path().to_str().unwrap();
+        assert!(SandboxProfile::for_run(&AgentSettings::default(), "claude", dir).is_none());
+        assert!(SandboxProfile::for_run(&sandboxed(true, vec![]), "claude", dir).is_some());
+    }
+
+    #[test]
+    fn every_built_in_agent_keeps_its_state_writable() {
+        for agent in [
+            "autohand", "claude", "codex", "gemini", "cursor", "copilot", "pi", "opencode", "vibe",
+            "amp",
+        ] {
+            assert!(!agent_state_paths(agent).is_empty(), "{agent}");
+        }
+        assert_eq!(agent_state_paths("/usr/local/bin/autohand"), &[".autohand"]);
+        assert!(agent_state_paths("unknown-agent").is_empty());
+    }
+
+    #[test]
+    fn the_project_is_writable_but_its_git_dirs_are_not() {
+        let repo = tempfile::tempdir().unwrap();
+        let git_dir = repo.path().join(".git/worktrees/feature");
+        std::fs::create_dir_all(&git_dir).unwrap();
+        std::fs::write(git_dir.join("commondir"), "../..\n").unwrap();
+        let worktree = tempfile::tempdir().unwrap();
+        std::fs::write(
+            worktree.path().join(".git"),
+            format!("gitdir: {}\n", git_dir.display()),
+        )
+        .unwrap();
+        let extra = tempfile::tempdir().unwrap();
+
+        let settings = sandboxed(
+            false,
+            vec![
+                extra.path().to_string_lossy().to_string(),
+                "/does/not/exist".to_string(),
+            ],
+        );
+        let profile =
+            SandboxProfile::for_run(&settings, "gemini", worktree.path().to_str().unwrap())
+                .unwrap();
+
+        let canonical = |p: &std::path::Path| std::fs::canonicalize(p).unwrap();
+        assert!(profile.writable.contains(&canonical(worktree.path())));
+        assert_eq!(
+            profile.read_only,
+            vec![canonical(&git_dir), canonical(&repo.path().join(".git"))]
+        );
+        assert!(!profile.writable.contains(&canonical(&git_dir)));
+        assert!(profile.writable.contains(&canonical(extra.path())));
+        assert!(!profile
+            .writable
+            .iter()
+            .any(|p| p.starts_with("/does/not/exist")));
+        assert!(!profile.allow_network);
+    }
+
+    #[test]
+    fn writable_binds_come_after_the_read_only_root() {
+        let project = tempfile::tempdir().unwrap();
+        let git_dir = project.path().join(".git");
+        let profile = SandboxProfile {
+            writable: vec![project.path().to_path_buf()],
+            read_only: vec![git_dir.clone()],
+            allow_network: false,
+        };
+        let args = profile.bwrap_args();
+        let position = |arg: &str| args.iter().position(|a| a == arg).unwrap();
+        for flag in [
+            "--unshare-user",
+            "--unshare-pid",
+            "--new-session",
+            "--unshare-net",
+        ] {
+            assert!(args.contains(&flag.to_string()), "{}", flag);
+        }
+        assert!(position("--ro-bind") < position("--bind"));
+        assert!(position("--tmpfs") < position("--bind"));
+        assert_eq!(
+            args[position("--bind") + 1],
+            project.path().to_string_lossy()
+        );
+        // The git dir is bound read-only over the writable project.
+        let git_bind = args.iter().rposition(|a| a == "--ro-bind").unwrap();
+        assert!(git_bind > position("--bind"));
+        assert_eq!(args[git_bind + 1], git_dir.to_string_lossy());
+
+        let online = SandboxProfile {
+            allow_network: true,
+            ..profile
+        };
+        assert!(!online.bwrap_args().contains(&"--unshare-net".to_string()));
+    }
+
+    #[test]
+    fn blocked_writes_and_connects_are_recognised() {
+        assert_eq!(
+            blocked_access("touch: cannot touch '/etc/x': Read-only file system", false),
+            Some(SandboxAccess::Write)
+        );
+        assert_eq!(
+            blocked_access("Error: EROFS: read-only file system, open '/usr/x'", true),
+            Some(SandboxAccess::Write)
+        );
+        let offline = "curl: (6) Could not resolve host: example.com";
+        assert_eq!(blocked_access(offline, true), Some(SandboxAccess::Network));
+        assert_eq!(blocked_access(offline, false), None);
+        assert_eq!(blocked_access("wrote src/main.rs", true), None);
+    }
+
+    #[test]
+    fn each_blocked_access_is_reported_once() {
+        let recorder = Arc::new(RecordingEventSink::new());
+        let profile = SandboxProfile {
+            writable: vec![],
+            read_only: vec![],
+            allow_network: false,
+        };
+        let sink = SandboxMonitor::shared(recorder.clone() as SharedEventSink, "sb", &profile);
+
+        let line = "mkdir: cannot create directory '/opt/x': Read-only file system";
+        for _ in 0..2 {
+            sink.emit_chunk(StreamChunk {
+                session_id: "sb".to_string(),
+                content: format!("{}\n", line),
+                finished: false,
+            });
+        }
+        sink.emit_event(ProtocolEvent::ToolEnd {
+            session_id: "sb".to_string(),
+            tool_id: "t1".to_string(),
+            tool_name: "Bash".to_string(),
+            output: Some("npm ERR! getaddrinfo EAI_AGAIN registry.npmjs.org".to_string()),
+            success: false,
+            duration_ms: None,
+        });
+
+        let blocked: Vec<(SandboxAccess, String)> = recorder
+            .protocol_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                ProtocolEvent::SandboxBlocked { access, detail, .. } => Some((access, detail)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            blocked,
+            vec![
+                (SandboxAccess::Write, line.to_string()),
+                (
+                    SandboxAccess::Network,
+                    "npm ERR! getaddrinfo EAI_AGAIN registry.npmjs.org".to_string()
+                ),
+            ]
+        );
+        assert_eq!(recorder.chunks().len(), 2);
+    }
+}
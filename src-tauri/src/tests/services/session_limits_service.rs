@@ -0,0 +1,172 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::models::ai_agent::{AgentSettings, StreamChunk};
+    use crate::models::protocol::{ProtocolEvent, SessionEndReason};
+    use crate::services::event_sink::{RecordingEventSink, SharedEventSink};
+    use crate::services::session_limits_service::{ActivitySink, SessionActivity, SessionLimits};
+
+    fn chunk(content: &str) -> StreamChunk {
+        StreamChunk {
+            session_id: "limits".to_string(),
+            content: content.to_string(),
+            finished: false,
+        }
+    }
+
+    fn permission_request(request_id: &str) -> ProtocolEvent {
+        ProtocolEvent::PermissionRequest {
+            session_id: "limits".to_string(),
+            request_id: request_id.to_string(),
+            tool_name: "Bash".to_string(),
+            description: "rm -rf build".to_string(),
+            target: None,
+            tool_id: None,
+        }
+    }
+
+    fn permission_resolved(request_id: &str) -> ProtocolEvent {
+        ProtocolEvent::PermissionResolved {
+            session_id: "limits".to_string(),
+            request_id: request_id.to_string(),
+            tool_name: "Bash".to_string(),
+            approved: true,
+            reason: "user".to_string(),
+            automatic: false,
+            target: None,
+            tool_id: None,
+        }
+    }
+
+    #[test]
+    fn zero_settings_mean_no_limit() {
+        let settings = AgentSettings {
+            session_timeout_minutes: 0,
+            max_run_minutes: 0,
+            max_output_bytes: 0,
+            ..AgentSettings::default()
+        };
+        assert_eq!(
+            SessionLimits::from_settings(&settings),
+            SessionLimits::default()
+        );
+
+        let settings = AgentSettings {
+            session_timeout_minutes: 10,
+            max_run_minutes: 90,
+            max_output_bytes: 1024,
+            ..AgentSettings::default()
+        };
+        let limits = SessionLimits::from_settings(&settings);
+        assert_eq!(limits.idle, Some(Duration::from_secs(600)));
+        assert_eq!(limits.max_run, Some(Duration::from_secs(5400)));
+        assert_eq!(limits.max_output_bytes, Some(1024));
+    }
+
+    #[test]
+    fn output_past_the_cap_is_counted_through_the_sink() {
+        let activity = Arc::new(SessionActivity::new(SessionLimits {
+            max_output_bytes: Some(10),
+            ..SessionLimits::default()
+        }));
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink = ActivitySink::shared(recorder.clone() as SharedEventSink, Arc::clone(&activity));
+
+        sink.emit_chunk(chunk("hello"));
+        sink.emit_event(ProtocolEvent::Message {
+            session_id: "limits".to_string(),
+            content: "world".to_string(),
+            role: "assistant".to_string(),
+        });
+        assert_eq!(activity.output_bytes(), 10);
+        assert_eq!(activity.exceeded(), None);
+
+        sink.emit_chunk(chunk("!"));
+        assert_eq!(activity.exceeded(), Some(SessionEndReason::OutputLimit));
+        assert_eq!(recorder.events().len(), 3);
+    }
+
+    #[test]
+    fn runs_past_their_time_limit_are_reported() {
+        let activity = SessionActivity::new(SessionLimits {
+            max_run: Some(Duration::ZERO),
+            ..SessionLimits::default()
+        });
+        assert_eq!(
+            activity.exceeded(),
+            Some(SessionEndReason::WallClockTimeout)
+        );
+    }
+
+    #[tokio::test]
+    async fn idle_clock_stops_while_a_permission_is_pending() {
+        let activity = Arc::new(SessionActivity::new(SessionLimits {
+            idle: Some(Duration::from_millis(50)),
+            ..SessionLimits::default()
+        }));
+        let sink = ActivitySink::shared(Arc::new(RecordingEventSink::new()), Arc::clone(&activity));
+
+        sink.emit_event(permission_request("perm-1"));
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(activity.exceeded(), None);
+
+        sink.emit_event(permission_resolved("perm-1"));
+        // Answers to requests a rule decided never were pending.
+        sink.emit_event(permission_resolved("perm-auto"));
+        assert_eq!(activity.exceeded(), None);
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(activity.exceeded(), Some(SessionEndReason::IdleTimeout));
+
+        let reason = tokio::time::timeout(Duration::from_secs(2), activity.limit_reached())
+            .await
+            .expect("limit reached");
+        assert_eq!(reason, SessionEndReason::IdleTimeout);
+    }
+
+    #[test]
+    fn the_first_end_reason_sticks() {
+        let activity = SessionActivity::new(SessionLimits::default());
+        assert_eq!(
+            activity.end(SessionEndReason::OutputLimit),
+            SessionEndReason::OutputLimit
+        );
+        assert_eq!(
+            activity.end(SessionEndReason::Completed),
+            SessionEndReason::OutputLimit
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn terminating_a_session_stops_its_whole_process_group() {
+        use crate::services::executors::own_process_group;
+        use crate::services::session_limits_service::{
+            register_process_group, terminate_session_processes,
+        };
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "sleep 30 & echo $!; wait"])
+            .stdout(std::process::Stdio::piped());
+        own_process_group(&mut cmd);
+        let mut child = cmd.spawn().expect("spawn sh");
+        let _group = register_process_group("limits-group", child.id().unwrap());
+
+        let stdout = child.stdout.take().unwrap();
+        let mut lines = BufReader::new(stdout).lines();
+        let grandchild: i32 = lines.next_line().await.unwrap().unwrap().parse().unwrap();
+
+        terminate_session_processes("limits-group").await;
+        let status = tokio::time::timeout(Duration::from_secs(5), child.wait())
+            .await
+            .expect("shell stopped")
+            .unwrap();
+        assert!(!status.success());
+
+        // Gone, or a zombie waiting for init to reap it.
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", grandchild));
+        assert!(stat.map_or(true, |stat| stat.contains(") Z ")));
+    }
+}
@@ -63,4 +63,53 @@ mod tests {
         let manager = SessionManager::new();
         assert!(manager.send_permission("unknown", "req-1".into(), true).is_err());
     }
+
+    #[test]
+    fn remembered_answers_are_shared_by_a_conversation() {
+        use crate::models::protocol::{PermissionTarget, ProtocolEvent, ToolKind};
+        use crate::services::event_sink::{EventSink, RecordingEventSink};
+        use crate::services::permission_policy_service::{EffectivePolicy, PermissionGate};
+        use std::sync::Arc;
+
+        let mut manager = SessionManager::new();
+        let request = |request_id: &str| ProtocolEvent::PermissionRequest {
+            session_id: "s1".into(),
+            request_id: request_id.into(),
+            tool_name: "Bash".into(),
+            description: "Bash".into(),
+            target: Some(PermissionTarget {
+                tool_kind: ToolKind::Execute,
+                command: Some("npm test".into()),
+                paths: Vec::new(),
+            }),
+            tool_id: None,
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let gate = Arc::new(PermissionGate::new(
+            Arc::new(RecordingEventSink::new()),
+            "s1",
+            EffectivePolicy::default(),
+            None,
+            manager.session_allowances("conv-1"),
+            tx,
+        ));
+        manager.set_permission_gate("s1", gate.clone());
+        gate.emit_event(request("req-1"));
+        assert!(manager.answer_permission("s1", "req-1".into(), true, true).is_ok());
+        assert!(rx.try_recv().unwrap().approved);
+
+        // A later run of the same conversation gets the allowance.
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let next = PermissionGate::new(
+            Arc::new(RecordingEventSink::new()),
+            "s2",
+            EffectivePolicy::default(),
+            None,
+            manager.session_allowances("conv-1"),
+            tx,
+        );
+        next.emit_event(request("req-2"));
+        assert_eq!(rx.try_recv().unwrap().request_id, "req-2");
+    }
 }
@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::ai_agent::StreamChunk;
+    use crate::models::protocol::{ProtocolEvent, ToolKind};
+    use crate::services::chat_history_service::{
+        load_chat_sessions, load_session_messages, rename_session,
+    };
+    use crate::services::event_sink::{EventRecord, EventSink, RecordedEvent};
+    use crate::services::transcript_service::{
+        delete_transcript, finalize_transcript, load_transcript, open_transcript,
+        transcript_path, transcript_turns,
+    };
+    use tempfile::TempDir;
+
+    fn user(content: &str) -> ProtocolEvent {
+        ProtocolEvent::Message {
+            session_id: "s1".into(),
+            role: "user".into(),
+            content: content.into(),
+        }
+    }
+
+    fn assistant(content: &str) -> ProtocolEvent {
+        ProtocolEvent::Message {
+            session_id: "s1".into(),
+            role: "assistant".into(),
+            content: content.into(),
+        }
+    }
+
+    fn chunk(content: &str) -> StreamChunk {
+        StreamChunk {
+            session_id: "s1".into(),
+            content: content.into(),
+            finished: false,
+        }
+    }
+
+    fn record(ts_secs: i64, event: RecordedEvent) -> EventRecord {
+        EventRecord::new(ts_secs * 1000, &event)
+    }
+
+    #[test]
+    fn transcript_lives_under_commander_dir() {
+        let path = transcript_path("/tmp/project", "conv-abc_1").unwrap();
+        assert!(path.ends_with(".commander/transcripts/conv-abc_1.jsonl"));
+    }
+
+    #[test]
+    fn session_ids_cannot_leave_the_transcripts_dir() {
+        for id in ["../../etc/passwd", "a/b", "a\\b", "..", "a\0b", "", "a.b"] {
+            assert!(transcript_path("/tmp/project", id).is_err(), "{:?}", id);
+            assert!(load_transcript("/tmp/project", id).is_err(), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn turns_split_on_user_messages() {
+        let records = vec![
+            record(5, RecordedEvent::Chunk(chunk("before any prompt"))),
+            record(10, RecordedEvent::Protocol(user("first"))),
+            record(10, RecordedEvent::Chunk(chunk("🔗 Agent: claude | Command: x\n"))),
+            record(11, RecordedEvent::Chunk(chunk("streamed "))),
+            record(12, RecordedEvent::Chunk(chunk("answer\n"))),
+            record(20, RecordedEvent::Protocol(user("second"))),
+            record(
+                21,
+                RecordedEvent::Protocol(ProtocolEvent::ToolStart {
+                    session_id: "s1".into(),
+                    tool_id: "t1".into(),
+                    tool_name: "read_file".into(),
+                    tool_kind: ToolKind::Read,
+                    args: None,
+                }),
+            ),
+            record(22, RecordedEvent::Chunk(chunk("raw output ignored"))),
+            record(23, RecordedEvent::Protocol(assistant("structured"))),
+            record(
+                24,
+                RecordedEvent::Protocol(ProtocolEvent::Error {
+                    session_id: "s1".into(),
+                    message: "oops".into(),
+                    failure: None,
+                }),
+            ),
+        ];
+
+        let turns = transcript_turns(&records);
+        assert_eq!(turns.len(), 2);
+
+        assert_eq!(turns[0].prompt, "first");
+        assert_eq!(turns[0].response, "streamed answer");
+        assert_eq!((turns[0].started_at, turns[0].ended_at), (10, 12));
+
+        assert_eq!(turns[1].prompt, "second");
+        assert_eq!(turns[1].response, "structured");
+        assert_eq!(turns[1].tool_calls, 1);
+        assert_eq!(turns[1].errors, 1);
+        assert_eq!(turns[1].ended_at, 24);
+    }
+
+    #[tokio::test]
+    async fn finalize_writes_session_and_keeps_user_title() {
+        let dir = TempDir::new().unwrap();
+        let project = dir.path().to_string_lossy().to_string();
+
+        let sink = open_transcript(&project, "s1").unwrap();
+        sink.emit_event(user("explain src/main.rs"));
+        sink.emit_event(assistant("it starts the app"));
+        drop(sink);
+
+        let session = finalize_transcript(&project, "s1", "claude", Some("opus".into()))
+            .await
+            .unwrap();
+        assert_eq!(session.id, "s1");
+        assert_eq!(session.message_count, 2);
+        assert_eq!(session.model.as_deref(), Some("opus"));
+
+        let messages = load_session_messages(&project, "s1").await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "user");
+        assert_eq!(messages[0].metadata.file_mentions, vec!["src/main.rs"]);
+        assert_eq!(messages[1].content, "it starts the app");
+
+        rename_session(&project, "s1", "Main walkthrough").await.unwrap();
+
+        // A follow-up in the same session appends to the transcript.
+        let sink = open_transcript(&project, "s1").unwrap();
+        sink.emit_event(user("and the tests?"));
+        sink.emit_chunk(chunk("they live in src/tests\n"));
+        drop(sink);
+
+        let session = finalize_transcript(&project, "s1", "claude", None).await.unwrap();
+        assert_eq!(session.message_count, 4);
+        assert_eq!(session.custom_title.as_deref(), Some("Main walkthrough"));
+
+        let sessions = load_chat_sessions(&project, None, None, None).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].message_count, 4);
+    }
+
+    #[tokio::test]
+    async fn finalize_fails_without_prompts_and_delete_is_idempotent() {
+        let dir = TempDir::new().unwrap();
+        let project = dir.path().to_string_lossy().to_string();
+
+        assert!(load_transcript(&project, "missing").is_err());
+
+        let sink = open_transcript(&project, "empty").unwrap();
+        sink.emit_chunk(chunk("no prompt recorded"));
+        drop(sink);
+        assert!(finalize_transcript(&project, "empty", "codex", None).await.is_err());
+
+        delete_transcript(&project, "empty").unwrap();
+        assert!(!transcript_path(&project, "empty").unwrap().exists());
+        delete_transcript(&project, "empty").unwrap();
+    }
+}
@@ -0,0 +1,231 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::models::protocol::{ProtocolEvent, TurnUsage};
+    use crate::models::usage::{ModelPrice, UsageRecord, UsageSource};
+    use crate::services::event_sink::{RecordingEventSink, SharedEventSink};
+    use crate::services::indexer::db::IndexDb;
+    use crate::services::indexer::scanner::AgentScanner;
+    use crate::services::indexer::scanners::claude_scanner::ClaudeScanner;
+    use crate::services::indexer::scanners::codex_scanner::CodexScanner;
+    use crate::services::usage_service::{
+        cost_of, default_pricing, live_record, load_pricing_from, price_for, save_pricing_to,
+        UsageMeter,
+    };
+
+    fn record(model: &str, input: u64, output: u64, reported: Option<f64>) -> UsageRecord {
+        live_record(
+            "claude",
+            "s1",
+            None,
+            None,
+            &TurnUsage {
+                model: Some(model.to_string()),
+                input_tokens: input,
+                output_tokens: output,
+                cost_usd: reported,
+                ..TurnUsage::default()
+            },
+        )
+    }
+
+    fn state_change(usage: Option<TurnUsage>, agent_session_id: Option<&str>) -> ProtocolEvent {
+        ProtocolEvent::StateChange {
+            session_id: "run-1".to_string(),
+            status: "idle".to_string(),
+            context_percent: None,
+            usage,
+            agent_session_id: agent_session_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn models_are_priced_by_their_longest_listed_prefix() {
+        let pricing = default_pricing();
+        assert_eq!(
+            price_for(&pricing, "claude-opus-4-5-20251101")
+                .unwrap()
+                .model,
+            "claude-opus-4-5"
+        );
+        assert_eq!(
+            price_for(&pricing, "claude-opus-4-1-20250805")
+                .unwrap()
+                .model,
+            "claude-opus-4"
+        );
+        assert_eq!(
+            price_for(&pricing, "openai/GPT-5-mini").unwrap().model,
+            "gpt-5-mini"
+        );
+        assert_eq!(price_for(&pricing, "gpt-5-codex").unwrap().model, "gpt-5");
+        assert!(price_for(&pricing, "llama3").is_none());
+    }
+
+    #[test]
+    fn cost_comes_from_the_table_or_what_the_agent_reported() {
+        let pricing = vec![ModelPrice {
+            model: "claude-sonnet-4".to_string(),
+            input: 3.0,
+            output: 15.0,
+            cache_read: 0.3,
+            cache_write: 3.75,
+        }];
+        let mut sonnet = record("claude-sonnet-4-5", 1_000_000, 100_000, Some(9.0));
+        sonnet.cache_read_tokens = 2_000_000;
+        sonnet.cache_write_tokens = 100_000;
+        let cost = cost_of(&sonnet, &pricing).unwrap();
+        assert!((cost - (3.0 + 1.5 + 0.6 + 0.375)).abs() < 1e-9);
+
+        assert_eq!(
+            cost_of(&record("local-model", 10, 10, Some(0.25)), &pricing),
+            Some(0.25)
+        );
+        assert_eq!(
+            cost_of(&record("local-model", 10, 10, None), &pricing),
+            None
+        );
+    }
+
+    #[test]
+    fn pricing_tables_round_trip_and_reject_bad_prices() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model-pricing.json");
+        assert_eq!(load_pricing_from(&path).unwrap(), default_pricing());
+
+        let custom = vec![ModelPrice {
+            model: "my-model".to_string(),
+            input: 1.0,
+            output: 2.0,
+            cache_read: 0.0,
+            cache_write: 0.0,
+        }];
+        save_pricing_to(&path, &custom).unwrap();
+        assert_eq!(load_pricing_from(&path).unwrap(), custom);
+
+        let mut negative = custom.clone();
+        negative[0].output = -1.0;
+        assert!(save_pricing_to(&path, &negative).is_err());
+        let mut unnamed = custom.clone();
+        unnamed[0].model = " ".to_string();
+        assert!(save_pricing_to(&path, &unnamed).is_err());
+    }
+
+    #[test]
+    fn the_meter_records_turns_and_fills_in_missing_cost() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(IndexDb::open(&dir.path().join("index.db")).unwrap());
+        let recorder = Arc::new(RecordingEventSink::new());
+        let sink = UsageMeter::shared(
+            recorder.clone() as SharedEventSink,
+            Arc::clone(&db),
+            default_pricing(),
+            "codex",
+            "run-1",
+            Some("/projects/app"),
+            Some("gpt-5"),
+        );
+
+        sink.emit_event(state_change(None, Some("thread-1")));
+        let turn = TurnUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cache_read_tokens: 1_000_000,
+            ..TurnUsage::default()
+        };
+        sink.emit_event(state_change(Some(turn.clone()), None));
+        sink.emit_event(state_change(Some(turn), None));
+
+        // The agent did not price the turn, so the meter did.
+        let costs: Vec<Option<f64>> = recorder
+            .protocol_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                ProtocolEvent::StateChange { usage, .. } => usage.map(|u| u.cost_usd),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(costs.len(), 2);
+        assert!((costs[0].unwrap() - 2.375).abs() < 1e-9);
+
+        let session = db.get_session_usage("run-1").unwrap().unwrap();
+        assert_eq!(session.agent_id, "codex");
+        assert_eq!(session.session_key, "thread-1");
+        assert_eq!(session.models, vec!["gpt-5"]);
+        assert_eq!(session.input_tokens, 2_000_000);
+        assert!((session.cost_usd - 4.75).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn claude_transcripts_count_each_response_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sess-1.jsonl");
+        let usage = r#"{"input_tokens":10,"output_tokens":5,"cache_read_input_tokens":100,"cache_creation_input_tokens":20}"#;
+        let lines = [
+            r#"{"type":"user","timestamp":"2026-03-04T10:00:00Z","cwd":"/projects/app","message":{"role":"user","content":"hi"}}"#.to_string(),
+            format!(r#"{{"type":"assistant","timestamp":"2026-03-04T10:00:01Z","message":{{"id":"msg_1","model":"claude-sonnet-4-5","usage":{}}}}}"#, usage),
+            format!(r#"{{"type":"assistant","timestamp":"2026-03-04T10:00:02Z","message":{{"id":"msg_1","model":"claude-sonnet-4-5","usage":{}}}}}"#, usage),
+            format!(r#"{{"type":"assistant","timestamp":"2026-03-05T09:00:00Z","message":{{"id":"msg_2","model":"claude-sonnet-4-5","usage":{}}}}}"#, usage),
+            format!(r#"{{"type":"assistant","timestamp":"2026-03-05T09:00:01Z","message":{{"id":"msg_3","model":"<synthetic>","usage":{}}}}}"#, usage),
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let result = ClaudeScanner::new()
+            .parse_file(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let dates: Vec<&str> = result.usage.iter().map(|u| u.date.as_str()).collect();
+        assert_eq!(dates, vec!["2026-03-04", "2026-03-05"]);
+        let first = &result.usage[0];
+        assert_eq!(first.source, UsageSource::Transcript);
+        assert_eq!(first.session_key, "sess-1");
+        assert_eq!(first.project_path.as_deref(), Some("/projects/app"));
+        assert_eq!(
+            (
+                first.input_tokens,
+                first.output_tokens,
+                first.cache_read_tokens,
+                first.cache_write_tokens
+            ),
+            (10, 5, 100, 20)
+        );
+    }
+
+    #[tokio::test]
+    async fn codex_transcripts_count_the_growth_of_running_totals() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollout-1.jsonl");
+        let token_count = |ts: &str, input: u64, cached: u64, output: u64| {
+            format!(
+                r#"{{"timestamp":"{}","type":"event_msg","payload":{{"type":"token_count","info":{{"total_token_usage":{{"input_tokens":{},"cached_input_tokens":{},"output_tokens":{}}}}}}}}}"#,
+                ts, input, cached, output
+            )
+        };
+        let lines = [
+            r#"{"timestamp":"2026-03-04T10:00:00Z","type":"session_meta","payload":{"id":"thread-1","cwd":"/projects/app","timestamp":"2026-03-04T10:00:00Z"}}"#.to_string(),
+            r#"{"timestamp":"2026-03-04T10:00:00Z","type":"turn_context","payload":{"model":"gpt-5-codex"}}"#.to_string(),
+            token_count("2026-03-04T10:00:05Z", 1000, 400, 50),
+            r#"{"timestamp":"2026-03-04T10:00:06Z","type":"event_msg","payload":{"type":"token_count","info":null}}"#.to_string(),
+            token_count("2026-03-04T10:01:00Z", 3000, 2000, 80),
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let result = CodexScanner::new()
+            .parse_file(path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result.usage.len(), 1);
+        let usage = &result.usage[0];
+        assert_eq!(usage.session_key, "thread-1");
+        assert_eq!(usage.model, "gpt-5-codex");
+        assert_eq!(
+            (
+                usage.input_tokens,
+                usage.cache_read_tokens,
+                usage.output_tokens
+            ),
+            (1000, 2000, 80)
+        );
+    }
+}
@@ -0,0 +1,288 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command as StdCommand;
+
+    use crate::models::project::{MergeStrategy, ProjectSettings};
+    use crate::services::project_settings_service::save_project_settings;
+    use crate::services::workspace_merge_service::{
+        has_conflict_markers, merge_workspace, parse_conflicts, resolve_base_branch, start_merge,
+        ConflictKind, ConflictResolution,
+    };
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let out = StdCommand::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(out.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&out.stderr));
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    }
+
+    /// Repo on `master` with one commit and a `workspace/ws` worktree.
+    fn setup(tmp: &TempDir) -> (String, String) {
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q"]);
+        git(&repo, &["config", "user.name", "Test"]);
+        git(&repo, &["config", "user.email", "test@example.com"]);
+        fs::write(repo.join("file.txt"), "one\ntwo\nthree\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-qm", "init"]);
+        git(&repo, &["branch", "-M", "master"]);
+
+        let ws = tmp.path().join("ws");
+        git(&repo, &["worktree", "add", "-q", "-b", "workspace/ws", ws.to_str().unwrap()]);
+        (repo.to_string_lossy().to_string(), ws.to_string_lossy().to_string())
+    }
+
+    fn commit_in(dir: &str, file: &str, content: &str, message: &str) {
+        let dir = Path::new(dir);
+        fs::write(dir.join(file), content).unwrap();
+        git(dir, &["add", "."]);
+        git(dir, &["commit", "-qm", message]);
+    }
+
+    fn parents(repo: &str, rev: &str) -> usize {
+        git(Path::new(repo), &["rev-list", "--parents", "-n1", rev])
+            .split_whitespace()
+            .count()
+            - 1
+    }
+
+    #[test]
+    fn merge_strategy_parses_aliases() {
+        assert_eq!(MergeStrategy::parse("merge").unwrap(), MergeStrategy::MergeCommit);
+        assert_eq!(MergeStrategy::parse("Squash").unwrap(), MergeStrategy::Squash);
+        assert_eq!(MergeStrategy::parse("rebase-ff").unwrap(), MergeStrategy::RebaseFastForward);
+        assert!(MergeStrategy::parse("octopus").is_err());
+    }
+
+    #[test]
+    fn parses_unmerged_porcelain_entries() {
+        let conflicts = parse_conflicts("UU src/lib.rs\n M clean.rs\nUD \"with space.txt\"\nAA new.rs\n");
+        assert_eq!(conflicts.len(), 3);
+        assert_eq!(conflicts[0].path, "src/lib.rs");
+        assert_eq!(conflicts[0].kind, ConflictKind::BothModified);
+        assert_eq!(conflicts[1].path, "with space.txt");
+        assert_eq!(conflicts[1].kind, ConflictKind::DeletedByThem);
+        assert_eq!(conflicts[2].kind, ConflictKind::BothAdded);
+    }
+
+    #[tokio::test]
+    async fn base_branch_resolution_order() {
+        let tmp = TempDir::new().unwrap();
+        let (repo, _ws) = setup(&tmp);
+        let repo_path = Path::new(&repo);
+
+        // No main, no origin: falls back to master.
+        assert_eq!(resolve_base_branch(&repo, None).await.unwrap(), "master");
+
+        // origin/HEAD wins over the fallbacks.
+        git(repo_path, &["branch", "develop"]);
+        git(repo_path, &["update-ref", "refs/remotes/origin/develop", "HEAD"]);
+        git(repo_path, &["symbolic-ref", "refs/remotes/origin/HEAD", "refs/remotes/origin/develop"]);
+        assert_eq!(resolve_base_branch(&repo, None).await.unwrap(), "develop");
+
+        // Project settings win over origin/HEAD, the explicit argument over both.
+        save_project_settings(
+            &repo,
+            &ProjectSettings {
+                base_branch: Some("master".into()),
+                ..ProjectSettings::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(resolve_base_branch(&repo, None).await.unwrap(), "master");
+        assert_eq!(resolve_base_branch(&repo, Some("develop")).await.unwrap(), "develop");
+        assert!(resolve_base_branch(&repo, Some("release/9")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn merge_commit_updates_base_and_its_checkout() {
+        let tmp = TempDir::new().unwrap();
+        let (repo, ws) = setup(&tmp);
+        commit_in(&ws, "file.txt", "one\ntwo\nthree\nfour\n", "ws change");
+        // An unrelated local edit in the main checkout survives the merge.
+        fs::write(Path::new(&repo).join("notes.txt"), "scratch\n").unwrap();
+
+        let outcome = merge_workspace(&repo, &ws, "master", MergeStrategy::MergeCommit, None)
+            .await
+            .unwrap();
+        assert!(outcome.merged);
+        assert!(outcome.conflicts.is_empty());
+        assert_eq!(outcome.workspace_branch, "workspace/ws");
+
+        let repo_path = Path::new(&repo);
+        assert_eq!(git(repo_path, &["rev-parse", "master"]), outcome.commit.unwrap());
+        assert_eq!(parents(&repo, "master"), 2);
+        assert_eq!(git(repo_path, &["rev-parse", "--abbrev-ref", "HEAD"]), "master");
+        assert!(fs::read_to_string(repo_path.join("file.txt")).unwrap().contains("four"));
+        assert!(repo_path.join("notes.txt").exists());
+        assert!(git(repo_path, &["status", "--porcelain", "--", "file.txt"]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn squash_and_rebase_produce_linear_history() {
+        let tmp = TempDir::new().unwrap();
+        let (repo, ws) = setup(&tmp);
+        commit_in(&ws, "a.txt", "a\n", "ws a");
+        commit_in(&ws, "b.txt", "b\n", "ws b");
+
+        let squashed = merge_workspace(&repo, &ws, "master", MergeStrategy::Squash, Some("squash ws".into()))
+            .await
+            .unwrap();
+        assert!(squashed.merged);
+        assert_eq!(parents(&repo, "master"), 1);
+        assert_eq!(git(Path::new(&repo), &["log", "-1", "--pretty=%s", "master"]), "squash ws");
+
+        // Base moved on; rebase the workspace's next commit on top of it.
+        commit_in(&ws, "c.txt", "c\n", "ws c");
+        let rebased = merge_workspace(&repo, &ws, "master", MergeStrategy::RebaseFastForward, None)
+            .await
+            .unwrap();
+        assert!(rebased.merged);
+        assert_eq!(parents(&repo, "master"), 1);
+        assert_eq!(git(Path::new(&repo), &["log", "-1", "--pretty=%s", "master"]), "ws c");
+        assert!(Path::new(&repo).join("c.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn conflicts_are_reported_and_leave_base_untouched() {
+        let tmp = TempDir::new().unwrap();
+        let (repo, ws) = setup(&tmp);
+        commit_in(&ws, "file.txt", "one\nWORKSPACE\nthree\n", "ws edit");
+        commit_in(&repo, "file.txt", "one\nMASTER\nthree\n", "master edit");
+        let before = git(Path::new(&repo), &["rev-parse", "master"]);
+
+        for strategy in [MergeStrategy::MergeCommit, MergeStrategy::Squash, MergeStrategy::RebaseFastForward] {
+            let outcome = merge_workspace(&repo, &ws, "master", strategy, None).await.unwrap();
+            assert!(!outcome.merged, "{:?} should not merge", strategy);
+            assert_eq!(outcome.conflicts.len(), 1);
+            assert_eq!(outcome.conflicts[0].path, "file.txt");
+            assert_eq!(outcome.conflicts[0].kind, ConflictKind::BothModified);
+        }
+
+        assert_eq!(git(Path::new(&repo), &["rev-parse", "master"]), before);
+        // No scratch worktrees are left behind.
+        let worktrees = git(Path::new(&repo), &["worktree", "list", "--porcelain"]);
+        assert_eq!(worktrees.matches("worktree ").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn nothing_to_merge_is_an_error() {
+        let tmp = TempDir::new().unwrap();
+        let (repo, ws) = setup(&tmp);
+        assert!(merge_workspace(&repo, &ws, "master", MergeStrategy::MergeCommit, None)
+            .await
+            .is_err());
+    }
+
+    fn conflicting(tmp: &TempDir) -> (String, String) {
+        let (repo, ws) = setup(tmp);
+        commit_in(&ws, "file.txt", "one\nWORKSPACE\nthree\n", "ws edit");
+        commit_in(&repo, "file.txt", "one\nMASTER\nthree\n", "master edit");
+        (repo, ws)
+    }
+
+    #[tokio::test]
+    async fn conflict_versions_expose_each_side() {
+        let tmp = TempDir::new().unwrap();
+        let (repo, ws) = conflicting(&tmp);
+
+        let (merge, conflicts) = start_merge(&repo, &ws, "master", MergeStrategy::MergeCommit, None)
+            .await
+            .unwrap();
+        assert_eq!(conflicts.len(), 1);
+
+        let versions = merge.conflict_versions("file.txt").await.unwrap();
+        assert_eq!(versions.base.as_deref(), Some("one\ntwo\nthree\n"));
+        assert_eq!(versions.ours.as_deref(), Some("one\nMASTER\nthree\n"));
+        assert_eq!(versions.theirs.as_deref(), Some("one\nWORKSPACE\nthree\n"));
+        assert!(has_conflict_markers(versions.working.as_deref().unwrap()));
+        assert!(!versions.binary);
+        assert!(merge.conflict_versions("missing.txt").await.is_err());
+        merge.abort().await;
+    }
+
+    #[tokio::test]
+    async fn resolved_merge_completes_onto_base() {
+        for (resolution, expected) in [
+            (ConflictResolution::Ours, "one\nMASTER\nthree\n"),
+            (ConflictResolution::Theirs, "one\nWORKSPACE\nthree\n"),
+            (
+                ConflictResolution::Manual { content: "one\nBOTH\nthree\n".to_string() },
+                "one\nBOTH\nthree\n",
+            ),
+        ] {
+            let tmp = TempDir::new().unwrap();
+            let (repo, ws) = conflicting(&tmp);
+            let (merge, _) = start_merge(&repo, &ws, "master", MergeStrategy::MergeCommit, None)
+                .await
+                .unwrap();
+
+            assert!(merge.complete().await.is_err(), "unresolved merge must not complete");
+            merge.resolve("file.txt", &resolution).await.unwrap();
+            assert!(merge.conflicts().await.is_empty());
+
+            let outcome = merge.complete().await.unwrap();
+            assert!(outcome.merged);
+            merge.close().await;
+            assert_eq!(parents(&repo, "master"), 2);
+            assert_eq!(fs::read_to_string(Path::new(&repo).join("file.txt")).unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn aborted_merge_leaves_base_untouched() {
+        let tmp = TempDir::new().unwrap();
+        let (repo, ws) = conflicting(&tmp);
+        let before = git(Path::new(&repo), &["rev-parse", "master"]);
+
+        let (merge, _) = start_merge(&repo, &ws, "master", MergeStrategy::Squash, None)
+            .await
+            .unwrap();
+        merge.resolve("file.txt", &ConflictResolution::Theirs).await.unwrap();
+        merge.abort().await;
+
+        assert_eq!(git(Path::new(&repo), &["rev-parse", "master"]), before);
+        let worktrees = git(Path::new(&repo), &["worktree", "list", "--porcelain"]);
+        assert_eq!(worktrees.matches("worktree ").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn rebase_stops_again_on_later_commits() {
+        let tmp = TempDir::new().unwrap();
+        let (repo, ws) = conflicting(&tmp);
+        commit_in(&ws, "file.txt", "one\nWORKSPACE 2\nthree\n", "ws edit 2");
+
+        let (merge, conflicts) = start_merge(&repo, &ws, "master", MergeStrategy::RebaseFastForward, None)
+            .await
+            .unwrap();
+        assert_eq!(conflicts.len(), 1);
+
+        merge
+            .resolve("file.txt", &ConflictResolution::Manual { content: "one\nMASTER+WS\nthree\n".to_string() })
+            .await
+            .unwrap();
+        let outcome = merge.complete().await.unwrap();
+        assert!(!outcome.merged);
+        assert_eq!(outcome.conflicts.len(), 1);
+
+        merge.resolve("file.txt", &ConflictResolution::Theirs).await.unwrap();
+        let outcome = merge.complete().await.unwrap();
+        assert!(outcome.merged);
+        merge.close().await;
+
+        assert_eq!(parents(&repo, "master"), 1);
+        assert_eq!(
+            fs::read_to_string(Path::new(&repo).join("file.txt")).unwrap(),
+            "one\nWORKSPACE 2\nthree\n"
+        );
+    }
+
+    #[test]
+    fn detects_conflict_markers() {
+        assert!(has_conflict_markers("a\n<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> ws\n"));
+        assert!(!has_conflict_markers("a\n=== heading ===\n"));
+    }
+}
@@ -17,11 +17,12 @@ import { SessionStatusHeader } from '@/components/chat/SessionStatusHeader';
 import { SessionManagementPanel } from '@/components/chat/SessionManagementPanel';
 import { useChatAutocomplete } from '@/components/chat/hooks/useChatAutocomplete';
 import { useCLIEvents } from '@/components/chat/hooks/useCLIEvents';
-import { useProtocolEvents } from '@/components/chat/hooks/useProtocolEvents'
+import { useProtocolEvents, type SessionEndReason } from '@/components/chat/hooks/useProtocolEvents'
 import { useRotatingPlaceholder } from '@/components/chat/hooks/useRotatingPlaceholder';
 import { useChatPersistence } from '@/components/chat/hooks/useChatPersistence';
 import { useAgentEnablement } from '@/components/chat/hooks/useAgentEnablement';
 import type { ChatMessage, ChatMessageToolEvent, TimelineStep } from '@/components/chat/types';
+import { mergeToolEvent } from '@/components/chat/utils/toolEvents';
 import type { SessionStatus } from '@/components/chat/types';
 import { useChatExecution } from '@/components/chat/hooks/useChatExecution';
 import { ClaudeStreamParser } from '@/components/chat/stream/claudeStreamParser'
@@ -43,6 +44,13 @@ import { generateId } from '@/components/chat/utils/id';
 
 // CLISession and SessionStatus moved to chat/types
 
+// Why a run was stopped, for the runs a session limit ended
+const SESSION_LIMIT_NOTES: Partial<Record<SessionEndReason, string>> = {
+  wall_clock_timeout: 'the run took longer than its time limit',
+  idle_timeout: 'the agent produced no output within the idle timeout',
+  output_limit: 'the agent produced more output than its limit',
+};
+
 interface LoadedSessionMessage {
   id: string;
   role: string;
@@ -1118,14 +1126,49 @@ Please focus only on this step.`;
         }
       }))
     },
-    onToolStart: (_data) => {
-      // Tool tracking for protocol agents — future enhancement
+    onToolStart: (data) => {
+      setMessages(prev => prev.map(msg => {
+        if (msg.id !== data.session_id) return msg
+        return {
+          ...msg,
+          toolEvents: mergeToolEvent(msg.toolEvents, {
+            tool_id: data.tool_id,
+            tool_name: data.tool_name,
+            phase: 'start',
+            args: data.args,
+          }),
+        }
+      }))
     },
-    onToolUpdate: (_data) => {
-      // Tool progress for protocol agents — future enhancement
+    onToolUpdate: (data) => {
+      setMessages(prev => prev.map(msg => {
+        if (msg.id !== data.session_id) return msg
+        return {
+          ...msg,
+          toolEvents: mergeToolEvent(msg.toolEvents, {
+            tool_id: data.tool_id,
+            tool_name: data.tool_name,
+            phase: 'update',
+            output: data.output,
+          }),
+        }
+      }))
     },
-    onToolEnd: (_data) => {
-      // Tool completion for protocol agents — future enhancement
+    onToolEnd: (data) => {
+      setMessages(prev => prev.map(msg => {
+        if (msg.id !== data.session_id) return msg
+        return {
+          ...msg,
+          toolEvents: mergeToolEvent(msg.toolEvents, {
+            tool_id: data.tool_id,
+            tool_name: data.tool_name,
+            phase: 'end',
+            output: data.output,
+            success: data.success,
+            duration_ms: data.duration_ms,
+          }),
+        }
+      }))
     },
     onPermissionRequest: (data) => {
       // Auto-approve for now — permission UI is a future enhancement
@@ -1135,15 +1178,22 @@ Please focus only on this step.`;
         approved: true,
       }).catch(console.error)
     },
-    onStateChange: (_data) => {
-      // State change handling — future enhancement
+    onStateChange: (data) => {
+      // Agents parsed in the backend (e.g. Claude stream-json) report their
+      // native session id here; keep it for --resume on follow-up turns.
+      if (data.agent_session_id && activeConversationRef.current && !activeConversationRef.current.agentSessionId) {
+        activeConversationRef.current.agentSessionId = data.agent_session_id
+      }
     },
     onError: (data) => {
       setMessages(prev => prev.map(msg => {
         if (msg.id !== data.session_id) return msg
         return {
           ...msg,
-          content: (msg.content || '') + `\n[Error: ${data.message}]`,
+          content:
+            (msg.content || '') +
+            `\n[Error: ${data.message}]` +
+            (data.failure ? `\n[Hint: ${data.failure.hint}]` : ''),
           isStreaming: false,
           status: 'failed' as const,
         }
@@ -1161,8 +1211,34 @@ Please focus only on this step.`;
             status: data.event === 'fallback_to_pty' ? ('running' as const) : ('completed' as const),
           }
         }))
+      } else if (data.event === 'reconnecting') {
+        // The agent crashed mid-turn and is being restarted; the turn goes on
+        setMessages(prev => prev.map(msg => {
+          if (msg.id !== data.session_id) return msg
+          return { ...msg, isStreaming: true, status: 'running' as const }
+        }))
       }
     },
+    onSandboxBlocked: (data) => {
+      const what = data.access === 'write' ? 'a write' : 'network access'
+      setMessages(prev => prev.map(msg => {
+        if (msg.id !== data.session_id) return msg
+        return { ...msg, content: (msg.content || '') + `\n[Sandbox blocked ${what}: ${data.detail}]` }
+      }))
+    },
+    onSessionEnded: (data) => {
+      const stopped = SESSION_LIMIT_NOTES[data.reason]
+      if (!stopped) return
+      setMessages(prev => prev.map(msg => {
+        if (msg.id !== data.session_id) return msg
+        return {
+          ...msg,
+          content: (msg.content || '') + `\n[Stopped: ${stopped}]`,
+          isStreaming: false,
+          status: 'failed' as const,
+        }
+      }))
+    },
   })
 
   // Auto-scroll to bottom when new messages arrive
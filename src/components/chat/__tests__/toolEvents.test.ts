@@ -0,0 +1,61 @@
+import { describe, it, expect } from 'vitest'
+import { mergeToolEvent } from '../utils/toolEvents'
+import { normalizeClaude, normalizeCodex } from '../unified/normalizers'
+
+describe('mergeToolEvent', () => {
+  it('keeps one entry per tool and accumulates update output', () => {
+    let events = mergeToolEvent(undefined, {
+      tool_id: 't1',
+      tool_name: 'Bash',
+      phase: 'start',
+      args: { command: 'ls' },
+    })
+    events = mergeToolEvent(events, { tool_id: 't1', tool_name: 'Bash', phase: 'update', output: 'a.txt\n' })
+    events = mergeToolEvent(events, { tool_id: 't1', tool_name: 'Bash', phase: 'update', output: 'b.txt\n' })
+
+    expect(events).toHaveLength(1)
+    expect(events[0].phase).toBe('update')
+    expect(events[0].output).toBe('a.txt\nb.txt\n')
+    expect(events[0].args).toEqual({ command: 'ls' })
+
+    events = mergeToolEvent(events, {
+      tool_id: 't1',
+      tool_name: 'Bash',
+      phase: 'end',
+      success: true,
+      duration_ms: 40,
+    })
+    expect(events[0]).toMatchObject({ phase: 'end', success: true, duration_ms: 40, output: 'a.txt\nb.txt\n' })
+  })
+
+  it('appends events for new tools in order', () => {
+    let events = mergeToolEvent([], { tool_id: 't1', tool_name: 'Read', phase: 'start' })
+    events = mergeToolEvent(events, { tool_id: 't2', tool_name: 'Edit', phase: 'start' })
+    expect(events.map((e) => e.tool_id)).toEqual(['t1', 't2'])
+  })
+})
+
+describe('normalizeClaude with backend-parsed events', () => {
+  it('shows tool events alongside plain answer text', () => {
+    const result = normalizeClaude('Listed the files.', {
+      toolEvents: [{ tool_id: 't1', tool_name: 'Bash', phase: 'end', success: true, output: 'a.txt' }],
+    })
+    expect(result.answer).toBe('Listed the files.')
+    expect(result.toolEvents).toEqual([
+      expect.objectContaining({ toolId: 't1', toolName: 'Bash', phase: 'end', success: true, output: 'a.txt' }),
+    ])
+  })
+})
+
+describe('normalizeCodex with backend-parsed events', () => {
+  it('shows command and file change tools with the answer', () => {
+    const result = normalizeCodex('Fixed it.', {
+      toolEvents: [
+        { tool_id: 'item_1', tool_name: 'cargo test', phase: 'end', success: true, output: 'ok' },
+        { tool_id: 'item_2', tool_name: 'Edit src/lib.rs', phase: 'end', success: true, output: 'update src/lib.rs' },
+      ],
+    })
+    expect(result.answer).toBe('Fixed it.')
+    expect(result.toolEvents.map((e) => e.toolName)).toEqual(['cargo test', 'Edit src/lib.rs'])
+  })
+})
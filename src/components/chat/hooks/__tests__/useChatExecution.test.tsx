@@ -31,6 +31,7 @@ describe('useChatExecution', () => {
     expect(calls[0].cmd).toBe('execute_codex_command')
     expect(calls[0].args.sessionId).toBe('turn-1')
     expect(calls[0].args.workingDir).toBe('/tmp/demo')
+    expect(calls[0].args.conversationId).toBe('conversation-1')
     // Assistant message added and marked streaming with conversation id preserved
     const assistant = messages.find((m) => m.id === 'turn-1')
     expect(assistant?.isStreaming).toBe(true)
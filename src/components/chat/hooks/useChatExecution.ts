@@ -65,7 +65,8 @@ export function useChatExecution({ resolveWorkingDir, setMessages, setExecutingS
         const commandFunction = (agentCommandMap as any)[name]
         if (!commandFunction) return sessionId
         const workingDir = await resolveWorkingDir()
-        const baseArgs: any = { sessionId, message, workingDir }
+        // Turns of one conversation share the agent process when its protocol allows
+        const baseArgs: any = { sessionId, message, workingDir, conversationId: assistantMessage.conversationId }
 
         // Use the registry to set the correct backend param key
         const modeConfig = AGENT_EXECUTION_MODES[name]
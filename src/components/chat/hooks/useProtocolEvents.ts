@@ -31,27 +31,101 @@ export interface ToolEndData {
   duration_ms?: number
 }
 
+export interface PermissionTargetData {
+  tool_kind: string
+  command?: string
+  paths: string[]
+}
+
 export interface PermissionData {
   session_id: string
   request_id: string
   tool_name: string
   description: string
+  target?: PermissionTargetData
+  tool_id?: string
+}
+
+export interface PermissionResolvedData {
+  session_id: string
+  request_id: string
+  tool_name: string
+  approved: boolean
+  reason: string
+  /** True when a policy rule or session allowance decided, not the user. */
+  automatic: boolean
+  target?: PermissionTargetData
+  tool_id?: string
+}
+
+export interface PlanEntryData {
+  content: string
+  priority: 'high' | 'medium' | 'low'
+  status: 'pending' | 'in_progress' | 'completed'
+}
+
+export interface PlanData {
+  session_id: string
+  entries: PlanEntryData[]
+}
+
+export interface TurnUsageData {
+  model?: string | null
+  input_tokens: number
+  output_tokens: number
+  cache_read_tokens: number
+  cache_creation_tokens: number
+  cost_usd?: number | null
 }
 
 export interface StateData {
   session_id: string
   status: string
   context_percent?: number
+  usage?: TurnUsageData
+  agent_session_id?: string
+}
+
+export type AgentFailureKind = 'missing_api_key' | 'rate_limit' | 'network' | 'version_mismatch'
+
+export interface AgentFailureData {
+  kind: AgentFailureKind
+  hint: string
 }
 
 export interface ErrorData {
   session_id: string
   message: string
+  failure?: AgentFailureData
 }
 
 export interface SessionData {
   session_id: string
-  event: 'connected' | 'reconnected' | 'disconnected' | 'fallback_to_pty'
+  event:
+    | 'connected'
+    | 'reconnecting'
+    | 'reconnected'
+    | 'reconnect_failed'
+    | 'disconnected'
+    | 'fallback_to_pty'
+}
+
+export type SessionEndReason =
+  | 'completed'
+  | 'cancelled'
+  | 'wall_clock_timeout'
+  | 'idle_timeout'
+  | 'output_limit'
+
+export interface SessionEndedData {
+  session_id: string
+  reason: SessionEndReason
+}
+
+export interface SandboxBlockedData {
+  session_id: string
+  access: 'write' | 'network'
+  detail: string
 }
 
 interface ProtocolEventPayload {
@@ -65,9 +139,13 @@ interface Callbacks {
   onToolUpdate: (data: ToolUpdateData) => void
   onToolEnd: (data: ToolEndData) => void
   onPermissionRequest: (data: PermissionData) => void
+  onPermissionResolved?: (data: PermissionResolvedData) => void
+  onPlan?: (data: PlanData) => void
   onStateChange: (data: StateData) => void
   onError: (data: ErrorData) => void
   onSessionEvent: (data: SessionData) => void
+  onSessionEnded?: (data: SessionEndedData) => void
+  onSandboxBlocked?: (data: SandboxBlockedData) => void
 }
 
 export function useProtocolEvents(sessionId: string, callbacks: Callbacks) {
@@ -97,6 +175,12 @@ export function useProtocolEvents(sessionId: string, callbacks: Callbacks) {
         case 'PermissionRequest':
           cbRef.current.onPermissionRequest(data as unknown as PermissionData)
           break
+        case 'PermissionResolved':
+          cbRef.current.onPermissionResolved?.(data as unknown as PermissionResolvedData)
+          break
+        case 'Plan':
+          cbRef.current.onPlan?.(data as unknown as PlanData)
+          break
         case 'StateChange':
           cbRef.current.onStateChange(data as unknown as StateData)
           break
@@ -106,6 +190,12 @@ export function useProtocolEvents(sessionId: string, callbacks: Callbacks) {
         case 'SessionEvent':
           cbRef.current.onSessionEvent(data as unknown as SessionData)
           break
+        case 'SessionEnded':
+          cbRef.current.onSessionEnded?.(data as unknown as SessionEndedData)
+          break
+        case 'SandboxBlocked':
+          cbRef.current.onSandboxBlocked?.(data as unknown as SandboxBlockedData)
+          break
       }
     }).then((fn) => {
       unlisten = fn
@@ -37,6 +37,20 @@ function stepsToWorkingSteps(
   }))
 }
 
+function toToolEvents(
+  events?: MessageShape['toolEvents']
+): NormalizedContent['toolEvents'] {
+  return (events ?? []).map((e) => ({
+    toolId: e.tool_id,
+    toolName: e.tool_name,
+    phase: e.phase,
+    args: e.args,
+    output: e.output,
+    success: e.success,
+    durationMs: e.duration_ms,
+  }))
+}
+
 function empty(isStreaming: boolean): NormalizedContent {
   return {
     reasoning: [],
@@ -56,11 +70,13 @@ export function normalizeClaude(
   const parsed = parseAgentTranscript(content)
 
   if (!parsed) {
-    // Fallback: treat raw content as markdown answer
+    // Fallback: treat raw content as markdown answer. Claude sessions parsed
+    // in the backend arrive this way, with their tools as toolEvents.
     return {
       ...empty(streaming),
       answer: content,
       workingSteps: stepsToWorkingSteps(message.steps),
+      toolEvents: toToolEvents(message.toolEvents),
     }
   }
 
@@ -100,7 +116,7 @@ export function normalizeClaude(
     workingSteps,
     answer,
     meta,
-    toolEvents: [],
+    toolEvents: toToolEvents(message.toolEvents),
     isStreaming: streaming,
   }
 }
@@ -122,7 +138,7 @@ export function normalizeCodex(
     workingSteps: stepsToWorkingSteps(message.steps),
     answer: parsed.response,
     meta: null,
-    toolEvents: [],
+    toolEvents: toToolEvents(message.toolEvents),
     isStreaming: streaming,
   }
 }
@@ -133,22 +149,12 @@ export function normalizeAutohand(
 ): NormalizedContent {
   const streaming = message.isStreaming ?? false
 
-  const toolEvents = (message.toolEvents ?? []).map((e) => ({
-    toolId: e.tool_id,
-    toolName: e.tool_name,
-    phase: e.phase,
-    args: e.args,
-    output: e.output,
-    success: e.success,
-    durationMs: e.duration_ms,
-  }))
-
   return {
     reasoning: [],
     workingSteps: stepsToWorkingSteps(message.steps),
     answer: content,
     meta: null,
-    toolEvents,
+    toolEvents: toToolEvents(message.toolEvents),
     isStreaming: streaming,
   }
 }
@@ -162,6 +168,7 @@ export function normalizeGeneric(
     ...empty(streaming),
     answer: content,
     workingSteps: stepsToWorkingSteps(message.steps),
+    toolEvents: toToolEvents(message.toolEvents),
   }
 }
 
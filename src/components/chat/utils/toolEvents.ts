@@ -0,0 +1,25 @@
+import type { ChatMessageToolEvent } from '../types'
+
+// Keep one entry per tool call: later phases update the entry the start
+// created, and update output accumulates until the tool ends.
+export const mergeToolEvent = (
+  events: ChatMessageToolEvent[] | undefined,
+  event: ChatMessageToolEvent
+): ChatMessageToolEvent[] => {
+  const list = events ?? []
+  const index = list.findIndex((e) => e.tool_id === event.tool_id)
+  if (index === -1) return [...list, event]
+
+  const previous = list[index]
+  const output =
+    event.phase === 'update'
+      ? (previous.output ?? '') + (event.output ?? '')
+      : event.output ?? previous.output
+  const merged: ChatMessageToolEvent = {
+    ...previous,
+    ...event,
+    args: event.args ?? previous.args,
+    output,
+  }
+  return list.map((e, i) => (i === index ? merged : e))
+}
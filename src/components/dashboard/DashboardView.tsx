@@ -168,6 +168,7 @@ export function DashboardView({ timeSavedMultiplier, days, onDaysChange }: Dashb
           <MetricsStrip
             totalSessions={stats.total_sessions}
             totalTokens={stats.total_tokens}
+            totalCostUsd={stats.total_cost_usd}
             totalMessages={stats.total_messages}
             timeSavedMinutes={timeSavedMinutes}
             currentStreak={stats.current_streak}
@@ -5,6 +5,8 @@ import { useChartTooltip } from '@/hooks/useChartTooltip'
 export interface MetricsStripProps {
   totalSessions: number
   totalTokens: number
+  /** Cost of the recorded token usage in USD, shown next to the tokens */
+  totalCostUsd?: number
   totalMessages: number
   timeSavedMinutes: number
   currentStreak: number
@@ -29,6 +31,12 @@ function formatTokens(n: number): string {
   return `${n}`
 }
 
+function formatCost(usd: number): string {
+  if (usd < 0.01) return '<$0.01'
+  if (usd >= 1000) return `$${Math.round(usd).toLocaleString()}`
+  return `$${usd.toFixed(2)}`
+}
+
 function formatTime(minutes: number): string {
   if (minutes === 0) return '0m'
   if (minutes < 60) return `${minutes}m`
@@ -256,6 +264,7 @@ function EmptySparkline() {
 export function MetricsStrip({
   totalSessions,
   totalTokens,
+  totalCostUsd = 0,
   totalMessages,
   timeSavedMinutes,
   currentStreak,
@@ -291,7 +300,9 @@ export function MetricsStrip({
       ? {
           icon: <Coins className="h-3.5 w-3.5" />,
           label: 'Tokens',
-          value: formatTokens(totalTokens),
+          value: totalCostUsd > 0
+            ? `${formatTokens(totalTokens)} · ${formatCost(totalCostUsd)}`
+            : formatTokens(totalTokens),
           sparkline: <StackedBarSparkline agents={agentsUsed} tt={tt} selectedAgent={selectedAgent} />,
         }
       : {
@@ -14,7 +14,7 @@ vi.mock('@tauri-apps/api/core', () => ({ invoke: vi.fn() }))
 vi.mock('@tauri-apps/api/event', () => ({ listen: vi.fn(async () => () => {}) }))
 
 const MOCK_STATS = {
-  total_messages: 150, total_sessions: 20, total_tokens: 50000,
+  total_messages: 150, total_sessions: 20, total_tokens: 50000, total_cost_usd: 1.25,
   agents_used: { claude: 12, codex: 5 },
   daily_activity: [{ date: '2026-03-04', message_count: 5, token_count: 1000 }],
   current_streak: 3, longest_streak: 7, memory_files_count: 4,
@@ -53,6 +53,11 @@ describe('MetricsStrip', () => {
     expect(screen.getByText('438K')).toBeInTheDocument()
   })
 
+  it('renders the cost next to the token count when known', () => {
+    render(<MetricsStrip {...defaultProps} totalCostUsd={12.345} />)
+    expect(screen.getByText('438K · $12.35')).toBeInTheDocument()
+  })
+
   it('renders time saved value', () => {
     render(<MetricsStrip {...defaultProps} />)
     expect(screen.getByText('~12h')).toBeInTheDocument()
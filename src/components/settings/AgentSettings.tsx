@@ -30,15 +30,32 @@ import { Switch } from "@/components/ui/switch"
 import { Tabs, TabsContent, TabsList, TabsTrigger } from "@/components/ui/tabs"
 import type { AgentSettingsProps } from "@/types/settings"
 
+type ProcessSandboxSettings = {
+  enabled: boolean
+  allow_network: boolean
+  writable_paths: string[]
+}
+
+const DEFAULT_PROCESS_SANDBOX: ProcessSandboxSettings = {
+  enabled: false,
+  allow_network: true,
+  writable_paths: [],
+}
+
 type GenericAgentSettings = {
   model?: string | null
   output_format?: string
   session_timeout_minutes?: number
+  max_run_minutes?: number
+  max_output_bytes?: number
   max_tokens?: number | null
   temperature?: number | null
   sandbox_mode?: boolean
+  process_sandbox?: ProcessSandboxSettings
+  env_profile?: string | null
   auto_approval?: boolean
   debug_mode?: boolean
+  acp_legacy_envelope?: boolean
 }
 
 const OUTPUT_FORMAT_OPTIONS = [
@@ -69,11 +86,16 @@ function transportToPromptMode(transport: AgentTransportKind): AgentPromptMode {
   return "flag"
 }
 
+const BYTES_PER_MB = 1024 * 1024
+const DEFAULT_MAX_OUTPUT_BYTES = 64 * BYTES_PER_MB
+
 function defaultAgentSettings() {
   return {
     model: "",
     output_format: "markdown",
     session_timeout_minutes: 30,
+    max_run_minutes: 0,
+    max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
     max_tokens: null,
     temperature: null,
     sandbox_mode: false,
@@ -155,6 +177,8 @@ function GenericAgentFields({
   onFetchModels: () => void
   onUpdate: (key: string, value: unknown) => void
 }) {
+  const sandbox = { ...DEFAULT_PROCESS_SANDBOX, ...settings.process_sandbox }
+
   return (
     <div className="space-y-5">
       {capabilities.model ? (
@@ -240,6 +264,32 @@ function GenericAgentFields({
         </FieldRow>
       ) : null}
 
+      <FieldRow
+        label="Max Run Time"
+        hint="Minutes a single run may take before Commander stops it. 0 means no limit."
+      >
+        <Input
+          type="number"
+          min={0}
+          value={settings.max_run_minutes ?? 0}
+          onChange={(e) => onUpdate("max_run_minutes", Math.max(0, parseInt(e.target.value, 10) || 0))}
+          className="max-w-xs"
+        />
+      </FieldRow>
+
+      <FieldRow
+        label="Output Limit"
+        hint="Megabytes of output a single run may produce before Commander stops it. 0 means no limit."
+      >
+        <Input
+          type="number"
+          min={0}
+          value={Math.round((settings.max_output_bytes ?? DEFAULT_MAX_OUTPUT_BYTES) / BYTES_PER_MB)}
+          onChange={(e) => onUpdate("max_output_bytes", Math.max(0, parseInt(e.target.value, 10) || 0) * BYTES_PER_MB)}
+          className="max-w-xs"
+        />
+      </FieldRow>
+
       {capabilities.max_tokens ? (
         <FieldRow
           label="Max Tokens"
@@ -286,6 +336,58 @@ function GenericAgentFields({
         </FieldRow>
       ) : null}
 
+      <FieldRow
+        label="Process Sandbox"
+        hint="Linux only, needs bubblewrap. Runs the agent with only the project directory writable."
+      >
+        <Switch
+          checked={!!sandbox.enabled}
+          onCheckedChange={(checked) => onUpdate("process_sandbox", { ...sandbox, enabled: checked })}
+          aria-label="Process Sandbox"
+        />
+      </FieldRow>
+
+      {sandbox.enabled ? (
+        <>
+          <FieldRow label="Sandbox Network" hint="Let the sandboxed agent reach the network.">
+            <Switch
+              checked={!!sandbox.allow_network}
+              onCheckedChange={(checked) => onUpdate("process_sandbox", { ...sandbox, allow_network: checked })}
+              aria-label="Sandbox Network"
+            />
+          </FieldRow>
+
+          <FieldRow
+            label="Extra Writable Paths"
+            hint="Comma-separated paths the sandboxed agent may also write to."
+          >
+            <Input
+              value={sandbox.writable_paths.join(", ")}
+              onChange={(e) =>
+                onUpdate("process_sandbox", {
+                  ...sandbox,
+                  writable_paths: e.target.value.split(",").map((p) => p.trim()),
+                })
+              }
+              placeholder="~/.cache"
+              className="max-w-md"
+            />
+          </FieldRow>
+        </>
+      ) : null}
+
+      <FieldRow
+        label="Environment Profile"
+        hint="Named environment the agent starts with, applied over the project's profile."
+      >
+        <Input
+          value={settings.env_profile ?? ""}
+          onChange={(e) => onUpdate("env_profile", e.target.value.trim() || null)}
+          placeholder="None"
+          className="max-w-md"
+        />
+      </FieldRow>
+
       {capabilities.auto_approval ? (
         <FieldRow
           label="Auto Approval"
@@ -562,6 +664,18 @@ const SafeAgentSettings = ({
                       </span>
                     ) : null}
                   </div>
+                  {effectiveTransport === 'acp' ? (
+                    <FieldRow
+                      label="Legacy ACP Envelope"
+                      hint="Speak the older {\"type\", \"data\"} ndJSON envelope instead of JSON-RPC for agents that predate it."
+                    >
+                      <Switch
+                        checked={!!settings.acp_legacy_envelope}
+                        onCheckedChange={(checked) => onUpdateAgentSetting(profile.id, 'acp_legacy_envelope', checked)}
+                        aria-label="Legacy ACP Envelope"
+                      />
+                    </FieldRow>
+                  ) : null}
                 </Section>
 
                 {profile.specialView === "autohand" ? (
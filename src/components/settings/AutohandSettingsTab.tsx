@@ -196,6 +196,8 @@ export function AutohandSettingsTab({ workingDir }: AutohandSettingsTabProps) {
   }
 
   const providerDetails = config.provider_details
+  // Saved keys live in the secret store; the config only holds their handle.
+  const apiKeyStored = providerDetails?.api_key?.startsWith('secret://') ?? false
   const permissions = config.permissions
   const agent = config.agent
   const network = config.network
@@ -274,7 +276,7 @@ export function AutohandSettingsTab({ workingDir }: AutohandSettingsTabProps) {
             <Label className="text-xs">API Key</Label>
             <Input
               type="password"
-              value={providerDetails?.api_key || ''}
+              value={apiKeyStored ? '' : providerDetails?.api_key || ''}
               onChange={(e) =>
                 updateConfig({
                   provider_details: {
@@ -283,7 +285,7 @@ export function AutohandSettingsTab({ workingDir }: AutohandSettingsTabProps) {
                   },
                 })
               }
-              placeholder="sk-..."
+              placeholder={apiKeyStored ? 'Saved in the secret store; type to replace' : 'sk-...'}
               className="h-8 text-sm font-mono"
             />
           </div>
@@ -29,6 +29,7 @@ export function SubAgentsSettings() {
   const [loading, setLoading] = useState(false)
   const [editing, setEditing] = useState<SubAgent | null>(null)
   const [editContent, setEditContent] = useState('')
+  const [savedContent, setSavedContent] = useState('')
   const [saving, setSaving] = useState(false)
   const [viewing, setViewing] = useState<SubAgent | null>(null)
   const [viewContent, setViewContent] = useState('')
@@ -59,11 +60,34 @@ export function SubAgentsSettings() {
     loadAgents()
   }, [])
 
+  // Publish unsaved edits so agents read them and do not write over them
+  useEffect(() => {
+    if (!editing) return
+    const filePath = editing.file_path
+    const timer = setTimeout(() => {
+      const update = editContent === savedContent
+        ? invoke('clear_unsaved_buffer', { filePath })
+        : invoke('set_unsaved_buffer', { filePath, content: editContent })
+      update.catch(() => {})
+    }, 300)
+    return () => clearTimeout(timer)
+  }, [editing, editContent, savedContent])
+
+  // Saved or discarded: agents go back to the file on disk
+  useEffect(() => {
+    if (!editing) return
+    const filePath = editing.file_path
+    return () => {
+      invoke('clear_unsaved_buffer', { filePath }).catch(() => {})
+    }
+  }, [editing])
+
   const startEdit = async (agent: SubAgent) => {
     try {
       setEditing(agent)
       const content = await invoke<string>('read_file_content', { filePath: agent.file_path })
       setEditContent(content)
+      setSavedContent(content)
     } catch (e) {
       showError('Failed to open agent file')
       setEditing(null)
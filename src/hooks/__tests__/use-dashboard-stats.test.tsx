@@ -13,6 +13,7 @@ const MOCK_STATS = {
   total_messages: 150,
   total_sessions: 20,
   total_tokens: 50000,
+  total_cost_usd: 1.25,
   agents_used: { claude: 12, codex: 5, gemini: 3 },
   daily_activity: [
     { date: '2026-03-03', message_count: 5, token_count: 1000 },
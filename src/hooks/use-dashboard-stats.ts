@@ -18,6 +18,7 @@ export interface DashboardStats {
   total_messages: number
   total_sessions: number
   total_tokens: number
+  total_cost_usd: number
   agents_used: Record<string, number>
   daily_activity: DailyActivity[]
   current_streak: number
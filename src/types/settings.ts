@@ -59,16 +59,31 @@ export interface AgentConfig {
   model: string;
   output_format: 'markdown' | 'json' | 'plain' | 'code';
   session_timeout_minutes: number;
+  /** Minutes a run may take before it is stopped; 0 means no limit. */
+  max_run_minutes?: number;
+  /** Bytes of output a run may produce before it is stopped; 0 means no limit. */
+  max_output_bytes?: number;
   max_tokens: number | null;
   temperature: number | null;
   sandbox_mode: boolean;
   auto_approval: boolean;
   debug_mode: boolean;
   transport?: 'cli-flags' | 'json-rpc' | 'acp';
+  acp_legacy_envelope?: boolean;
+  /** Commander's Linux namespace sandbox for the agent process. */
+  process_sandbox?: {
+    enabled: boolean;
+    allow_network: boolean;
+    writable_paths: string[];
+  };
+  /** Environment profile applied on top of the project's. */
+  env_profile?: string | null;
 }
 
 export interface AllAgentSettings {
   max_concurrent_sessions: number;
+  max_concurrent_sessions_per_project?: number | null;
+  max_concurrent_sessions_per_agent?: number | null;
   claude?: AgentConfig;
   codex?: AgentConfig;
   gemini?: AgentConfig;